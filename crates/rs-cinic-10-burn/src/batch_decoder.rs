@@ -0,0 +1,166 @@
+use anyhow::{Result, bail};
+use burn::prelude::{Backend, Tensor, TensorData};
+use burn::tensor;
+use rs_cinic_10_index::images::{DecoderBackend, ImageCrateDecoder, read_image_bytes};
+use rs_cinic_10_index::index::DatasetIndex;
+use rs_cinic_10_index::loader::BatchLoader;
+use rs_cinic_10_index::profile::Stage;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Decodes a whole batch of encoded images into a BHWC u8 tensor.
+///
+/// An extension point for decoders which work on a batch at a time, rather
+/// than image by image; an implementation receives every encoded image of the
+/// batch, and returns the tensor on the requested device. `HostDecoder`, the
+/// one implementation in this crate, decodes on the CPU.
+pub trait BatchDecoder<B>: Send + Sync
+where
+    B: Backend,
+{
+    /// A short name for the decoder, for logs and configuration.
+    fn name(&self) -> &str;
+
+    /// Decode a batch.
+    ///
+    /// # Parameters
+    ///
+    /// - `encoded`: The encoded images; all must decode to the same dimensions.
+    /// - `device`: The device to place the batch on.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `[B, H, W, C]` batch; or the first decode error.
+    fn decode_batch(
+        &self,
+        encoded: &[Vec<u8>],
+        device: &B::Device,
+    ) -> Result<Tensor<B, 4>>;
+}
+
+/// A `BatchDecoder` which decodes on the host with a `DecoderBackend`, then uploads the batch.
+///
+/// The reference implementation; pixels pass through host memory, as with
+/// `WithTensorBatches`, so it does not offload any decode work.
+#[derive(Debug, Clone)]
+pub struct HostDecoder {
+    backend: Arc<dyn DecoderBackend>,
+}
+
+impl Default for HostDecoder {
+    fn default() -> Self {
+        Self::new(Arc::new(ImageCrateDecoder))
+    }
+}
+
+impl HostDecoder {
+    pub fn new(backend: Arc<dyn DecoderBackend>) -> Self {
+        Self { backend }
+    }
+}
+
+impl<B> BatchDecoder<B> for HostDecoder
+where
+    B: Backend,
+{
+    fn name(&self) -> &str {
+        self.backend.name()
+    }
+
+    fn decode_batch(
+        &self,
+        encoded: &[Vec<u8>],
+        device: &B::Device,
+    ) -> Result<Tensor<B, 4>> {
+        let mut dims = None;
+        let mut data = Vec::new();
+        for bytes in encoded {
+            let img = self.backend.decode(bytes)?;
+            match dims {
+                None => dims = Some(img.dimensions()),
+                Some(expected) if expected != img.dimensions() => {
+                    bail!(
+                        "Image dimensions {:?} do not match {:?}",
+                        img.dimensions(),
+                        expected
+                    );
+                }
+                _ => {}
+            }
+            data.extend_from_slice(&img.into_raw());
+        }
+        let Some((width, height)) = dims else {
+            bail!("Cannot decode an empty batch");
+        };
+        let shape = vec![encoded.len(), height as usize, width as usize, 3];
+        let data = TensorData::from_bytes(data, shape, tensor::DType::U8);
+        Ok(Tensor::from_data(data, device))
+    }
+}
+
+/// Load a BHWC u8 tensor batch, decoding with a `BatchDecoder`.
+///
+/// The loader's `ErrorPolicy`, transform, and `DecoderBackend` are not applied;
+/// any unreadable or undecodable sample fails the batch. When the loader is
+/// profiling, file reads are recorded as `Stage::Read`, and the decode as
+/// `Stage::Decode`; the decoder places the batch on the device, so the
+/// transfer is part of the decode.
+///
+/// # Parameters
+///
+/// - `loader`: The loader, for profiling.
+/// - `decoder`: The batch decoder.
+/// - `paths`: A slice of paths to the images.
+/// - `device`: The device to place the batch on.
+///
+/// # Returns
+///
+/// A `Result` containing the batch.
+pub fn load_bhwc_u8_tensor_image_batch_decoded<B, D, P>(
+    loader: &BatchLoader,
+    decoder: &D,
+    paths: &[P],
+    device: &B::Device,
+) -> Result<Tensor<B, 4>>
+where
+    B: Backend,
+    D: BatchDecoder<B> + ?Sized,
+    P: AsRef<Path>,
+{
+    let encoded = paths
+        .iter()
+        .map(|path| loader.time(Stage::Read, || read_image_bytes(path)))
+        .collect::<Result<Vec<_>, _>>()?;
+    loader.time(Stage::Decode, || decoder.decode_batch(&encoded, device))
+}
+
+/// Batch loading through a `BatchDecoder`.
+pub trait WithDecodedBatches {
+    fn load_tensor_batch_decoded<B, D>(
+        &self,
+        loader: &BatchLoader,
+        decoder: &D,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<Tensor<B, 4>>
+    where
+        B: Backend,
+        D: BatchDecoder<B> + ?Sized;
+}
+
+impl WithDecodedBatches for DatasetIndex {
+    fn load_tensor_batch_decoded<B, D>(
+        &self,
+        loader: &BatchLoader,
+        decoder: &D,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<Tensor<B, 4>>
+    where
+        B: Backend,
+        D: BatchDecoder<B> + ?Sized,
+    {
+        let paths = self.indices_to_paths(indexes);
+        load_bhwc_u8_tensor_image_batch_decoded(loader, decoder, &paths, device)
+    }
+}
@@ -1,8 +1,19 @@
+#[cfg(feature = "batch-decoder")]
+pub mod batch_decoder;
+
 use anyhow::Result;
 use burn::prelude::{Backend, Tensor, TensorData};
 use burn::tensor;
+use rs_cinic_10_index::cifar::CifarBinary;
+use rs_cinic_10_index::image_folder::{FolderClass, ImageFolderIndex};
 use rs_cinic_10_index::images::{RgbImageBatch, load_bhwc_rgbimagebatch};
 use rs_cinic_10_index::index::DatasetIndex;
+use rs_cinic_10_index::loader::{BatchLoader, LoadedBatch};
+use rs_cinic_10_index::mock::MockIndex;
+use rs_cinic_10_index::packed::PackedDataset;
+use rs_cinic_10_index::patches::{PatchBatch, PatchConfig};
+use rs_cinic_10_index::profile::Stage;
+use rs_cinic_10_index::view::DatasetIndexView;
 use std::path::Path;
 
 fn batch_to_tensordata(batch: RgbImageBatch) -> TensorData {
@@ -26,9 +37,61 @@ where
     B: Backend,
     P: AsRef<Path>,
 {
-    let data = load_bhwc_u8_tensordata_image_batch(paths)?;
-    let tensor = Tensor::from_data(data, device);
-    Ok(tensor)
+    load_bhwc_u8_tensor_image_batch_with(&BatchLoader::default(), paths, device)
+}
+
+/// Load a BHWC u8 tensor batch with the given loader.
+///
+/// When the loader is profiling, the tensor construction is recorded
+/// as `Stage::DeviceTransfer`.
+pub fn load_bhwc_u8_tensor_image_batch_with<B, P>(
+    loader: &BatchLoader,
+    paths: &[P],
+    device: &B::Device,
+) -> Result<Tensor<B, 4>>
+where
+    B: Backend,
+    P: AsRef<Path>,
+{
+    Ok(load_bhwc_u8_tensor_image_batch_report_with(loader, paths, device)?.batch)
+}
+
+/// Load a BHWC u8 tensor batch with the given loader, reporting failed samples.
+pub fn load_bhwc_u8_tensor_image_batch_report_with<B, P>(
+    loader: &BatchLoader,
+    paths: &[P],
+    device: &B::Device,
+) -> Result<LoadedBatch<Tensor<B, 4>>>
+where
+    B: Backend,
+    P: AsRef<Path>,
+{
+    let loaded = loader.load_rgbimagebatch_report(paths)?;
+    Ok(loaded.map(|batch| {
+        let data = batch_to_tensordata(batch);
+        loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device))
+    }))
+}
+
+/// Load a batch as a `[B, N, P*P*C]` f32 patch tensor, reporting failed samples.
+///
+/// When the loader is profiling, the tensor construction is recorded
+/// as `Stage::DeviceTransfer`.
+pub fn load_patch_tensor_batch_report_with<B, P>(
+    loader: &BatchLoader,
+    paths: &[P],
+    config: &PatchConfig,
+    device: &B::Device,
+) -> Result<LoadedBatch<Tensor<B, 3>>>
+where
+    B: Backend,
+    P: AsRef<Path>,
+{
+    let loaded = loader.load_patchbatch_report(paths, config)?;
+    Ok(loaded.map(|batch: PatchBatch| {
+        let data = TensorData::new(batch.data, batch.shape);
+        loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device))
+    }))
 }
 
 pub fn load_hwc_u8_tensor_image<B, P>(
@@ -64,22 +127,279 @@ pub trait WithTensorBatches {
         indexes: &[usize],
         device: &B::Device,
     ) -> Result<Tensor<B, 4>>
+    where
+        B: Backend,
+    {
+        self.load_tensor_batch_with(&BatchLoader::default(), indexes, device)
+    }
+
+    fn load_tensor_batch_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<Tensor<B, 4>>
+    where
+        B: Backend,
+    {
+        Ok(self
+            .load_tensor_batch_report_with(loader, indexes, device)?
+            .batch)
+    }
+
+    fn load_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 4>>>
+    where
+        B: Backend;
+
+    /// Load a `[B, N, P*P*C]` patch tensor batch, for vision-transformer models.
+    fn load_patch_tensor_batch<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+        device: &B::Device,
+    ) -> Result<Tensor<B, 3>>
+    where
+        B: Backend,
+    {
+        Ok(self
+            .load_patch_tensor_batch_report_with(loader, indexes, config, device)?
+            .batch)
+    }
+
+    fn load_patch_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 3>>>
     where
         B: Backend;
 }
 
-impl WithTensorBatches for DatasetIndex {
-    fn load_tensor_batch<B>(
+/// A dataset which loads `RgbImageBatch`es; `WithTensorBatches` uploads them.
+///
+/// Implement this, rather than `WithTensorBatches`, for another dataset type;
+/// path-based datasets should also override `load_patchbatch_report_with()`,
+/// to patchify each sample in the loader's workers.
+pub trait RgbImageBatchSource {
+    /// Load an `RgbImageBatch` with the given loader, reporting failed samples.
+    fn load_rgbimagebatch_report_with(
         &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>>;
+
+    /// Load a `PatchBatch` with the given loader, reporting failed samples.
+    ///
+    /// The default patchifies the loaded `RgbImageBatch`, recorded as `Stage::Copy`.
+    fn load_patchbatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+    ) -> Result<LoadedBatch<PatchBatch>> {
+        let loaded = self.load_rgbimagebatch_report_with(loader, indexes)?;
+        let batch = loader.time(Stage::Copy, || {
+            PatchBatch::from_rgbimagebatch(&loaded.batch, config)
+        })?;
+        Ok(LoadedBatch {
+            batch,
+            failures: loaded.failures,
+        })
+    }
+}
+
+impl<T> WithTensorBatches for T
+where
+    T: RgbImageBatchSource,
+{
+    fn load_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
         indexes: &[usize],
         device: &B::Device,
-    ) -> Result<Tensor<B, 4>>
+    ) -> Result<LoadedBatch<Tensor<B, 4>>>
+    where
+        B: Backend,
+    {
+        let loaded = self.load_rgbimagebatch_report_with(loader, indexes)?;
+        Ok(loaded.map(|batch| {
+            let data = batch_to_tensordata(batch);
+            loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device))
+        }))
+    }
+
+    fn load_patch_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 3>>>
     where
         B: Backend,
     {
-        let paths = self.indices_to_paths(indexes);
-        let tensor = load_bhwc_u8_tensor_image_batch(&paths, device)?;
-        Ok(tensor)
+        let loaded = self.load_patchbatch_report_with(loader, indexes, config)?;
+        Ok(loaded.map(|batch| {
+            let data = TensorData::new(batch.data, batch.shape);
+            loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device))
+        }))
+    }
+}
+
+impl RgbImageBatchSource for DatasetIndex {
+    fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        Ok(DatasetIndex::load_rgbimagebatch_report_with(
+            self, loader, indexes,
+        )?)
+    }
+
+    fn load_patchbatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+    ) -> Result<LoadedBatch<PatchBatch>> {
+        Ok(loader.load_patchbatch_report(&self.indices_to_paths(indexes), config)?)
+    }
+}
+
+impl<C> RgbImageBatchSource for ImageFolderIndex<C>
+where
+    C: FolderClass,
+{
+    fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        Ok(ImageFolderIndex::load_rgbimagebatch_report_with(
+            self, loader, indexes,
+        )?)
+    }
+
+    fn load_patchbatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+    ) -> Result<LoadedBatch<PatchBatch>> {
+        Ok(loader.load_patchbatch_report(&self.indices_to_paths(indexes), config)?)
+    }
+}
+
+impl RgbImageBatchSource for MockIndex {
+    fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        Ok(MockIndex::load_rgbimagebatch_report_with(
+            self, loader, indexes,
+        )?)
+    }
+}
+
+impl RgbImageBatchSource for CifarBinary {
+    fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        Ok(CifarBinary::load_rgbimagebatch_report_with(
+            self, loader, indexes,
+        )?)
+    }
+}
+
+impl WithTensorBatches for DatasetIndexView<'_> {
+    fn load_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 4>>>
+    where
+        B: Backend,
+    {
+        self.source().load_tensor_batch_report_with(
+            loader,
+            &self.to_source_indices(indexes),
+            device,
+        )
+    }
+
+    fn load_patch_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 3>>>
+    where
+        B: Backend,
+    {
+        self.source().load_patch_tensor_batch_report_with(
+            loader,
+            &self.to_source_indices(indexes),
+            config,
+            device,
+        )
+    }
+}
+
+/// Packed splits are already decoded; the loader's transform and color
+/// space are those the split was packed with, and only its profiler is used.
+impl WithTensorBatches for PackedDataset {
+    fn load_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 4>>>
+    where
+        B: Backend,
+    {
+        let batch = loader.time(Stage::Copy, || self.load_rgbimagebatch(indexes))?;
+        let data = batch_to_tensordata(batch);
+        if let Some(profiler) = loader.profiler() {
+            profiler.record_batch();
+        }
+        Ok(LoadedBatch {
+            batch: loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device)),
+            failures: Vec::new(),
+        })
+    }
+
+    fn load_patch_tensor_batch_report_with<B>(
+        &self,
+        loader: &BatchLoader,
+        indexes: &[usize],
+        config: &PatchConfig,
+        device: &B::Device,
+    ) -> Result<LoadedBatch<Tensor<B, 3>>>
+    where
+        B: Backend,
+    {
+        let batch = loader.time(Stage::Copy, || {
+            PatchBatch::from_rgbimagebatch(&self.load_rgbimagebatch(indexes)?, config)
+        })?;
+        let data = TensorData::new(batch.data, batch.shape);
+        Ok(LoadedBatch {
+            batch: loader.time(Stage::DeviceTransfer, || Tensor::from_data(data, device)),
+            failures: Vec::new(),
+        })
     }
 }
 
@@ -89,6 +409,7 @@ mod tests {
     use burn::backend::NdArray;
 
     use rs_cinic_10_index::index::{CHANNELS, HEIGHT, ObjectClass, SAMPLES_PER_CLASS, WIDTH};
+    use rs_cinic_10_index::loader::LoaderConfig;
     use rs_cinic_10_index::{Cinic10Index, default_data_path_or_panic};
 
     #[test]
@@ -120,6 +441,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_mock_tensor_batch() -> Result<()> {
+        let mock = MockIndex::new(16, 0);
+        let device = Default::default();
+        let tensor: Tensor<NdArray, 4> = mock.load_tensor_batch(&[0, 3, 15], &device)?;
+        assert_eq!(tensor.dims(), [3, 32, 32, 3]);
+
+        let patches: Tensor<NdArray, 3> = mock.load_patch_tensor_batch(
+            &BatchLoader::default(),
+            &[0, 1],
+            &PatchConfig::default(),
+            &device,
+        )?;
+        assert_eq!(patches.dims()[0], 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_device_transfer() -> Result<()> {
+        let mock = MockIndex::new(16, 0);
+        let loader = BatchLoader::new(LoaderConfig {
+            profile: true,
+            ..Default::default()
+        });
+        let device = Default::default();
+        let _: Tensor<NdArray, 4> = mock.load_tensor_batch_with(&loader, &[0, 1], &device)?;
+
+        let report = loader.profile_report().unwrap();
+        assert_eq!(report.stage(Stage::DeviceTransfer).count, 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_load_test_batch() -> Result<()> {
         let cinic: Cinic10Index = Default::default();
@@ -0,0 +1,564 @@
+use crate::error::{Result, bail};
+use crate::images::{RgbImageBatch, decode_rgbimage};
+use crate::index::{
+    CONTRIB_FILE, Cinic10Index, DataSet, DatasetIndex, DatasetItem, ObjectClass, SYNSET_FILE,
+    parse_contrib_index, parse_synset_map,
+};
+use crate::source::DataSource;
+use anyhow::Context;
+use enum_ordinalize::Ordinalize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// A CINIC-10 archive, read in place without extracting it.
+///
+/// Extracting 270k small PNGs is slow, and wastes inodes, on networked
+/// filesystems. Files are addressed by their path in the dataset, e.g.
+/// `train/cat/x.png`, or by an index path under the archive path; see
+/// `Cinic10Index::new_from_archive()`. A top-level directory wrapping the
+/// dataset is skipped.
+pub trait DatasetArchive: Send + Sync {
+    /// The archive file.
+    fn path(&self) -> &Path;
+
+    /// The dataset files in the archive, relative to the dataset.
+    fn files(&self) -> Vec<&Path>;
+
+    /// Read the bytes of one file.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The file, relative to the dataset, or under `path()`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the bytes; or an error if the file is not in the archive.
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>>;
+
+    /// Read and decode a batch of images from the archive.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: The images; see `read()`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the batch.
+    fn load_rgbimagebatch<P>(
+        &self,
+        paths: &[P],
+    ) -> Result<RgbImageBatch>
+    where
+        Self: Sized,
+        P: AsRef<Path>,
+    {
+        let mut batch: Option<RgbImageBatch> = None;
+        for path in paths {
+            let path = path.as_ref();
+            let img = decode_rgbimage(&self.read(path)?)
+                .with_context(|| format!("Failed to decode {}", path.display()))?;
+            let batch = batch.get_or_insert_with(|| {
+                RgbImageBatch::new(&[paths.len(), img.height() as usize, img.width() as usize, 3])
+            });
+            if (img.width() as usize, img.height() as usize) != (batch.width(), batch.height()) {
+                bail!("Image dimensions do not match: {}", path.display());
+            }
+            batch.push_rgb_pixels(&img);
+        }
+        let Some(batch) = batch else {
+            bail!("Cannot load an empty batch");
+        };
+        Ok(batch)
+    }
+}
+
+/// The path of an archive entry in the dataset: from the split directory
+/// for images, or the file name for the metadata files.
+fn dataset_path(entry_path: &Path) -> Option<PathBuf> {
+    let parts: Vec<&str> = entry_path.iter().filter_map(|c| c.to_str()).collect();
+    match parts.as_slice() {
+        [.., split, class, name]
+            if DataSet::NAMES.contains(split)
+                && ObjectClass::from_str(class).is_ok()
+                && name.ends_with(".png") =>
+        {
+            Some([split, class, name].iter().collect())
+        }
+        [.., name] if *name == CONTRIB_FILE || *name == SYNSET_FILE => Some(PathBuf::from(name)),
+        _ => None,
+    }
+}
+
+/// The key of `path` in an archive at `archive_path`.
+fn archive_key<'a>(
+    archive_path: &Path,
+    path: &'a Path,
+) -> &'a Path {
+    path.strip_prefix(archive_path).unwrap_or(path)
+}
+
+/// The names of the archive files directly in `dir`, sorted; see `DataSource::list()`.
+fn list_archive<A>(
+    archive: &A,
+    dir: &Path,
+) -> Vec<String>
+where
+    A: DatasetArchive,
+{
+    let dir = archive_key(archive.path(), dir);
+    let mut names: Vec<String> = archive
+        .files()
+        .into_iter()
+        .filter(|rel| rel.parent() == Some(dir))
+        .filter_map(|rel| rel.file_name()?.to_str().map(str::to_string))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Where the bytes of a tar archive's files are kept.
+#[cfg(feature = "download")]
+#[derive(Debug)]
+enum Storage {
+    /// Seek into the uncompressed `.tar` file.
+    File(Mutex<File>),
+
+    /// The decompressed files of a `.tar.gz`, back to back.
+    Memory(Vec<u8>),
+}
+
+/// A CINIC-10 tar archive; see `DatasetArchive`.
+///
+/// An uncompressed `.tar` is read on demand, by seeking to each file; a gzip
+/// stream can't seek, so a `.tar.gz` is decompressed once, and its files held
+/// in memory (about 0.7 GiB for CINIC-10).
+#[cfg(feature = "download")]
+#[derive(Debug)]
+pub struct TarArchive {
+    path: PathBuf,
+    entries: HashMap<PathBuf, (u64, u64)>,
+    storage: Storage,
+}
+
+#[cfg(feature = "download")]
+impl TarArchive {
+    /// Index the files of a `.tar` or `.tar.gz` archive.
+    ///
+    /// The format is inferred from the extension: `.gz` and `.tgz` are gzipped.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The archive, e.g. the downloaded `CINIC-10.tar.gz`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the archive.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open archive {}", path.display()))?;
+        let gzipped = path
+            .extension()
+            .is_some_and(|ext| ext == "gz" || ext == "tgz");
+
+        let mut entries = HashMap::new();
+        let storage = (|| -> Result<Storage> {
+            if gzipped {
+                let mut data = Vec::new();
+                let decoder = flate2::read::GzDecoder::new(BufReader::new(file));
+                let mut archive = tar::Archive::new(decoder);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let Some(rel) = dataset_path(&entry.path()?) else {
+                        continue;
+                    };
+                    let offset = data.len() as u64;
+                    entry.read_to_end(&mut data)?;
+                    entries.insert(rel, (offset, data.len() as u64 - offset));
+                }
+                data.shrink_to_fit();
+                Ok(Storage::Memory(data))
+            } else {
+                let mut archive = tar::Archive::new(BufReader::new(file));
+                for entry in archive.entries()? {
+                    let entry = entry?;
+                    if let Some(rel) = dataset_path(&entry.path()?) {
+                        entries.insert(rel, (entry.raw_file_position(), entry.size()));
+                    }
+                }
+                Ok(Storage::File(Mutex::new(File::open(path)?)))
+            }
+        })()
+        .with_context(|| format!("Failed to read archive {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+            storage,
+        })
+    }
+
+    /// The number of dataset files in the archive.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(feature = "download")]
+impl DatasetArchive for TarArchive {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn files(&self) -> Vec<&Path> {
+        self.entries.keys().map(PathBuf::as_path).collect()
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        use std::io::{Seek, SeekFrom};
+
+        let Some(&(offset, size)) = self.entries.get(archive_key(&self.path, path)) else {
+            bail!(
+                "{} is not in archive {}",
+                path.display(),
+                self.path.display()
+            );
+        };
+        match &self.storage {
+            Storage::Memory(data) => Ok(data[offset as usize..(offset + size) as usize].to_vec()),
+            Storage::File(file) => {
+                let mut file = file.lock().unwrap();
+                let mut bytes = vec![0u8; size as usize];
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "download")]
+impl DataSource for TarArchive {
+    fn name(&self) -> &str {
+        "tar"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        DatasetArchive::read(self, path)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        Ok(list_archive(self, dir))
+    }
+}
+
+/// A CINIC-10 zip archive; see `DatasetArchive`.
+///
+/// Zip files have a central directory, so each file is read (and inflated)
+/// on demand; common for datasets repackaged for shared cluster storage.
+#[cfg(feature = "zip")]
+#[derive(Debug)]
+pub struct ZipArchive {
+    path: PathBuf,
+    entries: HashMap<PathBuf, usize>,
+    zip: Mutex<zip::ZipArchive<BufReader<File>>>,
+}
+
+#[cfg(feature = "zip")]
+impl ZipArchive {
+    /// Index the files of a `.zip` archive.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The archive.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the archive.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open archive {}", path.display()))?;
+        let mut zip = zip::ZipArchive::new(BufReader::new(file))
+            .with_context(|| format!("Failed to read archive {}", path.display()))?;
+        let mut entries = HashMap::new();
+        for i in 0..zip.len() {
+            let entry = zip
+                .by_index_raw(i)
+                .context("Failed to read archive entry")?;
+            if let Some(rel) = entry.enclosed_name().as_deref().and_then(dataset_path) {
+                entries.insert(rel, i);
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+            zip: Mutex::new(zip),
+        })
+    }
+
+    /// The number of dataset files in the archive.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(feature = "zip")]
+impl DatasetArchive for ZipArchive {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn files(&self) -> Vec<&Path> {
+        self.entries.keys().map(PathBuf::as_path).collect()
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        let Some(&index) = self.entries.get(archive_key(&self.path, path)) else {
+            bail!(
+                "{} is not in archive {}",
+                path.display(),
+                self.path.display()
+            );
+        };
+        let mut zip = self.zip.lock().unwrap();
+        let mut entry = zip
+            .by_index(index)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "zip")]
+impl DataSource for ZipArchive {
+    fn name(&self) -> &str {
+        "zip"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        DatasetArchive::read(self, path)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        Ok(list_archive(self, dir))
+    }
+}
+
+/// Index one split of an archive, in the order of `Cinic10Index::new_from_dir()`.
+fn split_index<A>(
+    archive: &A,
+    data_set: DataSet,
+) -> DatasetIndex
+where
+    A: DatasetArchive,
+{
+    let ds_path = archive.path().join(data_set.to_string());
+    let mut items: Vec<DatasetItem> = archive
+        .files()
+        .into_iter()
+        .filter_map(|rel| {
+            let mut parts = rel.iter().filter_map(|c| c.to_str());
+            let (Some(split), Some(class), Some(name)) = (parts.next(), parts.next(), parts.next())
+            else {
+                return None;
+            };
+            if split != data_set.to_string() {
+                return None;
+            }
+            Some(DatasetItem {
+                class: ObjectClass::from_str(class).ok()?,
+                path: PathBuf::from(name),
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| (a.class.ordinal(), &a.path).cmp(&(b.class.ordinal(), &b.path)));
+    DatasetIndex::new(ds_path, items)
+}
+
+impl Cinic10Index {
+    /// Index the dataset in an archive, without extracting it.
+    ///
+    /// The index's root is the archive path, so item paths such as
+    /// `index_to_path()` name archive entries, not files; load them with
+    /// `DatasetArchive::load_rgbimagebatch()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `archive`: The opened archive; e.g. a `TarArchive` or `ZipArchive`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index`.
+    pub fn new_from_archive<A>(archive: &A) -> Result<Cinic10Index>
+    where
+        A: DatasetArchive,
+    {
+        let train = split_index(archive, DataSet::Train);
+        let test = split_index(archive, DataSet::Test);
+        let valid = split_index(archive, DataSet::Valid);
+        if train.is_empty() && test.is_empty() && valid.is_empty() {
+            bail!(
+                "Archive {} does not contain the CINIC-10 splits",
+                archive.path().display()
+            );
+        }
+        Ok(Cinic10Index::new(
+            archive.path().to_path_buf(),
+            parse_contrib_index(archive.read(Path::new(CONTRIB_FILE))?.as_slice())?,
+            parse_synset_map(archive.read(Path::new(SYNSET_FILE))?.as_slice())?,
+            train,
+            test,
+            valid,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+    use std::fs;
+
+    /// Write a small dataset under `{dir}/CINIC-10`, returning its root.
+    fn write_dataset(dir: &Path) -> Result<PathBuf> {
+        let root = dir.join("CINIC-10");
+        for data_set in DataSet::ALL {
+            for (i, class) in [ObjectClass::Ship, ObjectClass::Cat]
+                .into_iter()
+                .enumerate()
+            {
+                let class_dir = root.join(data_set.to_string()).join(class.to_string());
+                fs::create_dir_all(&class_dir)?;
+                RgbImage::from_pixel(2, 2, Rgb([i as u8, 0, 0])).save(class_dir.join("a.png"))?;
+            }
+        }
+        fs::write(
+            root.join(CONTRIB_FILE),
+            "synset,image_num,cinic_set,class\n",
+        )?;
+        fs::write(root.join(SYNSET_FILE), "cat\n")?;
+        Ok(root)
+    }
+
+    fn check_archive<A>(archive: &A) -> Result<()>
+    where
+        A: DatasetArchive + DataSource,
+    {
+        assert_eq!(archive.files().len(), 8);
+        let cinic = Cinic10Index::new_from_archive(archive)?;
+        assert_eq!(
+            cinic.train.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        assert_eq!(
+            cinic.valid.index_to_path(0),
+            archive.path().join("valid/cat/a.png")
+        );
+
+        let batch = archive.load_rgbimagebatch(&cinic.test.indices_to_paths(&[0, 1]))?;
+        assert_eq!(batch.shape, vec![2, 2, 2, 3]);
+        assert_eq!(batch.data[0], 1);
+        assert!(DatasetArchive::read(archive, Path::new("train/dog/a.png")).is_err());
+
+        let from_source = Cinic10Index::new_from_source(archive, archive.path())?;
+        assert_eq!(
+            from_source.test.indices_to_paths(&[0, 1]),
+            cinic.test.indices_to_paths(&[0, 1])
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_tar_archive() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = write_dataset(dir.path())?;
+
+        let plain = dir.path().join("cinic.tar");
+        let mut builder = tar::Builder::new(File::create(&plain)?);
+        builder.append_dir_all("CINIC-10", &root)?;
+        builder.into_inner()?;
+        let gzipped = dir.path().join("cinic.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&gzipped)?,
+            flate2::Compression::fast(),
+        ));
+        builder.append_dir_all("CINIC-10", &root)?;
+        builder.into_inner()?.finish()?;
+
+        for path in [plain, gzipped] {
+            let archive = TarArchive::open(&path)?;
+            assert_eq!(archive.len(), 8);
+            check_archive(&archive)?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_archive() -> Result<()> {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir()?;
+        let root = write_dataset(dir.path())?;
+        let path = dir.path().join("cinic.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&path)?);
+        let (_, files) = crate::diff::walk(&root)?;
+        for rel in files {
+            let name = Path::new("CINIC-10").join(&rel);
+            writer.start_file(
+                name.to_str().unwrap(),
+                zip::write::SimpleFileOptions::default(),
+            )?;
+            writer.write_all(&fs::read(root.join(&rel))?)?;
+        }
+        writer.finish()?;
+
+        let archive = ZipArchive::open(&path)?;
+        assert_eq!(archive.len(), 8);
+        check_archive(&archive)
+    }
+}
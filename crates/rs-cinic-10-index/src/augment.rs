@@ -0,0 +1,676 @@
+use crate::error::{Result, bail};
+use crate::record::{ComponentRecord, Recordable};
+use crate::transform::ImageTransform;
+use image::{Rgb, RgbImage};
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A single image operation of the augmentation policies.
+///
+/// These follow the `torchvision` definitions, on `u8` images; geometric
+/// operations sample nearest-neighbour, and fill with black.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AugmentOp {
+    Identity,
+    ShearX,
+    ShearY,
+    TranslateX,
+    TranslateY,
+    Rotate,
+    Brightness,
+    Color,
+    Contrast,
+    Sharpness,
+    Posterize,
+    Solarize,
+    AutoContrast,
+    Equalize,
+    Invert,
+}
+
+/// The magnitude ranges of an augmentation space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AugmentSpace {
+    /// The AutoAugment / RandAugment ranges.
+    Standard,
+
+    /// The wider TrivialAugment ranges.
+    Wide,
+}
+
+/// `linspace(start, end, bins)[bin]`.
+fn linspace(
+    start: f32,
+    end: f32,
+    bin: usize,
+    bins: usize,
+) -> f32 {
+    if bins <= 1 {
+        return start;
+    }
+    start + (end - start) * bin as f32 / (bins - 1) as f32
+}
+
+impl AugmentOp {
+    /// The operations of the RandAugment and TrivialAugment spaces.
+    const RANDOM_OPS: [AugmentOp; 14] = [
+        AugmentOp::Identity,
+        AugmentOp::ShearX,
+        AugmentOp::ShearY,
+        AugmentOp::TranslateX,
+        AugmentOp::TranslateY,
+        AugmentOp::Rotate,
+        AugmentOp::Brightness,
+        AugmentOp::Color,
+        AugmentOp::Contrast,
+        AugmentOp::Sharpness,
+        AugmentOp::Posterize,
+        AugmentOp::Solarize,
+        AugmentOp::AutoContrast,
+        AugmentOp::Equalize,
+    ];
+
+    /// Does the operation's magnitude take a random sign?
+    fn is_signed(self) -> bool {
+        matches!(
+            self,
+            AugmentOp::ShearX
+                | AugmentOp::ShearY
+                | AugmentOp::TranslateX
+                | AugmentOp::TranslateY
+                | AugmentOp::Rotate
+                | AugmentOp::Brightness
+                | AugmentOp::Color
+                | AugmentOp::Contrast
+                | AugmentOp::Sharpness
+        )
+    }
+
+    /// The unsigned magnitude of `bin` (of `bins`) in `space`, for an image of `(width, height)`.
+    fn magnitude(
+        self,
+        space: AugmentSpace,
+        bin: usize,
+        bins: usize,
+        (width, height): (u32, u32),
+    ) -> f32 {
+        let wide = space == AugmentSpace::Wide;
+        match self {
+            AugmentOp::ShearX | AugmentOp::ShearY => {
+                linspace(0.0, if wide { 0.99 } else { 0.3 }, bin, bins)
+            }
+            AugmentOp::TranslateX => {
+                let max = if wide {
+                    32.0
+                } else {
+                    150.0 / 331.0 * width as f32
+                };
+                linspace(0.0, max, bin, bins)
+            }
+            AugmentOp::TranslateY => {
+                let max = if wide {
+                    32.0
+                } else {
+                    150.0 / 331.0 * height as f32
+                };
+                linspace(0.0, max, bin, bins)
+            }
+            AugmentOp::Rotate => linspace(0.0, if wide { 135.0 } else { 30.0 }, bin, bins),
+            AugmentOp::Brightness
+            | AugmentOp::Color
+            | AugmentOp::Contrast
+            | AugmentOp::Sharpness => linspace(0.0, if wide { 0.99 } else { 0.9 }, bin, bins),
+            AugmentOp::Posterize => {
+                let steps = if wide { 6.0 } else { 4.0 };
+                let step = (bins.max(2) - 1) as f32 / steps;
+                8.0 - (bin as f32 / step).round_ties_even()
+            }
+            AugmentOp::Solarize => linspace(255.0, 0.0, bin, bins),
+            AugmentOp::Identity
+            | AugmentOp::AutoContrast
+            | AugmentOp::Equalize
+            | AugmentOp::Invert => 0.0,
+        }
+    }
+
+    /// Apply the operation to an image.
+    ///
+    /// # Parameters
+    ///
+    /// - `img`: The image.
+    /// - `magnitude`: The signed magnitude: a shear factor, a translation in
+    ///   pixels, a counter-clockwise rotation in degrees, an enhancement
+    ///   factor offset from `1.0`, a number of bits kept, or a solarize threshold.
+    ///   Ignored by `Identity`, `AutoContrast`, `Equalize` and `Invert`.
+    ///
+    /// # Returns
+    ///
+    /// The transformed image.
+    pub fn apply(
+        self,
+        img: &RgbImage,
+        magnitude: f32,
+    ) -> RgbImage {
+        match self {
+            AugmentOp::Identity => img.clone(),
+            AugmentOp::ShearX => affine(img, |x, y| (x - magnitude * y, y), false),
+            AugmentOp::ShearY => affine(img, |x, y| (x, y - magnitude * x), false),
+            AugmentOp::TranslateX => {
+                let t = magnitude.trunc();
+                affine(img, |x, y| (x - t, y), false)
+            }
+            AugmentOp::TranslateY => {
+                let t = magnitude.trunc();
+                affine(img, |x, y| (x, y - t), false)
+            }
+            AugmentOp::Rotate => {
+                let (sin, cos) = magnitude.to_radians().sin_cos();
+                affine(img, |x, y| (x * cos - y * sin, x * sin + y * cos), true)
+            }
+            AugmentOp::Brightness => blend(
+                img,
+                &RgbImage::new(img.width(), img.height()),
+                1.0 + magnitude,
+            ),
+            AugmentOp::Color => {
+                let mut gray = img.clone();
+                for px in gray.pixels_mut() {
+                    let l = luma(px);
+                    *px = Rgb([l, l, l]);
+                }
+                blend(img, &gray, 1.0 + magnitude)
+            }
+            AugmentOp::Contrast => {
+                let n = (img.width() * img.height()).max(1) as f32;
+                let mean = img.pixels().map(|px| luma(px) as f32).sum::<f32>() / n;
+                let mean = mean.round() as u8;
+                let degenerate = RgbImage::from_pixel(img.width(), img.height(), Rgb([mean; 3]));
+                blend(img, &degenerate, 1.0 + magnitude)
+            }
+            AugmentOp::Sharpness => blend(img, &smooth(img), 1.0 + magnitude),
+            AugmentOp::Posterize => {
+                let bits = magnitude.clamp(0.0, 8.0) as u32;
+                let mask = !((1u16 << (8 - bits)) - 1) as u8;
+                map_values(img, |v| v & mask)
+            }
+            AugmentOp::Solarize => {
+                map_values(img, |v| if v as f32 >= magnitude { 255 - v } else { v })
+            }
+            AugmentOp::AutoContrast => {
+                let mut luts = [[0u8; 256]; 3];
+                for (ch, lut) in luts.iter_mut().enumerate() {
+                    let (lo, hi) = img.pixels().fold((255u8, 0u8), |(lo, hi), px| {
+                        (lo.min(px[ch]), hi.max(px[ch]))
+                    });
+                    for (v, out) in lut.iter_mut().enumerate() {
+                        *out = if hi <= lo {
+                            v as u8
+                        } else {
+                            let scale = 255.0 / (hi - lo) as f32;
+                            ((v as f32 - lo as f32) * scale).clamp(0.0, 255.0) as u8
+                        };
+                    }
+                }
+                apply_luts(img, &luts)
+            }
+            AugmentOp::Equalize => {
+                let mut luts = [[0u8; 256]; 3];
+                for (ch, lut) in luts.iter_mut().enumerate() {
+                    let mut hist = [0usize; 256];
+                    for px in img.pixels() {
+                        hist[px[ch] as usize] += 1;
+                    }
+                    *lut = equalize_lut(&hist);
+                }
+                apply_luts(img, &luts)
+            }
+            AugmentOp::Invert => map_values(img, |v| 255 - v),
+        }
+    }
+}
+
+/// The ITU-R 601-2 luma of a pixel.
+fn luma(px: &Rgb<u8>) -> u8 {
+    (0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32).round() as u8
+}
+
+/// Map every channel value of an image.
+fn map_values<F>(
+    img: &RgbImage,
+    f: F,
+) -> RgbImage
+where
+    F: Fn(u8) -> u8,
+{
+    let mut out = img.clone();
+    for v in out.iter_mut() {
+        *v = f(*v);
+    }
+    out
+}
+
+/// Map each channel through its lookup table.
+fn apply_luts(
+    img: &RgbImage,
+    luts: &[[u8; 256]; 3],
+) -> RgbImage {
+    let mut out = img.clone();
+    for px in out.pixels_mut() {
+        for ch in 0..3 {
+            px[ch] = luts[ch][px[ch] as usize];
+        }
+    }
+    out
+}
+
+/// The histogram equalization lookup table of one channel, as in PIL.
+fn equalize_lut(hist: &[usize; 256]) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let total: usize = hist.iter().sum();
+    let last = hist.iter().rev().find(|&&c| c > 0).copied().unwrap_or(0);
+    let step = (total - last) / 255;
+    if step == 0 {
+        for (v, out) in lut.iter_mut().enumerate() {
+            *out = v as u8;
+        }
+        return lut;
+    }
+    let mut n = step / 2;
+    for (v, out) in lut.iter_mut().enumerate() {
+        *out = (n / step).min(255) as u8;
+        n += hist[v];
+    }
+    lut
+}
+
+/// Interpolate from `degenerate` towards (and past) `img`: `degenerate + factor * (img - degenerate)`.
+fn blend(
+    img: &RgbImage,
+    degenerate: &RgbImage,
+    factor: f32,
+) -> RgbImage {
+    let mut out = img.clone();
+    for (v, d) in out.iter_mut().zip(degenerate.iter()) {
+        let d = *d as f32;
+        *v = (d + factor * (*v as f32 - d)).round().clamp(0.0, 255.0) as u8;
+    }
+    out
+}
+
+/// The PIL `SMOOTH` filter, leaving the border unchanged.
+fn smooth(img: &RgbImage) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let mut out = img.clone();
+    for y in 1..h.saturating_sub(1) {
+        for x in 1..w.saturating_sub(1) {
+            for ch in 0..3 {
+                let mut sum = 0u32;
+                for dy in 0..3 {
+                    for dx in 0..3 {
+                        let weight = if dx == 1 && dy == 1 { 5 } else { 1 };
+                        sum += weight * img.get_pixel(x + dx - 1, y + dy - 1)[ch] as u32;
+                    }
+                }
+                out.get_pixel_mut(x, y)[ch] = ((sum as f32) / 13.0).round() as u8;
+            }
+        }
+    }
+    out
+}
+
+/// Resample an image through an inverse coordinate map, nearest-neighbour, filling with black.
+///
+/// `src` maps an output coordinate to a source coordinate; relative to the
+/// image center when `centered`, and to the top-left pixel otherwise.
+fn affine<F>(
+    img: &RgbImage,
+    src: F,
+    centered: bool,
+) -> RgbImage
+where
+    F: Fn(f32, f32) -> (f32, f32),
+{
+    let (w, h) = img.dimensions();
+    let (cx, cy) = if centered {
+        ((w as f32 - 1.0) / 2.0, (h as f32 - 1.0) / 2.0)
+    } else {
+        (0.0, 0.0)
+    };
+    RgbImage::from_fn(w, h, |x, y| {
+        let (sx, sy) = src(x as f32 - cx, y as f32 - cy);
+        let (sx, sy) = ((sx + cx).round(), (sy + cy).round());
+        if sx >= 0.0 && sy >= 0.0 && sx < w as f32 && sy < h as f32 {
+            *img.get_pixel(sx as u32, sy as u32)
+        } else {
+            Rgb([0, 0, 0])
+        }
+    })
+}
+
+/// Apply `op` at magnitude `bin` of `bins`, with a random sign for signed operations.
+fn apply_binned(
+    op: AugmentOp,
+    img: &RgbImage,
+    space: AugmentSpace,
+    bin: usize,
+    bins: usize,
+    rng: &mut dyn RngCore,
+) -> RgbImage {
+    let mut magnitude = op.magnitude(space, bin, bins, img.dimensions());
+    if op.is_signed() && rng.random::<bool>() {
+        magnitude = -magnitude;
+    }
+    op.apply(img, magnitude)
+}
+
+/// One step of an AutoAugment sub-policy: `(op, probability, magnitude bin)`.
+type PolicyStep = (AugmentOp, f32, usize);
+
+/// The AutoAugment magnitude bins.
+const AUTOAUGMENT_BINS: usize = 10;
+
+/// The published AutoAugment CIFAR-10 policy (Cubuk et al., 2019).
+const CIFAR10_POLICY: [[PolicyStep; 2]; 25] = {
+    use AugmentOp::*;
+    [
+        [(Invert, 0.1, 0), (Contrast, 0.2, 6)],
+        [(Rotate, 0.7, 2), (TranslateX, 0.3, 9)],
+        [(Sharpness, 0.8, 1), (Sharpness, 0.9, 3)],
+        [(ShearY, 0.5, 8), (TranslateY, 0.7, 9)],
+        [(AutoContrast, 0.5, 0), (Equalize, 0.9, 0)],
+        [(ShearY, 0.2, 7), (Posterize, 0.3, 7)],
+        [(Color, 0.4, 3), (Brightness, 0.6, 7)],
+        [(Sharpness, 0.3, 9), (Brightness, 0.7, 9)],
+        [(Equalize, 0.6, 0), (Equalize, 0.5, 0)],
+        [(Contrast, 0.6, 7), (Sharpness, 0.6, 5)],
+        [(Color, 0.7, 7), (TranslateX, 0.5, 8)],
+        [(Equalize, 0.3, 0), (AutoContrast, 0.4, 0)],
+        [(TranslateY, 0.4, 3), (Sharpness, 0.2, 6)],
+        [(Brightness, 0.9, 6), (Color, 0.2, 8)],
+        [(Solarize, 0.5, 2), (Invert, 0.0, 0)],
+        [(Equalize, 0.2, 0), (AutoContrast, 0.6, 0)],
+        [(Equalize, 0.2, 0), (Equalize, 0.6, 0)],
+        [(Color, 0.9, 9), (Equalize, 0.6, 0)],
+        [(AutoContrast, 0.8, 0), (Solarize, 0.2, 8)],
+        [(Brightness, 0.1, 3), (Color, 0.7, 0)],
+        [(Solarize, 0.4, 5), (AutoContrast, 0.9, 0)],
+        [(TranslateY, 0.9, 9), (TranslateY, 0.7, 9)],
+        [(AutoContrast, 0.9, 0), (Solarize, 0.8, 3)],
+        [(Equalize, 0.8, 0), (Invert, 0.1, 0)],
+        [(TranslateY, 0.7, 9), (AutoContrast, 0.9, 0)],
+    ]
+};
+
+/// AutoAugment (Cubuk et al., 2019), with the published CIFAR-10 policy.
+///
+/// Each image gets one random sub-policy, of two operations, each applied
+/// with its own probability and magnitude.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoAugment;
+
+impl Recordable for AutoAugment {
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("auto_augment").with_param("policy", "cifar10")
+    }
+}
+
+impl ImageTransform for AutoAugment {
+    fn apply(
+        &self,
+        mut img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        let sub_policy = &CIFAR10_POLICY[rng.random_range(0..CIFAR10_POLICY.len())];
+        for &(op, probability, bin) in sub_policy {
+            if rng.random::<f32>() < probability {
+                img = apply_binned(op, &img, AugmentSpace::Standard, bin, AUTOAUGMENT_BINS, rng);
+            }
+        }
+        Ok(img)
+    }
+}
+
+/// RandAugment (Cubuk et al., 2020): `num_ops` uniformly chosen operations, at a fixed magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RandAugment {
+    pub num_ops: usize,
+
+    /// The magnitude bin, in `0..num_magnitude_bins`.
+    pub magnitude: usize,
+
+    pub num_magnitude_bins: usize,
+}
+
+impl Default for RandAugment {
+    fn default() -> Self {
+        Self {
+            num_ops: 2,
+            magnitude: 9,
+            num_magnitude_bins: 31,
+        }
+    }
+}
+
+impl RandAugment {
+    pub fn new(
+        num_ops: usize,
+        magnitude: usize,
+        num_magnitude_bins: usize,
+    ) -> Result<Self> {
+        if magnitude >= num_magnitude_bins {
+            bail!(
+                "Magnitude {magnitude} must be less than num_magnitude_bins ({num_magnitude_bins})"
+            );
+        }
+        Ok(Self {
+            num_ops,
+            magnitude,
+            num_magnitude_bins,
+        })
+    }
+}
+
+impl Recordable for RandAugment {
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("rand_augment")
+            .with_param("num_ops", self.num_ops)
+            .with_param("magnitude", self.magnitude)
+            .with_param("num_magnitude_bins", self.num_magnitude_bins)
+    }
+}
+
+impl ImageTransform for RandAugment {
+    fn apply(
+        &self,
+        mut img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        for _ in 0..self.num_ops {
+            let op = AugmentOp::RANDOM_OPS[rng.random_range(0..AugmentOp::RANDOM_OPS.len())];
+            img = apply_binned(
+                op,
+                &img,
+                AugmentSpace::Standard,
+                self.magnitude,
+                self.num_magnitude_bins,
+                rng,
+            );
+        }
+        Ok(img)
+    }
+}
+
+/// TrivialAugment Wide (Müller & Hutter, 2021): one uniformly chosen operation, at a uniformly chosen magnitude.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrivialAugment {
+    pub num_magnitude_bins: usize,
+}
+
+impl Default for TrivialAugment {
+    fn default() -> Self {
+        Self {
+            num_magnitude_bins: 31,
+        }
+    }
+}
+
+impl Recordable for TrivialAugment {
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("trivial_augment")
+            .with_param("num_magnitude_bins", self.num_magnitude_bins)
+    }
+}
+
+impl ImageTransform for TrivialAugment {
+    fn apply(
+        &self,
+        img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        let op = AugmentOp::RANDOM_OPS[rng.random_range(0..AugmentOp::RANDOM_OPS.len())];
+        let bin = rng.random_range(0..self.num_magnitude_bins.max(1));
+        Ok(apply_binned(
+            op,
+            &img,
+            AugmentSpace::Wide,
+            bin,
+            self.num_magnitude_bins,
+            rng,
+        ))
+    }
+}
+
+/// The augmentation policies, selectable by name; e.g. `"rand_augment".parse()`.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AugmentPolicy {
+    AutoAugment,
+    RandAugment,
+    TrivialAugment,
+}
+
+impl AugmentPolicy {
+    /// The policy's transform, with its default settings.
+    pub fn transform(self) -> Arc<dyn ImageTransform> {
+        match self {
+            AugmentPolicy::AutoAugment => Arc::new(AutoAugment),
+            AugmentPolicy::RandAugment => Arc::new(RandAugment::default()),
+            AugmentPolicy::TrivialAugment => Arc::new(TrivialAugment::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::sample_rng;
+    use anyhow::Result;
+    use std::path::Path;
+    use std::str::FromStr;
+    use strum::IntoEnumIterator;
+
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(8, 8, |x, y| Rgb([(x * 30) as u8, (y * 30) as u8, 100]))
+    }
+
+    #[test]
+    fn test_augment_ops() {
+        let img = gradient();
+        for op in [
+            AugmentOp::Identity,
+            AugmentOp::ShearX,
+            AugmentOp::TranslateY,
+            AugmentOp::Rotate,
+            AugmentOp::Brightness,
+            AugmentOp::Color,
+            AugmentOp::Contrast,
+            AugmentOp::Sharpness,
+        ] {
+            assert_eq!(op.apply(&img, 0.0), img, "{op}");
+        }
+        assert_eq!(AugmentOp::Posterize.apply(&img, 8.0), img);
+        assert_eq!(AugmentOp::Solarize.apply(&img, 256.0), img);
+        assert_eq!(
+            AugmentOp::Invert.apply(&AugmentOp::Invert.apply(&img, 0.0), 0.0),
+            img
+        );
+
+        let shifted = AugmentOp::TranslateX.apply(&img, 2.0);
+        assert_eq!(shifted.get_pixel(0, 3), &Rgb([0, 0, 0]));
+        assert_eq!(shifted.get_pixel(2, 3), img.get_pixel(0, 3));
+        assert_eq!(
+            AugmentOp::Rotate.apply(&AugmentOp::Rotate.apply(&img, 90.0), -90.0),
+            img
+        );
+        assert_eq!(
+            AugmentOp::Posterize.apply(&img, 1.0).get_pixel(5, 5)[0],
+            128
+        );
+        assert_eq!(AugmentOp::Brightness.apply(&img, -1.0), RgbImage::new(8, 8));
+
+        let dim = map_values(&img, |v| v / 4 + 10);
+        let stretched = AugmentOp::AutoContrast.apply(&dim, 0.0);
+        assert_eq!(stretched.get_pixel(0, 0)[0], 0);
+        assert_eq!(stretched.get_pixel(7, 0)[0], 255);
+        let ramp = RgbImage::from_fn(32, 32, |x, _| Rgb([(x * 2 + 10) as u8, 0, 0]));
+        let equalized = AugmentOp::Equalize.apply(&ramp, 0.0);
+        assert_eq!(equalized.get_pixel(0, 0)[0], 0);
+        assert_eq!(equalized.get_pixel(31, 0)[0], 255);
+    }
+
+    #[test]
+    fn test_augment_policies() -> Result<()> {
+        let img = gradient();
+        let path = Path::new("train/cat/a.png");
+        for policy in AugmentPolicy::iter() {
+            assert_eq!(AugmentPolicy::from_str(&policy.to_string())?, policy);
+            let transform = policy.transform();
+            let a = transform.apply(img.clone(), &mut sample_rng(1, path))?;
+            let b = transform.apply(img.clone(), &mut sample_rng(1, path))?;
+            assert_eq!(a, b);
+            assert_eq!(a.dimensions(), img.dimensions());
+
+            let changed = (0..16)
+                .filter(|&seed| {
+                    transform
+                        .apply(img.clone(), &mut sample_rng(seed, path))
+                        .unwrap()
+                        != img
+                })
+                .count();
+            assert!(changed > 0, "{policy}");
+        }
+        assert!(AugmentPolicy::from_str("mixup").is_err());
+        assert!(RandAugment::new(2, 31, 31).is_err());
+
+        Ok(())
+    }
+}
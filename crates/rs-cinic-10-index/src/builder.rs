@@ -0,0 +1,399 @@
+use crate::dataset_profile::DatasetProfile;
+use crate::error::{Cinic10Error, Result};
+use crate::index::{
+    CONTRIB_FILE, Cinic10Index, DEFAULT_IMAGE_EXTENSIONS, DataSet, DatasetIndex, IndexRecord,
+    ObjectClass, SYNSET_FILE, SynsetNode, normalize_extension, parse_contrib_index,
+    parse_synset_map,
+};
+use crate::lazy::LazyCinic10Index;
+use crate::ordering::FileOrdering;
+use crate::retry::with_retry;
+use crate::try_default_data_path;
+use crate::validation::ValidationReport;
+use crate::wnid::WnId;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Options for building a `Cinic10Index`; see `Cinic10Index::builder()`.
+///
+/// The defaults match `Cinic10Index::new_from_dir()`: every split is scanned,
+/// the metadata files are required, and the layout is validated strictly.
+#[derive(Debug, Clone)]
+pub struct Cinic10IndexBuilder {
+    root: Option<PathBuf>,
+    splits: Vec<DataSet>,
+    require_metadata: bool,
+    strict: bool,
+    ordering: FileOrdering,
+    extensions: Vec<String>,
+    profile: DatasetProfile,
+}
+
+impl Default for Cinic10IndexBuilder {
+    fn default() -> Self {
+        Self {
+            root: None,
+            splits: DataSet::ALL.to_vec(),
+            require_metadata: true,
+            strict: true,
+            ordering: FileOrdering::Lexical,
+            extensions: DEFAULT_IMAGE_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            profile: DatasetProfile::cinic10(),
+        }
+    }
+}
+
+impl Cinic10IndexBuilder {
+    /// Set the dataset root; defaults to `get_default_data_path()`.
+    pub fn root<P>(
+        mut self,
+        root: P,
+    ) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the splits to scan; the others are left empty.
+    pub fn splits<I>(
+        mut self,
+        splits: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = DataSet>,
+    {
+        self.splits = splits.into_iter().collect();
+        self
+    }
+
+    /// Require the `CONTRIB_FILE` and `SYNSET_FILE` metadata files.
+    ///
+    /// When `false`, a missing metadata file leaves its table empty; a present
+    /// but malformed one is still an error.
+    pub fn require_metadata(
+        mut self,
+        require_metadata: bool,
+    ) -> Self {
+        self.require_metadata = require_metadata;
+        self
+    }
+
+    /// Validate the layout and counts of the `profile()`; standard CINIC-10 by default.
+    ///
+    /// When `false`, missing split and class directories are treated as
+    /// empty, and splits may hold any number of images; useful for subsets
+    /// and derived datasets in the CINIC-10 layout.
+    pub fn strict(
+        mut self,
+        strict: bool,
+    ) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set the order of the items of each class; defaults to `FileOrdering::Lexical`.
+    pub fn ordering(
+        mut self,
+        ordering: FileOrdering,
+    ) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    /// Set the image file extensions to index; defaults to `DEFAULT_IMAGE_EXTENSIONS`.
+    ///
+    /// Extensions match case-insensitively, with or without a leading dot;
+    /// e.g. `["png", "jpg", "webp"]` for a recompressed copy of the dataset.
+    /// Only the default extensions use the index cache.
+    pub fn extensions<I, S>(
+        mut self,
+        extensions: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extensions = extensions
+            .into_iter()
+            .map(|e| normalize_extension(e.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Set the expected layout; defaults to `DatasetProfile::cinic10()`.
+    ///
+    /// Also selects the profile's splits; call `splits()` afterwards to
+    /// narrow them. Splits the profile gives no count for accept any number
+    /// of images, even when `strict()`.
+    pub fn profile(
+        mut self,
+        profile: DatasetProfile,
+    ) -> Self {
+        self.splits = profile.data_sets();
+        self.profile = profile;
+        self
+    }
+
+    /// Open a metadata file; `None` if it is missing and not required.
+    fn open_metadata(
+        &self,
+        path: &Path,
+    ) -> Result<Option<File>> {
+        if !self.require_metadata && !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(with_retry(|| File::open(path))?))
+    }
+
+    /// Parse the metadata files under `root`.
+    pub(crate) fn load_metadata(
+        &self,
+        root: &Path,
+    ) -> Result<(Vec<IndexRecord>, HashMap<WnId, SynsetNode>)> {
+        let imagenet_contrib = match self.open_metadata(&root.join(CONTRIB_FILE))? {
+            Some(file) => parse_contrib_index(file)?,
+            None => Vec::new(),
+        };
+        let synset_map = match self.open_metadata(&root.join(SYNSET_FILE))? {
+            Some(file) => parse_synset_map(file)?,
+            None => HashMap::new(),
+        };
+        Ok((imagenet_contrib, synset_map))
+    }
+
+    /// The dataset root; checked to exist.
+    fn resolve_root(&self) -> Result<PathBuf> {
+        let root = match &self.root {
+            Some(root) => root.clone(),
+            None => try_default_data_path()?,
+        };
+        if !root.exists() {
+            return Err(Cinic10Error::MissingRoot(root));
+        }
+        if !root.is_dir() {
+            return Err(Cinic10Error::NotADirectory(root));
+        }
+        Ok(root)
+    }
+
+    /// Scan one split under `root`; empty if it is not one of the selected splits.
+    pub(crate) fn scan_split(
+        &self,
+        root: &Path,
+        data_set: DataSet,
+    ) -> Result<DatasetIndex> {
+        let mut index = DatasetIndex::new(root.join(data_set.to_string()), Vec::new());
+        if !self.splits.contains(&data_set) {
+            return Ok(index);
+        }
+        if self.strict {
+            index = DatasetIndex::load_index_from_dir_with(
+                &index.ds_path,
+                &self.extensions,
+                self.profile.samples_per_class(data_set),
+            )?;
+        } else {
+            index.set_extensions(&self.extensions);
+            index.refresh_classes(&ObjectClass::ALL)?;
+        }
+        if self.ordering != FileOrdering::Lexical {
+            index.sort_items(self.ordering);
+        }
+        Ok(index)
+    }
+
+    /// Build the index.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index`; or an error if the root is
+    /// missing, or fails validation.
+    pub fn build(self) -> Result<Cinic10Index> {
+        let root = self.resolve_root()?;
+        let (imagenet_contrib, synset_map) = self.load_metadata(&root)?;
+        // The splits are scanned concurrently; on network filesystems the
+        // directory listings, not the CPU, dominate.
+        let [train, test, valid] = thread::scope(|scope| {
+            DataSet::ALL
+                .map(|data_set| {
+                    let (builder, root) = (&self, &root);
+                    scope.spawn(move || builder.scan_split(root, data_set))
+                })
+                .map(|handle| handle.join().unwrap())
+        });
+        Ok(Cinic10Index::new(
+            root,
+            imagenet_contrib,
+            synset_map,
+            train?,
+            test?,
+            valid?,
+        ))
+    }
+
+    /// Build whatever exists of the dataset, and report how it deviates from the `profile()`.
+    ///
+    /// This is lenient regardless of `strict()` and `require_metadata()`:
+    /// missing directories and metadata files are listed in the report
+    /// rather than failing; a malformed metadata file is still an error.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index and the `ValidationReport` of the selected splits.
+    pub fn build_with_report(self) -> Result<(Cinic10Index, ValidationReport)> {
+        let profile = self.profile.clone().with_splits(
+            self.splits
+                .iter()
+                .map(|&ds| (ds, self.profile.samples_per_class(ds))),
+        );
+        let cinic = self.require_metadata(false).strict(false).build()?;
+        let report = cinic.validate_profile(&profile);
+        Ok((cinic, report))
+    }
+
+    /// Build a single split, standalone; ignores `splits()` and the metadata files.
+    ///
+    /// # Parameters
+    ///
+    /// - `data_set`: The split to scan.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the split's index.
+    pub fn build_split(
+        mut self,
+        data_set: DataSet,
+    ) -> Result<DatasetIndex> {
+        let root = self.resolve_root()?;
+        self.splits = vec![data_set];
+        self.scan_split(&root, data_set)
+    }
+
+    /// Build an index which scans each split on first access; see `LazyCinic10Index`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the lazy index; or an error if the root is missing.
+    pub fn build_lazy(self) -> Result<LazyCinic10Index> {
+        let root = self.resolve_root()?;
+        Ok(LazyCinic10Index::new(self, root))
+    }
+}
+
+impl Cinic10Index {
+    /// Start building a `Cinic10Index` with non-default options.
+    ///
+    /// E.g. `Cinic10Index::builder().root(p).splits([Train, Valid]).strict(false).build()`.
+    pub fn builder() -> Cinic10IndexBuilder {
+        Cinic10IndexBuilder::default()
+    }
+
+    /// Open the dataset at `root`, deferring each split's scan until it is first used.
+    ///
+    /// Shorthand for `Cinic10Index::builder().root(root).build_lazy()`.
+    pub fn lazy<P>(root: P) -> Result<LazyCinic10Index>
+    where
+        P: AsRef<Path>,
+    {
+        Cinic10Index::builder().root(root).build_lazy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::load_rgbimage;
+    use image::{Rgb, RgbImage};
+    use std::fs;
+
+    #[test]
+    fn test_builder() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for data_set in ["train", "valid"] {
+            for name in ["a.png", "b.png"] {
+                let class_dir = root.join(data_set).join("cat");
+                fs::create_dir_all(&class_dir)?;
+                fs::write(class_dir.join(name), b"")?;
+            }
+        }
+
+        // The default is as strict as `new_from_dir()`.
+        assert!(Cinic10Index::builder().root(root).build().is_err());
+        assert!(
+            Cinic10Index::builder()
+                .root(root)
+                .strict(false)
+                .build()
+                .is_err()
+        );
+
+        let cinic = Cinic10Index::builder()
+            .root(root)
+            .splits([DataSet::Train, DataSet::Valid])
+            .require_metadata(false)
+            .strict(false)
+            .build()?;
+        assert_eq!(cinic.root, root);
+        assert!(cinic.imagenet_contrib.is_empty());
+        assert_eq!(cinic.train.len(), 2);
+        assert_eq!(cinic.valid.index_to_class(1), ObjectClass::Cat);
+        assert!(cinic.test.is_empty());
+        assert_eq!(cinic.test.ds_path(), root.join("test"));
+
+        for name in ["a10.png", "a9.png"] {
+            fs::write(root.join("train/cat").join(name), b"")?;
+        }
+        let natural = Cinic10Index::builder()
+            .root(root)
+            .require_metadata(false)
+            .strict(false)
+            .ordering(FileOrdering::Natural)
+            .build_split(DataSet::Train)?;
+        assert_eq!(natural.rel_path(2), Path::new("cat/a10.png"));
+
+        let pixels = RgbImage::from_pixel(2, 2, Rgb([9, 8, 7]));
+        pixels.save(root.join("train/cat/c.JPG"))?;
+        pixels.save(root.join("train/cat/d.tga"))?;
+        let mixed = Cinic10Index::builder()
+            .root(root)
+            .require_metadata(false)
+            .strict(false)
+            .extensions([".png", "jpg", "tga"])
+            .build_split(DataSet::Train)?;
+        assert_eq!(mixed.len(), natural.len() + 2);
+        assert_eq!(mixed.rel_path(5), Path::new("cat/d.tga"));
+        assert_eq!(
+            load_rgbimage(mixed.abs_path(5))?.get_pixel(1, 1),
+            &Rgb([9, 8, 7])
+        );
+        assert_eq!(load_rgbimage(mixed.abs_path(4))?.dimensions(), (2, 2));
+        assert_eq!(mixed.extensions(), ["png", "jpg", "tga"]);
+        let mut refreshed = mixed.clone();
+        assert!(refreshed.refresh()?.is_empty());
+        assert_eq!(refreshed.len(), mixed.len());
+
+        fs::write(root.join(SYNSET_FILE), "kitten\n")?;
+        assert!(matches!(
+            Cinic10Index::builder()
+                .root(root)
+                .require_metadata(false)
+                .strict(false)
+                .build(),
+            Err(Cinic10Error::MalformedSynsetFile { .. })
+        ));
+        assert!(matches!(
+            Cinic10Index::builder().root(root.join("nope")).build(),
+            Err(Cinic10Error::MissingRoot(_))
+        ));
+
+        Ok(())
+    }
+}
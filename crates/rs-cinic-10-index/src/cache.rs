@@ -0,0 +1,353 @@
+use crate::checksum::ChecksumAlgorithm;
+use crate::error::Result;
+use crate::metrics;
+use crate::sample_id::SampleId;
+use crate::source::DataSource;
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// A `DataSource` which keeps a local disk copy of the files it reads.
+///
+/// For remote and archive sources, so repeated epochs don't refetch or
+/// re-inflate the same bytes. Entries are keyed by content digest when the
+/// inner source knows it (see `DataSource::digest()`), and are checked
+/// against it on every hit.
+///
+/// Sources with no digest, e.g. a remote dataset without a checksum manifest,
+/// fall back to a key hashed from the source name and the file's `SampleId`
+/// (see `SampleId::from_path()`), so copies of the dataset under different
+/// roots share entries; or from the path, for files outside the layout.
+/// Those entries are not content-addressed: they are not verified, and go
+/// stale if the file changes in place; `clear()` the cache after updating
+/// such a source.
+///
+/// Entries are written to a temporary file and renamed into place, so several
+/// trainers can share a cache directory. When the cache grows past its size
+/// limit, the least recently used entries are evicted.
+#[derive(Debug)]
+pub struct CachedSource {
+    inner: Arc<dyn DataSource>,
+    dir: PathBuf,
+    max_bytes: u64,
+    size: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// The cache entries under `dir`, as `(last use, size, path)`.
+fn scan_entries(dir: &Path) -> Vec<(SystemTime, u64, PathBuf)> {
+    let Ok(shards) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    shards
+        .flatten()
+        .filter(|shard| shard.path().is_dir())
+        .filter_map(|shard| fs::read_dir(shard.path()).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            Some((meta.modified().ok()?, meta.len(), entry.path()))
+        })
+        .collect()
+}
+
+impl CachedSource {
+    /// Cache the files of `inner` under `dir`.
+    ///
+    /// # Parameters
+    ///
+    /// - `inner`: The source to cache.
+    /// - `dir`: The cache directory; created if missing, and may be shared.
+    /// - `max_bytes`: The size limit of the cache.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the cached source.
+    pub fn new<P>(
+        inner: Arc<dyn DataSource>,
+        dir: P,
+        max_bytes: u64,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create cache dir {}", dir.display()))?;
+        let size = scan_entries(dir).iter().map(|(_, len, _)| len).sum();
+        Ok(Self {
+            inner,
+            dir: dir.to_path_buf(),
+            max_bytes,
+            size: AtomicU64::new(size),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// The number of reads served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// The number of reads passed to the inner source.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The cache file of `path`, and its expected digest, if known.
+    fn entry(
+        &self,
+        path: &Path,
+    ) -> (PathBuf, Option<(ChecksumAlgorithm, String)>) {
+        let digest = self.inner.digest(path);
+        let name = match &digest {
+            Some((algorithm, digest)) => format!("{algorithm}-{digest}"),
+            None => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(self.inner.name().as_bytes());
+                hasher.update(&[0]);
+                match SampleId::from_path(path) {
+                    Some(id) => {
+                        hasher.update(id.to_string().as_bytes());
+                        format!("sample-{}", hasher.finalize().to_hex())
+                    }
+                    None => {
+                        hasher.update(path.as_os_str().as_encoded_bytes());
+                        format!("path-{}", hasher.finalize().to_hex())
+                    }
+                }
+            }
+        };
+        let hex = name.rsplit('-').next().unwrap();
+        (self.dir.join(&hex[..2]).join(&name), digest)
+    }
+
+    /// Write an entry, atomically.
+    fn store(
+        &self,
+        entry: &Path,
+        bytes: &[u8],
+    ) -> Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        fs::create_dir_all(entry.parent().unwrap())?;
+        let tmp = self.dir.join(format!(
+            ".tmp-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, bytes)?;
+        if let Err(err) = fs::rename(&tmp, entry) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err.into());
+        }
+        let len = bytes.len() as u64;
+        if self.size.fetch_add(len, Ordering::Relaxed) + len > self.max_bytes {
+            self.evict();
+        }
+        Ok(())
+    }
+
+    /// Evict least recently used entries until the cache fits its limit.
+    ///
+    /// Other processes may share the directory, so the size is re-counted from disk.
+    pub fn evict(&self) {
+        let mut entries = scan_entries(&self.dir);
+        entries.sort();
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in entries {
+            if size <= self.max_bytes {
+                break;
+            }
+            // Another process may have evicted it first.
+            let _ = fs::remove_file(&path);
+            size -= len;
+        }
+        self.size.store(size, Ordering::Relaxed);
+    }
+
+    /// Remove every entry.
+    pub fn clear(&self) {
+        for (_, _, path) in scan_entries(&self.dir) {
+            let _ = fs::remove_file(path);
+        }
+        self.size.store(0, Ordering::Relaxed);
+    }
+}
+
+impl DataSource for CachedSource {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        let (entry, digest) = self.entry(path);
+        if let Ok(bytes) = fs::read(&entry) {
+            match &digest {
+                Some((algorithm, digest)) if algorithm.digest(&bytes) != *digest => {
+                    let _ = fs::remove_file(&entry);
+                }
+                _ => {
+                    // The modification time orders eviction; failing to bump it is harmless.
+                    if let Ok(file) = fs::File::options().append(true).open(&entry) {
+                        let _ = file.set_modified(SystemTime::now());
+                    }
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    metrics::record(|m| m.cache_hit());
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(path = %path.display(), "cache hit");
+                    return Ok(bytes);
+                }
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        metrics::record(|m| m.cache_miss());
+        #[cfg(feature = "tracing")]
+        tracing::trace!(path = %path.display(), "cache miss");
+        let bytes = self.inner.read(path)?;
+        if let Err(_err) = self.store(&entry, &bytes) {
+            // A full or read-only cache disk shouldn't fail the read.
+            #[cfg(feature = "tracing")]
+            tracing::warn!(path = %entry.display(), error = %_err, "failed to cache file");
+        }
+        Ok(bytes)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        self.inner.list(dir)
+    }
+
+    fn digest(
+        &self,
+        path: &Path,
+    ) -> Option<(ChecksumAlgorithm, String)> {
+        self.inner.digest(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{AtomicMetrics, set_metrics_sink};
+    use crate::source::MemorySource;
+
+    /// A `MemorySource` which knows its files' digests.
+    #[derive(Debug)]
+    struct Digested(MemorySource);
+
+    impl DataSource for Digested {
+        fn name(&self) -> &str {
+            "digested"
+        }
+
+        fn read(
+            &self,
+            path: &Path,
+        ) -> Result<Vec<u8>> {
+            self.0.read(path)
+        }
+
+        fn list(
+            &self,
+            dir: &Path,
+        ) -> Result<Vec<String>> {
+            self.0.list(dir)
+        }
+
+        fn digest(
+            &self,
+            path: &Path,
+        ) -> Option<(ChecksumAlgorithm, String)> {
+            let bytes = self.0.read(path).ok()?;
+            Some((
+                ChecksumAlgorithm::Blake3,
+                ChecksumAlgorithm::Blake3.digest(&bytes),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_cached_source() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let memory = MemorySource::new()
+            .with_file("a.png", vec![1; 100])
+            .with_file("b.png", vec![2; 100])
+            .with_file("c.png", vec![3; 100]);
+
+        let sink = Arc::new(AtomicMetrics::new());
+        set_metrics_sink(Some(sink.clone()));
+        let cache = CachedSource::new(Arc::new(memory.clone()), dir.path(), 1000)?;
+        assert_eq!(cache.read(Path::new("a.png"))?, vec![1; 100]);
+        assert_eq!(cache.read(Path::new("a.png"))?, vec![1; 100]);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+        set_metrics_sink(None);
+        // Other tests may share the process-wide sink.
+        let snapshot = sink.snapshot();
+        assert!(snapshot.cache_hits >= 1 && snapshot.cache_misses >= 1);
+        assert!(cache.read(Path::new("nope.png")).is_err());
+
+        // A second cache on the same directory, e.g. another trainer, shares entries.
+        let shared = CachedSource::new(Arc::new(memory.clone()), dir.path(), 1000)?;
+        shared.read(Path::new("a.png"))?;
+        assert_eq!(shared.hits(), 1);
+        shared.clear();
+
+        // Without digests, the same sample under another root shares an entry.
+        let moved = MemorySource::new()
+            .with_file("/a/train/cat/x.png", vec![4; 10])
+            .with_file("/b/train/cat/x.png", vec![5; 10]);
+        let by_sample = CachedSource::new(Arc::new(moved), dir.path(), 1000)?;
+        by_sample.read(Path::new("/a/train/cat/x.png"))?;
+        assert_eq!(
+            by_sample.read(Path::new("/b/train/cat/x.png"))?,
+            vec![4; 10]
+        );
+        assert_eq!(by_sample.hits(), 1);
+        by_sample.clear();
+
+        let digested = CachedSource::new(Arc::new(Digested(memory.clone())), dir.path(), 1000)?;
+        for name in ["a.png", "b.png", "c.png"] {
+            digested.read(Path::new(name))?;
+        }
+        let entries = scan_entries(dir.path());
+        assert_eq!(entries.len(), 3);
+        assert!(
+            entries
+                .iter()
+                .all(|(_, _, path)| path.to_str().unwrap().contains("blake3-"))
+        );
+
+        // A corrupted entry is refetched.
+        let (entry, _) = digested.entry(Path::new("c.png"));
+        fs::write(&entry, b"corrupt")?;
+        assert_eq!(digested.read(Path::new("c.png"))?, vec![3; 100]);
+        assert_eq!(digested.misses(), 4);
+        assert_eq!(fs::read(&entry)?, vec![3; 100]);
+
+        let small = CachedSource::new(Arc::new(Digested(memory)), dir.path(), 250)?;
+        small.evict();
+        assert_eq!(scan_entries(dir.path()).len(), 2);
+
+        Ok(())
+    }
+}
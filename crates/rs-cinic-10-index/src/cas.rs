@@ -0,0 +1,318 @@
+use crate::checksum::ChecksumAlgorithm;
+use crate::diff::walk;
+use crate::error::{Result, bail};
+use crate::retry::with_retry;
+use crate::source::DataSource;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the sample-to-blob index in a content store.
+pub const CONTENT_INDEX_FILE: &str = "index.json";
+
+/// The directory of blobs in a content store.
+const BLOBS_DIR: &str = "blobs";
+
+/// The version of the content index format; bump it when the format changes.
+const CONTENT_INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ContentIndexFile {
+    version: u32,
+
+    /// The blake3 digest of every file, by its path relative to the dataset root.
+    entries: BTreeMap<PathBuf, String>,
+}
+
+/// A content-addressed, deduplicated copy of a dataset.
+///
+/// CINIC-10 repeats some CIFAR frames across its splits. A content store keeps
+/// each distinct file once, as `blobs/{hh}/{digest}` named by its blake3
+/// digest, and an index (`index.json`) mapping every dataset path to its
+/// blob. Exact duplicates share a blob, so they take no extra space and can
+/// be listed without reading any images; see `duplicates()`.
+///
+/// Index paths are under `dir()`, so build the index with
+/// `Cinic10Index::new_from_source(&store, store.dir())`, and load batches with
+/// the store as `LoaderConfig::source`.
+#[derive(Debug, Clone)]
+pub struct ContentStore {
+    dir: PathBuf,
+    entries: BTreeMap<PathBuf, String>,
+}
+
+impl ContentStore {
+    /// Copy every file under a dataset root into a content store.
+    ///
+    /// Blobs already in the store are reused, so importing into an existing
+    /// store only adds new content; the index is replaced.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The dataset root; e.g. a CINIC-10 directory.
+    /// - `dir`: The store directory; created if missing.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the store.
+    pub fn import<P, Q>(
+        root: P,
+        dir: Q,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let dir = dir.as_ref();
+        if !root.is_dir() {
+            bail!("Dataset root is not a directory: {}", root.display());
+        }
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create content store {}", dir.display()))?;
+
+        let mut store = Self {
+            dir: dir.to_path_buf(),
+            entries: BTreeMap::new(),
+        };
+        let (_, files) = walk(root)?;
+        for rel in files {
+            let path = root.join(&rel);
+            let bytes = with_retry(|| fs::read(&path))
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let digest = ChecksumAlgorithm::Blake3.digest(&bytes);
+            let blob = store.blob_path(&digest);
+            if !blob.exists() {
+                fs::create_dir_all(blob.parent().unwrap())?;
+                let tmp = blob.with_extension("tmp");
+                fs::write(&tmp, &bytes)?;
+                fs::rename(&tmp, &blob)?;
+            }
+            store.entries.insert(rel, digest);
+        }
+
+        let file = ContentIndexFile {
+            version: CONTENT_INDEX_VERSION,
+            entries: store.entries.clone(),
+        };
+        let index_path = dir.join(CONTENT_INDEX_FILE);
+        fs::write(&index_path, serde_json::to_string(&file)?)
+            .with_context(|| format!("Failed to write {}", index_path.display()))?;
+        Ok(store)
+    }
+
+    /// Open a store written by `import()`.
+    pub fn open<P>(dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let index_path = dir.join(CONTENT_INDEX_FILE);
+        let file: ContentIndexFile = serde_json::from_str(
+            &fs::read_to_string(&index_path)
+                .with_context(|| format!("Failed to read {}", index_path.display()))?,
+        )?;
+        if file.version != CONTENT_INDEX_VERSION {
+            bail!("Unsupported content index version {}", file.version);
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            entries: file.entries,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The number of dataset files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of distinct blobs; `len() - blob_count()` files are duplicates.
+    pub fn blob_count(&self) -> usize {
+        self.entries.values().collect::<HashSet<_>>().len()
+    }
+
+    /// The blob file of a digest.
+    pub fn blob_path(
+        &self,
+        digest: &str,
+    ) -> PathBuf {
+        self.dir.join(BLOBS_DIR).join(&digest[..2]).join(digest)
+    }
+
+    /// The path of `path` relative to the dataset root.
+    fn key<'a>(
+        &self,
+        path: &'a Path,
+    ) -> &'a Path {
+        path.strip_prefix(&self.dir).unwrap_or(path)
+    }
+
+    /// The blake3 digest of a dataset file, if it is in the store.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The file; relative to the dataset root, or under `dir()`.
+    pub fn digest_of(
+        &self,
+        path: &Path,
+    ) -> Option<&str> {
+        self.entries.get(self.key(path)).map(String::as_str)
+    }
+
+    /// The other dataset files with exactly the same content as `path`.
+    ///
+    /// # Returns
+    ///
+    /// The duplicates' paths, relative to the dataset root, sorted; empty if
+    /// `path` is not in the store.
+    pub fn duplicates_of(
+        &self,
+        path: &Path,
+    ) -> Vec<&Path> {
+        let key = self.key(path);
+        let Some(digest) = self.entries.get(key) else {
+            return Vec::new();
+        };
+        self.entries
+            .iter()
+            .filter(|&(other, d)| d == digest && other != key)
+            .map(|(other, _)| other.as_path())
+            .collect()
+    }
+
+    /// Every group of dataset files which share a blob.
+    ///
+    /// # Returns
+    ///
+    /// Groups of two or more paths, relative to the dataset root; each
+    /// group is sorted, and the groups are ordered by their first path.
+    pub fn duplicates(&self) -> Vec<Vec<&Path>> {
+        let mut by_digest: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+        for (path, digest) in &self.entries {
+            by_digest.entry(digest).or_default().push(path);
+        }
+        let mut groups: Vec<Vec<&Path>> = by_digest
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// The total size of the blobs, in bytes.
+    pub fn size_bytes(&self) -> Result<u64> {
+        let digests: HashSet<&String> = self.entries.values().collect();
+        let mut size = 0;
+        for digest in digests {
+            size += fs::metadata(self.blob_path(digest))?.len();
+        }
+        Ok(size)
+    }
+}
+
+impl DataSource for ContentStore {
+    fn name(&self) -> &str {
+        "content-store"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        let Some(digest) = self.digest_of(path) else {
+            bail!("{} is not in the content store", path.display());
+        };
+        let blob = self.blob_path(digest);
+        Ok(with_retry(|| fs::read(&blob))
+            .with_context(|| format!("Failed to read {}", blob.display()))?)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        let dir = self.key(dir);
+        Ok(self
+            .entries
+            .keys()
+            .filter(|rel| rel.parent() == Some(dir))
+            .filter_map(|rel| rel.file_name()?.to_str().map(str::to_string))
+            .collect())
+    }
+
+    fn digest(
+        &self,
+        path: &Path,
+    ) -> Option<(ChecksumAlgorithm, String)> {
+        Some((ChecksumAlgorithm::Blake3, self.digest_of(path)?.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cinic10Index;
+    use crate::index::{CONTRIB_FILE, ObjectClass, SYNSET_FILE};
+    use crate::loader::{BatchLoader, LoaderConfig};
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_content_store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("cinic");
+        for data_set in ["train", "test", "valid"] {
+            for (i, class) in ["cat", "ship"].into_iter().enumerate() {
+                let class_dir = root.join(data_set).join(class);
+                fs::create_dir_all(&class_dir)?;
+                // The same frame in every split.
+                RgbImage::from_pixel(2, 2, Rgb([i as u8, 0, 0])).save(class_dir.join("a.png"))?;
+            }
+        }
+        RgbImage::from_pixel(2, 2, Rgb([9, 0, 0])).save(root.join("train/cat/b.png"))?;
+        fs::write(
+            root.join(CONTRIB_FILE),
+            "synset,image_num,cinic_set,class\n",
+        )?;
+        fs::write(root.join(SYNSET_FILE), "cat\n")?;
+
+        let store = ContentStore::import(&root, dir.path().join("store"))?;
+        assert_eq!(store.len(), 9);
+        assert_eq!(store.blob_count(), 5);
+        assert_eq!(
+            store.duplicates_of(Path::new("train/cat/a.png")),
+            vec![Path::new("test/cat/a.png"), Path::new("valid/cat/a.png")]
+        );
+        assert!(store.duplicates_of(Path::new("train/cat/b.png")).is_empty());
+        assert_eq!(store.duplicates().len(), 2);
+
+        let store = ContentStore::open(store.dir())?;
+        assert_eq!(store.len(), 9);
+        let cinic = Cinic10Index::new_from_source(&store, store.dir())?;
+        assert_eq!(cinic.train.len(), 3);
+        assert_eq!(
+            cinic.valid.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        let loader = BatchLoader::new(LoaderConfig {
+            source: Some(Arc::new(store)),
+            ..Default::default()
+        });
+        let batch = cinic.train.load_rgbimagebatch_with(&loader, &[1, 2])?;
+        assert_eq!(batch.shape, vec![2, 2, 2, 3]);
+        assert_eq!((batch.data[0], batch.data[12]), (9, 1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+use crate::Cinic10Index;
+use crate::diff::walk;
+use crate::error::{Result, bail};
+use crate::parallel::par_fold;
+use crate::retry::with_retry;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The file stem of checksum manifests in a dataset root, e.g. `CHECKSUMS.sha256`.
+///
+/// Root-level files with this stem are never themselves checksummed.
+pub const CHECKSUMS_FILE_STEM: &str = "CHECKSUMS";
+
+/// A file checksum algorithm.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// The conventional manifest file extension: `b3` or `sha256`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Blake3 => "b3",
+            ChecksumAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Infer the algorithm from a manifest file extension, if it is known.
+    pub fn from_path<P>(path: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension()?.to_str()? {
+            "b3" | "blake3" => Some(ChecksumAlgorithm::Blake3),
+            "sha256" => Some(ChecksumAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    /// The hex digest of a byte slice.
+    pub fn digest(
+        self,
+        bytes: &[u8],
+    ) -> String {
+        match self {
+            ChecksumAlgorithm::Blake3 => blake3::hash(bytes).to_hex().to_string(),
+            ChecksumAlgorithm::Sha256 => Sha256::digest(bytes)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+        }
+    }
+
+    /// The hex digest of a file.
+    pub fn file_digest<P>(
+        self,
+        path: P,
+    ) -> Result<String>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut file = with_retry(|| fs::File::open(path))
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(match self {
+            ChecksumAlgorithm::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update_reader(file)?;
+                hasher.finalize().to_hex().to_string()
+            }
+            ChecksumAlgorithm::Sha256 => {
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher)?;
+                hasher
+                    .finalize()
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect()
+            }
+        })
+    }
+}
+
+/// The files of a dataset which failed verification, as paths relative to the root.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// The number of files whose checksum matched.
+    pub verified: usize,
+
+    /// Files in the manifest, but not on disk.
+    pub missing: Vec<PathBuf>,
+
+    /// Files whose checksum does not match the manifest.
+    pub corrupt: Vec<PathBuf>,
+
+    /// Files on disk, but not in the manifest.
+    pub extra: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    /// Did every manifest file match, with no extra files?
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupt.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Per-file checksums of a dataset copy, keyed by path relative to the root.
+///
+/// Manifests are written in the `sha256sum` / `b3sum` format, one
+/// `{digest}  {path}` line per file; so `sha256sum -c` can check them too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    pub algorithm: ChecksumAlgorithm,
+    pub entries: BTreeMap<PathBuf, String>,
+}
+
+/// Is `rel` a checksum manifest in the dataset root?
+fn is_manifest_file(rel: &Path) -> bool {
+    rel.parent() == Some(Path::new(""))
+        && rel
+            .file_stem()
+            .is_some_and(|stem| stem == CHECKSUMS_FILE_STEM)
+}
+
+/// The checksummed files under `root`, relative to `root`.
+fn dataset_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let (_, files) = walk(root)?;
+    Ok(files.into_iter().filter(|f| !is_manifest_file(f)).collect())
+}
+
+impl ChecksumManifest {
+    /// Checksum every file under `root`.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The dataset root.
+    /// - `algorithm`: The checksum algorithm.
+    /// - `parallelism`: The number of hashing threads; `0` means "all available cores".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the manifest.
+    pub fn generate<P>(
+        root: P,
+        algorithm: ChecksumAlgorithm,
+        parallelism: usize,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let files = dataset_files(root)?;
+        let entries = par_fold(
+            &files,
+            parallelism,
+            BTreeMap::new,
+            |entries, rel| {
+                entries.insert(rel.clone(), algorithm.file_digest(root.join(rel))?);
+                Ok(())
+            },
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        )?;
+        Ok(Self { algorithm, entries })
+    }
+
+    /// Check the files under `root` against the manifest.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The dataset root.
+    /// - `parallelism`: The number of hashing threads; `0` means "all available cores".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the report; an error only if `root` can't be listed.
+    pub fn verify<P>(
+        &self,
+        root: P,
+        parallelism: usize,
+    ) -> Result<VerifyReport>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let on_disk = dataset_files(root)?;
+        let expected: Vec<(&PathBuf, &String)> = self.entries.iter().collect();
+        let mut report = par_fold(
+            &expected,
+            parallelism,
+            VerifyReport::default,
+            |report, (rel, digest)| {
+                let path = root.join(rel);
+                if !path.is_file() {
+                    report.missing.push(rel.to_path_buf());
+                } else if self.algorithm.file_digest(&path)? != **digest {
+                    report.corrupt.push(rel.to_path_buf());
+                } else {
+                    report.verified += 1;
+                }
+                Ok(())
+            },
+            |mut a, b| {
+                a.verified += b.verified;
+                a.missing.extend(b.missing);
+                a.corrupt.extend(b.corrupt);
+                a
+            },
+        )?;
+        report.missing.sort();
+        report.corrupt.sort();
+        report.extra = on_disk
+            .into_iter()
+            .filter(|rel| !self.entries.contains_key(rel))
+            .collect();
+        Ok(report)
+    }
+
+    /// Write the manifest, as `{digest}  {path}` lines.
+    pub fn write<P>(
+        &self,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut out = BufWriter::new(
+            fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?,
+        );
+        for (rel, digest) in &self.entries {
+            let Some(rel) = rel.to_str() else {
+                bail!("Path is not valid UTF-8: {}", rel.display());
+            };
+            writeln!(out, "{digest}  {}", rel.replace('\\', "/"))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read a manifest written by `write()`, `sha256sum`, or `b3sum`.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The manifest file.
+    /// - `algorithm`: The algorithm; `None` infers it from the file extension.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the manifest.
+    pub fn read<P>(
+        path: P,
+        algorithm: Option<ChecksumAlgorithm>,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let Some(algorithm) = algorithm.or_else(|| ChecksumAlgorithm::from_path(path)) else {
+            bail!("Can't infer the checksum algorithm of {}", path.display());
+        };
+        let file = with_retry(|| fs::File::open(path))
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self::parse(BufReader::new(file), algorithm)
+            .with_context(|| format!("Failed to read {}", path.display()))?)
+    }
+
+    /// Parse a manifest in the format of `write()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `reader`: The manifest lines.
+    /// - `algorithm`: The algorithm of the digests.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the manifest.
+    pub fn parse<R>(
+        reader: R,
+        algorithm: ChecksumAlgorithm,
+    ) -> Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut entries = BTreeMap::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // `sha256sum` marks binary-mode entries with `*` in place of the second space.
+            let Some((digest, rel)) = line.split_once("  ").or_else(|| line.split_once(" *"))
+            else {
+                bail!("Malformed checksum line {}: {line:?}", line_no + 1);
+            };
+            entries.insert(PathBuf::from(rel), digest.to_ascii_lowercase());
+        }
+        Ok(Self { algorithm, entries })
+    }
+}
+
+impl Cinic10Index {
+    /// Checksum every file of the dataset; see `ChecksumManifest::generate()`.
+    pub fn checksum_manifest(
+        &self,
+        algorithm: ChecksumAlgorithm,
+        parallelism: usize,
+    ) -> Result<ChecksumManifest> {
+        ChecksumManifest::generate(&self.root, algorithm, parallelism)
+    }
+
+    /// Check the dataset's files against a checksum manifest.
+    ///
+    /// Every file under the root is checked, not just the indexed images;
+    /// so stray files left by a partial copy are reported as extra.
+    ///
+    /// # Parameters
+    ///
+    /// - `manifest`: The expected checksums.
+    /// - `parallelism`: The number of hashing threads; `0` means "all available cores".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the report of missing, corrupt, and extra files.
+    pub fn verify(
+        &self,
+        manifest: &ChecksumManifest,
+        parallelism: usize,
+    ) -> Result<VerifyReport> {
+        manifest.verify(&self.root, parallelism)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_checksum_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        fs::create_dir_all(root.join("train/cat"))?;
+        fs::write(root.join("train/cat/a.png"), "a")?;
+        fs::write(root.join("train/cat/b.png"), "b")?;
+        fs::write(root.join("README"), "readme")?;
+
+        for algorithm in ChecksumAlgorithm::iter() {
+            let manifest = ChecksumManifest::generate(root, algorithm, 2)?;
+            assert_eq!(manifest.entries.len(), 3);
+            let path = root.join(format!("{CHECKSUMS_FILE_STEM}.{}", algorithm.extension()));
+            manifest.write(&path)?;
+            assert_eq!(ChecksumManifest::read(&path, None)?, manifest);
+            assert!(manifest.verify(root, 1)?.is_ok());
+        }
+        assert_eq!(
+            ChecksumAlgorithm::Sha256.file_digest(root.join("train/cat/a.png"))?,
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+        );
+
+        let manifest = ChecksumManifest::read(root.join("CHECKSUMS.sha256"), None)?;
+        fs::write(root.join("train/cat/a.png"), "corrupted")?;
+        fs::remove_file(root.join("README"))?;
+        fs::write(root.join("train/cat/c.png"), "c")?;
+        let report = manifest.verify(root, 0)?;
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.corrupt, vec![PathBuf::from("train/cat/a.png")]);
+        assert_eq!(report.missing, vec![PathBuf::from("README")]);
+        assert_eq!(report.extra, vec![PathBuf::from("train/cat/c.png")]);
+
+        fs::write(root.join("bad.sha256"), "no-separator\n")?;
+        assert!(ChecksumManifest::read(root.join("bad.sha256"), None).is_err());
+
+        Ok(())
+    }
+}
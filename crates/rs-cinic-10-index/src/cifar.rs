@@ -0,0 +1,434 @@
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::index::{CHANNELS, HEIGHT, ObjectClass, WIDTH};
+use crate::loader::{BatchLoader, LoadedBatch};
+use crate::profile::Stage;
+use crate::provenance::CifarSplit;
+use crate::retry::with_retry;
+use crate::stats::ClassDistribution;
+use image::RgbImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The pixels of one image: 32x32 RGB.
+const IMAGE_LEN: usize = HEIGHT * WIDTH * CHANNELS;
+
+/// The original CIFAR datasets, in their binary distribution formats.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum CifarVariant {
+    /// `cifar-10-batches-bin`: a label byte, then the image, per record.
+    Cifar10,
+
+    /// `cifar-100-binary`: coarse and fine label bytes, then the image, per record.
+    Cifar100,
+}
+
+impl CifarVariant {
+    /// The number of label bytes before each image.
+    fn label_len(self) -> usize {
+        match self {
+            CifarVariant::Cifar10 => 1,
+            CifarVariant::Cifar100 => 2,
+        }
+    }
+
+    /// The length of one record.
+    pub fn record_len(self) -> usize {
+        self.label_len() + IMAGE_LEN
+    }
+
+    /// The number of (fine) classes.
+    pub fn num_classes(self) -> usize {
+        match self {
+            CifarVariant::Cifar10 => 10,
+            CifarVariant::Cifar100 => 100,
+        }
+    }
+
+    /// The batch files of a split, in order.
+    pub fn batch_files(
+        self,
+        split: CifarSplit,
+    ) -> &'static [&'static str] {
+        match (self, split) {
+            (CifarVariant::Cifar10, CifarSplit::Train) => &[
+                "data_batch_1.bin",
+                "data_batch_2.bin",
+                "data_batch_3.bin",
+                "data_batch_4.bin",
+                "data_batch_5.bin",
+            ],
+            (CifarVariant::Cifar10, CifarSplit::Test) => &["test_batch.bin"],
+            (CifarVariant::Cifar100, CifarSplit::Train) => &["train.bin"],
+            (CifarVariant::Cifar100, CifarSplit::Test) => &["test.bin"],
+        }
+    }
+}
+
+/// A split of CIFAR-10 or CIFAR-100, decoded from the original binary batches.
+///
+/// Mirrors the `DatasetIndex` query and loading API, so a training loop
+/// written against CINIC-10 runs on CIFAR unchanged; CIFAR-10 labels are the
+/// CINIC-10 `ObjectClass` ordinals. The whole split is held in memory, in
+/// `[H, W, 3]` order (about 150 MB for a training split).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CifarBinary {
+    variant: CifarVariant,
+    split: CifarSplit,
+    dir: PathBuf,
+    labels: Vec<u8>,
+    coarse_labels: Vec<u8>,
+    pixels: Vec<u8>,
+}
+
+impl CifarBinary {
+    /// Read a split from the batch files in `dir`.
+    ///
+    /// # Parameters
+    ///
+    /// - `dir`: The extracted archive, e.g. `cifar-10-batches-bin`.
+    /// - `variant`: The dataset.
+    /// - `split`: The split; see `CifarVariant::batch_files()`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the split; or an error if a batch file is
+    /// missing or malformed.
+    pub fn open<P>(
+        dir: P,
+        variant: CifarVariant,
+        split: CifarSplit,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let mut cifar = Self {
+            variant,
+            split,
+            dir: dir.to_path_buf(),
+            labels: Vec::new(),
+            coarse_labels: Vec::new(),
+            pixels: Vec::new(),
+        };
+        for name in variant.batch_files(split) {
+            let path = dir.join(name);
+            let bytes = with_retry(|| fs::read(&path))?;
+            cifar
+                .push_records(&bytes)
+                .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+        }
+        Ok(cifar)
+    }
+
+    /// Decode a batch file's records onto the split.
+    fn push_records(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let record_len = self.variant.record_len();
+        if !bytes.len().is_multiple_of(record_len) {
+            bail!(
+                "Batch length {} is not a multiple of the {record_len}-byte record",
+                bytes.len()
+            );
+        }
+        for record in bytes.chunks_exact(record_len) {
+            let (labels, planes) = record.split_at(self.variant.label_len());
+            let label = *labels.last().unwrap();
+            if label as usize >= self.variant.num_classes() {
+                bail!("Label {label} out of range for {}", self.variant);
+            }
+            if self.variant == CifarVariant::Cifar100 {
+                self.coarse_labels.push(labels[0]);
+            }
+            self.labels.push(label);
+
+            // Records are planar, all of R then G then B; interleave them.
+            let plane = HEIGHT * WIDTH;
+            self.pixels.extend(
+                (0..plane).flat_map(|i| [planes[i], planes[plane + i], planes[2 * plane + i]]),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn variant(&self) -> CifarVariant {
+        self.variant
+    }
+
+    pub fn split(&self) -> CifarSplit {
+        self.split
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// The label of an item; the fine label, for CIFAR-100.
+    pub fn index_to_label(
+        &self,
+        index: usize,
+    ) -> usize {
+        self.labels[index] as usize
+    }
+
+    /// Convert a slice of indices to labels.
+    pub fn indices_to_labels(
+        &self,
+        indices: &[usize],
+    ) -> Vec<usize> {
+        indices.iter().map(|&i| self.index_to_label(i)).collect()
+    }
+
+    /// The CIFAR-100 coarse (superclass) label of an item; `None` for CIFAR-10.
+    pub fn coarse_label(
+        &self,
+        index: usize,
+    ) -> Option<usize> {
+        self.coarse_labels.get(index).map(|&l| l as usize)
+    }
+
+    /// Convert an item index to an object class.
+    ///
+    /// # Returns
+    ///
+    /// The class; or `None` for CIFAR-100, whose labels are not `ObjectClass`es.
+    pub fn index_to_class(
+        &self,
+        index: usize,
+    ) -> Option<ObjectClass> {
+        match self.variant {
+            CifarVariant::Cifar10 => Some(ObjectClass::ALL[self.index_to_label(index)]),
+            CifarVariant::Cifar100 => None,
+        }
+    }
+
+    /// Convert a slice of indices to a vector of object classes; `None` for CIFAR-100.
+    pub fn indices_to_classes(
+        &self,
+        indices: &[usize],
+    ) -> Option<Vec<ObjectClass>> {
+        indices.iter().map(|&i| self.index_to_class(i)).collect()
+    }
+
+    /// Count the items of each class; `None` for CIFAR-100.
+    pub fn class_distribution(&self) -> Option<ClassDistribution> {
+        let classes = self.indices_to_classes(&(0..self.len()).collect::<Vec<_>>())?;
+        Some(ClassDistribution::from_classes(classes))
+    }
+
+    /// The pseudo-path of an item, e.g. `{dir}/cat/cifar10-test-17.png`.
+    ///
+    /// Nothing exists at this path; it is the name CINIC-10 gives the same
+    /// image, so reports and loader transforms treat it as they would the
+    /// CINIC-10 copy. CIFAR-100 items use their fine label as the directory.
+    pub fn index_to_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        let class = match self.index_to_class(index) {
+            Some(class) => class.to_string(),
+            None => self.index_to_label(index).to_string(),
+        };
+        self.dir
+            .join(class)
+            .join(format!("{}-{}-{index}.png", self.variant, self.split))
+    }
+
+    /// Convert a slice of indices to a vector of pseudo-paths.
+    pub fn indices_to_paths(
+        &self,
+        indices: &[usize],
+    ) -> Vec<PathBuf> {
+        indices.iter().map(|&i| self.index_to_path(i)).collect()
+    }
+
+    /// The `[H, W, 3]` pixels of an item.
+    pub fn image_bytes(
+        &self,
+        index: usize,
+    ) -> &[u8] {
+        assert!(
+            index < self.len(),
+            "Index {index} out of range for a CIFAR split of {}",
+            self.len()
+        );
+        &self.pixels[index * IMAGE_LEN..(index + 1) * IMAGE_LEN]
+    }
+
+    /// Copy out the image of an item.
+    pub fn image(
+        &self,
+        index: usize,
+    ) -> RgbImage {
+        RgbImage::from_raw(
+            WIDTH as u32,
+            HEIGHT as u32,
+            self.image_bytes(index).to_vec(),
+        )
+        .unwrap()
+    }
+
+    /// Load an `RgbImageBatch` for a batch of indices.
+    pub fn load_rgbimagebatch(
+        &self,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        self.load_rgbimagebatch_with(&BatchLoader::default(), indices)
+    }
+
+    /// Load an `RgbImageBatch` for a batch of indices, using the given loader.
+    pub fn load_rgbimagebatch_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        Ok(self.load_rgbimagebatch_report_with(loader, indices)?.batch)
+    }
+
+    /// Load an `RgbImageBatch` with the given loader.
+    ///
+    /// The loader's transform and color space are applied as for a CINIC-10
+    /// split; images are already decoded, so no sample ever fails.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the batch, with an empty failure report.
+    pub fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        if indices.is_empty() {
+            bail!("Cannot load an empty batch");
+        }
+        let mut batch = None;
+        for &index in indices {
+            let img = loader.transform_image(&self.index_to_path(index), self.image(index))?;
+            let batch = batch.get_or_insert_with(|| {
+                RgbImageBatch::new(&[
+                    indices.len(),
+                    img.height() as usize,
+                    img.width() as usize,
+                    CHANNELS,
+                ])
+            });
+            if (img.width() as usize, img.height() as usize) != (batch.width(), batch.height()) {
+                bail!(
+                    "Transformed CIFAR image {index} is {:?}, expected {:?}",
+                    img.dimensions(),
+                    (batch.width(), batch.height())
+                );
+            }
+            loader.time(Stage::Copy, || batch.push_rgb_pixels(&img));
+        }
+        let mut batch = batch.unwrap();
+        loader.convert_color_space(&mut batch);
+        if let Some(profiler) = loader.profiler() {
+            profiler.record_batch();
+        }
+        Ok(LoadedBatch {
+            batch,
+            failures: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::parse_item_path;
+    use anyhow::Result;
+    use image::Rgb;
+
+    /// A record of a solid `[r, g, b]` image.
+    fn record(
+        labels: &[u8],
+        rgb: [u8; 3],
+    ) -> Vec<u8> {
+        let mut record = labels.to_vec();
+        for c in rgb {
+            record.extend(std::iter::repeat_n(c, HEIGHT * WIDTH));
+        }
+        record
+    }
+
+    #[test]
+    fn test_cifar10() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let bytes = [record(&[3], [1, 2, 3]), record(&[8], [4, 5, 6])].concat();
+        fs::write(dir.path().join("test_batch.bin"), bytes)?;
+
+        let cifar = CifarBinary::open(dir.path(), CifarVariant::Cifar10, CifarSplit::Test)?;
+        assert_eq!(cifar.len(), 2);
+        assert_eq!(
+            cifar.indices_to_classes(&[0, 1]),
+            Some(vec![ObjectClass::Cat, ObjectClass::Ship])
+        );
+        assert_eq!(
+            cifar
+                .class_distribution()
+                .map(|dist| dist.count(ObjectClass::Ship)),
+            Some(1)
+        );
+        assert_eq!(cifar.coarse_label(0), None);
+        assert_eq!(cifar.image(1).get_pixel(31, 0), &Rgb([4, 5, 6]));
+
+        let path = cifar.index_to_path(1);
+        assert_eq!(path, dir.path().join("ship/cifar10-test-1.png"));
+        assert_eq!(parse_item_path(&path).map(|name| name.number), Some(1));
+
+        let batch = cifar.load_rgbimagebatch(&[1, 0])?;
+        assert_eq!(batch.shape, vec![2, HEIGHT, WIDTH, CHANNELS]);
+        assert_eq!(&batch.data[..3], &[4, 5, 6]);
+        assert_eq!(batch.image(1), cifar.image(0));
+
+        assert!(CifarBinary::open(dir.path(), CifarVariant::Cifar10, CifarSplit::Train).is_err());
+        fs::write(dir.path().join("test_batch.bin"), [0u8; 10])?;
+        assert!(CifarBinary::open(dir.path(), CifarVariant::Cifar10, CifarSplit::Test).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cifar100() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("train.bin"), record(&[4, 72], [9, 9, 9]))?;
+
+        let cifar = CifarBinary::open(dir.path(), CifarVariant::Cifar100, CifarSplit::Train)?;
+        assert_eq!(cifar.indices_to_labels(&[0]), vec![72]);
+        assert_eq!(cifar.coarse_label(0), Some(4));
+        assert_eq!(cifar.index_to_class(0), None);
+        assert!(cifar.class_distribution().is_none());
+        assert_eq!(
+            cifar.index_to_path(0),
+            dir.path().join("72/cifar100-train-0.png")
+        );
+
+        fs::write(dir.path().join("train.bin"), record(&[4, 100], [9, 9, 9]))?;
+        assert!(CifarBinary::open(dir.path(), CifarVariant::Cifar100, CifarSplit::Train).is_err());
+
+        Ok(())
+    }
+}
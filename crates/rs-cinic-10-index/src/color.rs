@@ -0,0 +1,222 @@
+use crate::images::RgbImageBatch;
+use serde::{Deserialize, Serialize};
+
+/// The color space of loaded pixels; see `LoaderConfig::color_space`.
+///
+/// Conversions assume sRGB input; LAB uses the D65 white point.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ColorSpace {
+    /// The decoded sRGB pixels, unchanged.
+    #[default]
+    Rgb,
+
+    /// Full-range (JPEG) YCbCr.
+    #[serde(rename = "ycbcr")]
+    #[strum(serialize = "ycbcr")]
+    YCbCr,
+
+    /// CIE L*a*b*.
+    Lab,
+
+    /// Hue, saturation, value.
+    Hsv,
+}
+
+/// The sRGB transfer function, inverted; `c` in `[0, 1]`.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The CIE LAB companding function.
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+impl ColorSpace {
+    /// Convert an sRGB pixel to this space, as floats.
+    ///
+    /// The channel ranges are:
+    ///
+    /// - `Rgb`: `[0, 1]`.
+    /// - `YCbCr`: `[0, 1]`; chroma is centered on `0.5`.
+    /// - `Lab`: `L*` in `[0, 100]`; `a*` and `b*` roughly in `[-128, 127]`.
+    /// - `Hsv`: hue in degrees, in `[0, 360)`; saturation and value in `[0, 1]`.
+    pub fn convert_f32(
+        self,
+        rgb: [u8; 3],
+    ) -> [f32; 3] {
+        let [r, g, b] = rgb.map(|c| c as f32 / 255.0);
+        match self {
+            ColorSpace::Rgb => [r, g, b],
+            ColorSpace::YCbCr => [
+                0.299 * r + 0.587 * g + 0.114 * b,
+                0.5 - 0.168_736 * r - 0.331_264 * g + 0.5 * b,
+                0.5 + 0.5 * r - 0.418_688 * g - 0.081_312 * b,
+            ],
+            ColorSpace::Lab => {
+                let [r, g, b] = [r, g, b].map(srgb_to_linear);
+                let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+                let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+                let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+                let (fx, fy, fz) = (lab_f(x), lab_f(y), lab_f(z));
+                [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+            }
+            ColorSpace::Hsv => {
+                let max = r.max(g).max(b);
+                let chroma = max - r.min(g).min(b);
+                let hue = if chroma == 0.0 {
+                    0.0
+                } else if max == r {
+                    60.0 * ((g - b) / chroma).rem_euclid(6.0)
+                } else if max == g {
+                    60.0 * ((b - r) / chroma + 2.0)
+                } else {
+                    60.0 * ((r - g) / chroma + 4.0)
+                };
+                let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+                [hue, saturation, max]
+            }
+        }
+    }
+
+    /// Convert an sRGB pixel to this space, as bytes.
+    ///
+    /// Each channel of `convert_f32()` is scaled to fill `[0, 255]`:
+    /// `L*` by `255 / 100`, `a*` and `b*` offset by `128`, and hue by `255 / 360`.
+    pub fn convert_u8(
+        self,
+        rgb: [u8; 3],
+    ) -> [u8; 3] {
+        let [c0, c1, c2] = self.convert_f32(rgb);
+        let scaled = match self {
+            ColorSpace::Rgb => return rgb,
+            ColorSpace::YCbCr => [c0 * 255.0, c1 * 255.0, c2 * 255.0],
+            ColorSpace::Lab => [c0 * 2.55, c1 + 128.0, c2 + 128.0],
+            ColorSpace::Hsv => [c0 * 255.0 / 360.0, c1 * 255.0, c2 * 255.0],
+        };
+        scaled.map(|c| c.round().clamp(0.0, 255.0) as u8)
+    }
+
+    /// Convert packed sRGB pixels to this space, in place; see `convert_u8()`.
+    pub fn convert_u8_slice(
+        self,
+        data: &mut [u8],
+    ) {
+        if self == ColorSpace::Rgb {
+            return;
+        }
+        for px in data.chunks_exact_mut(3) {
+            let converted = self.convert_u8([px[0], px[1], px[2]]);
+            px.copy_from_slice(&converted);
+        }
+    }
+}
+
+/// A batch of float images, with shape `[B, H, W, C]`; see `BatchLoader::load_f32_batch_report()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct F32ImageBatch {
+    pub data: Vec<f32>,
+    pub shape: [usize; 4],
+}
+
+impl F32ImageBatch {
+    /// Convert an sRGB batch to floats in `space`; see `ColorSpace::convert_f32()`.
+    pub fn from_rgbimagebatch(
+        batch: &RgbImageBatch,
+        space: ColorSpace,
+    ) -> Self {
+        Self {
+            data: batch
+                .data
+                .chunks_exact(3)
+                .flat_map(|px| space.convert_f32([px[0], px[1], px[2]]))
+                .collect(),
+            shape: [batch.batch_size(), batch.height(), batch.width(), 3],
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.shape[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.shape[1]
+    }
+
+    pub fn width(&self) -> usize {
+        self.shape[2]
+    }
+
+    pub fn channels(&self) -> usize {
+        self.shape[3]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::{BatchLoader, LoaderConfig};
+    use image::{Rgb, RgbImage};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_color_spaces() -> anyhow::Result<()> {
+        let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 0.05);
+
+        assert!(close(
+            ColorSpace::Lab.convert_f32([255; 3]),
+            [100.0, 0.0, 0.0]
+        ));
+        assert!(close(
+            ColorSpace::Lab.convert_f32([255, 0, 0]),
+            [53.24, 80.09, 67.2]
+        ));
+        assert!(close(
+            ColorSpace::Hsv.convert_f32([0, 255, 0]),
+            [120.0, 1.0, 1.0]
+        ));
+        assert_eq!(ColorSpace::YCbCr.convert_u8([255; 3]), [255, 128, 128]);
+        assert_eq!(ColorSpace::Lab.convert_u8([0; 3]), [0, 128, 128]);
+        assert_eq!(ColorSpace::Rgb.convert_u8([1, 2, 3]), [1, 2, 3]);
+        assert_eq!(ColorSpace::from_str("ycbcr")?, ColorSpace::YCbCr);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("red.png");
+        RgbImage::from_pixel(2, 2, Rgb([255, 0, 0])).save(&path)?;
+        let loader = BatchLoader::new(LoaderConfig {
+            color_space: ColorSpace::Hsv,
+            ..Default::default()
+        });
+        let batch = loader.load_rgbimagebatch(&[&path])?;
+        assert_eq!(&batch.data[..3], &[0, 255, 255]);
+        let floats = loader.load_f32_batch_report(&[&path, &path])?.batch;
+        assert_eq!(floats.shape, [2, 2, 2, 3]);
+        assert_eq!(&floats.data[..3], &[0.0, 1.0, 1.0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::CINC10_PATH_ENV_VAR;
+use crate::error::{Result, bail};
+use crate::images::decoder_by_name;
+use crate::loader::{ErrorPolicy, LoaderConfig};
+use anyhow::Context;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The directory for derived data: index caches, packed splits, and downloads.
+pub const CINIC10_CACHE_DIR_ENV_VAR: &str = "CINIC10_CACHE_DIR";
+
+/// The number of worker threads for scans and stats; `0` means "all available cores".
+pub const CINIC10_THREADS_ENV_VAR: &str = "CINIC10_THREADS";
+
+/// Fail batches on any unloadable image (`1`), or skip such images (`0`).
+pub const CINIC10_STRICT_ENV_VAR: &str = "CINIC10_STRICT";
+
+/// The image decoder backend; see `images::decoder_by_name()`.
+pub const CINIC10_DECODER_ENV_VAR: &str = "CINIC10_DECODER";
+
+/// Read packed datasets with direct IO (`1`); see `LoaderConfig::direct_io`.
+pub const CINIC10_DIRECT_IO_ENV_VAR: &str = "CINIC10_DIRECT_IO";
+
+/// The number of threads reading each batch; see `LoaderConfig::read_threads`.
+pub const CINIC10_READ_THREADS_ENV_VAR: &str = "CINIC10_READ_THREADS";
+
+/// Record per-stage loader timings (`1`); see `LoaderConfig::profile`.
+pub const CINIC10_PROFILE_ENV_VAR: &str = "CINIC10_PROFILE";
+
+/// Data pipeline settings, usually read from `CINIC10_*` env vars.
+///
+/// Every field is optional; unset fields keep the library defaults.
+/// This lets cluster jobs tune loading without code changes:
+///
+/// | Env var                | Field          | Value                    |
+/// |------------------------|----------------|--------------------------|
+/// | `CINIC10_PATH`         | `data_path`    | a directory              |
+/// | `CINIC10_CACHE_DIR`    | `cache_dir`    | a directory              |
+/// | `CINIC10_THREADS`      | `threads`      | an integer; `0` for all  |
+/// | `CINIC10_STRICT`       | `strict`       | a boolean                |
+/// | `CINIC10_DECODER`      | `decoder`      | a decoder backend name   |
+/// | `CINIC10_DIRECT_IO`    | `direct_io`    | a boolean                |
+/// | `CINIC10_READ_THREADS` | `read_threads` | an integer; `0` disables |
+/// | `CINIC10_PROFILE`      | `profile`      | a boolean                |
+///
+/// Booleans accept `1`/`0`, `true`/`false`, `yes`/`no`, and `on`/`off`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cinic10Config {
+    pub data_path: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub threads: Option<usize>,
+    pub strict: Option<bool>,
+    pub decoder: Option<String>,
+    pub direct_io: Option<bool>,
+    pub read_threads: Option<usize>,
+    pub profile: Option<bool>,
+}
+
+/// Parse an env var boolean.
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+impl Cinic10Config {
+    /// Read the config from the process environment.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config; or an error naming the first malformed var.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(env::vars())
+    }
+
+    /// Read the config from a set of `(name, value)` variables.
+    ///
+    /// Unrelated variables are ignored, and empty values are treated as unset.
+    ///
+    /// # Parameters
+    ///
+    /// - `vars`: The variables.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the config; or an error naming the first malformed var.
+    pub fn from_vars<I, K, V>(vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let vars: HashMap<String, String> = vars
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .filter(|(_, v)| !v.trim().is_empty())
+            .collect();
+
+        let path = |name: &str| vars.get(name).map(PathBuf::from);
+        let number = |name: &str| -> Result<Option<usize>> {
+            vars.get(name)
+                .map(|v| {
+                    Ok(usize::from_str(v.trim())
+                        .with_context(|| format!("{name}: expected an integer, got {v:?}"))?)
+                })
+                .transpose()
+        };
+        let flag = |name: &str| -> Result<Option<bool>> {
+            vars.get(name)
+                .map(|v| match parse_bool(v) {
+                    Some(b) => Ok(b),
+                    None => bail!("{name}: expected a boolean, got {v:?}"),
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            data_path: path(CINC10_PATH_ENV_VAR),
+            cache_dir: path(CINIC10_CACHE_DIR_ENV_VAR),
+            threads: number(CINIC10_THREADS_ENV_VAR)?,
+            strict: flag(CINIC10_STRICT_ENV_VAR)?,
+            decoder: vars
+                .get(CINIC10_DECODER_ENV_VAR)
+                .map(|v| v.trim().to_string()),
+            direct_io: flag(CINIC10_DIRECT_IO_ENV_VAR)?,
+            read_threads: number(CINIC10_READ_THREADS_ENV_VAR)?,
+            profile: flag(CINIC10_PROFILE_ENV_VAR)?,
+        })
+    }
+
+    /// The worker thread count for parallel scans; `0` means "all available cores".
+    pub fn parallelism(&self) -> usize {
+        self.threads.unwrap_or(0)
+    }
+
+    /// Apply the loader settings of the config to `base`.
+    ///
+    /// `strict` selects `ErrorPolicy::FailFast` or `ErrorPolicy::Skip`.
+    ///
+    /// # Parameters
+    ///
+    /// - `base`: The config to override.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loader config; or an error for an unknown decoder.
+    pub fn apply_to_loader_config(
+        &self,
+        mut base: LoaderConfig,
+    ) -> Result<LoaderConfig> {
+        if let Some(strict) = self.strict {
+            base.on_error = if strict {
+                ErrorPolicy::FailFast
+            } else {
+                ErrorPolicy::Skip
+            };
+        }
+        if let Some(name) = &self.decoder {
+            base.decoder = Some(decoder_by_name(name)?);
+        }
+        if let Some(direct_io) = self.direct_io {
+            base.direct_io = direct_io;
+        }
+        if let Some(read_threads) = self.read_threads {
+            base.read_threads = read_threads;
+        }
+        if let Some(profile) = self.profile {
+            base.profile = profile;
+        }
+        Ok(base)
+    }
+
+    /// The loader config: the defaults, with the config's settings applied.
+    pub fn loader_config(&self) -> Result<LoaderConfig> {
+        self.apply_to_loader_config(LoaderConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_from_vars() -> Result<()> {
+        let config = Cinic10Config::from_vars([
+            ("CINIC10_PATH", "/data/cinic"),
+            ("CINIC10_CACHE_DIR", "/scratch/cinic"),
+            ("CINIC10_THREADS", "8"),
+            ("CINIC10_STRICT", "no"),
+            ("CINIC10_DECODER", "image"),
+            ("CINIC10_DIRECT_IO", ""),
+            ("CINIC10_READ_THREADS", " 4 "),
+            ("HOME", "/root"),
+        ])?;
+        assert_eq!(config.data_path, Some(PathBuf::from("/data/cinic")));
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/scratch/cinic")));
+        assert_eq!(config.parallelism(), 8);
+        assert_eq!(config.direct_io, None);
+
+        let loader = config.loader_config()?;
+        assert_eq!(loader.on_error, ErrorPolicy::Skip);
+        assert_eq!(loader.decoder.unwrap().name(), "image");
+        assert_eq!(loader.read_threads, 4);
+        assert!(!loader.profile);
+
+        assert_eq!(
+            Cinic10Config::from_vars::<_, &str, &str>([])?,
+            Cinic10Config::default()
+        );
+        assert!(Cinic10Config::from_vars([("CINIC10_THREADS", "many")]).is_err());
+        assert!(Cinic10Config::from_vars([("CINIC10_STRICT", "maybe")]).is_err());
+        let unknown = Cinic10Config::from_vars([("CINIC10_DECODER", "nope")])?;
+        assert!(unknown.loader_config().is_err());
+
+        Ok(())
+    }
+}
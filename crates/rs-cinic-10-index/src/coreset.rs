@@ -0,0 +1,207 @@
+use crate::embeddings::{Embeddings, Metric};
+
+/// Select a subset which covers embedding space, by k-center greedy.
+///
+/// Repeatedly adds the candidate farthest from everything selected so far,
+/// which minimizes (within a factor of 2) the largest distance from any
+/// candidate to its nearest selected point.
+///
+/// # Parameters
+///
+/// - `embeddings`: The embeddings of the split.
+/// - `candidates`: The item indices to select from.
+/// - `initial`: Already selected (e.g. labeled) indices; not included in the result.
+/// - `size`: The number of indices to select.
+/// - `metric`: The distance between embeddings.
+///
+/// # Returns
+///
+/// Up to `size` distinct candidate indices, in selection order.
+pub fn k_center_greedy(
+    embeddings: &Embeddings,
+    candidates: &[usize],
+    initial: &[usize],
+    size: usize,
+    metric: Metric,
+) -> Vec<usize> {
+    let size = size.min(candidates.len());
+    let mut selected = Vec::with_capacity(size);
+    if size == 0 {
+        return selected;
+    }
+
+    let mut seeds = initial.to_vec();
+    if seeds.is_empty() {
+        // Without a seed set, start from the candidate nearest the centroid.
+        let mean = centroid(embeddings, candidates);
+        let first = candidates
+            .iter()
+            .copied()
+            .min_by(|&a, &b| {
+                metric
+                    .distance(&mean, embeddings.get(a))
+                    .total_cmp(&metric.distance(&mean, embeddings.get(b)))
+            })
+            .unwrap();
+        selected.push(first);
+        seeds.push(first);
+    }
+
+    // `NaN` marks candidates which are already selected or seeded.
+    let mut min_dist: Vec<f32> = candidates
+        .iter()
+        .map(|&c| {
+            if seeds.contains(&c) {
+                return f32::NAN;
+            }
+            seeds
+                .iter()
+                .map(|&s| metric.distance(embeddings.get(c), embeddings.get(s)))
+                .fold(f32::INFINITY, f32::min)
+        })
+        .collect();
+
+    while selected.len() < size {
+        let Some((pos, _)) = min_dist
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| !d.is_nan())
+            .max_by(|a, b| a.1.total_cmp(b.1))
+        else {
+            break;
+        };
+        let pick = candidates[pos];
+        selected.push(pick);
+        min_dist[pos] = f32::NAN;
+
+        for (d, &c) in min_dist.iter_mut().zip(candidates) {
+            if !d.is_nan() {
+                *d = d.min(metric.distance(embeddings.get(c), embeddings.get(pick)));
+            }
+        }
+    }
+    selected
+}
+
+fn centroid(
+    embeddings: &Embeddings,
+    indices: &[usize],
+) -> Vec<f32> {
+    let mut mean = vec![0.0f32; embeddings.dim()];
+    for &i in indices {
+        for (m, v) in mean.iter_mut().zip(embeddings.get(i)) {
+            *m += v;
+        }
+    }
+    let n = indices.len().max(1) as f32;
+    mean.iter_mut().for_each(|m| *m /= n);
+    mean
+}
+
+/// Select a subset whose mean embedding tracks the candidates' mean, by herding.
+///
+/// At each step, adds the candidate which brings the mean of the selection
+/// closest to the mean of all candidates (as in iCaRL exemplar selection).
+///
+/// # Parameters
+///
+/// - `embeddings`: The embeddings of the split.
+/// - `candidates`: The item indices to select from.
+/// - `size`: The number of indices to select.
+///
+/// # Returns
+///
+/// Up to `size` distinct candidate indices, in selection order.
+pub fn herding(
+    embeddings: &Embeddings,
+    candidates: &[usize],
+    size: usize,
+) -> Vec<usize> {
+    let size = size.min(candidates.len());
+    let target = centroid(embeddings, candidates);
+    let mut sum = vec![0.0f32; embeddings.dim()];
+    let mut taken = vec![false; candidates.len()];
+    let mut selected = Vec::with_capacity(size);
+
+    for k in 1..=size {
+        let k = k as f32;
+        let best = candidates
+            .iter()
+            .enumerate()
+            .filter(|(pos, _)| !taken[*pos])
+            .map(|(pos, &c)| {
+                let err: f32 = target
+                    .iter()
+                    .zip(&sum)
+                    .zip(embeddings.get(c))
+                    .map(|((t, s), x)| {
+                        let d = t - (s + x) / k;
+                        d * d
+                    })
+                    .sum();
+                (pos, err)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((pos, _)) = best else {
+            break;
+        };
+        taken[pos] = true;
+        let pick = candidates[pos];
+        for (s, x) in sum.iter_mut().zip(embeddings.get(pick)) {
+            *s += x;
+        }
+        selected.push(pick);
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_k_center_greedy() -> Result<()> {
+        // Two tight clusters and one outlier.
+        let embeddings = Embeddings::from_rows(vec![
+            vec![0.0, 0.0],
+            vec![0.1, 0.0],
+            vec![0.0, 0.1],
+            vec![5.0, 5.0],
+            vec![5.1, 5.0],
+            vec![-9.0, 0.0],
+        ])?;
+        let all: Vec<usize> = (0..6).collect();
+
+        let picked = k_center_greedy(&embeddings, &all, &[], 3, Metric::L2);
+        assert_eq!(picked.len(), 3);
+        assert!(picked.contains(&5));
+        assert!(picked.iter().any(|&i| i < 3));
+        assert!(picked.iter().any(|&i| i == 3 || i == 4));
+
+        let extended = k_center_greedy(&embeddings, &all, &[0, 3], 1, Metric::L2);
+        assert_eq!(extended, vec![5]);
+
+        assert_eq!(
+            k_center_greedy(&embeddings, &all, &[], 10, Metric::L2).len(),
+            6
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_herding() -> Result<()> {
+        let embeddings =
+            Embeddings::from_rows(vec![vec![0.0], vec![10.0], vec![4.0], vec![6.0], vec![5.0]])?;
+        let all: Vec<usize> = (0..5).collect();
+
+        assert_eq!(herding(&embeddings, &all, 1), vec![4]);
+        let picked = herding(&embeddings, &all, 3);
+        assert_eq!(picked[0], 4);
+        assert_eq!(picked.len(), 3);
+        assert_eq!(herding(&embeddings, &[0, 1], 1).len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,639 @@
+use crate::error::{Result, bail};
+use crate::index::DatasetIndex;
+use crate::record::{ComponentRecord, Recordable};
+use crate::transform::ImageTransform;
+use crate::writer::DatasetWriter;
+use image::RgbImage;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::path::Path;
+
+/// The number of severity levels of each corruption; severities are `1..=5`.
+pub const SEVERITIES: u8 = 5;
+
+/// The common corruptions (Hendrycks & Dietterich, 2019), with CIFAR-10-C parameters.
+///
+/// `frost`, `snow`, `glass_blur`, `elastic_transform` and `zoom_blur` are not
+/// implemented; `frost` needs external texture images, and the others depend on
+/// OpenCV/Wand behaviour which is not reproduced here.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Corruption {
+    GaussianNoise,
+    ShotNoise,
+    ImpulseNoise,
+    SpeckleNoise,
+    GaussianBlur,
+    DefocusBlur,
+    MotionBlur,
+    Fog,
+    Brightness,
+    Contrast,
+    Saturate,
+    Pixelate,
+    JpegCompression,
+}
+
+impl Corruption {
+    /// Apply the corruption to an image.
+    ///
+    /// # Parameters
+    ///
+    /// - `img`: The image.
+    /// - `severity`: The severity, `1..=5`.
+    /// - `rng`: The source of randomness.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the corrupted image.
+    pub fn apply(
+        self,
+        img: &RgbImage,
+        severity: u8,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        if !(1..=SEVERITIES).contains(&severity) {
+            bail!("Severity must be in 1..={SEVERITIES}, got {severity}");
+        }
+        let s = (severity - 1) as usize;
+        let x = FloatImage::from_rgb(img);
+
+        let out = match self {
+            Corruption::GaussianNoise => {
+                let c = [0.04, 0.06, 0.08, 0.09, 0.10][s];
+                x.map(|v| v + c * normal(rng))
+            }
+            Corruption::ShotNoise => {
+                let c = [500.0, 250.0, 100.0, 75.0, 50.0][s];
+                x.map(|v| poisson(rng, v * c) / c)
+            }
+            Corruption::ImpulseNoise => {
+                let c = [0.01, 0.02, 0.03, 0.05, 0.07][s];
+                x.map(|v| {
+                    if rng.random::<f32>() >= c {
+                        v
+                    } else if rng.random::<bool>() {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                })
+            }
+            Corruption::SpeckleNoise => {
+                let c = [0.06, 0.1, 0.12, 0.16, 0.2][s];
+                x.map(|v| v + v * c * normal(rng))
+            }
+            Corruption::GaussianBlur => {
+                let c = [0.4, 0.6, 0.7, 0.8, 1.0][s];
+                gaussian_blur(&x, c)
+            }
+            Corruption::DefocusBlur => {
+                let (radius, alias) =
+                    [(0.3, 0.4), (0.4, 0.5), (0.5, 0.6), (1.0, 0.2), (1.5, 0.1)][s];
+                convolve(&x, &disk_kernel(radius, alias))
+            }
+            Corruption::MotionBlur => {
+                let (radius, sigma) = [
+                    (10.0, 1.0),
+                    (10.0, 1.5),
+                    (10.0, 2.0),
+                    (10.0, 2.5),
+                    (12.0, 3.0),
+                ][s];
+                let angle = rng.random_range(-45.0f32..45.0).to_radians();
+                motion_blur(&x, radius, sigma, angle)
+            }
+            Corruption::Fog => {
+                let (strength, decay) =
+                    [(0.2, 3.0), (0.5, 3.0), (0.75, 2.5), (1.0, 2.0), (1.5, 1.75)][s];
+                let max = x.data.iter().cloned().fold(0.0f32, f32::max);
+                let fractal = plasma_fractal(32, decay, rng);
+                let mut out = x.clone();
+                for (i, px) in out.data.chunks_mut(3).enumerate() {
+                    let (row, col) = ((i / x.width) % 32, (i % x.width) % 32);
+                    let f = fractal[row * 32 + col];
+                    for v in px {
+                        *v = (*v + strength * f) * max / (max + strength);
+                    }
+                }
+                out
+            }
+            Corruption::Brightness => {
+                let c = [0.05, 0.1, 0.15, 0.2, 0.3][s];
+                x.map_hsv(|h, sat, v| (h, sat, (v + c).clamp(0.0, 1.0)))
+            }
+            Corruption::Contrast => {
+                let c = [0.75, 0.5, 0.4, 0.3, 0.15][s];
+                let n = (x.width * x.height).max(1) as f32;
+                let mut means = [0.0f32; 3];
+                for px in x.data.chunks(3) {
+                    for ch in 0..3 {
+                        means[ch] += px[ch] / n;
+                    }
+                }
+                let mut out = x.clone();
+                for px in out.data.chunks_mut(3) {
+                    for ch in 0..3 {
+                        px[ch] = (px[ch] - means[ch]) * c + means[ch];
+                    }
+                }
+                out
+            }
+            Corruption::Saturate => {
+                let (scale, shift) =
+                    [(0.3, 0.0), (0.1, 0.0), (1.5, 0.0), (2.0, 0.1), (2.5, 0.2)][s];
+                x.map_hsv(|h, sat, v| (h, (sat * scale + shift).clamp(0.0, 1.0), v))
+            }
+            Corruption::Pixelate => {
+                let c = [0.95, 0.9, 0.85, 0.75, 0.65][s];
+                let (w, h) = img.dimensions();
+                let sw = ((w as f32 * c) as u32).max(1);
+                let sh = ((h as f32 * c) as u32).max(1);
+                let small = image::imageops::resize(img, sw, sh, FilterType::Triangle);
+                return Ok(image::imageops::resize(&small, w, h, FilterType::Nearest));
+            }
+            Corruption::JpegCompression => {
+                let quality = [80, 65, 58, 50, 40][s];
+                let mut bytes = Vec::new();
+                JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(img)?;
+                return Ok(image::load_from_memory(&bytes)?.to_rgb8());
+            }
+        };
+
+        Ok(out.to_rgb())
+    }
+}
+
+/// A `Corruption` at a fixed severity, as an `ImageTransform`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CorruptionTransform {
+    pub corruption: Corruption,
+    pub severity: u8,
+}
+
+impl CorruptionTransform {
+    pub fn new(
+        corruption: Corruption,
+        severity: u8,
+    ) -> Result<Self> {
+        if !(1..=SEVERITIES).contains(&severity) {
+            bail!("Severity must be in 1..={SEVERITIES}, got {severity}");
+        }
+        Ok(Self {
+            corruption,
+            severity,
+        })
+    }
+}
+
+impl Recordable for CorruptionTransform {
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("corruption")
+            .with_param("corruption", self.corruption.to_string())
+            .with_param("severity", self.severity)
+    }
+}
+
+impl ImageTransform for CorruptionTransform {
+    fn apply(
+        &self,
+        img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        self.corruption.apply(&img, self.severity, rng)
+    }
+}
+
+/// Write corrupted copies of a split, in the CINIC-10-C layout.
+///
+/// Each copy is written to `{root}/{corruption}/{severity}/{split_name}/{class}/`.
+///
+/// # Parameters
+///
+/// - `root`: The output root.
+/// - `split_name`: The split directory name, e.g. `"test"`.
+/// - `split`: The source split.
+/// - `corruptions`: The corruptions to apply.
+/// - `severities`: The severities to apply each corruption at.
+/// - `seed`: The random seed; see `transform::sample_rng()`.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the written splits, in `(corruption, severity)` order.
+pub fn materialize_corrupted<P>(
+    root: P,
+    split_name: &str,
+    split: &DatasetIndex,
+    corruptions: &[Corruption],
+    severities: &[u8],
+    seed: u64,
+    parallelism: usize,
+) -> Result<Vec<(CorruptionTransform, DatasetIndex)>>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref();
+    let mut written = Vec::with_capacity(corruptions.len() * severities.len());
+    for &corruption in corruptions {
+        for &severity in severities {
+            let transform = CorruptionTransform::new(corruption, severity)?;
+            let writer =
+                DatasetWriter::new(root.join(corruption.to_string()).join(severity.to_string()));
+            let index =
+                writer.write_split(split_name, split, Some((&transform, seed)), parallelism)?;
+            written.push((transform, index));
+        }
+    }
+    Ok(written)
+}
+
+/// An RGB image as interleaved `f32` values in `[0, 1]`.
+#[derive(Debug, Clone)]
+struct FloatImage {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl FloatImage {
+    fn from_rgb(img: &RgbImage) -> Self {
+        Self {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img.as_raw().iter().map(|&v| v as f32 / 255.0).collect(),
+        }
+    }
+
+    fn to_rgb(&self) -> RgbImage {
+        let raw = self
+            .data
+            .iter()
+            .map(|&v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect();
+        RgbImage::from_raw(self.width as u32, self.height as u32, raw).unwrap()
+    }
+
+    fn map<F>(
+        &self,
+        mut f: F,
+    ) -> Self
+    where
+        F: FnMut(f32) -> f32,
+    {
+        Self {
+            data: self.data.iter().map(|&v| f(v)).collect(),
+            ..*self
+        }
+    }
+
+    fn map_hsv<F>(
+        &self,
+        f: F,
+    ) -> Self
+    where
+        F: Fn(f32, f32, f32) -> (f32, f32, f32),
+    {
+        let mut out = self.clone();
+        for px in out.data.chunks_mut(3) {
+            let (h, s, v) = rgb_to_hsv(px[0], px[1], px[2]);
+            let (h, s, v) = f(h, s, v);
+            let (r, g, b) = hsv_to_rgb(h, s, v);
+            px.copy_from_slice(&[r, g, b]);
+        }
+        out
+    }
+
+    /// Bilinear sample of one channel, clamping at the edges.
+    fn sample(
+        &self,
+        x: f32,
+        y: f32,
+        ch: usize,
+    ) -> f32 {
+        let max_x = self.width as f32 - 1.0;
+        let max_y = self.height as f32 - 1.0;
+        let (x, y) = (x.clamp(0.0, max_x), y.clamp(0.0, max_y));
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let at = |x: usize, y: usize| self.data[(y * self.width + x) * 3 + ch];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// A standard normal draw (Box-Muller).
+fn normal(rng: &mut dyn RngCore) -> f32 {
+    let u1 = rng.random::<f32>().max(f32::MIN_POSITIVE);
+    let u2 = rng.random::<f32>();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// A Poisson draw; Knuth's method for small `lambda`, else the normal approximation.
+fn poisson(
+    rng: &mut dyn RngCore,
+    lambda: f32,
+) -> f32 {
+    if lambda <= 0.0 {
+        return 0.0;
+    }
+    if lambda > 30.0 {
+        return (lambda + lambda.sqrt() * normal(rng)).round().max(0.0);
+    }
+    let limit = (-lambda).exp();
+    let mut k = 0.0;
+    let mut p = rng.random::<f32>();
+    while p > limit {
+        k += 1.0;
+        p *= rng.random::<f32>();
+    }
+    k
+}
+
+fn rgb_to_hsv(
+    r: f32,
+    g: f32,
+    b: f32,
+) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let s = if max > 0.0 { delta / max } else { 0.0 };
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    (h, s, max)
+}
+
+fn hsv_to_rgb(
+    h: f32,
+    s: f32,
+    v: f32,
+) -> (f32, f32, f32) {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    (r + m, g + m, b + m)
+}
+
+/// A square convolution kernel, with odd side length.
+struct Kernel {
+    size: usize,
+    weights: Vec<f32>,
+}
+
+/// Convolve each channel with `kernel`, replicating edge pixels.
+fn convolve(
+    img: &FloatImage,
+    kernel: &Kernel,
+) -> FloatImage {
+    let half = (kernel.size / 2) as isize;
+    let mut out = img.clone();
+    for y in 0..img.height {
+        for x in 0..img.width {
+            for ch in 0..3 {
+                let mut acc = 0.0;
+                for ky in 0..kernel.size {
+                    for kx in 0..kernel.size {
+                        let sy =
+                            (y as isize + ky as isize - half).clamp(0, img.height as isize - 1);
+                        let sx = (x as isize + kx as isize - half).clamp(0, img.width as isize - 1);
+                        acc += kernel.weights[ky * kernel.size + kx]
+                            * img.data[(sy as usize * img.width + sx as usize) * 3 + ch];
+                    }
+                }
+                out.data[(y * img.width + x) * 3 + ch] = acc;
+            }
+        }
+    }
+    out
+}
+
+fn gaussian_kernel(sigma: f32) -> Kernel {
+    let half = (3.0 * sigma).ceil().max(1.0) as isize;
+    let size = (2 * half + 1) as usize;
+    let mut weights = Vec::with_capacity(size * size);
+    for y in -half..=half {
+        for x in -half..=half {
+            weights.push((-((x * x + y * y) as f32) / (2.0 * sigma * sigma)).exp());
+        }
+    }
+    let total: f32 = weights.iter().sum();
+    weights.iter_mut().for_each(|w| *w /= total);
+    Kernel { size, weights }
+}
+
+fn gaussian_blur(
+    img: &FloatImage,
+    sigma: f32,
+) -> FloatImage {
+    convolve(img, &gaussian_kernel(sigma))
+}
+
+/// A disk of the given radius, softened by a 3x3 gaussian of sigma `alias_blur`.
+fn disk_kernel(
+    radius: f32,
+    alias_blur: f32,
+) -> Kernel {
+    let half = radius.ceil().max(1.0) as isize + 1;
+    let size = (2 * half + 1) as usize;
+    let disk: Vec<f32> = (-half..=half)
+        .flat_map(|y| {
+            (-half..=half).map(move |x| ((x * x + y * y) as f32 <= radius * radius) as u8 as f32)
+        })
+        .collect();
+
+    let mut weights = vec![0.0; size * size];
+    for y in 0..size {
+        for x in 0..size {
+            for dy in -1isize..=1 {
+                for dx in -1isize..=1 {
+                    let (sy, sx) = (y as isize + dy, x as isize + dx);
+                    if sy < 0 || sx < 0 || sy >= size as isize || sx >= size as isize {
+                        continue;
+                    }
+                    let w = (-((dx * dx + dy * dy) as f32) / (2.0 * alias_blur * alias_blur)).exp();
+                    weights[y * size + x] += w * disk[sy as usize * size + sx as usize];
+                }
+            }
+        }
+    }
+    let total: f32 = weights.iter().sum();
+    weights.iter_mut().for_each(|w| *w /= total);
+    Kernel { size, weights }
+}
+
+/// A one-sided blur along `angle`, with gaussian weights over `radius` pixels.
+fn motion_blur(
+    img: &FloatImage,
+    radius: f32,
+    sigma: f32,
+    angle: f32,
+) -> FloatImage {
+    let taps: Vec<(f32, f32, f32)> = (0..=radius as usize)
+        .map(|t| {
+            let t = t as f32;
+            (
+                t * angle.cos(),
+                t * angle.sin(),
+                (-t * t / (2.0 * sigma * sigma)).exp(),
+            )
+        })
+        .collect();
+    let total: f32 = taps.iter().map(|t| t.2).sum();
+
+    let mut out = img.clone();
+    for y in 0..img.height {
+        for x in 0..img.width {
+            for ch in 0..3 {
+                let acc: f32 = taps
+                    .iter()
+                    .map(|&(dx, dy, w)| w * img.sample(x as f32 - dx, y as f32 - dy, ch))
+                    .sum();
+                out.data[(y * img.width + x) * 3 + ch] = acc / total;
+            }
+        }
+    }
+    out
+}
+
+/// A `size * size` diamond-square height map, normalized to `[0, 1]`.
+///
+/// `size` must be a power of two.
+fn plasma_fractal(
+    size: usize,
+    decay: f32,
+    rng: &mut dyn RngCore,
+) -> Vec<f32> {
+    let mut map = vec![0.0f32; size * size];
+    let at = |i: usize, j: usize| (i % size) * size + (j % size);
+    let mut wibble = 100.0f32;
+    let mut step = size;
+
+    while step >= 2 {
+        let half = step / 2;
+        let mut wibbled = |sum: f32| sum / 4.0 + wibble * rng.random_range(-wibble..=wibble);
+
+        for i in (0..size).step_by(step) {
+            for j in (0..size).step_by(step) {
+                let sum = map[at(i, j)]
+                    + map[at(i + step, j)]
+                    + map[at(i, j + step)]
+                    + map[at(i + step, j + step)];
+                map[at(i + half, j + half)] = wibbled(sum);
+            }
+        }
+        for i in (0..size).step_by(step) {
+            for j in (0..size).step_by(step) {
+                let centre = map[at(i + half, j + half)];
+                let top = centre
+                    + map[at(i + size - half, j + half)]
+                    + map[at(i, j)]
+                    + map[at(i, j + step)];
+                let left = centre
+                    + map[at(i + half, j + size - half)]
+                    + map[at(i, j)]
+                    + map[at(i + step, j)];
+                map[at(i, j + half)] = wibbled(top);
+                map[at(i + half, j)] = wibbled(left);
+            }
+        }
+
+        step /= 2;
+        wibble /= decay;
+    }
+
+    let min = map.iter().cloned().fold(f32::INFINITY, f32::min);
+    map.iter_mut().for_each(|v| *v -= min);
+    let max = map.iter().cloned().fold(0.0f32, f32::max);
+    if max > 0.0 {
+        map.iter_mut().for_each(|v| *v /= max);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::sample_rng;
+    use anyhow::Result;
+    use image::Rgb;
+    use strum::IntoEnumIterator;
+
+    fn textured() -> RgbImage {
+        RgbImage::from_fn(32, 32, |x, y| {
+            Rgb([(x * 8) as u8, (y * 8) as u8, ((x ^ y) * 8) as u8])
+        })
+    }
+
+    #[test]
+    fn test_corruptions() -> Result<()> {
+        let img = textured();
+        let path = Path::new("cifar10-test-1.png");
+        for corruption in Corruption::iter() {
+            let mild = corruption.apply(&img, 1, &mut sample_rng(0, path))?;
+            let severe = corruption.apply(&img, 5, &mut sample_rng(0, path))?;
+            let again = corruption.apply(&img, 5, &mut sample_rng(0, path))?;
+            assert_eq!(severe.dimensions(), img.dimensions(), "{corruption}");
+            assert_eq!(severe, again, "{corruption}");
+
+            let error = |out: &RgbImage| -> u64 {
+                out.as_raw()
+                    .iter()
+                    .zip(img.as_raw())
+                    .map(|(&a, &b)| (a as i64 - b as i64).unsigned_abs())
+                    .sum()
+            };
+            assert!(error(&severe) > 0, "{corruption}");
+            if corruption != Corruption::Saturate {
+                // CIFAR-10-C saturate desaturates at low severities.
+                assert!(error(&severe) >= error(&mild), "{corruption}");
+            }
+        }
+
+        assert!(
+            Corruption::Fog
+                .apply(&img, 0, &mut sample_rng(0, path))
+                .is_err()
+        );
+        assert!(CorruptionTransform::new(Corruption::Fog, 6).is_err());
+        assert_eq!(
+            "jpeg_compression".parse::<Corruption>()?,
+            Corruption::JpegCompression
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::index::{DataSet, HEIGHT, SAMPLES_PER_CLASS, WIDTH};
+use serde::{Deserialize, Serialize};
+
+/// The expected layout of a dataset in the CINIC-10 folder structure.
+///
+/// Variants of CINIC-10, and other dumps using its `{split}/{class}/{image}`
+/// layout, differ in their splits, counts and image size. Pass a profile to
+/// `Cinic10IndexBuilder::profile()` to index and validate one of them
+/// without tripping the CINIC-10 constants, like `SAMPLES_PER_CLASS`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetProfile {
+    pub name: String,
+
+    /// The splits, with the expected images per class of each; `None` accepts any count.
+    pub splits: Vec<(DataSet, Option<usize>)>,
+
+    /// The `(width, height)` of every image.
+    pub image_size: (u32, u32),
+}
+
+impl Default for DatasetProfile {
+    fn default() -> Self {
+        Self::cinic10()
+    }
+}
+
+impl DatasetProfile {
+    /// Standard CINIC-10: three splits of 9000 32x32 images per class.
+    pub fn cinic10() -> Self {
+        Self {
+            name: "cinic10".to_string(),
+            splits: DataSet::ALL
+                .iter()
+                .map(|&ds| (ds, Some(SAMPLES_PER_CLASS)))
+                .collect(),
+            image_size: (WIDTH as u32, HEIGHT as u32),
+        }
+    }
+
+    /// Enlarged CINIC-10: the validation split merged into train.
+    pub fn cinic10_enlarged() -> Self {
+        Self {
+            name: "cinic10-enlarged".to_string(),
+            splits: vec![
+                (DataSet::Train, Some(2 * SAMPLES_PER_CLASS)),
+                (DataSet::Test, Some(SAMPLES_PER_CLASS)),
+            ],
+            image_size: (WIDTH as u32, HEIGHT as u32),
+        }
+    }
+
+    /// Downsampled ImageNet, filtered to the CINIC-10 classes.
+    ///
+    /// ImageNet has `train` and `val` sets, dumped as `train` and `valid`;
+    /// the per-class counts depend on the synsets kept, so any are accepted.
+    ///
+    /// # Parameters
+    ///
+    /// - `size`: The side of the square images, e.g. 32 or 64.
+    pub fn imagenet_downsampled(size: u32) -> Self {
+        Self {
+            name: format!("imagenet{size}"),
+            splits: vec![(DataSet::Train, None), (DataSet::Valid, None)],
+            image_size: (size, size),
+        }
+    }
+
+    /// Set the splits and per-class counts.
+    pub fn with_splits<I>(
+        mut self,
+        splits: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = (DataSet, Option<usize>)>,
+    {
+        self.splits = splits.into_iter().collect();
+        self
+    }
+
+    /// Set the image `(width, height)`.
+    pub fn with_image_size(
+        mut self,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        self.image_size = (width, height);
+        self
+    }
+
+    /// The splits of the profile, in profile order.
+    pub fn data_sets(&self) -> Vec<DataSet> {
+        self.splits.iter().map(|&(ds, _)| ds).collect()
+    }
+
+    /// Does the profile include a split?
+    pub fn has_split(
+        &self,
+        data_set: DataSet,
+    ) -> bool {
+        self.splits.iter().any(|&(ds, _)| ds == data_set)
+    }
+
+    /// The expected images per class of a split; `None` for any count, or a split not in the profile.
+    pub fn samples_per_class(
+        &self,
+        data_set: DataSet,
+    ) -> Option<usize> {
+        self.splits
+            .iter()
+            .find(|&&(ds, _)| ds == data_set)
+            .and_then(|&(_, n)| n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let cinic = DatasetProfile::default();
+        assert_eq!(cinic, DatasetProfile::cinic10());
+        assert_eq!(cinic.data_sets(), DataSet::ALL.to_vec());
+        assert_eq!(cinic.samples_per_class(DataSet::Valid), Some(9000));
+
+        let enlarged = DatasetProfile::cinic10_enlarged();
+        assert!(!enlarged.has_split(DataSet::Valid));
+        assert_eq!(enlarged.samples_per_class(DataSet::Train), Some(18000));
+
+        let imagenet = DatasetProfile::imagenet_downsampled(64);
+        assert_eq!(imagenet.name, "imagenet64");
+        assert_eq!(imagenet.image_size, (64, 64));
+        assert!(imagenet.has_split(DataSet::Train));
+        assert_eq!(imagenet.samples_per_class(DataSet::Train), None);
+
+        let custom = imagenet
+            .with_splits([(DataSet::Test, Some(5))])
+            .with_image_size(8, 4);
+        assert_eq!(custom.samples_per_class(DataSet::Test), Some(5));
+        assert_eq!(custom.image_size, (8, 4));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::error::Result;
+use crate::images::{decode_rgbimage, read_image_bytes};
+use crate::index::DatasetIndex;
+use crate::parallel::par_fold;
+use crate::readahead::advise_split_ahead;
+use image::imageops::{self, FilterType};
+use image::{GrayImage, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// The hash used to compare images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKind {
+    /// blake3 over the file bytes; only byte-identical files match.
+    Exact,
+
+    /// 64-bit difference hash; robust to re-encoding and small intensity shifts.
+    #[default]
+    DHash,
+
+    /// 64-bit DCT perceptual hash; robust to blurring and rescaling.
+    PHash,
+}
+
+/// The hashes of a single image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageHashes {
+    pub exact: [u8; 32],
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+impl ImageHashes {
+    /// Hash an encoded image.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let img = decode_rgbimage(bytes)?;
+        Ok(Self {
+            exact: *blake3::hash(bytes).as_bytes(),
+            dhash: dhash(&img),
+            phash: phash(&img),
+        })
+    }
+
+    /// The Hamming distance to another image under the given hash.
+    ///
+    /// Exact hashes are either equal (`0`) or entirely different (`64`).
+    pub fn distance(
+        &self,
+        other: &ImageHashes,
+        kind: HashKind,
+    ) -> u32 {
+        match kind {
+            HashKind::Exact => {
+                if self.exact == other.exact {
+                    0
+                } else {
+                    64
+                }
+            }
+            HashKind::DHash => (self.dhash ^ other.dhash).count_ones(),
+            HashKind::PHash => (self.phash ^ other.phash).count_ones(),
+        }
+    }
+}
+
+fn grayscale_thumbnail(
+    img: &RgbImage,
+    width: u32,
+    height: u32,
+) -> GrayImage {
+    let gray = imageops::grayscale(img);
+    imageops::resize(&gray, width, height, FilterType::Triangle)
+}
+
+/// Compute the 64-bit difference hash of an image.
+///
+/// Each bit records whether a pixel of a 9x8 grayscale thumbnail
+/// is brighter than its right neighbour.
+pub fn dhash(img: &RgbImage) -> u64 {
+    let thumb = grayscale_thumbnail(img, 9, 8);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = thumb.get_pixel(x, y)[0] > thumb.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | bit as u64;
+        }
+    }
+    hash
+}
+
+/// Compute the 64-bit perceptual hash of an image.
+///
+/// Each bit records whether one of the 8x8 lowest-frequency DCT coefficients
+/// of a 32x32 grayscale thumbnail is above their median.
+pub fn phash(img: &RgbImage) -> u64 {
+    const N: usize = 32;
+    const K: usize = 8;
+
+    let thumb = grayscale_thumbnail(img, N as u32, N as u32);
+    let pixels: Vec<f64> = thumb.as_raw().iter().map(|&v| v as f64).collect();
+
+    let cos: Vec<f64> = (0..K * N)
+        .map(|i| {
+            let (u, x) = (i / N, i % N);
+            (std::f64::consts::PI * (2 * x + 1) as f64 * u as f64 / (2 * N) as f64).cos()
+        })
+        .collect();
+
+    let mut coeffs = [0.0f64; K * K];
+    for v in 0..K {
+        for u in 0..K {
+            let mut sum = 0.0;
+            for y in 0..N {
+                for x in 0..N {
+                    sum += pixels[y * N + x] * cos[u * N + x] * cos[v * N + y];
+                }
+            }
+            coeffs[v * K + u] = sum;
+        }
+    }
+
+    // The DC term dominates and carries no structure; keep it out of the median.
+    let mut sorted = coeffs[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    coeffs
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
+
+/// Hash every item of a split.
+///
+/// # Parameters
+///
+/// - `split`: The split to hash.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the hashes, in item order.
+pub fn hash_split(
+    split: &DatasetIndex,
+    parallelism: usize,
+) -> Result<Vec<ImageHashes>> {
+    let indices: Vec<usize> = (0..split.len()).collect();
+    par_fold(
+        &indices,
+        parallelism,
+        Vec::new,
+        |hashes, &i| {
+            advise_split_ahead(split, i);
+            hashes.push(ImageHashes::from_bytes(&read_image_bytes(
+                split.index_to_path(i),
+            )?)?);
+            Ok(())
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    )
+}
+
+/// Group hashes which are within `max_distance` of each other.
+///
+/// Grouping is transitive: `a ~ b` and `b ~ c` puts `a`, `b` and `c` in one cluster.
+///
+/// Candidate pairs are found by splitting each hash into `max_distance + 1` bands;
+/// by the pigeonhole principle, any two hashes within `max_distance` agree on at least
+/// one band, so only hashes sharing a band are compared.
+///
+/// # Parameters
+///
+/// - `hashes`: The hashes to group.
+/// - `max_distance`: The maximum Hamming distance of a matching pair.
+///
+/// # Returns
+///
+/// The clusters of more than one hash, as sorted positions into `hashes`,
+/// ordered by their first position.
+pub fn cluster_hashes(
+    hashes: &[u64],
+    max_distance: u32,
+) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    fn find(
+        parents: &mut [usize],
+        mut i: usize,
+    ) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let bands = (max_distance as usize + 1).min(64);
+    for band in 0..bands {
+        let lo = band * 64 / bands;
+        let hi = (band + 1) * 64 / bands;
+        let mask = if hi - lo == 64 {
+            u64::MAX
+        } else {
+            ((1u64 << (hi - lo)) - 1) << lo
+        };
+
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (i, &h) in hashes.iter().enumerate() {
+            buckets.entry(h & mask).or_default().push(i);
+        }
+        for bucket in buckets.values().filter(|b| b.len() > 1) {
+            for (k, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[k + 1..] {
+                    if (hashes[a] ^ hashes[b]).count_ones() <= max_distance {
+                        let (ra, rb) = (find(&mut parents, a), find(&mut parents, b));
+                        if ra != rb {
+                            parents[ra.max(rb)] = ra.min(rb);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashes.len() {
+        let root = find(&mut parents, i);
+        clusters.entry(root).or_default().push(i);
+    }
+    let mut clusters: Vec<Vec<usize>> = clusters.into_values().filter(|c| c.len() > 1).collect();
+    clusters.sort();
+    clusters
+}
+
+/// Group image hashes which match under `kind`; see `cluster_hashes()`.
+pub fn cluster_image_hashes(
+    hashes: &[ImageHashes],
+    kind: HashKind,
+    max_distance: u32,
+) -> Vec<Vec<usize>> {
+    match kind {
+        HashKind::Exact => {
+            let mut groups: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+            for (i, h) in hashes.iter().enumerate() {
+                groups.entry(h.exact).or_default().push(i);
+            }
+            let mut clusters: Vec<Vec<usize>> =
+                groups.into_values().filter(|c| c.len() > 1).collect();
+            clusters.sort();
+            clusters
+        }
+        HashKind::DHash => cluster_hashes(
+            &hashes.iter().map(|h| h.dhash).collect::<Vec<_>>(),
+            max_distance,
+        ),
+        HashKind::PHash => cluster_hashes(
+            &hashes.iter().map(|h| h.phash).collect::<Vec<_>>(),
+            max_distance,
+        ),
+    }
+}
+
+/// Duplicate detection settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DedupConfig {
+    pub kind: HashKind,
+
+    /// The maximum Hamming distance of a near-duplicate; ignored for `HashKind::Exact`.
+    pub max_distance: u32,
+
+    /// The number of hashing threads; `0` means "all available cores".
+    pub parallelism: usize,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            kind: HashKind::DHash,
+            max_distance: 4,
+            parallelism: 0,
+        }
+    }
+}
+
+/// A group of (near-)identical images in a split.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateCluster {
+    /// Item indices, in increasing order.
+    pub indices: Vec<usize>,
+
+    /// Item paths, parallel to `indices`.
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateCluster {
+    fn new(
+        split: &DatasetIndex,
+        indices: Vec<usize>,
+    ) -> Self {
+        let paths = split.indices_to_paths(&indices);
+        Self { indices, paths }
+    }
+}
+
+/// Find duplicate clusters in a split.
+///
+/// # Parameters
+///
+/// - `split`: The split to search.
+/// - `config`: The hash, distance and parallelism to use.
+///
+/// # Returns
+///
+/// A `Result` containing the duplicate clusters, ordered by their first index.
+pub fn find_duplicates(
+    split: &DatasetIndex,
+    config: &DedupConfig,
+) -> Result<Vec<DuplicateCluster>> {
+    let hashes = hash_split(split, config.parallelism)?;
+    Ok(
+        cluster_image_hashes(&hashes, config.kind, config.max_distance)
+            .into_iter()
+            .map(|indices| DuplicateCluster::new(split, indices))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::Rgb;
+
+    #[test]
+    fn test_cluster_hashes() {
+        let hashes = [0b0000, 0b1111_0000, 0b0001, 0b1111_0001, u64::MAX, 0b0011];
+        assert_eq!(cluster_hashes(&hashes, 0), Vec::<Vec<usize>>::new());
+        assert_eq!(cluster_hashes(&hashes, 1), vec![vec![0, 2, 5], vec![1, 3]]);
+        assert_eq!(cluster_hashes(&hashes, 64), vec![vec![0, 1, 2, 3, 4, 5]]);
+    }
+
+    #[test]
+    fn test_find_duplicates() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let texture = |offset: u32| {
+            RgbImage::from_fn(32, 32, move |x, y| {
+                let v = ((x * 37 + y * 11) ^ (x * y)) % 200 + offset;
+                Rgb([v as u8, (x * 4 + offset) as u8, 0])
+            })
+        };
+        let gradient = texture(0);
+        let brighter = texture(3);
+        let flipped = imageops::flip_horizontal(&gradient);
+
+        let split = fixtures::write_split(
+            dir.path().join("train"),
+            [
+                ("a.png", gradient.clone()),
+                ("b.png", flipped),
+                ("c.png", gradient),
+                ("d.png", brighter),
+            ]
+            .map(|(name, img)| (ObjectClass::Cat, name, img)),
+        )?;
+
+        let exact = find_duplicates(
+            &split,
+            &DedupConfig {
+                kind: HashKind::Exact,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].indices, vec![0, 2]);
+        assert_eq!(exact[0].paths, split.indices_to_paths(&[0, 2]));
+
+        for kind in [HashKind::DHash, HashKind::PHash] {
+            let near = find_duplicates(
+                &split,
+                &DedupConfig {
+                    kind,
+                    parallelism: 2,
+                    ..Default::default()
+                },
+            )?;
+            assert_eq!(near.len(), 1, "{kind:?}");
+            assert_eq!(near[0].indices, vec![0, 2, 3], "{kind:?}");
+        }
+
+        Ok(())
+    }
+}
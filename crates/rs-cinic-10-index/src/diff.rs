@@ -0,0 +1,180 @@
+use crate::error::Result;
+use crate::parallel::par_fold;
+use crate::retry::with_retry;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How `diff_datasets()` decides that a file present in both roots was modified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffOptions {
+    /// Compare blake3 content hashes; otherwise only file sizes are compared.
+    pub compare_content: bool,
+
+    /// The number of hashing threads; `0` means "all available cores".
+    pub parallelism: usize,
+}
+
+/// The differences between two dataset roots, as paths relative to the roots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetDiff {
+    /// Directories only in the second root.
+    pub added_dirs: Vec<PathBuf>,
+
+    /// Directories only in the first root.
+    pub removed_dirs: Vec<PathBuf>,
+
+    /// Files only in the second root.
+    pub added: Vec<PathBuf>,
+
+    /// Files only in the first root.
+    pub removed: Vec<PathBuf>,
+
+    /// Files in both roots, whose size (or content) differs.
+    pub modified: Vec<PathBuf>,
+}
+
+impl DatasetDiff {
+    pub fn is_identical(&self) -> bool {
+        self.added_dirs.is_empty()
+            && self.removed_dirs.is_empty()
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// Recursively list the directories and files under `root`, relative to `root`.
+pub(crate) fn walk(root: &Path) -> Result<(BTreeSet<PathBuf>, BTreeSet<PathBuf>)> {
+    let mut dirs = BTreeSet::new();
+    let mut files = BTreeSet::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(rel) = pending.pop() {
+        for entry in with_retry(|| fs::read_dir(root.join(&rel)))? {
+            let entry = entry?;
+            let path = rel.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                dirs.insert(path.clone());
+                pending.push(path);
+            } else {
+                files.insert(path);
+            }
+        }
+    }
+    Ok((dirs, files))
+}
+
+fn file_hash(path: &Path) -> Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(with_retry(|| fs::File::open(path))?)?;
+    Ok(hasher.finalize())
+}
+
+/// Compare the structure and files of two dataset copies.
+///
+/// Useful for verifying mirrors, or comparing a cleaned dataset against the original.
+///
+/// # Parameters
+///
+/// - `root_a`: The first (original) root.
+/// - `root_b`: The second root.
+/// - `options`: How to compare files present in both roots.
+///
+/// # Returns
+///
+/// A `Result` containing the sorted differences.
+pub fn diff_datasets<A, B>(
+    root_a: A,
+    root_b: B,
+    options: &DiffOptions,
+) -> Result<DatasetDiff>
+where
+    A: AsRef<Path>,
+    B: AsRef<Path>,
+{
+    let (root_a, root_b) = (root_a.as_ref(), root_b.as_ref());
+    let (dirs_a, files_a) = walk(root_a)?;
+    let (dirs_b, files_b) = walk(root_b)?;
+
+    let common: Vec<PathBuf> = files_a.intersection(&files_b).cloned().collect();
+    let modified = par_fold(
+        &common,
+        options.parallelism,
+        Vec::new,
+        |modified, rel| {
+            let (a, b) = (root_a.join(rel), root_b.join(rel));
+            let differs = fs::metadata(&a)?.len() != fs::metadata(&b)?.len()
+                || (options.compare_content && file_hash(&a)? != file_hash(&b)?);
+            if differs {
+                modified.push(rel.clone());
+            }
+            Ok(())
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    )?;
+
+    Ok(DatasetDiff {
+        added_dirs: dirs_b.difference(&dirs_a).cloned().collect(),
+        removed_dirs: dirs_a.difference(&dirs_b).cloned().collect(),
+        added: files_b.difference(&files_a).cloned().collect(),
+        removed: files_a.difference(&files_b).cloned().collect(),
+        modified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_diff_datasets() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        for root in [&a, &b] {
+            fs::create_dir_all(root.join("train/cat"))?;
+            fs::write(root.join("train/cat/same.png"), "same")?;
+            fs::write(root.join("train/cat/resized.png"), "abc")?;
+        }
+        fs::write(a.join("train/cat/edited.png"), "abc")?;
+        fs::write(b.join("train/cat/edited.png"), "abd")?;
+        fs::write(b.join("train/cat/resized.png"), "abcd")?;
+        fs::write(a.join("train/cat/gone.png"), "x")?;
+        fs::create_dir_all(b.join("test/dog"))?;
+        fs::write(b.join("test/dog/new.png"), "y")?;
+
+        let fast = diff_datasets(&a, &b, &DiffOptions::default())?;
+        assert_eq!(
+            fast.added_dirs,
+            vec![PathBuf::from("test"), PathBuf::from("test/dog")]
+        );
+        assert!(fast.removed_dirs.is_empty());
+        assert_eq!(fast.added, vec![PathBuf::from("test/dog/new.png")]);
+        assert_eq!(fast.removed, vec![PathBuf::from("train/cat/gone.png")]);
+        assert_eq!(fast.modified, vec![PathBuf::from("train/cat/resized.png")]);
+
+        let full = diff_datasets(
+            &a,
+            &b,
+            &DiffOptions {
+                compare_content: true,
+                parallelism: 2,
+            },
+        )?;
+        assert_eq!(
+            full.modified,
+            vec![
+                PathBuf::from("train/cat/edited.png"),
+                PathBuf::from("train/cat/resized.png")
+            ]
+        );
+
+        assert!(diff_datasets(&a, &a, &DiffOptions::default())?.is_identical());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// The buffer, offset, and length alignment of direct reads.
+///
+/// 4 KiB covers the logical block size of common disks and filesystems.
+pub const ALIGNMENT: usize = 4096;
+
+/// Does this platform support direct reads?
+pub fn is_supported() -> bool {
+    cfg!(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        windows
+    ))
+}
+
+/// Open `path` for direct reads.
+fn open_direct(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_DIRECT);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+        options.custom_flags(FILE_FLAG_NO_BUFFERING);
+    }
+
+    let file = options.open(path)?;
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        use std::os::fd::AsRawFd;
+        // SAFETY: `fcntl` on an open descriptor, with an integer argument.
+        if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Read `buf.len()` bytes at `offset`, or fewer at end of file.
+fn read_at(
+    file: &File,
+    buf: &mut [u8],
+    offset: u64,
+) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        #[cfg(unix)]
+        let read =
+            std::os::unix::fs::FileExt::read_at(file, &mut buf[filled..], offset + filled as u64);
+        #[cfg(windows)]
+        let read = std::os::windows::fs::FileExt::seek_read(
+            file,
+            &mut buf[filled..],
+            offset + filled as u64,
+        );
+        #[cfg(not(any(unix, windows)))]
+        let read: io::Result<usize> = Err(io::ErrorKind::Unsupported.into());
+        match read {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// A file read at arbitrary offsets with direct IO, bypassing the OS page cache.
+///
+/// When a file is larger than RAM and streamed once per epoch, buffered reads
+/// only evict other useful pages. Each read is widened to whole `ALIGNMENT`
+/// blocks, issued into an aligned buffer, and the requested span copied out:
+///
+/// | Platform      | Mechanism                | Alignment   |
+/// |---------------|--------------------------|-------------|
+/// | Linux/Android | `O_DIRECT`               | `ALIGNMENT` |
+/// | macOS/iOS     | `F_NOCACHE`              | none        |
+/// | Windows       | `FILE_FLAG_NO_BUFFERING` | `ALIGNMENT` |
+/// | other         | buffered read            | none        |
+///
+/// Filesystems which reject direct IO (such as tmpfs) fall back to buffered reads.
+#[derive(Debug)]
+pub struct DirectReader {
+    file: File,
+    direct: bool,
+}
+
+impl DirectReader {
+    /// Open `path` for direct reads.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let buffered = || {
+            Ok(Self {
+                file: File::open(path)?,
+                direct: false,
+            })
+        };
+        if !is_supported() {
+            return buffered();
+        }
+        let reader = match open_direct(path) {
+            Ok(file) => Self { file, direct: true },
+            Err(err) if err.kind() == io::ErrorKind::InvalidInput => return buffered(),
+            Err(err) => return Err(err),
+        };
+        // Some filesystems accept the flag, and only reject the reads.
+        match reader.read_exact_at(0, &mut [0u8; 1]) {
+            Err(err) if err.kind() == io::ErrorKind::InvalidInput => buffered(),
+            _ => Ok(reader),
+        }
+    }
+
+    /// Are reads bypassing the page cache?
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    /// Fill `buf` from the bytes at `offset`.
+    ///
+    /// # Returns
+    ///
+    /// An `io::Result`; an `UnexpectedEof` error if the file ends first.
+    pub fn read_exact_at(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> io::Result<()> {
+        let read = if self.direct {
+            let start = offset - offset % ALIGNMENT as u64;
+            let skip = (offset - start) as usize;
+            let capacity = (skip + buf.len())
+                .next_multiple_of(ALIGNMENT)
+                .max(ALIGNMENT);
+            let mut raw = vec![0u8; capacity + ALIGNMENT];
+            let align = raw.as_ptr().align_offset(ALIGNMENT);
+            let aligned = &mut raw[align..align + capacity];
+            let read = read_at(&self.file, aligned, start)?.saturating_sub(skip);
+            let read = read.min(buf.len());
+            buf[..read].copy_from_slice(&aligned[skip..skip + read]);
+            read
+        } else {
+            read_at(&self.file, buf, offset)?
+        };
+        if read < buf.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_direct_reader() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..3 * ALIGNMENT + 17).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data)?;
+
+        let reader = DirectReader::open(&path)?;
+        for (offset, len) in [
+            (0, 1),
+            (5, ALIGNMENT),
+            (ALIGNMENT, ALIGNMENT),
+            (3 * ALIGNMENT, 17),
+        ] {
+            let mut buf = vec![0u8; len];
+            reader.read_exact_at(offset as u64, &mut buf)?;
+            assert_eq!(buf, data[offset..offset + len], "{offset}+{len}");
+        }
+        let mut buf = vec![0u8; 18];
+        assert!(
+            reader
+                .read_exact_at(3 * ALIGNMENT as u64, &mut buf)
+                .is_err()
+        );
+        assert!(DirectReader::open(dir.path().join("missing")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,914 @@
+use crate::error::{Result, bail};
+use crate::index::{Cinic10Index, DataSet};
+use crate::retry::RetryPolicy;
+use crate::{CINC10_PATH_ENV_VAR, default_download_path};
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use std::collections::HashSet;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// The official CINIC-10 archive.
+pub const CINIC10_URL: &str =
+    "https://datashare.is.ed.ac.uk/bitstream/handle/10283/3192/CINIC-10.tar.gz";
+
+/// The env var which enables auto-download in `Cinic10Index::default()`.
+///
+/// Set to `1`, `true`, or `yes` to download and extract the dataset when
+/// the default data path does not exist.
+pub const CINIC10_AUTO_DOWNLOAD_ENV_VAR: &str = "CINIC10_AUTO_DOWNLOAD";
+
+/// Is auto-download enabled by `CINIC10_AUTO_DOWNLOAD_ENV_VAR`?
+pub fn auto_download_enabled() -> bool {
+    env::var(CINIC10_AUTO_DOWNLOAD_ENV_VAR)
+        .is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// Settings for `download_file()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadOptions {
+    /// The number of parallel ranged connections; `1` forces a single stream.
+    pub connections: usize,
+
+    /// Files smaller than twice this are fetched in a single stream.
+    pub min_segment_size: u64,
+
+    /// The attempts and backoff for each segment.
+    ///
+    /// Every network error is retried; only the backoff settings and
+    /// `max_attempts` are used.
+    pub retry: RetryPolicy,
+
+    /// The connect and read timeout of each connection.
+    pub timeout: Duration,
+
+    /// Resume from the `.part` file of an interrupted download, if any;
+    /// otherwise it is discarded.
+    pub resume: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            connections: 4,
+            min_segment_size: 8 << 20,
+            retry: RetryPolicy {
+                max_attempts: 5,
+                initial_backoff: Duration::from_millis(500),
+                max_backoff: Duration::from_secs(30),
+                ..Default::default()
+            },
+            timeout: Duration::from_secs(30),
+            resume: true,
+        }
+    }
+}
+
+impl DownloadOptions {
+    pub fn with_connections(
+        mut self,
+        connections: usize,
+    ) -> Self {
+        self.connections = connections;
+        self
+    }
+
+    pub fn with_min_segment_size(
+        mut self,
+        min_segment_size: u64,
+    ) -> Self {
+        self.min_segment_size = min_segment_size;
+        self
+    }
+
+    pub fn with_retry(
+        mut self,
+        retry: RetryPolicy,
+    ) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_resume(
+        mut self,
+        resume: bool,
+    ) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+/// Split `len` bytes into at most `connections` contiguous, inclusive byte ranges.
+fn segments(
+    len: u64,
+    connections: usize,
+    min_segment_size: u64,
+) -> Vec<(u64, u64)> {
+    let max_segments = (len / min_segment_size.max(1)).max(1);
+    let count = (connections.max(1) as u64).min(max_segments);
+    let size = len.div_ceil(count);
+    (0..count)
+        .map(|i| (i * size, ((i + 1) * size).min(len) - 1))
+        .filter(|&(start, end)| start <= end)
+        .collect()
+}
+
+/// A download progress callback; see `download_file_with_progress()`.
+type ProgressFn<'a> = dyn FnMut(u64, Option<u64>) + Send + 'a;
+
+/// Download progress, shared by the connections of one download.
+struct Progress<'a> {
+    /// The bytes done, and the total length (if known).
+    state: Mutex<(u64, Option<u64>)>,
+    callback: Mutex<&'a mut ProgressFn<'a>>,
+}
+
+impl<'a> Progress<'a> {
+    fn new(callback: &'a mut ProgressFn<'a>) -> Self {
+        Self {
+            state: Mutex::new((0, None)),
+            callback: Mutex::new(callback),
+        }
+    }
+
+    /// Reset the progress, e.g. when a download (re)starts.
+    fn reset(
+        &self,
+        done: u64,
+        total: Option<u64>,
+    ) {
+        *self.state.lock().unwrap() = (done, total);
+        (self.callback.lock().unwrap())(done, total);
+    }
+
+    /// Record `n` more bytes done.
+    fn add(
+        &self,
+        n: u64,
+    ) {
+        let (done, total) = {
+            let mut state = self.state.lock().unwrap();
+            state.0 += n;
+            *state
+        };
+        (self.callback.lock().unwrap())(done, total);
+    }
+}
+
+/// The length of the resource, if the server reports it and accepts byte ranges.
+fn probe_ranged_len(
+    agent: &ureq::Agent,
+    url: &str,
+) -> anyhow::Result<Option<u64>> {
+    let response = agent.head(url).call()?;
+    let accepts_ranges = response
+        .header("Accept-Ranges")
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    let len = response
+        .header("Content-Length")
+        .and_then(|v| v.parse::<u64>().ok());
+    Ok(len.filter(|_| accepts_ranges))
+}
+
+/// Copy `reader` to `file` until `limit` bytes are written or the reader ends.
+///
+/// `copied` counts the bytes written, even when the copy fails part-way.
+fn copy_with_progress<R: io::Read>(
+    reader: &mut R,
+    file: &mut File,
+    limit: Option<u64>,
+    copied: &mut u64,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let mut buf = vec![0u8; 64 << 10];
+    while limit.is_none_or(|limit| *copied < limit) {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let n = limit.map_or(n, |limit| n.min((limit - *copied) as usize));
+        io::Write::write_all(file, &buf[..n])?;
+        *copied += n as u64;
+        progress.add(n as u64);
+    }
+    Ok(())
+}
+
+/// Fetch the inclusive byte range `[start, end]` into the same range of `path`.
+///
+/// A failed attempt resumes after the bytes already written.
+fn fetch_segment(
+    agent: &ureq::Agent,
+    url: &str,
+    path: &Path,
+    (start, end): (u64, u64),
+    retry: &RetryPolicy,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut offset = start;
+    let mut attempt = 0;
+    loop {
+        let result: anyhow::Result<()> = (|| {
+            let response = agent
+                .get(url)
+                .set("Range", &format!("bytes={offset}-{end}"))
+                .call()?;
+            if response.status() != 206 {
+                anyhow::bail!(
+                    "Expected a partial response, got HTTP {}",
+                    response.status()
+                );
+            }
+            file.seek(SeekFrom::Start(offset))?;
+            let mut copied = 0;
+            let result = copy_with_progress(
+                &mut response.into_reader(),
+                &mut file,
+                Some(end + 1 - offset),
+                &mut copied,
+                progress,
+            );
+            offset += copied;
+            result?;
+            if offset <= end {
+                anyhow::bail!("Connection closed at byte {offset}, expected {}", end + 1);
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => return Ok(file.sync_data()?),
+            Err(err) if attempt + 1 < retry.max_attempts && !is_client_error(&err) => {
+                let delay = retry.backoff(attempt);
+
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    error = %err,
+                    start,
+                    offset,
+                    retry = attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "retrying download segment"
+                );
+
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => {
+                return Err(err.context(format!("Failed to fetch bytes {start}-{end} of {url}")));
+            }
+        }
+    }
+}
+
+/// Is `err` an HTTP `4xx` response, which retrying will not fix?
+fn is_client_error(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ureq::Error>(),
+        Some(ureq::Error::Status(400..=499, _))
+    )
+}
+
+/// Fetch the whole resource in one stream.
+///
+/// Bytes already in `path` are kept, and only the rest requested, when the
+/// server honors the range; otherwise the file is rewritten from the start.
+fn fetch_single(
+    agent: &ureq::Agent,
+    url: &str,
+    path: &Path,
+    retry: &RetryPolicy,
+    progress: &Progress,
+) -> anyhow::Result<()> {
+    let mut attempt = 0;
+    loop {
+        let result: anyhow::Result<()> = (|| {
+            let offset = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            let mut request = agent.get(url);
+            if offset > 0 {
+                request = request.set("Range", &format!("bytes={offset}-"));
+            }
+            let response = match request.call() {
+                // The partial file is no shorter than the resource; start over.
+                Err(ureq::Error::Status(416, _)) => {
+                    fs::remove_file(path)?;
+                    agent.get(url).call()?
+                }
+                response => response?,
+            };
+            let len = response
+                .header("Content-Length")
+                .and_then(|v| v.parse::<u64>().ok());
+            let (mut file, offset) = if response.status() == 206 {
+                (OpenOptions::new().append(true).open(path)?, offset)
+            } else {
+                (File::create(path)?, 0)
+            };
+            progress.reset(offset, len.map(|len| offset + len));
+            let mut copied = 0;
+            copy_with_progress(
+                &mut response.into_reader(),
+                &mut file,
+                len,
+                &mut copied,
+                progress,
+            )?;
+            if len.is_some_and(|len| copied < len) {
+                anyhow::bail!("Connection closed at byte {}", offset + copied);
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt + 1 < retry.max_attempts && !is_client_error(&err) => {
+                thread::sleep(retry.backoff(attempt));
+                attempt += 1;
+            }
+            Err(err) => return Err(err.context(format!("Failed to fetch {url}"))),
+        }
+    }
+}
+
+/// `dest`, with `suffix` appended to its file name.
+fn with_suffix(
+    dest: &Path,
+    suffix: &str,
+) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    dest.with_file_name(name)
+}
+
+/// The temporary path a download is written to, before it is complete.
+fn partial_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, ".part")
+}
+
+/// The journal of completed segments of a segmented download.
+///
+/// The first line is the resource length; each further line is a completed
+/// `start-end` range, appended once its bytes are synced to the partial file.
+fn journal_path(dest: &Path) -> PathBuf {
+    with_suffix(dest, ".part.segments")
+}
+
+/// The completed segments recorded for a partial download of `len` bytes.
+fn read_journal(
+    journal: &Path,
+    len: u64,
+) -> HashSet<(u64, u64)> {
+    let Ok(text) = fs::read_to_string(journal) else {
+        return HashSet::new();
+    };
+    let mut lines = text.lines();
+    if lines.next().and_then(|l| l.parse::<u64>().ok()) != Some(len) {
+        return HashSet::new();
+    }
+    lines
+        .filter_map(|line| {
+            let (start, end) = line.split_once('-')?;
+            Some((start.parse().ok()?, end.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Download a file, over several parallel ranged connections when the server supports them.
+///
+/// The file is split into contiguous segments, each fetched (and retried)
+/// independently into its own region of a `.part` file, which is renamed
+/// to `dest` once every segment is complete. Servers which do not report a
+/// length or accept byte ranges are read in a single stream.
+///
+/// With `DownloadOptions::resume`, an interrupted download picks up where it
+/// left off: completed segments are skipped, and a single-stream download
+/// requests only the bytes it is missing.
+///
+/// # Parameters
+///
+/// - `url`: The URL to fetch, e.g. `CINIC10_URL`.
+/// - `dest`: The file to write.
+/// - `options`: The connection and retry settings.
+///
+/// # Returns
+///
+/// A `Result` containing the number of bytes downloaded.
+pub fn download_file<P>(
+    url: &str,
+    dest: P,
+    options: &DownloadOptions,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+{
+    download_file_with_progress(url, dest, options, |_, _| {})
+}
+
+/// Download a file, reporting progress; see `download_file()`.
+///
+/// # Parameters
+///
+/// - `url`: The URL to fetch, e.g. `CINIC10_URL`.
+/// - `dest`: The file to write.
+/// - `options`: The connection and retry settings.
+/// - `progress`: Called as `progress(bytes_done, total)` as data arrives;
+///   `total` is `None` when the server does not report a length. Resumed
+///   bytes count as done.
+///
+/// # Returns
+///
+/// A `Result` containing the number of bytes downloaded.
+pub fn download_file_with_progress<P, F>(
+    url: &str,
+    dest: P,
+    options: &DownloadOptions,
+    mut progress: F,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+    F: FnMut(u64, Option<u64>) + Send,
+{
+    let dest = dest.as_ref();
+    let part = partial_path(dest);
+    let journal = journal_path(dest);
+    if !options.resume {
+        for path in [&part, &journal] {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    let progress = Progress::new(&mut progress);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(options.timeout)
+        .timeout_read(options.timeout)
+        .build();
+
+    let ranged_len = if options.connections > 1 {
+        probe_ranged_len(&agent, url)?
+    } else {
+        None
+    };
+
+    match ranged_len {
+        Some(len) if len >= 2 * options.min_segment_size => {
+            let segments = segments(len, options.connections, options.min_segment_size);
+            let resumable = fs::metadata(&part).is_ok_and(|m| m.len() == len);
+            let done = if resumable {
+                read_journal(&journal, len)
+            } else {
+                HashSet::new()
+            };
+            if done.is_empty() {
+                File::create(&part)?.set_len(len)?;
+                fs::write(&journal, format!("{len}\n"))?;
+            }
+            let pending: Vec<(u64, u64)> = segments
+                .iter()
+                .copied()
+                .filter(|range| !done.contains(range))
+                .collect();
+            let resumed: u64 = segments
+                .iter()
+                .filter(|range| done.contains(range))
+                .map(|(start, end)| end + 1 - start)
+                .sum();
+            progress.reset(resumed, Some(len));
+
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                url,
+                len,
+                segments = segments.len(),
+                pending = pending.len(),
+                "segmented download"
+            );
+
+            let journal_file = Mutex::new(OpenOptions::new().append(true).open(&journal)?);
+            thread::scope(|scope| {
+                let handles: Vec<_> = pending
+                    .iter()
+                    .map(|&range| {
+                        let (agent, part, progress) = (&agent, &part, &progress);
+                        let journal_file = &journal_file;
+                        scope.spawn(move || -> Result<()> {
+                            fetch_segment(agent, url, part, range, &options.retry, progress)?;
+                            let line = format!("{}-{}\n", range.0, range.1);
+                            io::Write::write_all(
+                                &mut *journal_file.lock().unwrap(),
+                                line.as_bytes(),
+                            )?;
+                            Ok(())
+                        })
+                    })
+                    .collect();
+                handles.into_iter().try_for_each(|h| h.join().unwrap())
+            })?;
+        }
+        _ => fetch_single(&agent, url, &part, &options.retry, &progress)?,
+    }
+
+    fs::rename(&part, dest)
+        .with_context(|| format!("Failed to move download to {}", dest.display()))?;
+    if journal.exists() {
+        fs::remove_file(&journal)?;
+    }
+    Ok(fs::metadata(dest)?.len())
+}
+
+/// Does `dir` hold the CINIC-10 split directories?
+fn has_splits(dir: &Path) -> bool {
+    DataSet::NAMES.iter().all(|name| dir.join(name).is_dir())
+}
+
+/// Extract a `.tar.gz` archive of the dataset into `root`.
+///
+/// The archive is unpacked into a sibling staging directory, which is
+/// renamed to `root` once complete; so an interrupted extraction never
+/// leaves a partial dataset at `root`. An archive which wraps the dataset
+/// in a single top-level directory is unwrapped.
+///
+/// # Parameters
+///
+/// - `archive`: The `.tar.gz` file.
+/// - `root`: The dataset directory to create; must not exist.
+pub fn extract_dataset<P, Q>(
+    archive: P,
+    root: Q,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (archive, root) = (archive.as_ref(), root.as_ref());
+    if root.exists() {
+        bail!("Refusing to extract over existing {}", root.display());
+    }
+    let mut staging = root.as_os_str().to_os_string();
+    staging.push(".extracting");
+    let staging = PathBuf::from(staging);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let file = File::open(archive)
+        .with_context(|| format!("Failed to open archive {}", archive.display()))?;
+    tar::Archive::new(GzDecoder::new(BufReader::new(file)))
+        .unpack(&staging)
+        .with_context(|| format!("Failed to extract {}", archive.display()))?;
+
+    let mut dataset = staging.clone();
+    if !has_splits(&dataset) {
+        let entries = fs::read_dir(&staging)?.collect::<io::Result<Vec<_>>>()?;
+        match entries.as_slice() {
+            [entry] if has_splits(&entry.path()) => dataset = entry.path(),
+            _ => bail!(
+                "Archive {} does not contain the CINIC-10 splits",
+                archive.display()
+            ),
+        }
+    }
+    fs::rename(&dataset, root)?;
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(())
+}
+
+/// Download and extract the dataset into `root`, unless it already exists.
+///
+/// The archive is downloaded next to `root`, and removed after extraction.
+/// An archive left by an interrupted run is reused; and a partial download
+/// is resumed, per `DownloadOptions::resume`.
+///
+/// # Parameters
+///
+/// - `url`: The archive URL, e.g. `CINIC10_URL`.
+/// - `root`: The dataset directory.
+/// - `options`: The download settings.
+///
+/// # Returns
+///
+/// `true` if the dataset was downloaded.
+pub fn ensure_dataset<P>(
+    url: &str,
+    root: P,
+    options: &DownloadOptions,
+) -> Result<bool>
+where
+    P: AsRef<Path>,
+{
+    ensure_dataset_with_progress(url, root, options, |_, _| {})
+}
+
+/// Download and extract the dataset into `root`, reporting download progress; see `ensure_dataset()`.
+///
+/// # Parameters
+///
+/// - `url`: The archive URL, e.g. `CINIC10_URL`.
+/// - `root`: The dataset directory.
+/// - `options`: The download settings.
+/// - `progress`: Called as `progress(bytes_done, total)`; see `download_file_with_progress()`.
+///
+/// # Returns
+///
+/// `true` if the dataset was downloaded.
+pub fn ensure_dataset_with_progress<P, F>(
+    url: &str,
+    root: P,
+    options: &DownloadOptions,
+    progress: F,
+) -> Result<bool>
+where
+    P: AsRef<Path>,
+    F: FnMut(u64, Option<u64>) + Send,
+{
+    let root = root.as_ref();
+    if root.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = root.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut archive = root.as_os_str().to_os_string();
+    archive.push(".tar.gz");
+    let archive = PathBuf::from(archive);
+
+    #[cfg(feature = "tracing")]
+    tracing::info!(url, root = %root.display(), "downloading CINIC-10");
+
+    if !archive.exists() {
+        download_file_with_progress(url, &archive, options, progress)?;
+    }
+    extract_dataset(&archive, root)?;
+    fs::remove_file(&archive)?;
+    Ok(true)
+}
+
+/// The directory name of the dataset under a download target directory.
+pub const CINIC10_DIR_NAME: &str = "CINIC-10";
+
+/// Download the official CINIC-10 archive into `target_dir`, and open it.
+///
+/// The dataset is extracted to `{target_dir}/CINIC-10`; if that directory
+/// already exists, it is opened without downloading. Point `CINIC10_PATH`
+/// (or `set_default_data_path()`) at it to make it the default dataset.
+///
+/// # Parameters
+///
+/// - `target_dir`: The directory to download into; created if missing.
+/// - `options`: The download settings.
+///
+/// # Returns
+///
+/// A `Result` containing the ready `Cinic10Index`.
+pub fn download_cinic10<P>(
+    target_dir: P,
+    options: &DownloadOptions,
+) -> Result<Cinic10Index>
+where
+    P: AsRef<Path>,
+{
+    download_cinic10_from(CINIC10_URL, target_dir, options)
+}
+
+/// Download a CINIC-10 archive from `url` into `target_dir`, and open it; see `download_cinic10()`.
+///
+/// Useful for mirrors of the official archive.
+pub fn download_cinic10_from<P>(
+    url: &str,
+    target_dir: P,
+    options: &DownloadOptions,
+) -> Result<Cinic10Index>
+where
+    P: AsRef<Path>,
+{
+    let root = target_dir.as_ref().join(CINIC10_DIR_NAME);
+    ensure_dataset(url, &root, options)?;
+    Cinic10Index::new_from_dir(&root)
+}
+
+/// Download CINIC-10 to the default data path, and open it.
+///
+/// The default path is `get_default_data_path()`: the `CINIC10_PATH` env var
+/// when set, otherwise the platform cache dir, e.g. `~/.cache/cinic10`, which
+/// is created if missing. An existing dataset there is opened as-is.
+///
+/// # Parameters
+///
+/// - `options`: The download settings.
+///
+/// # Returns
+///
+/// A `Result` containing the ready `Cinic10Index`.
+pub fn download_cinic10_default(options: &DownloadOptions) -> Result<Cinic10Index> {
+    let Some(root) = default_download_path() else {
+        bail!("No default CINIC-10 data path: set {CINC10_PATH_ENV_VAR}");
+    };
+    Cinic10Index::new_from_dir_or_download(root, options)
+}
+
+impl Cinic10Index {
+    /// Open the dataset at `root`, downloading and extracting it first if it does not exist.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The root directory of the CINIC-10 dataset.
+    /// - `options`: The download settings.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index`.
+    pub fn new_from_dir_or_download<P>(
+        root: P,
+        options: &DownloadOptions,
+    ) -> Result<Cinic10Index>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        ensure_dataset(CINIC10_URL, root, options)?;
+        Cinic10Index::new_from_dir(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Serve `body` over HTTP with range support; every `drop_every`-th
+    /// GET is cut off half-way through its body.
+    fn serve(
+        body: Vec<u8>,
+        drop_every: usize,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.bin", listener.local_addr().unwrap());
+        let gets = Arc::new(AtomicUsize::new(0));
+        let counter = gets.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if request.is_empty() {
+                        request = line.clone();
+                    }
+                    if let Some(r) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (a, b) = r.trim().split_once('-').unwrap();
+                        let end = b.parse::<usize>().unwrap_or(body.len() - 1);
+                        range = Some((a.parse::<usize>().unwrap(), end));
+                    }
+                }
+                if request.starts_with("HEAD") {
+                    let header = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    );
+                    stream.write_all(header.as_bytes()).unwrap();
+                    continue;
+                }
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                let (status, (a, b)) = match range {
+                    Some(r) => ("206 Partial Content", r),
+                    None => ("200 OK", (0, body.len() - 1)),
+                };
+                let chunk = &body[a..=b];
+                let header = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    chunk.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                let sent = if n.is_multiple_of(drop_every) {
+                    chunk.len() / 2
+                } else {
+                    chunk.len()
+                };
+                let _ = stream.write_all(&chunk[..sent]);
+            }
+        });
+        (url, gets)
+    }
+
+    #[test]
+    fn test_ensure_dataset() -> Result<()> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::fast(),
+        ));
+        for name in DataSet::NAMES {
+            let data = name.as_bytes();
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, format!("CINIC-10/{name}/cat/x.png"), data)?;
+        }
+        let body = builder.into_inner()?.finish()?;
+        let (url, _) = serve(body, usize::MAX);
+
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().join("cinic");
+        let options = DownloadOptions::default().with_connections(1);
+        assert!(ensure_dataset(&url, &root, &options)?);
+        assert_eq!(fs::read(root.join("valid/cat/x.png"))?, b"valid");
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+        assert!(!ensure_dataset(&url, &root, &options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(segments(10, 3, 1), vec![(0, 3), (4, 7), (8, 9)]);
+        assert_eq!(segments(10, 4, 5), vec![(0, 4), (5, 9)]);
+        assert_eq!(segments(3, 1, 1), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn test_download_file_segmented_with_retry() -> Result<()> {
+        let body: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let (url, gets) = serve(body.clone(), 3);
+
+        let dir = tempfile::tempdir()?;
+        let dest = dir.path().join("data.bin");
+        let options = DownloadOptions::default()
+            .with_connections(4)
+            .with_min_segment_size(1000)
+            .with_retry(RetryPolicy {
+                initial_backoff: Duration::ZERO,
+                ..Default::default()
+            });
+
+        assert_eq!(download_file(&url, &dest, &options)?, body.len() as u64);
+        assert_eq!(fs::read(&dest)?, body);
+        assert!(!partial_path(&dest).exists());
+        // 4 segments, with at least one dropped connection resumed.
+        assert!(gets.load(Ordering::SeqCst) > 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_download_resume_with_progress() -> Result<()> {
+        let body: Vec<u8> = (0..10_000u32).map(|i| (i * 13 % 251) as u8).collect();
+        let (url, gets) = serve(body.clone(), usize::MAX);
+        let dir = tempfile::tempdir()?;
+        let no_backoff = RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+
+        // A single stream requests only the missing tail.
+        let dest = dir.path().join("single.bin");
+        fs::write(partial_path(&dest), &body[..4000])?;
+        let mut reports = Vec::new();
+        let options = DownloadOptions::default()
+            .with_connections(1)
+            .with_retry(no_backoff.clone());
+        download_file_with_progress(&url, &dest, &options, |done, total| {
+            reports.push((done, total))
+        })?;
+        assert_eq!(fs::read(&dest)?, body);
+        assert_eq!(reports.first(), Some(&(4000, Some(10_000))));
+        assert_eq!(reports.last(), Some(&(10_000, Some(10_000))));
+
+        // A segmented download skips journaled segments.
+        let dest = dir.path().join("segmented.bin");
+        let mut part = body.clone();
+        part[2500..].fill(0);
+        fs::write(partial_path(&dest), &part)?;
+        fs::write(journal_path(&dest), "10000\n0-2499\n")?;
+        let options = DownloadOptions::default()
+            .with_connections(4)
+            .with_min_segment_size(1000)
+            .with_retry(no_backoff);
+        let before = gets.load(Ordering::SeqCst);
+        let mut last = (0, None);
+        download_file_with_progress(&url, &dest, &options, |done, total| last = (done, total))?;
+        assert_eq!(fs::read(&dest)?, body);
+        assert_eq!(gets.load(Ordering::SeqCst) - before, 3);
+        assert_eq!(last, (10_000, Some(10_000)));
+        assert!(!journal_path(&dest).exists());
+
+        Ok(())
+    }
+}
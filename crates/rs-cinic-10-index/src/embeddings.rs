@@ -0,0 +1,280 @@
+use crate::error::{Result, bail};
+use crate::images::load_rgbimage;
+use crate::index::DatasetIndex;
+use crate::parallel::par_fold;
+use image::RgbImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"CNE1";
+
+/// The distance between embedding vectors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// Squared Euclidean distance.
+    #[default]
+    L2,
+
+    /// `1 - cos(a, b)`.
+    Cosine,
+}
+
+impl Metric {
+    pub fn distance(
+        &self,
+        a: &[f32],
+        b: &[f32],
+    ) -> f32 {
+        match self {
+            Metric::L2 => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Metric::Cosine => {
+                let (mut dot, mut na, mut nb) = (0.0f32, 0.0f32, 0.0f32);
+                for (x, y) in a.iter().zip(b) {
+                    dot += x * y;
+                    na += x * x;
+                    nb += y * y;
+                }
+                let norm = (na * nb).sqrt();
+                if norm == 0.0 { 1.0 } else { 1.0 - dot / norm }
+            }
+        }
+    }
+}
+
+/// Dense per-item embedding vectors of a split; row `i` belongs to item `i`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embeddings {
+    dim: usize,
+    data: Vec<f32>,
+}
+
+impl Embeddings {
+    /// Wrap a row-major buffer of `data.len() / dim` vectors.
+    pub fn new(
+        dim: usize,
+        data: Vec<f32>,
+    ) -> Result<Self> {
+        if dim == 0 || !data.len().is_multiple_of(dim) {
+            bail!(
+                "Embedding buffer of {} values is not a whole number of {dim}-dim rows",
+                data.len()
+            );
+        }
+        Ok(Self { dim, data })
+    }
+
+    /// Stack equal-length vectors.
+    pub fn from_rows(rows: Vec<Vec<f32>>) -> Result<Self> {
+        let Some(dim) = rows.first().map(|r| r.len()) else {
+            bail!("Cannot infer the dimension of empty embeddings");
+        };
+        if let Some(row) = rows.iter().position(|r| r.len() != dim) {
+            bail!(
+                "Embedding row {row} has {} dims, expected {dim}",
+                rows[row].len()
+            );
+        }
+        Self::new(dim, rows.concat())
+    }
+
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / self.dim
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(
+        &self,
+        index: usize,
+    ) -> &[f32] {
+        &self.data[index * self.dim..(index + 1) * self.dim]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[f32]> {
+        self.data.chunks_exact(self.dim)
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    /// Write the embeddings in a compact little-endian binary format.
+    pub fn save<P>(
+        &self,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut bytes = Vec::with_capacity(12 + self.data.len() * 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.dim as u64).to_le_bytes());
+        for v in &self.data {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Read embeddings written by `save()`.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        if bytes.len() < 12 || &bytes[..4] != MAGIC || (bytes.len() - 12) % 4 != 0 {
+            bail!("Not an embeddings file: {}", path.display());
+        }
+        let dim = u64::from_le_bytes(bytes[4..12].try_into().unwrap()) as usize;
+        let data = bytes[12..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        Self::new(dim, data)
+    }
+}
+
+/// Embed every item of a split.
+///
+/// # Parameters
+///
+/// - `split`: The split to embed.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+/// - `embed`: Maps an image to its embedding vector.
+///
+/// # Returns
+///
+/// A `Result` containing the embeddings, in item order.
+pub fn compute_embeddings<F>(
+    split: &DatasetIndex,
+    parallelism: usize,
+    embed: F,
+) -> Result<Embeddings>
+where
+    F: Fn(&RgbImage) -> Result<Vec<f32>> + Sync,
+{
+    let indices: Vec<usize> = (0..split.len()).collect();
+    let rows = par_fold(
+        &indices,
+        parallelism,
+        Vec::new,
+        |rows, &i| {
+            rows.push(embed(&load_rgbimage(split.index_to_path(i))?)?);
+            Ok(())
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    )?;
+    Embeddings::from_rows(rows)
+}
+
+/// Where `cached_embeddings()` stores the embeddings of a split under a model name.
+///
+/// Like `stats::channel_stats_cache_path()`, the file is keyed by the split's fingerprint.
+pub fn embeddings_cache_path(
+    split: &DatasetIndex,
+    model: &str,
+) -> PathBuf {
+    let ds_path = split.ds_path();
+    let name = ds_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fingerprint = split.fingerprint();
+    ds_path.parent().unwrap_or(ds_path).join(format!(
+        "embeddings-{model}-{name}-{}.bin",
+        &fingerprint[..16]
+    ))
+}
+
+/// Embed a split, caching the result next to the dataset.
+///
+/// Embedding is expensive; the cache lets kNN and core-set tools share one pass.
+/// Failing to write the cache is not an error.
+///
+/// # Parameters
+///
+/// - `split`: The split to embed.
+/// - `model`: A name identifying `embed`; part of the cache key.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+/// - `embed`: Maps an image to its embedding vector.
+///
+/// # Returns
+///
+/// A `Result` containing the embeddings, in item order.
+pub fn cached_embeddings<F>(
+    split: &DatasetIndex,
+    model: &str,
+    parallelism: usize,
+    embed: F,
+) -> Result<Embeddings>
+where
+    F: Fn(&RgbImage) -> Result<Vec<f32>> + Sync,
+{
+    let path = embeddings_cache_path(split, model);
+    if let Ok(embeddings) = Embeddings::load(&path)
+        && embeddings.len() == split.len()
+    {
+        return Ok(embeddings);
+    }
+
+    let embeddings = compute_embeddings(split, parallelism, embed)?;
+    if let Err(_err) = embeddings.save(&path) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(path = %path.display(), error = %_err, "failed to cache embeddings");
+    }
+    Ok(embeddings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::Rgb;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_cached_embeddings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = fixtures::write_split(
+            dir.path().join("valid"),
+            [10u8, 20, 30].map(|v| {
+                (
+                    ObjectClass::Frog,
+                    format!("{v}.png"),
+                    RgbImage::from_pixel(2, 2, Rgb([v, 0, 0])),
+                )
+            }),
+        )?;
+
+        let calls = AtomicUsize::new(0);
+        let embed = |img: &RgbImage| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![img.get_pixel(0, 0)[0] as f32, 1.0])
+        };
+
+        let embeddings = cached_embeddings(&split, "red", 2, embed)?;
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(embeddings.dim(), 2);
+        assert_eq!(embeddings.get(2), &[30.0, 1.0]);
+        assert!(embeddings_cache_path(&split, "red").exists());
+
+        assert_eq!(cached_embeddings(&split, "red", 2, embed)?, embeddings);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        assert!(Embeddings::from_rows(vec![vec![1.0], vec![1.0, 2.0]]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::io;
+use std::path::PathBuf;
+
+/// The error type of the crate's public APIs.
+///
+/// Failures which callers are expected to handle have their own variants;
+/// everything else is an `Other`, carrying its context chain.
+#[derive(Debug, thiserror::Error)]
+pub enum Cinic10Error {
+    /// No dataset path is configured; see `get_default_data_path()`.
+    #[error(
+        "CINIC-10 data path not set. \
+        Set the {} environment variable or call set_default_data_path()",
+        crate::CINC10_PATH_ENV_VAR
+    )]
+    NotConfigured,
+
+    /// The dataset root does not exist.
+    #[error("CINIC-10 dataset not found at {}", .0.display())]
+    MissingRoot(PathBuf),
+
+    /// The dataset root is not a directory.
+    #[error("CINIC-10 dataset path is not a directory: {}", .0.display())]
+    NotADirectory(PathBuf),
+
+    /// A malformed line in the synset map; see `index::parse_synset_map()`.
+    #[error("Malformed synset map, line {line}: {message}")]
+    MalformedSynsetFile { line: usize, message: String },
+
+    /// A row of the contributor CSV which does not parse; see `index::parse_contrib_index()`.
+    #[error("Bad CSV row {row}: {source}")]
+    BadCsvRow {
+        /// The 1-based row number, counting the header.
+        row: u64,
+        source: csv::Error,
+    },
+
+    /// An image whose dimensions differ from the rest of its batch.
+    #[error("Image dimensions {actual:?} do not match {expected:?}: {}", path.display())]
+    DimensionMismatch {
+        path: PathBuf,
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+
+    /// An item index past the end of a split.
+    #[error("Index {index} out of range for a split of {len}")]
+    IndexOutOfRange { index: usize, len: usize },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// A `Result` with `Cinic10Error` as the default error type.
+pub type Result<T, E = Cinic10Error> = std::result::Result<T, E>;
+
+/// Return early with a `Cinic10Error::Other`; `anyhow::bail!` for `Cinic10Error`.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err($crate::error::Cinic10Error::Other(anyhow::anyhow!($($arg)*)))
+    };
+}
+pub(crate) use bail;
@@ -0,0 +1,310 @@
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::index::{DatasetIndex, DatasetItem, ObjectClass, list_images_sorted};
+use crate::loader::{BatchLoader, LoadedBatch};
+use crate::parallel::par_map;
+use crate::retry::with_retry;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A class of an `ImageFolderIndex`, stored in its own directory.
+///
+/// Implemented for `ObjectClass`, for CINIC-10; and for `String`, for class
+/// lists only known at runtime. Implement it for your own class enum to get
+/// typed labels for another `{root}/{class}/{image}` dataset.
+pub trait FolderClass: Clone + PartialEq + fmt::Debug + Send + Sync {
+    /// The name of the class's directory under the root.
+    fn dir_name(&self) -> String;
+}
+
+impl FolderClass for ObjectClass {
+    fn dir_name(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FolderClass for String {
+    fn dir_name(&self) -> String {
+        self.clone()
+    }
+}
+
+/// An image of an `ImageFolderIndex`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageFolderItem<C> {
+    pub class: C,
+
+    /// The file name, relative to the class directory.
+    pub path: PathBuf,
+}
+
+/// The images of a `{root}/{class}/{image}` directory tree, in a fixed order.
+///
+/// Items are grouped by class, in `classes` order, and sorted by name within
+/// each class. `DatasetIndex` is the CINIC-10 specialization, over
+/// `ObjectClass`; see its `From` conversions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageFolderIndex<C> {
+    pub root: PathBuf,
+
+    /// The classes; a class's label is its position here.
+    pub classes: Vec<C>,
+
+    pub items: Vec<ImageFolderItem<C>>,
+}
+
+impl<C> ImageFolderIndex<C>
+where
+    C: FolderClass,
+{
+    /// Scan the class directories of `root`, listing them concurrently.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The directory holding one directory per class.
+    /// - `classes`: The classes, in label order.
+    /// - `extensions`: The image file extensions to index, without the dot.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; or an error if a class directory
+    /// cannot be listed.
+    pub fn scan<P, S>(
+        root: P,
+        classes: Vec<C>,
+        extensions: &[S],
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        S: AsRef<str> + Sync,
+    {
+        let root = root.as_ref();
+        let listings = par_map(&classes, 0, |class| {
+            list_images_sorted(root.join(class.dir_name()), extensions)
+        })?;
+
+        let mut items = Vec::new();
+        for (class, paths) in classes.iter().zip(listings) {
+            items.extend(paths.into_iter().map(|path| ImageFolderItem {
+                class: class.clone(),
+                path: PathBuf::from(path.file_name().unwrap_or_default()),
+            }));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            classes,
+            items,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The class of an item.
+    pub fn index_to_class(
+        &self,
+        index: usize,
+    ) -> &C {
+        &self.items[index].class
+    }
+
+    /// The label of an item: its class's position in `classes`.
+    pub fn index_to_label(
+        &self,
+        index: usize,
+    ) -> usize {
+        let class = self.index_to_class(index);
+        self.classes.iter().position(|c| c == class).unwrap()
+    }
+
+    /// Convert a slice of indices to labels.
+    pub fn indices_to_labels(
+        &self,
+        indices: &[usize],
+    ) -> Vec<usize> {
+        indices.iter().map(|&i| self.index_to_label(i)).collect()
+    }
+
+    /// The path of an item: `{root}/{class}/{name}`.
+    pub fn index_to_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        let item = &self.items[index];
+        self.root.join(item.class.dir_name()).join(&item.path)
+    }
+
+    /// Convert a slice of indices to a vector of image paths.
+    pub fn indices_to_paths(
+        &self,
+        indices: &[usize],
+    ) -> Vec<PathBuf> {
+        indices.iter().map(|&i| self.index_to_path(i)).collect()
+    }
+
+    /// Load an `RgbImageBatch` for a batch of indices.
+    pub fn load_rgbimagebatch(
+        &self,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        self.load_rgbimagebatch_with(&BatchLoader::default(), indices)
+    }
+
+    /// Load an `RgbImageBatch` for a batch of indices, using the given loader.
+    pub fn load_rgbimagebatch_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        loader.load_rgbimagebatch(&self.indices_to_paths(indices))
+    }
+
+    /// Load an `RgbImageBatch` with the given loader, reporting failed samples.
+    pub fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        loader.load_rgbimagebatch_report(&self.indices_to_paths(indices))
+    }
+}
+
+impl ImageFolderIndex<String> {
+    /// Scan `root`, taking every subdirectory as a class, in name order.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The directory holding one directory per class.
+    /// - `extensions`: The image file extensions to index, without the dot.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; or an error if `root` has no class directories.
+    pub fn discover<P, S>(
+        root: P,
+        extensions: &[S],
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        S: AsRef<str> + Sync,
+    {
+        let root = root.as_ref();
+        let mut classes: Vec<String> = with_retry(|| {
+            Ok(fs::read_dir(root)?
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    entry.file_type().ok()?.is_dir().then_some(())?;
+                    entry.file_name().into_string().ok()
+                })
+                .collect())
+        })?;
+        if classes.is_empty() {
+            bail!("No class directories in {}", root.display());
+        }
+        classes.sort();
+        Self::scan(root, classes, extensions)
+    }
+}
+
+impl From<ImageFolderIndex<ObjectClass>> for DatasetIndex {
+    fn from(folder: ImageFolderIndex<ObjectClass>) -> Self {
+        let items = folder
+            .items
+            .into_iter()
+            .map(|item| DatasetItem {
+                class: item.class,
+                path: item.path,
+            })
+            .collect();
+        DatasetIndex::new(folder.root, items)
+    }
+}
+
+/// Items stored outside the split keep their absolute path, which
+/// `index_to_path()` joins back unchanged; so do all the items of a
+/// concatenated index, made absolute against the working directory.
+impl From<&DatasetIndex> for ImageFolderIndex<ObjectClass> {
+    fn from(index: &DatasetIndex) -> Self {
+        let concatenated = index.runs().iter().any(|run| run.ds_path != index.ds_path);
+        Self {
+            root: index.ds_path.clone(),
+            classes: ObjectClass::ALL.to_vec(),
+            items: index
+                .items()
+                .enumerate()
+                .map(|(i, item)| ImageFolderItem {
+                    class: item.class,
+                    path: match concatenated {
+                        true => {
+                            let path = index.index_to_path(i);
+                            std::path::absolute(&path).unwrap_or(path)
+                        }
+                        false => item.path.clone(),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_image_folder() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for (class, names) in [("roses", vec!["b.png", "a.png"]), ("tulips", vec!["c.png"])] {
+            fs::create_dir_all(root.join(class))?;
+            for (i, name) in names.into_iter().enumerate() {
+                RgbImage::from_pixel(4, 4, Rgb([i as u8 + 1; 3]))
+                    .save(root.join(class).join(name))?;
+            }
+        }
+        fs::write(root.join("roses/notes.txt"), b"")?;
+
+        let folder = ImageFolderIndex::discover(root, &["png"])?;
+        assert_eq!(folder.classes, vec!["roses", "tulips"]);
+        assert_eq!(folder.len(), 3);
+        assert_eq!(folder.indices_to_labels(&[0, 1, 2]), vec![0, 0, 1]);
+        assert_eq!(folder.index_to_path(0), root.join("roses/a.png"));
+
+        let batch = folder.load_rgbimagebatch(&[2, 0])?;
+        assert_eq!(batch.shape, vec![2, 4, 4, 3]);
+        assert_eq!((batch.data[0], batch.data[48]), (1, 2));
+
+        let classes = vec!["tulips".to_string()];
+        assert_eq!(ImageFolderIndex::scan(root, classes, &["png"])?.len(), 1);
+        assert!(ImageFolderIndex::discover(root.join("tulips"), &["png"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dataset_index_conversion() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for class in ObjectClass::ALL {
+            fs::create_dir_all(dir.path().join(class.to_string()))?;
+        }
+        fs::write(dir.path().join("ship/x.png"), b"")?;
+
+        let folder = ImageFolderIndex::scan(dir.path(), ObjectClass::ALL.to_vec(), &["png"])?;
+        assert_eq!(folder.index_to_label(0), 8);
+
+        let index = DatasetIndex::from(folder.clone());
+        assert_eq!(index.index_to_path(0), folder.index_to_path(0));
+        assert_eq!(ImageFolderIndex::from(&index), folder);
+
+        Ok(())
+    }
+}
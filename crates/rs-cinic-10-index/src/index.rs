@@ -1,13 +1,23 @@
-use crate::default_data_path_or_panic;
+use crate::error::{Cinic10Error, Result, bail};
+use crate::image_folder::ImageFolderIndex;
 use crate::images::{RgbImageBatch, load_bhwc_rgbimagebatch};
-use anyhow::Result;
+use crate::item_store::ItemStore;
+use crate::loader::{BatchLoader, LoadedBatch};
+use crate::ordering::FileOrdering;
+use crate::parallel::{par_fold, par_map};
+use crate::retry::with_retry;
+use crate::source::DataSource;
+use crate::stats::ClassDistribution;
+use crate::try_default_data_path;
+use crate::wnid::WnId;
 use enum_ordinalize::Ordinalize;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::File;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::io::BufRead;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::OnceLock;
 use std::{fs, io};
 use strum::{EnumCount, IntoEnumIterator};
 
@@ -25,11 +35,14 @@ pub const SYNSET_FILE: &str = "synsets-to-cifar-10-classes.txt";
     PartialEq,
     Eq,
     Hash,
+    PartialOrd,
+    Ord,
     Serialize,
     Deserialize,
     strum_macros::EnumString,
     strum_macros::Display,
     strum_macros::EnumIter,
+    strum_macros::EnumCount,
 )]
 #[serde(rename_all = "lowercase")]
 #[strum(serialize_all = "lowercase")]
@@ -39,6 +52,14 @@ pub enum DataSet {
     Valid,
 }
 
+impl DataSet {
+    /// Every split, in declaration order.
+    pub const ALL: [DataSet; DataSet::COUNT] = [DataSet::Train, DataSet::Test, DataSet::Valid];
+
+    /// The split directory names, in `ALL` order.
+    pub const NAMES: [&'static str; DataSet::COUNT] = ["train", "test", "valid"];
+}
+
 #[derive(
     Debug,
     Clone,
@@ -69,17 +90,70 @@ pub enum ObjectClass {
     Truck,
 }
 
+impl ObjectClass {
+    /// Every class, in label order.
+    pub const ALL: [ObjectClass; ObjectClass::COUNT] = [
+        ObjectClass::Airplane,
+        ObjectClass::Automobile,
+        ObjectClass::Bird,
+        ObjectClass::Cat,
+        ObjectClass::Deer,
+        ObjectClass::Dog,
+        ObjectClass::Frog,
+        ObjectClass::Horse,
+        ObjectClass::Ship,
+        ObjectClass::Truck,
+    ];
+
+    /// The class names, in label order.
+    pub const NAMES: [&'static str; ObjectClass::COUNT] = [
+        "airplane",
+        "automobile",
+        "bird",
+        "cat",
+        "deer",
+        "dog",
+        "frog",
+        "horse",
+        "ship",
+        "truck",
+    ];
+}
+
 pub const SAMPLES_PER_CLASS: usize = 9000;
 pub const SAMPLES_PER_DATASET: usize = SAMPLES_PER_CLASS * ObjectClass::COUNT;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct IndexRecord {
-    synset: String,
+    synset: WnId,
     image_num: usize,
     data_set: DataSet,
     class: ObjectClass,
 }
 
+impl IndexRecord {
+    pub fn synset(&self) -> WnId {
+        self.synset
+    }
+
+    pub fn image_num(&self) -> usize {
+        self.image_num
+    }
+
+    pub fn data_set(&self) -> DataSet {
+        self.data_set
+    }
+
+    pub fn class(&self) -> ObjectClass {
+        self.class
+    }
+
+    /// The file name of the record's image, e.g. `n02690373_6332.png`.
+    pub fn filename(&self) -> String {
+        format!("{}_{}.png", self.synset, self.image_num)
+    }
+}
+
 impl TryFrom<&csv::StringRecord> for IndexRecord {
     type Error = csv::Error;
 
@@ -102,7 +176,8 @@ impl TryFrom<&csv::StringRecord> for IndexRecord {
 ///
 /// # Returns:
 ///
-/// A `Result` containing a vector of `IndexRecord` on success, or an error on failure.
+/// A `Result` containing a vector of `IndexRecord` on success; or a
+/// `Cinic10Error::BadCsvRow` for a row which does not parse.
 pub fn parse_contrib_index<R>(rdr: R) -> Result<Vec<IndexRecord>>
 where
     R: io::Read,
@@ -113,19 +188,23 @@ where
         .trim(csv::Trim::All)
         .from_reader(rdr);
 
-    let records = rdr
-        .records()
-        .map(|r| r.unwrap().deserialize(None).unwrap())
-        .collect::<Vec<IndexRecord>>();
-
-    Ok(records)
+    rdr.records()
+        .enumerate()
+        .map(|(i, record)| {
+            // Row 1 is the header.
+            let row = i as u64 + 2;
+            record
+                .and_then(|record| record.deserialize(None))
+                .map_err(|source| Cinic10Error::BadCsvRow { row, source })
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SynsetNode {
-    pub synset_id: String,
+    pub synset_id: WnId,
     pub object_class: ObjectClass,
-    pub synset_base_id: Option<String>,
+    pub synset_base_id: Option<WnId>,
     pub aliases: Vec<String>,
 }
 
@@ -137,32 +216,40 @@ pub struct SynsetNode {
 ///
 /// # Returns:
 ///
-/// A `Result` containing a HashMap of SynsetNode on success, or an error on failure.
-pub fn parse_synset_map<R>(rdr: R) -> Result<HashMap<String, SynsetNode>>
+/// A `Result` containing a HashMap of SynsetNode on success; or a
+/// `Cinic10Error::MalformedSynsetFile` for a line which does not parse.
+pub fn parse_synset_map<R>(rdr: R) -> Result<HashMap<WnId, SynsetNode>>
 where
     R: io::Read,
 {
-    let mut synset_map: HashMap<String, SynsetNode> = HashMap::new();
+    let mut synset_map: HashMap<WnId, SynsetNode> = HashMap::new();
 
     let mut object_class: Option<ObjectClass> = None;
-    let mut synset_stack: Vec<(usize, String)> = Vec::new();
+    let mut synset_stack: Vec<(usize, WnId)> = Vec::new();
 
     let rdr = io::BufReader::new(rdr);
-    for res in rdr.lines() {
+    for (i, res) in rdr.lines().enumerate() {
         let full_line = res?;
         let cur = full_line.trim();
+        let malformed = |message: String| Cinic10Error::MalformedSynsetFile {
+            line: i + 1,
+            message,
+        };
 
         if !cur.starts_with("-") {
-            object_class = Some(ObjectClass::from_str(cur)?);
+            object_class = Some(
+                ObjectClass::from_str(cur)
+                    .map_err(|_| malformed(format!("unknown object class {cur:?}")))?,
+            );
             synset_stack.clear();
             continue;
         }
 
-        assert!(
-            object_class.is_some(),
-            "Malformed synset map; synset entry before class: {}",
-            full_line
-        );
+        let Some(object_class) = object_class else {
+            return Err(malformed(format!(
+                "synset entry before class: {full_line:?}"
+            )));
+        };
 
         // This line is a nested synset node:
         //   (?P<depth>: "-"+)
@@ -179,8 +266,11 @@ where
         let line: &str = cur.trim_start_matches('-');
         let depth = orig_len - line.len();
 
-        let split_pos = line.find(':').unwrap();
-        let synset_id = line[..split_pos].to_owned();
+        let Some(split_pos) = line.find(':') else {
+            return Err(malformed(format!("missing ':' in {full_line:?}")));
+        };
+        let synset_id =
+            WnId::from_str(line[..split_pos].trim()).map_err(|err| malformed(err.to_string()))?;
 
         let aliases = &line[split_pos + 1..];
         let aliases: Vec<String> = aliases.split(',').map(|s| s.trim().to_string()).collect();
@@ -194,25 +284,65 @@ where
         }
 
         // If a parent exists, get the id.
-        let synset_base_id = synset_stack
-            .last()
-            .map(|(_, parent_id)| parent_id.to_string());
+        let synset_base_id = synset_stack.last().map(|(_, parent_id)| *parent_id);
         // Add this node to the stack.
-        synset_stack.push((depth, synset_id.clone()));
+        synset_stack.push((depth, synset_id));
 
         let node = SynsetNode {
             synset_id,
-            object_class: object_class.unwrap(),
+            object_class,
             synset_base_id,
             aliases,
         };
 
-        synset_map.insert(node.synset_id.clone(), node);
+        synset_map.insert(node.synset_id, node);
     }
 
     Ok(synset_map)
 }
 
+/// The image file extensions of the standard CINIC-10 layout.
+pub const DEFAULT_IMAGE_EXTENSIONS: &[&str] = &["png"];
+
+/// Are `extensions` exactly `DEFAULT_IMAGE_EXTENSIONS`?
+pub(crate) fn is_default_extensions<S>(extensions: &[S]) -> bool
+where
+    S: AsRef<str>,
+{
+    extensions
+        .iter()
+        .map(AsRef::as_ref)
+        .eq(DEFAULT_IMAGE_EXTENSIONS.iter().copied())
+}
+
+/// `DEFAULT_IMAGE_EXTENSIONS`, as the owned list a `DatasetIndex` records.
+fn default_extensions() -> Vec<String> {
+    DEFAULT_IMAGE_EXTENSIONS
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+/// Normalize an image file extension: lowercase, without a leading dot.
+pub(crate) fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_ascii_lowercase()
+}
+
+/// Does the file at `path` have any of `extensions`? Matches case-insensitively.
+pub(crate) fn has_extension<S>(
+    path: &Path,
+    extensions: &[S],
+) -> bool
+where
+    S: AsRef<str>,
+{
+    path.extension().is_some_and(|ext| {
+        extensions
+            .iter()
+            .any(|e| ext.eq_ignore_ascii_case(e.as_ref()))
+    })
+}
+
 /// Lists all PNG files in the given directory, sorted by their names.
 ///
 /// # Parameters
@@ -222,68 +352,606 @@ where
 /// # Returns
 ///
 /// A result containing a vector of paths to the PNG files.
-fn list_pngs_sorted<P>(dir: P) -> Result<Vec<PathBuf>>
+pub(crate) fn list_pngs_sorted<P>(dir: P) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    list_images_sorted(dir, DEFAULT_IMAGE_EXTENSIONS)
+}
+
+/// Lists the files with any of the given extensions in a directory, sorted by their names.
+///
+/// # Parameters
+///
+/// - `dir`: The directory to list.
+/// - `extensions`: The extensions to match, without the dot; case-insensitive.
+///
+/// # Returns
+///
+/// A result containing a vector of paths to the matching files.
+pub(crate) fn list_images_sorted<P, S>(
+    dir: P,
+    extensions: &[S],
+) -> Result<Vec<PathBuf>>
 where
     P: AsRef<Path>,
+    S: AsRef<str>,
 {
     let dir = dir.as_ref();
 
-    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
-        .filter_map(|entry| {
-            if let Ok(entry) = entry {
-                if let Some(ext) = entry.path().extension() {
-                    if ext == "png" {
-                        return Some(entry.path().to_str().unwrap().to_string());
-                    }
-                }
-            }
-            None
-        })
-        .map(PathBuf::from)
-        .collect();
+    let mut files: Vec<PathBuf> = with_retry(|| {
+        Ok(fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                has_extension(&path, extensions).then_some(path)
+            })
+            .collect())
+    })?;
 
     files.sort();
 
     Ok(files)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatasetItem {
     pub class: ObjectClass,
+
+    /// The file name, relative to the class directory; or an absolute path,
+    /// for items stored outside the split. See `DatasetIndex::abs_path()`.
     pub path: PathBuf,
 }
 
-#[derive(Debug, Clone)]
+impl DatasetItem {
+    /// An item stored as `{class}/{name}` under its split.
+    fn in_class_dir(
+        class: ObjectClass,
+        path: &Path,
+    ) -> Self {
+        Self {
+            class,
+            path: PathBuf::from(path.file_name().unwrap_or_default()),
+        }
+    }
+}
+
+/// A run of items of a concatenated `DatasetIndex`, stored under their own
+/// dataset path; see `DatasetIndex::concat()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexPart {
+    /// The dataset path the items of the run are stored under.
+    pub ds_path: PathBuf,
+
+    /// The number of items in the run.
+    pub len: usize,
+}
+
+/// The files added and removed by a `DatasetIndex` refresh.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefreshDelta {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl RefreshDelta {
+    /// Did the refresh change anything?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The items of a split directory, in a fixed order.
+///
+/// Serializes to its `ds_path` and items, so a saved index reloads with
+/// exactly the same sample ordering.
+///
+/// Items are stored packed, in about 8 bytes each for standard CINIC-10
+/// file names; read them through `item()`, `items()` and the index accessors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatasetIndex {
     pub ds_path: PathBuf,
-    pub items: Vec<DatasetItem>,
+
+    /// The items, packed; see `items()` and `ItemStore`.
+    items: ItemStore,
+
+    /// The runs of items stored under other dataset paths; empty unless
+    /// built by `concat()`, when they cover all the items, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    parts: Vec<IndexPart>,
+
+    /// The order of the items of each class; see `ordering()`.
+    #[serde(default, skip_serializing_if = "FileOrdering::is_lexical")]
+    ordering: FileOrdering,
+
+    /// The image file extensions `refresh()` indexes; see `set_extensions()`.
+    #[serde(
+        default = "default_extensions",
+        skip_serializing_if = "is_default_extensions"
+    )]
+    extensions: Vec<String>,
+
+    /// The indices of the items with each file name; see `index_of_path()`.
+    #[serde(skip)]
+    path_index: OnceLock<HashMap<OsString, Vec<usize>>>,
+
+    /// The indices of the items of each class; see `items_of_class()`.
+    #[serde(skip)]
+    class_index: OnceLock<ClassIndices>,
 }
 
 impl DatasetIndex {
-    fn load_index_from_dir(ds_path: &Path) -> Result<Self> {
-        let ds_path = ds_path.to_path_buf();
-        let mut items = Vec::with_capacity(SAMPLES_PER_DATASET);
+    /// Create an index of `items`, stored under `ds_path`.
+    pub fn new(
+        ds_path: PathBuf,
+        items: Vec<DatasetItem>,
+    ) -> Self {
+        Self {
+            ds_path,
+            items: items.into_iter().collect(),
+            parts: Vec::new(),
+            ordering: FileOrdering::Lexical,
+            extensions: default_extensions(),
+            path_index: OnceLock::new(),
+            class_index: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn load_index_from_dir(ds_path: &Path) -> Result<Self> {
+        Self::load_index_from_dir_with(ds_path, DEFAULT_IMAGE_EXTENSIONS, Some(SAMPLES_PER_CLASS))
+    }
+
+    /// Like `load_index_from_dir()`, indexing files with any of `extensions`,
+    /// and expecting `samples_per_class` images of each class in total;
+    /// `None` accepts any number.
+    ///
+    /// Only the default extensions use the index cache.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(ds_path = %ds_path.display()))
+    )]
+    pub(crate) fn load_index_from_dir_with<S>(
+        ds_path: &Path,
+        extensions: &[S],
+        samples_per_class: Option<usize>,
+    ) -> Result<Self>
+    where
+        S: AsRef<str> + Sync,
+    {
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
 
+        let mut di = if is_default_extensions(extensions) {
+            Self::load_index_cached(ds_path)?
+        } else {
+            Self::scan_dir(ds_path, extensions)?
+        };
+        di.set_extensions(extensions);
+        if let Some(n) = samples_per_class
+            && di.len() != n * ObjectClass::COUNT
+        {
+            let expected = n * ObjectClass::COUNT;
+            bail!(
+                "Expected {expected} images in {}, found {}",
+                ds_path.display(),
+                di.len()
+            );
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            items = di.len(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "indexed dataset directory"
+        );
+
+        Ok(di)
+    }
+
+    /// Scan the class directories of `ds_path`; see `ImageFolderIndex::scan()`.
+    pub(crate) fn scan_dir<S>(
+        ds_path: &Path,
+        extensions: &[S],
+    ) -> Result<Self>
+    where
+        S: AsRef<str> + Sync,
+    {
+        Ok(ImageFolderIndex::scan(ds_path, ObjectClass::ALL.to_vec(), extensions)?.into())
+    }
+
+    /// Index the class directories of `ds_path` in a `DataSource`, with any of `extensions`.
+    ///
+    /// Unlike the directory scan, this does not require the standard CINIC-10
+    /// counts; missing class directories are treated as empty.
+    pub(crate) fn load_index_from_source<S>(
+        source: &dyn DataSource,
+        ds_path: &Path,
+        extensions: &[S],
+    ) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let mut items = Vec::new();
         for oc in ObjectClass::iter() {
-            let oc_path = ds_path.join(oc.to_string());
             items.extend(
-                list_pngs_sorted(&oc_path)?
+                source
+                    .list(&ds_path.join(oc.to_string()))?
                     .into_iter()
-                    .map(|p| DatasetItem { class: oc, path: p }),
-            )
+                    .map(PathBuf::from)
+                    .filter(|path| has_extension(path, extensions))
+                    .map(|path| DatasetItem { class: oc, path }),
+            );
         }
+        let mut index = Self::new(ds_path.to_path_buf(), items);
+        index.set_extensions(extensions);
+        Ok(index)
+    }
 
-        let di = Self { ds_path, items };
-        assert_eq!(di.len(), SAMPLES_PER_DATASET);
+    /// The order of the items of each class, which `refresh()` keeps;
+    /// `FileOrdering::Lexical`, unless set by `sort_items()`.
+    pub fn ordering(&self) -> FileOrdering {
+        self.ordering
+    }
 
-        Ok(di)
+    pub(crate) fn set_ordering(
+        &mut self,
+        ordering: FileOrdering,
+    ) {
+        self.ordering = ordering;
+    }
+
+    /// The image file extensions `refresh()` indexes; those the index was
+    /// built with, by default `DEFAULT_IMAGE_EXTENSIONS`.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Set the image file extensions `refresh()` indexes.
+    ///
+    /// Extensions match case-insensitively, with or without a leading dot.
+    pub fn set_extensions<I, S>(
+        &mut self,
+        extensions: I,
+    ) where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extensions = extensions
+            .into_iter()
+            .map(|e| normalize_extension(e.as_ref()))
+            .collect();
+    }
+
+    /// Re-scan all class directories, updating the index in place.
+    ///
+    /// Unlike the initial scan, this does not require the standard CINIC-10
+    /// layout or counts; missing class directories are treated as empty.
+    /// Items keep the class-contiguous layout, sorted by `ordering()`, so
+    /// indices after a changed item may shift.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RefreshDelta` of added and removed files.
+    pub fn refresh(&mut self) -> Result<RefreshDelta> {
+        self.refresh_classes(&ObjectClass::ALL)
+    }
+
+    /// Re-scan only the given class directories, updating the index in place.
+    ///
+    /// Files with any of `extensions()` are indexed.
+    ///
+    /// # Parameters
+    ///
+    /// - `classes`: The classes whose directories should be re-scanned.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RefreshDelta` of added and removed files.
+    pub fn refresh_classes(
+        &mut self,
+        classes: &[ObjectClass],
+    ) -> Result<RefreshDelta> {
+        if !self.parts.is_empty() {
+            bail!(
+                "Cannot refresh a concatenated index of {}; refresh its parts",
+                self.ds_path.display()
+            );
+        }
+        let listings = par_map(&ObjectClass::ALL, 0, |oc| {
+            let oc_path = self.ds_path.join(oc.to_string());
+            if classes.contains(oc) && oc_path.is_dir() {
+                list_images_sorted(&oc_path, &self.extensions)
+            } else {
+                Ok(Vec::new())
+            }
+        })?;
+
+        let mut delta = RefreshDelta::default();
+        let mut items = Vec::with_capacity(self.len());
+
+        for (oc, current) in ObjectClass::ALL.into_iter().zip(listings) {
+            let old: Vec<DatasetItem> = self.items().filter(|i| i.class == oc).collect();
+            if !classes.contains(&oc) {
+                items.extend(old);
+                continue;
+            }
+
+            let oc_path = self.ds_path.join(oc.to_string());
+
+            let old_paths: HashSet<PathBuf> = old.iter().map(|i| oc_path.join(&i.path)).collect();
+            let new_paths: HashSet<&Path> = current.iter().map(PathBuf::as_path).collect();
+            delta
+                .added
+                .extend(current.iter().filter(|p| !old_paths.contains(*p)).cloned());
+            delta.removed.extend(
+                old.iter()
+                    .map(|i| oc_path.join(&i.path))
+                    .filter(|p| !new_paths.contains(p.as_path())),
+            );
+
+            items.extend(current.iter().map(|p| DatasetItem::in_class_dir(oc, p)));
+        }
+
+        self.replace_items(items);
+        if !self.ordering.is_lexical() {
+            self.sort_items(self.ordering);
+        }
+        Ok(delta)
+    }
+
+    /// Find the index of an item by its path or file name.
+    ///
+    /// Built on first use, from a map of the item file names; so logs, reports,
+    /// and label files which name e.g. `cifar10-train-3318.png` can be mapped
+    /// back into the index.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: A bare file name; or a path, absolute or relative to `ds_path`
+    ///   (e.g. `cat/cifar10-train-3318.png`), which must match `index_to_path()`.
+    ///
+    /// # Returns
+    ///
+    /// The index of the first matching item, or `None`.
+    pub fn index_of_path(
+        &self,
+        path: &Path,
+    ) -> Option<usize> {
+        let candidates = self
+            .path_index
+            .get_or_init(|| {
+                let mut map: HashMap<OsString, Vec<usize>> = HashMap::new();
+                for (index, item) in self.items().enumerate() {
+                    if let Some(name) = item.path.file_name() {
+                        map.entry(name.to_os_string()).or_default().push(index);
+                    }
+                }
+                map
+            })
+            .get(path.file_name()?)?;
+        if path.components().count() == 1 {
+            return candidates.first().copied();
+        }
+        let path = self.ds_path.join(path);
+        candidates
+            .iter()
+            .copied()
+            .find(|&index| self.index_to_path(index) == path)
+    }
+
+    /// Drop the maps built by `index_of_path()` and `items_of_class()`;
+    /// the methods which change the items call this.
+    pub fn clear_path_index(&mut self) {
+        self.path_index = OnceLock::new();
+        self.class_index = OnceLock::new();
+    }
+
+    /// Move the index to a new dataset path, without re-scanning.
+    ///
+    /// Item paths under the old `ds_path` are remapped under `new_ds_path`;
+    /// relative item paths already resolve against it, and other paths are
+    /// left as they are. Useful when a saved index is loaded on a host where
+    /// the dataset lives elsewhere.
+    ///
+    /// # Parameters
+    ///
+    /// - `new_ds_path`: The new dataset path.
+    pub fn rebase<P>(
+        &mut self,
+        new_ds_path: P,
+    ) where
+        P: AsRef<Path>,
+    {
+        let new_ds_path = new_ds_path.as_ref();
+        self.items.map_dirs(|dir| {
+            dir.strip_prefix(&self.ds_path)
+                .ok()
+                .map(|rel| new_ds_path.join(rel))
+        });
+        for part in &mut self.parts {
+            if let Ok(rel) = part.ds_path.strip_prefix(&self.ds_path) {
+                part.ds_path = new_ds_path.join(rel);
+            }
+        }
+        self.ds_path = new_ds_path.to_path_buf();
+    }
+
+    /// Concatenate indexes into one, in order.
+    ///
+    /// Item `i` of `parts[k]` is item `i + offset` of the result, where
+    /// `offset` is the total length of `parts[..k]`. Each part keeps its own
+    /// `ds_path`, relative or not, so items resolve as before; `ds_path` is
+    /// the deepest directory containing every part's `ds_path`, e.g. the
+    /// dataset root. The result cannot be refreshed; refresh the parts.
+    ///
+    /// # Parameters
+    ///
+    /// - `parts`: The indexes to join.
+    ///
+    /// # Returns
+    ///
+    /// The combined `DatasetIndex`.
+    pub fn concat(parts: &[&DatasetIndex]) -> DatasetIndex {
+        let mut ds_path = parts
+            .first()
+            .map(|part| part.ds_path.clone())
+            .unwrap_or_default();
+        for part in parts {
+            while !part.ds_path.starts_with(&ds_path) {
+                ds_path.pop();
+            }
+        }
+        let mut joined = DatasetIndex::new(
+            ds_path,
+            parts.iter().flat_map(|part| part.items()).collect(),
+        );
+        joined.parts = parts
+            .iter()
+            .flat_map(|part| part.runs())
+            .filter(|run| run.len > 0)
+            .collect();
+        joined
+    }
+
+    /// The items at `indices`, in order, as a standalone index.
+    ///
+    /// Items of a concatenated index keep their part's `ds_path`.
+    pub(crate) fn select(
+        &self,
+        indices: &[usize],
+    ) -> DatasetIndex {
+        let mut selected = DatasetIndex::new(
+            self.ds_path.clone(),
+            indices.iter().map(|&i| self.items.get(i)).collect(),
+        );
+        selected.ordering = self.ordering;
+        selected.extensions = self.extensions.clone();
+        if self.parts.is_empty() {
+            return selected;
+        }
+        for &i in indices {
+            let root = self.item_root(i);
+            match selected.parts.last_mut() {
+                Some(run) if run.ds_path == root => run.len += 1,
+                _ => selected.parts.push(IndexPart {
+                    ds_path: root.to_path_buf(),
+                    len: 1,
+                }),
+            }
+        }
+        selected
+    }
+
+    /// The runs of items under each dataset path; a single run, for an index
+    /// which is not concatenated.
+    pub fn runs(&self) -> Vec<IndexPart> {
+        if self.parts.is_empty() {
+            vec![IndexPart {
+                ds_path: self.ds_path.clone(),
+                len: self.len(),
+            }]
+        } else {
+            self.parts.clone()
+        }
+    }
+
+    /// The dataset path an item is stored under; `ds_path`, unless concatenated.
+    fn item_root(
+        &self,
+        index: usize,
+    ) -> &Path {
+        let mut end = 0;
+        for part in &self.parts {
+            end += part.len;
+            if index < end {
+                return &part.ds_path;
+            }
+        }
+        &self.ds_path
+    }
+
+    /// A stable fingerprint of the ordered `(class, filename)` item list.
+    ///
+    /// The fingerprint ignores where the dataset is stored, so two runs
+    /// on different machines with identical splits and orderings agree.
+    ///
+    /// # Returns
+    ///
+    /// A hex-encoded BLAKE3 hash.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for item in self.items() {
+            let name = item.path.file_name().unwrap_or_default();
+            hasher.update(item.class.to_string().as_bytes());
+            hasher.update(b"/");
+            hasher.update(name.as_encoded_bytes());
+            hasher.update(b"\n");
+        }
+        hasher.finalize().to_hex().to_string()
     }
 
     pub fn get(
         &self,
         index: usize,
     ) -> Option<DatasetItem> {
-        self.items.get(index).cloned()
+        (index < self.len()).then(|| self.items.get(index))
+    }
+
+    /// The item at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of range.
+    pub fn item(
+        &self,
+        index: usize,
+    ) -> DatasetItem {
+        self.items.get(index)
+    }
+
+    pub(crate) fn item_store(&self) -> &ItemStore {
+        &self.items
+    }
+
+    /// Iterate over the items, in index order.
+    ///
+    /// Items are stored packed, so each is decoded as it is yielded; prefer
+    /// `index_to_class()` when only the class is needed.
+    pub fn items(&self) -> impl ExactSizeIterator<Item = DatasetItem> + '_ {
+        self.items.iter()
+    }
+
+    /// Remove the item at `index`, shifting the items after it down by one.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of range.
+    pub fn remove(
+        &mut self,
+        index: usize,
+    ) -> DatasetItem {
+        let mut end = 0;
+        for part in &mut self.parts {
+            end += part.len;
+            if index < end {
+                part.len -= 1;
+                break;
+            }
+        }
+        self.parts.retain(|part| part.len > 0);
+        let item = self.items.remove(index);
+        self.clear_path_index();
+        item
+    }
+
+    /// Replace all the items, keeping the part table of a concatenated index.
+    pub(crate) fn replace_items<I>(
+        &mut self,
+        items: I,
+    ) where
+        I: IntoIterator<Item = DatasetItem>,
+    {
+        self.items = items.into_iter().collect();
+        self.clear_path_index();
     }
 
     /// Get the size of the dataset.
@@ -304,7 +972,101 @@ impl DatasetIndex {
         &self,
         index: usize,
     ) -> ObjectClass {
-        self.items[index].class
+        self.items.class(index)
+    }
+
+    /// Count the items of each class.
+    pub fn class_distribution(&self) -> ClassDistribution {
+        ClassDistribution::from_classes((0..self.len()).map(|i| self.index_to_class(i)))
+    }
+
+    /// Iterate over the item indices of each class, in class order.
+    ///
+    /// Every class is yielded, with an empty iterator if it has no items;
+    /// this does not assume the items are class-contiguous.
+    pub fn iter_by_class(
+        &self
+    ) -> impl Iterator<Item = (ObjectClass, impl Iterator<Item = usize>)> {
+        ObjectClass::iter().map(move |oc| {
+            let indices = (0..self.len()).filter(move |&i| self.index_to_class(i) == oc);
+            (oc, indices)
+        })
+    }
+
+    /// The item indices of each class, built on first use.
+    fn class_index(&self) -> &ClassIndices {
+        self.class_index.get_or_init(|| {
+            let mut indices: [Vec<usize>; ObjectClass::COUNT] = Default::default();
+            for index in 0..self.len() {
+                indices[self.index_to_class(index).ordinal() as usize].push(index);
+            }
+            ClassIndices { indices }
+        })
+    }
+
+    /// Collect the item indices of each class; see `ClassIndices`.
+    pub fn class_indices(&self) -> ClassIndices {
+        self.class_index().clone()
+    }
+
+    /// The indices of the items of a class, in index order.
+    ///
+    /// Built on first use, like `index_of_path()`; so samplers can draw
+    /// "the k-th airplane" in constant time, without assuming the items are
+    /// class-contiguous or 9000 per class.
+    pub fn items_of_class(
+        &self,
+        class: ObjectClass,
+    ) -> &[usize] {
+        self.class_index().items_of_class(class)
+    }
+
+    /// The index of the `i`-th item of a class, in index order.
+    ///
+    /// # Parameters
+    ///
+    /// - `class`: The class.
+    /// - `i`: The position among the items of `class`.
+    ///
+    /// # Returns
+    ///
+    /// The item's index, or `None` if the class has `i` or fewer items.
+    pub fn index_of(
+        &self,
+        class: ObjectClass,
+        i: usize,
+    ) -> Option<usize> {
+        self.class_index().index_of(class, i)
+    }
+
+    /// Call `f` with the item indices of each class, on parallel threads.
+    ///
+    /// # Parameters
+    ///
+    /// - `parallelism`: The number of threads; `0` means "all available cores".
+    /// - `f`: Called once per class, with that class's indices in order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is the first error returned by `f`, if any.
+    pub fn for_each_class<F>(
+        &self,
+        parallelism: usize,
+        f: F,
+    ) -> Result<()>
+    where
+        F: Fn(ObjectClass, &[usize]) -> Result<()> + Sync,
+    {
+        let groups: Vec<(ObjectClass, &[usize])> = ObjectClass::iter()
+            .map(|oc| (oc, self.items_of_class(oc)))
+            .collect();
+        par_fold(
+            &groups,
+            parallelism,
+            || (),
+            |_, (oc, indices)| f(*oc, indices),
+            |_, _| (),
+        )
     }
 
     /// Convert a slice of indices to a vector of object classes.
@@ -315,7 +1077,7 @@ impl DatasetIndex {
         indices.iter().map(|&i| self.index_to_class(i)).collect()
     }
 
-    /// Convert an item index to an image path.
+    /// Convert an item index to an image path; the same as `abs_path()`.
     ///
     /// # Parameters
     ///
@@ -328,8 +1090,34 @@ impl DatasetIndex {
         &self,
         index: usize,
     ) -> PathBuf {
-        let item = &self.items[index];
-        self.ds_path.join(item.class.to_string()).join(&item.path)
+        self.abs_path(index)
+    }
+
+    /// The path of an item, under `ds_path`: `ds_path/{class}/{name}`;
+    /// or under its part's `ds_path`, for a concatenated index.
+    pub fn abs_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        let item = self.items.get(index);
+        self.item_root(index)
+            .join(item.class.to_string())
+            .join(&item.path)
+    }
+
+    /// The path of an item relative to `ds_path`: `{class}/{name}`.
+    ///
+    /// Relative paths are the same on every machine the split is copied to;
+    /// items stored outside `ds_path` keep their absolute path.
+    pub fn rel_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        let path = self.abs_path(index);
+        match path.strip_prefix(&self.ds_path) {
+            Ok(rel) => rel.to_path_buf(),
+            Err(_) => path,
+        }
     }
 
     /// Convert a slice of indices to a vector of image paths.
@@ -365,6 +1153,74 @@ impl DatasetIndex {
         let paths = self.indices_to_paths(indices);
         load_bhwc_rgbimagebatch(&paths)
     }
+
+    /// Load an `RgbImageBatch` for a batch of indexes, using the given loader.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loaded `RgbImageBatch` on success, or an error on failure.
+    pub fn load_rgbimagebatch_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        let paths = self.indices_to_paths(indices);
+        loader.load_rgbimagebatch(&paths)
+    }
+
+    /// Load an `RgbImageBatch` with the given loader, reporting failed samples.
+    ///
+    /// Use `LoadedBatch::dropped_from(indices)` to recover the dropped dataset indices.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loaded batch and failure report.
+    pub fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        let paths = self.indices_to_paths(indices);
+        loader.load_rgbimagebatch_report(&paths)
+    }
+}
+
+/// The item indices of each class of a `DatasetIndex`; see `DatasetIndex::class_indices()`.
+///
+/// An owned snapshot of `DatasetIndex::items_of_class()`, which outlives
+/// the index; rebuild it if the index's items change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassIndices {
+    indices: [Vec<usize>; ObjectClass::COUNT],
+}
+
+impl ClassIndices {
+    /// The indices of the items of a class, in index order.
+    pub fn items_of_class(
+        &self,
+        class: ObjectClass,
+    ) -> &[usize] {
+        &self.indices[class.ordinal() as usize]
+    }
+
+    /// The index of the `i`-th item of a class; `None` if out of range.
+    pub fn index_of(
+        &self,
+        class: ObjectClass,
+        i: usize,
+    ) -> Option<usize> {
+        self.items_of_class(class).get(i).copied()
+    }
 }
 
 /// The main index for the CINIC-10 dataset.
@@ -372,15 +1228,57 @@ impl DatasetIndex {
 pub struct Cinic10Index {
     pub root: PathBuf,
 
+    /// The contributor records; call `clear_record_index()` after modifying them directly.
     pub imagenet_contrib: Vec<IndexRecord>,
-    pub synset_map: HashMap<String, SynsetNode>,
+    pub synset_map: HashMap<WnId, SynsetNode>,
 
     pub train: DatasetIndex,
     pub test: DatasetIndex,
     pub valid: DatasetIndex,
+
+    /// The contributor records of each `(synset, image_num)`; see `record_of()`.
+    record_index: OnceLock<HashMap<(WnId, usize), Vec<usize>>>,
 }
 
 impl Cinic10Index {
+    /// Assemble an index from its parts.
+    pub fn new(
+        root: PathBuf,
+        imagenet_contrib: Vec<IndexRecord>,
+        synset_map: HashMap<WnId, SynsetNode>,
+        train: DatasetIndex,
+        test: DatasetIndex,
+        valid: DatasetIndex,
+    ) -> Self {
+        Self {
+            root,
+            imagenet_contrib,
+            synset_map,
+            train,
+            test,
+            valid,
+            record_index: OnceLock::new(),
+        }
+    }
+
+    /// The positions in `imagenet_contrib` of each `(synset, image_num)`, built on first use.
+    pub(crate) fn record_index(&self) -> &HashMap<(WnId, usize), Vec<usize>> {
+        self.record_index.get_or_init(|| {
+            let mut map: HashMap<(WnId, usize), Vec<usize>> = HashMap::new();
+            for (i, record) in self.imagenet_contrib.iter().enumerate() {
+                map.entry((record.synset, record.image_num))
+                    .or_default()
+                    .push(i);
+            }
+            map
+        })
+    }
+
+    /// Drop the map built by `record_of()`; needed after modifying `imagenet_contrib` directly.
+    pub fn clear_record_index(&mut self) {
+        self.record_index = OnceLock::new();
+    }
+
     /// Create a new `Cinic10Index` from the given directory.
     ///
     /// # Parameters
@@ -389,33 +1287,112 @@ impl Cinic10Index {
     ///
     /// # Returns
     ///
-    /// A `Result` containing the `Cinic10Index` on success, or an error on failure.
+    /// A `Result` containing the `Cinic10Index` on success; or
+    /// `Cinic10Error::MissingRoot` or `Cinic10Error::NotADirectory` for a bad
+    /// `root`, or another error on failure. See `Cinic10Index::builder()` for
+    /// partial or lenient loading.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(root = %root.as_ref().display()))
+    )]
     pub fn new_from_dir<P>(root: P) -> Result<Cinic10Index>
     where
         P: AsRef<Path>,
     {
-        let root = root.as_ref();
+        Cinic10Index::builder().root(root).build()
+    }
+}
 
-        if !root.exists() {
-            panic!("CINIC-10 dataset not found at {}", root.display());
+impl Cinic10Index {
+    /// Move the index to a new root, without re-scanning; see `DatasetIndex::rebase()`.
+    ///
+    /// Splits under the old root are moved to the same place under `new_root`.
+    pub fn rebase<P>(
+        &mut self,
+        new_root: P,
+    ) where
+        P: AsRef<Path>,
+    {
+        let new_root = new_root.as_ref();
+        for split in [&mut self.train, &mut self.test, &mut self.valid] {
+            if let Ok(rel) = split.ds_path.strip_prefix(&self.root) {
+                let ds_path = new_root.join(rel);
+                split.rebase(ds_path);
+            }
         }
-        if !root.is_dir() {
-            panic!(
-                "CINIC-10 dataset path is not a directory: {}",
-                root.display()
+        self.root = new_root.to_path_buf();
+    }
+
+    /// The train and valid splits as one index; train items first.
+    ///
+    /// The standard CINIC-10 protocol trains on both; valid item `i` is
+    /// item `train.len() + i`. See `DatasetIndex::concat()`.
+    pub fn train_plus_valid(&self) -> DatasetIndex {
+        DatasetIndex::concat(&[&self.train, &self.valid])
+    }
+
+    /// Create a new `Cinic10Index` from the files of a `DataSource`.
+    ///
+    /// Load its batches with a loader reading the same source; see
+    /// `LoaderConfig::source`.
+    ///
+    /// # Parameters
+    ///
+    /// - `source`: The source of the dataset files.
+    /// - `root`: The root of the dataset, in the source's paths.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index` on success, or an error on failure.
+    pub fn new_from_source<P>(
+        source: &dyn DataSource,
+        root: P,
+    ) -> Result<Cinic10Index>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let split = |data_set: DataSet| {
+            DatasetIndex::load_index_from_source(
+                source,
+                &root.join(data_set.to_string()),
+                DEFAULT_IMAGE_EXTENSIONS,
+            )
+        };
+        Ok(Cinic10Index::new(
+            root.to_path_buf(),
+            parse_contrib_index(source.read(&root.join(CONTRIB_FILE))?.as_slice())?,
+            parse_synset_map(source.read(&root.join(SYNSET_FILE))?.as_slice())?,
+            split(DataSet::Train)?,
+            split(DataSet::Test)?,
+            split(DataSet::Valid)?,
+        ))
+    }
+}
+
+impl Cinic10Index {
+    /// Create a new `Cinic10Index` with the current default path.
+    ///
+    /// With the `download` feature, and `CINIC10_AUTO_DOWNLOAD` set, a missing
+    /// dataset is downloaded and extracted to the default path instead.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index`; or `Cinic10Error::NotConfigured`
+    /// if no default path is set, or another error if it does not hold a
+    /// valid CINIC-10 dataset.
+    pub fn try_default() -> Result<Self> {
+        let root = try_default_data_path()?;
+
+        #[cfg(feature = "download")]
+        if crate::download::auto_download_enabled() {
+            return Cinic10Index::new_from_dir_or_download(
+                root,
+                &crate::download::DownloadOptions::default(),
             );
         }
 
-        let index = parse_contrib_index(File::open(root.join(CONTRIB_FILE))?)?;
-
-        Ok(Cinic10Index {
-            root: root.to_path_buf(),
-            imagenet_contrib: index,
-            synset_map: parse_synset_map(File::open(root.join(SYNSET_FILE))?)?,
-            train: DatasetIndex::load_index_from_dir(&root.join(DataSet::Train.to_string()))?,
-            test: DatasetIndex::load_index_from_dir(&root.join(DataSet::Test.to_string()))?,
-            valid: DatasetIndex::load_index_from_dir(&root.join(DataSet::Valid.to_string()))?,
-        })
+        Cinic10Index::new_from_dir(root)
     }
 }
 
@@ -428,16 +1405,19 @@ impl Default for Cinic10Index {
     ///
     /// # Panics
     ///
-    /// Panics if the default path does not exist, is not a directory,
-    /// or does not contain a valid CINIC-10 dataset.
+    /// Panics if the default path is not set, does not exist, is not a
+    /// directory, or does not contain a valid CINIC-10 dataset; see
+    /// `Cinic10Index::try_default()` for the fallible version.
     fn default() -> Self {
-        Cinic10Index::new_from_dir(default_data_path_or_panic()).unwrap()
+        Cinic10Index::try_default().unwrap_or_else(|err| panic!("{err:#}"))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::fixtures;
+    use anyhow::Result;
 
     use csv::StringRecord;
     use indoc::{formatdoc, indoc};
@@ -454,6 +1434,22 @@ mod tests {
         assert!(DataSet::from_str("").is_err());
     }
 
+    #[test]
+    fn test_enum_constants() {
+        assert_eq!(DataSet::ALL.to_vec(), DataSet::iter().collect::<Vec<_>>());
+        assert_eq!(
+            ObjectClass::ALL.to_vec(),
+            ObjectClass::iter().collect::<Vec<_>>()
+        );
+        for (ds, name) in DataSet::ALL.iter().zip(DataSet::NAMES) {
+            assert_eq!(ds.to_string(), name);
+        }
+        for (i, (oc, name)) in ObjectClass::ALL.iter().zip(ObjectClass::NAMES).enumerate() {
+            assert_eq!(oc.to_string(), name);
+            assert_eq!(oc.ordinal() as usize, i);
+        }
+    }
+
     #[test]
     fn parse_class() {
         assert_eq!(
@@ -480,7 +1476,7 @@ mod tests {
     #[test]
     fn parse_index_record() {
         let expected = IndexRecord {
-            synset: "n02123045".to_string(),
+            synset: "n02123045".parse().unwrap(),
             image_num: 1,
             data_set: DataSet::Train,
             class: ObjectClass::Airplane,
@@ -506,18 +1502,50 @@ mod tests {
         assert_eq!(records.len(), 2);
 
         let record = records.first().unwrap();
-        assert_eq!(record.synset, "n02704645");
+        assert_eq!(record.synset.to_string(), "n02704645");
         assert_eq!(record.image_num, 14894);
         assert_eq!(record.data_set, DataSet::Train);
         assert_eq!(record.class, ObjectClass::Airplane);
 
         let record = records.get(1).unwrap();
-        assert_eq!(record.synset, "n02690373");
+        assert_eq!(record.synset.to_string(), "n02690373");
         assert_eq!(record.image_num, 6332);
         assert_eq!(record.data_set, DataSet::Valid);
         assert_eq!(record.class, ObjectClass::Frog);
     }
 
+    #[test]
+    fn test_parse_errors() {
+        let source =
+            "synset,image_num,cinic_set,class\nn02690373,6332,valid,frog\nn1,x,train,cat\n";
+        assert!(matches!(
+            parse_contrib_index(source.as_bytes()),
+            Err(Cinic10Error::BadCsvRow { row: 3, .. })
+        ));
+
+        for (source, line) in [
+            ("----n1: a\n", 1),
+            ("cat\n--n1 a\n", 2),
+            ("cat\nkitten\n", 2),
+        ] {
+            match parse_synset_map(source.as_bytes()) {
+                Err(Cinic10Error::MalformedSynsetFile { line: l, .. }) => assert_eq!(l, line),
+                other => panic!("expected a malformed synset error, got {other:?}"),
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            Cinic10Index::new_from_dir(dir.path().join("nope")),
+            Err(Cinic10Error::MissingRoot(_))
+        ));
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        assert!(matches!(
+            Cinic10Index::new_from_dir(dir.path().join("file")),
+            Err(Cinic10Error::NotADirectory(_))
+        ));
+    }
+
     #[test]
     fn test_parse_synsets_from_reader() -> Result<()> {
         let source = formatdoc! {"
@@ -536,9 +1564,9 @@ mod tests {
         let synset_map = parse_synset_map(rdr)?;
 
         assert_eq!(
-            synset_map.get("n123").unwrap(),
+            synset_map.get(&"n123".parse()?).unwrap(),
             &SynsetNode {
-                synset_id: "n123".to_string(),
+                synset_id: "n123".parse()?,
                 object_class: ObjectClass::Dog,
                 synset_base_id: None,
                 aliases: vec!["good boy".to_string()],
@@ -546,19 +1574,19 @@ mod tests {
         );
 
         assert_eq!(
-            synset_map.get("n1230").unwrap(),
+            synset_map.get(&"n1230".parse()?).unwrap(),
             &SynsetNode {
-                synset_id: "n1230".to_string(),
+                synset_id: "n1230".parse()?,
                 object_class: ObjectClass::Dog,
-                synset_base_id: Some("n123".to_string()),
+                synset_base_id: Some("n123".parse()?),
                 aliases: vec!["bestest boy".to_string()],
             }
         );
 
         assert_eq!(
-            synset_map.get("n9").unwrap(),
+            synset_map.get(&"n9".parse()?).unwrap(),
             &SynsetNode {
-                synset_id: "n9".to_string(),
+                synset_id: "n9".parse()?,
                 object_class: ObjectClass::Dog,
                 synset_base_id: None,
                 aliases: vec!["cujo".to_string()],
@@ -566,9 +1594,9 @@ mod tests {
         );
 
         assert_eq!(
-            synset_map.get("n999").unwrap(),
+            synset_map.get(&"n999".parse()?).unwrap(),
             &SynsetNode {
-                synset_id: "n999".to_string(),
+                synset_id: "n999".parse()?,
                 object_class: ObjectClass::Cat,
                 synset_base_id: None,
                 aliases: vec!["chonk".to_string()],
@@ -578,6 +1606,331 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_list_images_non_utf8() -> Result<()> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::tempdir()?;
+        let odd = dir.path().join(OsStr::from_bytes(b"\xffcat.png"));
+        fs::write(&odd, b"")?;
+        fs::write(dir.path().join("a.png"), b"")?;
+
+        let files = list_pngs_sorted(dir.path())?;
+        assert_eq!(files, vec![dir.path().join("a.png"), odd]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let touch = |class: ObjectClass, name: &str| -> Result<PathBuf> {
+            let class_dir = dir.path().join(class.to_string());
+            fs::create_dir_all(&class_dir)?;
+            let path = class_dir.join(name);
+            fs::write(&path, b"")?;
+            Ok(path)
+        };
+
+        let cat_a = touch(ObjectClass::Cat, "a.png")?;
+        let dog_b = touch(ObjectClass::Dog, "b.png")?;
+        touch(ObjectClass::Dog, "notes.txt")?;
+
+        let mut index = DatasetIndex::new(dir.path().to_path_buf(), Vec::new());
+
+        let delta = index.refresh()?;
+        assert_eq!(delta.added, vec![cat_a.clone(), dog_b.clone()]);
+        assert!(delta.removed.is_empty());
+        assert_eq!(
+            index.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Dog]
+        );
+
+        let cat_0 = touch(ObjectClass::Cat, "0.png")?;
+        fs::remove_file(&dog_b)?;
+
+        // Only re-scanning cats doesn't notice the removed dog.
+        let delta = index.refresh_classes(&[ObjectClass::Cat])?;
+        assert_eq!(delta.added, vec![cat_0.clone()]);
+        assert!(delta.removed.is_empty());
+        assert_eq!(index.len(), 3);
+
+        let delta = index.refresh()?;
+        assert!(delta.added.is_empty());
+        assert_eq!(delta.removed, vec![dog_b]);
+        assert_eq!(index.indices_to_paths(&[0, 1]), vec![cat_0, cat_a.clone()]);
+        assert_eq!(index.item(0).path, PathBuf::from("0.png"));
+        assert_eq!(index.rel_path(1), PathBuf::from("cat/a.png"));
+        assert_eq!(index.abs_path(1), cat_a);
+
+        assert!(index.refresh()?.is_empty());
+        assert_eq!(index.class_distribution().count(ObjectClass::Cat), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebase() {
+        let mut cinic = fixtures::cinic(
+            "/nfs/cinic",
+            DatasetIndex::new(
+                PathBuf::from("/nfs/cinic/train"),
+                vec![
+                    DatasetItem {
+                        class: ObjectClass::Cat,
+                        path: PathBuf::from("/nfs/cinic/train/cat/a.png"),
+                    },
+                    DatasetItem {
+                        class: ObjectClass::Dog,
+                        path: PathBuf::from("b.png"),
+                    },
+                    DatasetItem {
+                        class: ObjectClass::Dog,
+                        path: PathBuf::from("/elsewhere/dog/c.png"),
+                    },
+                ],
+            ),
+            DatasetIndex::new(PathBuf::from("/nfs/cinic/test"), Vec::new()),
+            DatasetIndex::new(PathBuf::from("/other/valid"), Vec::new()),
+        );
+        let fingerprint = cinic.train.fingerprint();
+
+        cinic.rebase("/scratch/cinic");
+        assert_eq!(cinic.root, PathBuf::from("/scratch/cinic"));
+        assert_eq!(
+            cinic.train.indices_to_paths(&[0, 1, 2]),
+            vec![
+                PathBuf::from("/scratch/cinic/train/cat/a.png"),
+                PathBuf::from("/scratch/cinic/train/dog/b.png"),
+                PathBuf::from("/elsewhere/dog/c.png"),
+            ]
+        );
+        assert_eq!(cinic.test.ds_path, PathBuf::from("/scratch/cinic/test"));
+        assert_eq!(cinic.valid.ds_path, PathBuf::from("/other/valid"));
+        assert_eq!(cinic.train.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_index_of_path() {
+        let mut index = DatasetIndex::new(
+            PathBuf::from("/cinic/train"),
+            vec![
+                DatasetItem {
+                    class: ObjectClass::Cat,
+                    path: PathBuf::from("/cinic/train/cat/cifar10-train-1.png"),
+                },
+                DatasetItem {
+                    class: ObjectClass::Dog,
+                    path: PathBuf::from("n02085620_7.png"),
+                },
+                DatasetItem {
+                    class: ObjectClass::Ship,
+                    path: PathBuf::from("n02085620_7.png"),
+                },
+            ],
+        );
+
+        assert_eq!(
+            index.index_of_path(Path::new("cifar10-train-1.png")),
+            Some(0)
+        );
+        assert_eq!(
+            index.index_of_path(Path::new("/cinic/train/cat/cifar10-train-1.png")),
+            Some(0)
+        );
+        assert_eq!(index.index_of_path(Path::new("n02085620_7.png")), Some(1));
+        assert_eq!(
+            index.index_of_path(Path::new("ship/n02085620_7.png")),
+            Some(2)
+        );
+        assert_eq!(index.index_of_path(Path::new("cat/n02085620_7.png")), None);
+        assert_eq!(index.index_of_path(Path::new("missing.png")), None);
+
+        index.remove(0);
+        assert_eq!(
+            index.index_of_path(Path::new("ship/n02085620_7.png")),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_concat() {
+        let split = |name: &str, class: ObjectClass| {
+            fixtures::split(
+                Path::new("/cinic").join(name),
+                [(class, format!("{name}.png"))],
+            )
+        };
+        let cinic = fixtures::cinic(
+            "/cinic",
+            split("train", ObjectClass::Cat),
+            split("test", ObjectClass::Dog),
+            split("valid", ObjectClass::Ship),
+        );
+
+        let joined = cinic.train_plus_valid();
+        assert_eq!(joined.ds_path, PathBuf::from("/cinic"));
+        assert_eq!(
+            joined.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        assert_eq!(
+            joined.indices_to_paths(&[0, 1]),
+            vec![cinic.train.index_to_path(0), cinic.valid.index_to_path(0)]
+        );
+
+        assert_eq!(joined.rel_path(1), PathBuf::from("valid/ship/valid.png"));
+
+        let joined = DatasetIndex::concat(&[&cinic.test]);
+        assert_eq!(joined.ds_path, cinic.test.ds_path);
+        assert_eq!(joined.index_to_path(0), cinic.test.index_to_path(0));
+        assert!(DatasetIndex::concat(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_concat_relative_roots() -> Result<()> {
+        let train = fixtures::split("data/train", [(ObjectClass::Cat, "a.png")]);
+        let valid = fixtures::split("valid", [(ObjectClass::Dog, "b.png")]);
+
+        let joined = DatasetIndex::concat(&[&train, &valid]);
+        assert_eq!(joined.ds_path, PathBuf::new());
+        assert_eq!(
+            joined.indices_to_paths(&[0, 1]),
+            vec![
+                PathBuf::from("data/train/cat/a.png"),
+                PathBuf::from("valid/dog/b.png")
+            ]
+        );
+        assert!(joined.clone().refresh().is_err());
+
+        let loaded: DatasetIndex = serde_json::from_str(&serde_json::to_string(&joined)?)?;
+        assert_eq!(loaded.runs(), joined.runs());
+        assert_eq!(loaded.index_to_path(1), PathBuf::from("valid/dog/b.png"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat_non_sibling_roots() {
+        let train = fixtures::split(
+            "/a/train",
+            [(ObjectClass::Dog, "b.png"), (ObjectClass::Cat, "a.png")],
+        );
+        let valid = fixtures::split("/b/deep/valid", [(ObjectClass::Cat, "c.png")]);
+
+        let mut joined = DatasetIndex::concat(&[&train, &valid]);
+        assert_eq!(joined.ds_path, PathBuf::from("/"));
+        assert_eq!(joined.rel_path(2), PathBuf::from("b/deep/valid/cat/c.png"));
+
+        joined.sort_items(FileOrdering::Lexical);
+        assert_eq!(
+            joined.indices_to_paths(&[0, 1, 2]),
+            vec![
+                PathBuf::from("/a/train/cat/a.png"),
+                PathBuf::from("/a/train/dog/b.png"),
+                PathBuf::from("/b/deep/valid/cat/c.png"),
+            ]
+        );
+
+        let selected = joined.select(&[2, 0]);
+        assert_eq!(
+            selected.indices_to_paths(&[0, 1]),
+            vec![
+                PathBuf::from("/b/deep/valid/cat/c.png"),
+                PathBuf::from("/a/train/cat/a.png"),
+            ]
+        );
+
+        joined.rebase("/mnt");
+        assert_eq!(
+            joined.index_to_path(2),
+            PathBuf::from("/mnt/b/deep/valid/cat/c.png")
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = fixtures::split(
+            "/a",
+            [(ObjectClass::Cat, "x.png"), (ObjectClass::Dog, "y.png")],
+        );
+        let b = fixtures::split(
+            "/b",
+            [(ObjectClass::Cat, "x.png"), (ObjectClass::Dog, "y.png")],
+        );
+        let reordered = fixtures::split(
+            "/a",
+            [(ObjectClass::Dog, "y.png"), (ObjectClass::Cat, "x.png")],
+        );
+        let relabeled = fixtures::split(
+            "/a",
+            [(ObjectClass::Dog, "x.png"), (ObjectClass::Dog, "y.png")],
+        );
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().len(), 64);
+        assert_ne!(a.fingerprint(), reordered.fingerprint());
+        assert_ne!(a.fingerprint(), relabeled.fingerprint());
+    }
+
+    fn interleaved() -> DatasetIndex {
+        fixtures::split_of_classes(
+            "/a",
+            &[ObjectClass::Dog, ObjectClass::Cat, ObjectClass::Dog],
+        )
+    }
+
+    #[test]
+    fn test_iter_by_class() {
+        let groups: Vec<(ObjectClass, Vec<usize>)> = interleaved()
+            .iter_by_class()
+            .map(|(oc, indices)| (oc, indices.collect()))
+            .filter(|(_, indices): &(_, Vec<usize>)| !indices.is_empty())
+            .collect();
+        assert_eq!(
+            groups,
+            vec![(ObjectClass::Cat, vec![1]), (ObjectClass::Dog, vec![0, 2])]
+        );
+    }
+
+    #[test]
+    fn test_for_each_class() -> Result<()> {
+        let seen = std::sync::Mutex::new(Vec::new());
+        interleaved().for_each_class(4, |oc, indices| {
+            seen.lock().unwrap().push((oc, indices.len()));
+            Ok(())
+        })?;
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), ObjectClass::COUNT);
+        assert!(seen.contains(&(ObjectClass::Dog, 2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_class_indices() {
+        let index = interleaved();
+        let by_class = index.class_indices();
+        assert_eq!(by_class.items_of_class(ObjectClass::Dog), &[0, 2]);
+        assert!(by_class.items_of_class(ObjectClass::Ship).is_empty());
+        assert_eq!(by_class.index_of(ObjectClass::Dog, 1), Some(2));
+        assert_eq!(by_class.index_of(ObjectClass::Cat, 1), None);
+    }
+
+    #[test]
+    fn test_items_of_class() {
+        let mut index = interleaved();
+        assert_eq!(index.items_of_class(ObjectClass::Dog), &[0, 2]);
+        assert!(index.items_of_class(ObjectClass::Ship).is_empty());
+        assert_eq!(index.index_of(ObjectClass::Dog, 1), Some(2));
+        assert_eq!(index.index_of(ObjectClass::Ship, 0), None);
+
+        index.remove(0);
+        assert_eq!(index.items_of_class(ObjectClass::Dog), &[1]);
+    }
+
     #[test]
     fn test_load_test_batch() -> Result<()> {
         let cinic: Cinic10Index = Default::default();
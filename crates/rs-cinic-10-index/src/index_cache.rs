@@ -0,0 +1,202 @@
+use crate::config::CINIC10_CACHE_DIR_ENV_VAR;
+use crate::error::{Result, bail};
+use crate::index::{DEFAULT_IMAGE_EXTENSIONS, DatasetIndex, DatasetItem, ObjectClass};
+use anyhow::Context;
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The version of the index cache format; bump it when the format changes.
+const INDEX_CACHE_VERSION: u32 = 1;
+
+/// A saved `DatasetIndex`, with the directory state it was scanned from.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexCacheFile {
+    version: u32,
+    ds_path: PathBuf,
+
+    /// The modification time of each class directory, in nanoseconds; in `ObjectClass` order.
+    mtimes: Vec<Option<u128>>,
+
+    /// The file names of each class, in `ObjectClass` order.
+    names: Vec<Vec<String>>,
+}
+
+/// The modification times of the class directories of `ds_path`.
+///
+/// Adding, removing, or renaming a file updates its directory's mtime.
+fn class_dir_mtimes(ds_path: &Path) -> Vec<Option<u128>> {
+    ObjectClass::ALL
+        .iter()
+        .map(|oc| {
+            let modified = fs::metadata(ds_path.join(oc.to_string()))
+                .ok()?
+                .modified()
+                .ok()?;
+            Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+        })
+        .collect()
+}
+
+/// Where `DatasetIndex::load_index_cached()` keeps the index of a split directory.
+///
+/// Under `CINIC10_CACHE_DIR` when it is set; otherwise next to the split
+/// directory. The file is keyed by the split's path.
+pub fn index_cache_path(ds_path: &Path) -> PathBuf {
+    let name = ds_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let key = blake3::hash(ds_path.as_os_str().as_encoded_bytes()).to_hex();
+    let dir = match env::var(CINIC10_CACHE_DIR_ENV_VAR) {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => ds_path.parent().unwrap_or(ds_path).to_path_buf(),
+    };
+    dir.join(format!("index-{name}-{}.json", &key[..16]))
+}
+
+impl DatasetIndex {
+    /// Save the index, with the modification times of its class directories.
+    ///
+    /// Items must be stored as `ds_path/{class}/{name}`, as scanned.
+    pub fn save<P>(
+        &self,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut names = vec![Vec::new(); ObjectClass::ALL.len()];
+        for (index, item) in self.items().enumerate() {
+            if self.index_to_path(index).parent()
+                != Some(&self.ds_path.join(item.class.to_string()))
+            {
+                bail!(
+                    "Item is outside its class directory: {}",
+                    item.path.display()
+                );
+            }
+            let Some(name) = item.path.file_name().and_then(|n| n.to_str()) else {
+                bail!("Path is not valid UTF-8: {}", item.path.display());
+            };
+            names[item.class.ordinal() as usize].push(name.to_string());
+        }
+        let file = IndexCacheFile {
+            version: INDEX_CACHE_VERSION,
+            ds_path: self.ds_path.clone(),
+            mtimes: class_dir_mtimes(&self.ds_path),
+            names,
+        };
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(&file)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Load an index written by `save()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The saved index.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; or an error if any class directory
+    /// has been modified since it was saved.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file: IndexCacheFile = serde_json::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        )?;
+        if file.version != INDEX_CACHE_VERSION {
+            bail!("Unsupported index cache version {}", file.version);
+        }
+        if file.mtimes != class_dir_mtimes(&file.ds_path) {
+            bail!("Stale index cache: {}", path.display());
+        }
+
+        let items = ObjectClass::ALL
+            .iter()
+            .zip(file.names)
+            .flat_map(|(&class, names)| {
+                names.into_iter().map(move |name| DatasetItem {
+                    class,
+                    path: PathBuf::from(name),
+                })
+            })
+            .collect();
+        Ok(Self::new(file.ds_path, items))
+    }
+
+    /// Scan the class directories of `ds_path`, reusing a saved index if it is current.
+    ///
+    /// See `index_cache_path()`; failing to write the cache is not an error.
+    pub(crate) fn load_index_cached(ds_path: &Path) -> Result<Self> {
+        let path = index_cache_path(ds_path);
+        if let Ok(index) = Self::load(&path)
+            && index.ds_path == ds_path
+        {
+            return Ok(index);
+        }
+
+        let index = Self::scan_dir(ds_path, DEFAULT_IMAGE_EXTENSIONS)?;
+        if let Err(_err) = index.save(&path) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(path = %path.display(), error = %_err, "failed to cache index");
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_index_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ds_path = dir.path().join("train");
+        for class in ObjectClass::ALL {
+            fs::create_dir_all(ds_path.join(class.to_string()))?;
+        }
+        for (class, names) in [("cat", ["b.png", "a.png"]), ("ship", ["c.png", "d.png"])] {
+            for name in names {
+                fs::write(ds_path.join(class).join(name), b"")?;
+            }
+        }
+
+        let scanned = DatasetIndex::load_index_cached(&ds_path)?;
+        let cache = index_cache_path(&ds_path);
+        assert!(cache.exists());
+        let loaded = DatasetIndex::load(&cache)?;
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(loaded.fingerprint(), scanned.fingerprint());
+        assert_eq!(
+            loaded.indices_to_paths(&[0, 3]),
+            scanned.indices_to_paths(&[0, 3])
+        );
+        assert_eq!(loaded.index_to_path(0), ds_path.join("cat/a.png"));
+
+        // Coarse filesystem timestamps may hide a change made in the same tick.
+        thread::sleep(Duration::from_millis(20));
+        fs::write(ds_path.join("cat/e.png"), b"")?;
+        assert!(DatasetIndex::load(&cache).is_err());
+        assert_eq!(DatasetIndex::load_index_cached(&ds_path)?.len(), 5);
+        assert_eq!(DatasetIndex::load(&cache)?.len(), 5);
+
+        Ok(())
+    }
+}
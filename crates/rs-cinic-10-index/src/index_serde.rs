@@ -0,0 +1,167 @@
+use crate::index::{Cinic10Index, DatasetIndex, IndexRecord, SynsetNode, is_default_extensions};
+use crate::item_store::ItemStore;
+use crate::ordering::FileOrdering;
+use crate::wnid::WnId;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A split of a serialized `Cinic10Index`, with `ds_path` relative to the root.
+#[derive(Serialize)]
+struct SplitRef<'a> {
+    ds_path: &'a Path,
+    items: &'a ItemStore,
+    #[serde(skip_serializing_if = "FileOrdering::is_lexical")]
+    ordering: FileOrdering,
+    #[serde(skip_serializing_if = "is_default_extensions")]
+    extensions: &'a [String],
+}
+
+impl<'a> SplitRef<'a> {
+    fn new(
+        root: &Path,
+        split: &'a DatasetIndex,
+    ) -> Self {
+        Self {
+            ds_path: split.ds_path.strip_prefix(root).unwrap_or(&split.ds_path),
+            items: split.item_store(),
+            ordering: split.ordering(),
+            extensions: split.extensions(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Cinic10IndexRef<'a> {
+    root: &'a Path,
+    imagenet_contrib: &'a [IndexRecord],
+    synset_map: &'a HashMap<WnId, SynsetNode>,
+    train: SplitRef<'a>,
+    test: SplitRef<'a>,
+    valid: SplitRef<'a>,
+}
+
+#[derive(Deserialize)]
+struct Cinic10IndexFile {
+    root: PathBuf,
+    imagenet_contrib: Vec<IndexRecord>,
+    synset_map: HashMap<WnId, SynsetNode>,
+    train: DatasetIndex,
+    test: DatasetIndex,
+    valid: DatasetIndex,
+}
+
+/// Split paths are stored relative to `root`, so a serialized index can be
+/// moved with `Cinic10Index::rebase()` after it is loaded.
+impl Serialize for Cinic10Index {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Cinic10IndexRef {
+            root: &self.root,
+            imagenet_contrib: &self.imagenet_contrib,
+            synset_map: &self.synset_map,
+            train: SplitRef::new(&self.root, &self.train),
+            test: SplitRef::new(&self.root, &self.test),
+            valid: SplitRef::new(&self.root, &self.valid),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cinic10Index {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let file = Cinic10IndexFile::deserialize(deserializer)?;
+        let root = file.root;
+        let [train, test, valid] = [file.train, file.test, file.valid].map(|mut split| {
+            split.ds_path = root.join(&split.ds_path);
+            split
+        });
+        Ok(Cinic10Index::new(
+            root,
+            file.imagenet_contrib,
+            file.synset_map,
+            train,
+            test,
+            valid,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    fn cinic() -> Cinic10Index {
+        let split = |name: &str| {
+            fixtures::split_of_names(
+                Path::new("/cinic").join(name),
+                ObjectClass::Bird,
+                &["b.png", "a.png"],
+            )
+        };
+        fixtures::cinic(
+            "/cinic",
+            split("train"),
+            split("test"),
+            fixtures::split_of_classes("/elsewhere/valid", &[]),
+        )
+    }
+
+    #[test]
+    fn test_index_serde() -> Result<()> {
+        let cinic = cinic();
+
+        let json = serde_json::to_value(&cinic)?;
+        assert_eq!(json["train"]["ds_path"], "train");
+        assert_eq!(json["valid"]["ds_path"], "/elsewhere/valid");
+
+        let loaded: Cinic10Index = serde_json::from_value(json)?;
+        assert_eq!(loaded.root, cinic.root);
+        assert_eq!(loaded.train.ds_path, cinic.train.ds_path);
+        assert_eq!(loaded.valid.ds_path, cinic.valid.ds_path);
+        assert_eq!(loaded.test.fingerprint(), cinic.test.fingerprint());
+        assert_eq!(
+            loaded.train.indices_to_paths(&[0, 1]),
+            cinic.train.indices_to_paths(&[0, 1])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extensions_serde() -> Result<()> {
+        let mut cinic = cinic();
+        cinic.test.set_extensions([".PNG", "jpg"]);
+
+        let json = serde_json::to_value(&cinic)?;
+        assert!(json["train"].get("extensions").is_none());
+
+        let loaded: Cinic10Index = serde_json::from_value(json)?;
+        assert_eq!(loaded.test.extensions(), ["png", "jpg"]);
+        assert_eq!(loaded.train.extensions(), cinic.train.extensions());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_serde() -> Result<()> {
+        let cinic = cinic();
+
+        let split: DatasetIndex = serde_json::from_str(&serde_json::to_string(&cinic.train)?)?;
+        assert_eq!(split.ds_path, cinic.train.ds_path);
+        assert_eq!(split.index_of_path(Path::new("a.png")), Some(1));
+
+        Ok(())
+    }
+}
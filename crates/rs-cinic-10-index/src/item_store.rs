@@ -0,0 +1,322 @@
+use crate::index::{DatasetItem, ObjectClass};
+use crate::provenance::{CifarSplit, parse_item_filename};
+use crate::wnid::WnId;
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const CLASS_SHIFT: u32 = 60;
+const KIND_SHIFT: u32 = 58;
+const EXT_SHIFT: u32 = 55;
+const SYNSET_SHIFT: u32 = 28;
+const EXT_MASK: u64 = (1 << 3) - 1;
+const SYNSET_MASK: u64 = (1 << 27) - 1;
+const NUMBER_MASK: u64 = (1 << 28) - 1;
+const OTHER_MASK: u64 = (1 << KIND_SHIFT) - 1;
+
+const KIND_CIFAR_TRAIN: u64 = 0;
+const KIND_CIFAR_TEST: u64 = 1;
+const KIND_IMAGENET: u64 = 2;
+const KIND_OTHER: u64 = 3;
+
+/// The canonical file name of a packed item.
+fn format_name(
+    kind: u64,
+    synset: u64,
+    number: u64,
+    ext: &str,
+) -> String {
+    match kind {
+        KIND_CIFAR_TRAIN => format!("cifar10-{}-{number}.{ext}", CifarSplit::Train),
+        KIND_CIFAR_TEST => format!("cifar10-{}-{number}.{ext}", CifarSplit::Test),
+        _ => format!("{}_{number}.{ext}", WnId::new(synset as u32).unwrap()),
+    }
+}
+
+/// The items of a `DatasetIndex`, packed into one `u64` each.
+///
+/// An item whose path is a canonical CINIC-10 file name
+/// (`cifar10-{split}-{n}.{ext}` or `{wnid}_{n}.{ext}`), relative to its class
+/// directory, packs its class, source tag, interned extension, synset and
+/// number into the word; the class directory is implied by the class.
+/// Other items keep their file name in a side table, under an interned
+/// directory. For the standard layout this is 8 bytes per item, against
+/// roughly 50 for a `DatasetItem`.
+#[derive(Clone, Default)]
+pub(crate) struct ItemStore {
+    packed: Vec<u64>,
+
+    /// The extensions of packed names; at most 8.
+    exts: Vec<Box<str>>,
+
+    /// The directories of side-table items; `""` for bare file names.
+    dirs: Vec<PathBuf>,
+    dir_ids: HashMap<PathBuf, u32>,
+
+    /// The interned directory and file name of each side-table item.
+    others: Vec<(u32, OsString)>,
+}
+
+impl ItemStore {
+    pub(crate) fn len(&self) -> usize {
+        self.packed.len()
+    }
+
+    /// Pack a bare canonical file name; `None` if it is not one, or a field does not fit.
+    fn pack_name(
+        &mut self,
+        path: &Path,
+    ) -> Option<u64> {
+        if path.parent() != Some(Path::new("")) {
+            return None;
+        }
+        let name = path.to_str()?;
+        let parsed = parse_item_filename(name).ok()?;
+        let (_, ext) = name.rsplit_once('.')?;
+        let number = u64::try_from(parsed.number)
+            .ok()
+            .filter(|&n| n <= NUMBER_MASK)?;
+        let (kind, synset) = match (parsed.source_split, parsed.synset) {
+            (Some(CifarSplit::Train), None) => (KIND_CIFAR_TRAIN, 0),
+            (Some(CifarSplit::Test), None) => (KIND_CIFAR_TEST, 0),
+            (None, Some(synset)) => (KIND_IMAGENET, synset.offset() as u64),
+            _ => return None,
+        };
+        // Names which do not format back exactly, e.g. zero-padded numbers, are not packed.
+        if format_name(kind, synset, number, ext) != name {
+            return None;
+        }
+        let ext = match self.exts.iter().position(|e| **e == *ext) {
+            Some(ext) => ext,
+            None if self.exts.len() <= EXT_MASK as usize => {
+                self.exts.push(ext.into());
+                self.exts.len() - 1
+            }
+            None => return None,
+        };
+        Some((kind << KIND_SHIFT) | ((ext as u64) << EXT_SHIFT) | (synset << SYNSET_SHIFT) | number)
+    }
+
+    fn unpack_name(
+        &self,
+        bits: u64,
+    ) -> String {
+        format_name(
+            (bits >> KIND_SHIFT) & 0b11,
+            (bits >> SYNSET_SHIFT) & SYNSET_MASK,
+            bits & NUMBER_MASK,
+            &self.exts[((bits >> EXT_SHIFT) & EXT_MASK) as usize],
+        )
+    }
+
+    fn intern_dir(
+        &mut self,
+        dir: &Path,
+    ) -> u32 {
+        if let Some(&id) = self.dir_ids.get(dir) {
+            return id;
+        }
+        let id = self.dirs.len() as u32;
+        self.dirs.push(dir.to_path_buf());
+        self.dir_ids.insert(dir.to_path_buf(), id);
+        id
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        item: &DatasetItem,
+    ) {
+        let class = (item.class.ordinal() as u64) << CLASS_SHIFT;
+        let bits = match self.pack_name(&item.path) {
+            Some(bits) => bits,
+            None => {
+                let (dir, name) = match item.path.file_name() {
+                    Some(name) => (item.path.parent().unwrap_or(Path::new("")), name),
+                    None => (item.path.as_path(), Default::default()),
+                };
+                let dir = self.intern_dir(dir);
+                self.others.push((dir, name.to_os_string()));
+                (KIND_OTHER << KIND_SHIFT) | (self.others.len() - 1) as u64
+            }
+        };
+        self.packed.push(class | bits);
+    }
+
+    pub(crate) fn class(
+        &self,
+        index: usize,
+    ) -> ObjectClass {
+        ObjectClass::from_ordinal((self.packed[index] >> CLASS_SHIFT) as i8).unwrap()
+    }
+
+    /// The stored item path; see `DatasetItem::path`.
+    pub(crate) fn path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        let bits = self.packed[index];
+        if (bits >> KIND_SHIFT) & 0b11 != KIND_OTHER {
+            return PathBuf::from(self.unpack_name(bits));
+        }
+        let (dir, name) = &self.others[(bits & OTHER_MASK) as usize];
+        let dir = &self.dirs[*dir as usize];
+        if name.is_empty() {
+            dir.clone()
+        } else {
+            dir.join(name)
+        }
+    }
+
+    pub(crate) fn get(
+        &self,
+        index: usize,
+    ) -> DatasetItem {
+        DatasetItem {
+            class: self.class(index),
+            path: self.path(index),
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = DatasetItem> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+
+    /// Remove an item; a side-table entry is kept until the store is rebuilt.
+    pub(crate) fn remove(
+        &mut self,
+        index: usize,
+    ) -> DatasetItem {
+        let item = self.get(index);
+        self.packed.remove(index);
+        item
+    }
+
+    /// Rewrite the interned directories of side-table items.
+    pub(crate) fn map_dirs<F>(
+        &mut self,
+        mut f: F,
+    ) where
+        F: FnMut(&Path) -> Option<PathBuf>,
+    {
+        for dir in &mut self.dirs {
+            if let Some(new_dir) = f(dir) {
+                *dir = new_dir;
+            }
+        }
+        self.dir_ids = self
+            .dirs
+            .iter()
+            .enumerate()
+            .map(|(id, dir)| (dir.clone(), id as u32))
+            .collect();
+    }
+}
+
+impl FromIterator<DatasetItem> for ItemStore {
+    fn from_iter<I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = DatasetItem>,
+    {
+        let mut store = Self::default();
+        for item in items {
+            store.push(&item);
+        }
+        store
+    }
+}
+
+impl fmt::Debug for ItemStore {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Serializes as the list of `DatasetItem`s, as a plain `Vec` would.
+impl Serialize for ItemStore {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemStore {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<DatasetItem>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_store() {
+        let items: Vec<DatasetItem> = [
+            (ObjectClass::Cat, "cifar10-train-3318.png"),
+            (ObjectClass::Dog, "cifar10-test-0.png"),
+            (ObjectClass::Ship, "n02690373_6332.JPEG"),
+            (ObjectClass::Ship, "n02690373_06332.png"),
+            (ObjectClass::Frog, "notes.txt"),
+            (ObjectClass::Frog, "/elsewhere/frog/a.png"),
+            (ObjectClass::Frog, "/elsewhere/frog/b.png"),
+            (ObjectClass::Truck, "/"),
+        ]
+        .into_iter()
+        .map(|(class, path)| DatasetItem {
+            class,
+            path: PathBuf::from(path),
+        })
+        .collect();
+
+        let mut store: ItemStore = items.iter().cloned().collect();
+        assert_eq!(store.len(), items.len());
+        for (index, item) in items.iter().enumerate() {
+            assert_eq!(store.class(index), item.class);
+            assert_eq!(store.path(index), item.path);
+        }
+        assert_eq!(store.others.len(), 5);
+        assert_eq!(store.dirs.len(), 3);
+
+        store.map_dirs(|dir| {
+            dir.strip_prefix("/elsewhere")
+                .ok()
+                .map(|rel| Path::new("/mnt").join(rel))
+        });
+        assert_eq!(store.path(6), Path::new("/mnt/frog/b.png"));
+
+        assert_eq!(store.remove(0).path, Path::new("cifar10-train-3318.png"));
+        assert_eq!(store.path(0), Path::new("cifar10-test-0.png"));
+    }
+
+    #[test]
+    fn test_item_store_extensions() {
+        let mut store = ItemStore::default();
+        for ext in [
+            "png", "jpg", "jpeg", "gif", "bmp", "tga", "webp", "tiff", "tif",
+        ] {
+            store.push(&DatasetItem {
+                class: ObjectClass::Cat,
+                path: PathBuf::from(format!("cifar10-train-1.{ext}")),
+            });
+        }
+        assert_eq!(store.exts.len(), 8);
+        assert_eq!(store.path(8), Path::new("cifar10-train-1.tif"));
+        assert_eq!(store.others.len(), 1);
+    }
+}
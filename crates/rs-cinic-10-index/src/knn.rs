@@ -0,0 +1,392 @@
+use crate::embeddings::{Embeddings, Metric};
+use crate::error::{Result, bail};
+use crate::index::DatasetIndex;
+use crate::sample_id::SampleId;
+use anyhow::Context;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// HNSW graph construction and search parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HnswConfig {
+    pub metric: Metric,
+
+    /// Links per node on the upper layers; layer 0 keeps `2 * m`.
+    pub m: usize,
+
+    /// Candidate list size while building; larger is slower and more accurate.
+    pub ef_construction: usize,
+
+    /// Candidate list size while searching; raised to `k` when smaller.
+    pub ef_search: usize,
+
+    /// Seeds the (deterministic) layer assignment.
+    pub seed: u64,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            metric: Metric::L2,
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+            seed: 0,
+        }
+    }
+}
+
+/// A search result: a dataset index and its distance to the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    pub index: usize,
+    pub distance: f32,
+}
+
+impl Eq for Neighbor {}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(
+        &self,
+        other: &Self,
+    ) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor {
+    fn cmp(
+        &self,
+        other: &Self,
+    ) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// An approximate nearest-neighbor index (HNSW) over the embeddings of a split.
+pub struct KnnIndex {
+    config: HnswConfig,
+    embeddings: Embeddings,
+    sample_ids: HashMap<SampleId, usize>,
+
+    /// `links[node][layer]` are the neighbors of `node` on `layer`.
+    links: Vec<Vec<Vec<usize>>>,
+    entry: Option<usize>,
+}
+
+impl KnnIndex {
+    /// Build the index.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The split the embeddings were computed for.
+    /// - `embeddings`: One embedding per item of `split`.
+    /// - `config`: Graph parameters.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; an error if the embeddings do not match the split.
+    pub fn build(
+        split: &DatasetIndex,
+        embeddings: Embeddings,
+        config: HnswConfig,
+    ) -> Result<Self> {
+        if embeddings.len() != split.len() {
+            bail!(
+                "Split has {} items but {} embeddings",
+                split.len(),
+                embeddings.len()
+            );
+        }
+        let mut index = Self {
+            config: HnswConfig {
+                m: config.m.max(2),
+                ..config
+            },
+            embeddings,
+            sample_ids: split.sample_id_map(),
+            links: Vec::new(),
+            entry: None,
+        };
+        for node in 0..index.embeddings.len() {
+            index.insert(node);
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn embeddings(&self) -> &Embeddings {
+        &self.embeddings
+    }
+
+    fn max_links(
+        &self,
+        layer: usize,
+    ) -> usize {
+        if layer == 0 {
+            2 * self.config.m
+        } else {
+            self.config.m
+        }
+    }
+
+    fn random_level(
+        &self,
+        node: usize,
+    ) -> usize {
+        let bits = splitmix64(self.config.seed ^ splitmix64(node as u64));
+        let u = ((bits >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        (-u.ln() / (self.config.m as f64).ln()) as usize
+    }
+
+    fn distance_to(
+        &self,
+        query: &[f32],
+        node: usize,
+    ) -> f32 {
+        self.config
+            .metric
+            .distance(query, self.embeddings.get(node))
+    }
+
+    /// Best-first search of one layer.
+    ///
+    /// # Returns
+    ///
+    /// Up to `ef` nearest nodes found, sorted by distance.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[Neighbor],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Neighbor> {
+        let mut visited: HashSet<usize> = entries.iter().map(|n| n.index).collect();
+        let mut candidates: BinaryHeap<Reverse<Neighbor>> =
+            entries.iter().copied().map(Reverse).collect();
+        let mut results: BinaryHeap<Neighbor> = entries.iter().copied().collect();
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if results.len() >= ef
+                && let Some(worst) = results.peek()
+                && current.distance > worst.distance
+            {
+                break;
+            }
+            for &next in &self.links[current.index][layer] {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let candidate = Neighbor {
+                    index: next,
+                    distance: self.distance_to(query, next),
+                };
+                if results.len() < ef || results.peek().is_some_and(|w| candidate < *w) {
+                    candidates.push(Reverse(candidate));
+                    results.push(candidate);
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+        results.into_sorted_vec()
+    }
+
+    fn insert(
+        &mut self,
+        node: usize,
+    ) {
+        let level = self.random_level(node);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return;
+        };
+        let top = self.links[entry].len() - 1;
+        let query = self.embeddings.get(node).to_vec();
+
+        let mut nearest = vec![Neighbor {
+            index: entry,
+            distance: self.distance_to(&query, entry),
+        }];
+        for layer in (level + 1..=top).rev() {
+            nearest = self.search_layer(&query, &nearest, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            nearest = self.search_layer(&query, &nearest, self.config.ef_construction, layer);
+            let max_links = self.max_links(layer);
+            self.links[node][layer] = nearest
+                .iter()
+                .take(self.config.m)
+                .map(|n| n.index)
+                .collect();
+
+            for &Neighbor { index: other, .. } in nearest.iter().take(self.config.m) {
+                self.links[other][layer].push(node);
+                if self.links[other][layer].len() > max_links {
+                    let origin = self.embeddings.get(other).to_vec();
+                    let mut scored: Vec<Neighbor> = self.links[other][layer]
+                        .iter()
+                        .map(|&n| Neighbor {
+                            index: n,
+                            distance: self.distance_to(&origin, n),
+                        })
+                        .collect();
+                    scored.sort();
+                    self.links[other][layer] =
+                        scored.iter().take(max_links).map(|n| n.index).collect();
+                }
+            }
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Find the (approximate) `k` nearest items to a vector.
+    ///
+    /// # Parameters
+    ///
+    /// - `vector`: The query; must have the embedding dimension.
+    /// - `k`: The number of neighbors.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` neighbors, nearest first.
+    pub fn nearest_to_vector(
+        &self,
+        vector: &[f32],
+        k: usize,
+    ) -> Result<Vec<Neighbor>> {
+        if vector.len() != self.embeddings.dim() {
+            bail!(
+                "Query has {} dims, expected {}",
+                vector.len(),
+                self.embeddings.dim()
+            );
+        }
+        let Some(entry) = self.entry else {
+            return Ok(Vec::new());
+        };
+
+        let mut nearest = vec![Neighbor {
+            index: entry,
+            distance: self.distance_to(vector, entry),
+        }];
+        for layer in (1..self.links[entry].len()).rev() {
+            nearest = self.search_layer(vector, &nearest, 1, layer);
+        }
+        let mut nearest = self.search_layer(vector, &nearest, self.config.ef_search.max(k), 0);
+        nearest.truncate(k);
+        Ok(nearest)
+    }
+
+    /// Find the (approximate) `k` nearest other items to a sample.
+    ///
+    /// # Parameters
+    ///
+    /// - `id`: The query sample.
+    /// - `k`: The number of neighbors; the sample itself is excluded.
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` neighbors, nearest first; an error if the sample is not in the split.
+    pub fn nearest(
+        &self,
+        id: &SampleId,
+        k: usize,
+    ) -> Result<Vec<Neighbor>> {
+        let index = *self
+            .sample_ids
+            .get(id)
+            .with_context(|| format!("Sample not in index: {id}"))?;
+        Ok(self
+            .nearest_to_vector(self.embeddings.get(index), k + 1)?
+            .into_iter()
+            .filter(|n| n.index != index)
+            .take(k)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{DataSet, ObjectClass};
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    fn points(
+        n: usize,
+        dim: usize,
+    ) -> Vec<Vec<f32>> {
+        (0..n)
+            .map(|i| {
+                (0..dim)
+                    .map(|d| (splitmix64((i * dim + d) as u64) % 1000) as f32 / 1000.0)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nearest_matches_brute_force() -> Result<()> {
+        let rows = points(300, 8);
+        let split = fixtures::numbered_split("/data/train", &[ObjectClass::Deer], rows.len());
+        let index = KnnIndex::build(
+            &split,
+            Embeddings::from_rows(rows.clone())?,
+            HnswConfig::default(),
+        )?;
+        assert_eq!(index.len(), 300);
+
+        for query in points(10, 8)
+            .iter()
+            .map(|q| q.iter().map(|v| v + 0.01).collect::<Vec<_>>())
+        {
+            let mut expected: Vec<Neighbor> = rows
+                .iter()
+                .enumerate()
+                .map(|(i, r)| Neighbor {
+                    index: i,
+                    distance: Metric::L2.distance(&query, r),
+                })
+                .collect();
+            expected.sort();
+            expected.truncate(5);
+            assert_eq!(index.nearest_to_vector(&query, 5)?, expected);
+        }
+
+        let id = SampleId::new(DataSet::Train, "7.png");
+        let neighbors = index.nearest(&id, 3)?;
+        assert_eq!(neighbors.len(), 3);
+        assert!(neighbors.iter().all(|n| n.index != 7));
+        assert!(
+            index
+                .nearest(&SampleId::new(DataSet::Test, "7.png"), 3)
+                .is_err()
+        );
+        assert!(index.nearest_to_vector(&[0.0], 3).is_err());
+
+        Ok(())
+    }
+}
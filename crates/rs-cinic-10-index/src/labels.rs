@@ -0,0 +1,179 @@
+use crate::error::Result;
+use crate::index::{CHANNELS, ObjectClass};
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use strum::IntoEnumIterator;
+
+/// The file format of an exported label map.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum LabelMapFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+/// One class of a label map.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelEntry {
+    pub name: String,
+    pub label: u8,
+}
+
+/// Per-channel (RGB) normalization constants, on the `[0, 1]` scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Normalization {
+    pub mean: [f64; CHANNELS],
+    pub std: [f64; CHANNELS],
+}
+
+/// The class-name to integer-label mapping, for consumers outside this crate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabelMap {
+    /// The classes, in label order.
+    pub classes: Vec<LabelEntry>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<Normalization>,
+}
+
+impl Default for LabelMap {
+    /// The canonical mapping: `ObjectClass::ordinal()` labels, without normalization.
+    fn default() -> Self {
+        Self {
+            classes: ObjectClass::iter()
+                .map(|class| LabelEntry {
+                    name: class.to_string(),
+                    label: class.ordinal() as u8,
+                })
+                .collect(),
+            normalization: None,
+        }
+    }
+}
+
+impl LabelMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Include normalization constants, e.g. `stats::CINIC10_MEAN` and `stats::CINIC10_STD`.
+    pub fn with_normalization(
+        mut self,
+        mean: [f64; CHANNELS],
+        std: [f64; CHANNELS],
+    ) -> Self {
+        self.normalization = Some(Normalization { mean, std });
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::from("classes:\n");
+        for entry in &self.classes {
+            let _ = writeln!(yaml, "  - name: {}\n    label: {}", entry.name, entry.label);
+        }
+        if let Some(norm) = &self.normalization {
+            let list = |v: &[f64]| {
+                v.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let _ = writeln!(
+                yaml,
+                "normalization:\n  mean: [{}]\n  std: [{}]",
+                list(&norm.mean),
+                list(&norm.std)
+            );
+        }
+        yaml
+    }
+
+    /// Write the label map to a file.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The file to write.
+    /// - `format`: The file format.
+    pub fn save<P>(
+        &self,
+        path: P,
+        format: LabelMapFormat,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let text = match format {
+            LabelMapFormat::Json => self.to_json()?,
+            LabelMapFormat::Yaml => self.to_yaml(),
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Write the canonical class-name to label mapping to a file.
+///
+/// Use `LabelMap::with_normalization()` and `LabelMap::save()` to include
+/// normalization constants.
+///
+/// # Parameters
+///
+/// - `path`: The file to write.
+/// - `format`: The file format.
+pub fn export_label_map<P>(
+    path: P,
+    format: LabelMapFormat,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    LabelMap::new().save(path, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{CINIC10_MEAN, CINIC10_STD};
+    use anyhow::Result;
+
+    #[test]
+    fn test_export_label_map() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("labels.json");
+        export_label_map(&path, LabelMapFormat::Json)?;
+        let map: LabelMap = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(map, LabelMap::new());
+        assert_eq!(map.classes[9].name, "truck");
+        assert_eq!(map.classes[9].label, 9);
+
+        let yaml = LabelMap::new()
+            .with_normalization(CINIC10_MEAN, CINIC10_STD)
+            .to_yaml();
+        assert!(yaml.starts_with("classes:\n  - name: airplane\n    label: 0\n"));
+        assert!(yaml.ends_with("std: [0.24205776, 0.23828046, 0.25874835]\n"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use crate::builder::Cinic10IndexBuilder;
+use crate::error::Result;
+use crate::index::{Cinic10Index, DataSet, DatasetIndex};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use strum::EnumCount;
+
+/// A `Cinic10Index` which scans each split on first access.
+///
+/// Scanning all three splits lists 270,000 files; code which only evaluates
+/// on `valid` need only pay for `valid`. Splits are scanned at most once, and
+/// may be accessed from several threads. Create one with
+/// `Cinic10Index::lazy()` or `Cinic10IndexBuilder::build_lazy()`.
+#[derive(Debug)]
+pub struct LazyCinic10Index {
+    builder: Cinic10IndexBuilder,
+    root: PathBuf,
+    splits: [OnceLock<DatasetIndex>; DataSet::COUNT],
+}
+
+impl LazyCinic10Index {
+    pub(crate) fn new(
+        builder: Cinic10IndexBuilder,
+        root: PathBuf,
+    ) -> Self {
+        Self {
+            builder,
+            root,
+            splits: Default::default(),
+        }
+    }
+
+    fn cell(
+        &self,
+        data_set: DataSet,
+    ) -> &OnceLock<DatasetIndex> {
+        let i = DataSet::ALL.iter().position(|&d| d == data_set).unwrap();
+        &self.splits[i]
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get a split, scanning it if this is its first access.
+    ///
+    /// # Parameters
+    ///
+    /// - `data_set`: The split.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the split's index; a failed scan is retried on the next access.
+    pub fn split(
+        &self,
+        data_set: DataSet,
+    ) -> Result<&DatasetIndex> {
+        let cell = self.cell(data_set);
+        if let Some(index) = cell.get() {
+            return Ok(index);
+        }
+        // Racing threads may both scan; the first to finish wins.
+        let index = self.builder.scan_split(&self.root, data_set)?;
+        Ok(cell.get_or_init(|| index))
+    }
+
+    pub fn train(&self) -> Result<&DatasetIndex> {
+        self.split(DataSet::Train)
+    }
+
+    pub fn test(&self) -> Result<&DatasetIndex> {
+        self.split(DataSet::Test)
+    }
+
+    pub fn valid(&self) -> Result<&DatasetIndex> {
+        self.split(DataSet::Valid)
+    }
+
+    /// Has a split been scanned yet?
+    pub fn is_loaded(
+        &self,
+        data_set: DataSet,
+    ) -> bool {
+        self.cell(data_set).get().is_some()
+    }
+
+    /// Scan the remaining splits and the metadata files, into a full `Cinic10Index`.
+    pub fn into_index(self) -> Result<Cinic10Index> {
+        let (imagenet_contrib, synset_map) = self.builder.load_metadata(&self.root)?;
+        let [train, test, valid] = self.splits.map(OnceLock::into_inner);
+        let take = |split: Option<DatasetIndex>, data_set: DataSet| match split {
+            Some(split) => Ok(split),
+            None => self.builder.scan_split(&self.root, data_set),
+        };
+        Ok(Cinic10Index::new(
+            self.root.clone(),
+            imagenet_contrib,
+            synset_map,
+            take(train, DataSet::Train)?,
+            take(test, DataSet::Test)?,
+            take(valid, DataSet::Valid)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use std::fs;
+
+    #[test]
+    fn test_lazy_index() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for (data_set, names) in [("train", &["a.png", "b.png"][..]), ("valid", &["c.png"])] {
+            let class_dir = root.join(data_set).join("ship");
+            fs::create_dir_all(&class_dir)?;
+            for name in names {
+                fs::write(class_dir.join(name), b"")?;
+            }
+        }
+
+        // Strict scans fail, but only when the split is used.
+        let strict = Cinic10Index::lazy(root)?;
+        assert!(strict.valid().is_err());
+        assert!(!strict.is_loaded(DataSet::Valid));
+
+        let lazy = Cinic10Index::builder()
+            .root(root)
+            .require_metadata(false)
+            .strict(false)
+            .build_lazy()?;
+        assert_eq!(lazy.root(), root);
+        assert!(!lazy.is_loaded(DataSet::Valid));
+        assert_eq!(lazy.valid()?.len(), 1);
+        assert_eq!(lazy.valid()?.index_to_class(0), ObjectClass::Ship);
+        assert!(lazy.is_loaded(DataSet::Valid));
+        assert!(!lazy.is_loaded(DataSet::Train));
+
+        fs::write(root.join("valid/ship/d.png"), b"")?;
+        let cinic = lazy.into_index()?;
+        // The scanned split is kept as it was.
+        assert_eq!(cinic.valid.len(), 1);
+        assert_eq!(cinic.train.len(), 2);
+        assert!(cinic.test.is_empty());
+
+        let valid = Cinic10Index::builder()
+            .root(root)
+            .strict(false)
+            .build_split(DataSet::Valid)?;
+        assert_eq!(valid.len(), 2);
+
+        Ok(())
+    }
+}
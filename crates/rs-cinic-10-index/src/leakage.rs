@@ -0,0 +1,267 @@
+use crate::dedup::{DedupConfig, HashKind, ImageHashes, cluster_image_hashes, hash_split};
+use crate::error::Result;
+use crate::index::{Cinic10Index, DataSet, DatasetIndex};
+use crate::sample_id::SampleId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A sample of a specific split.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SampleRef {
+    pub split: DataSet,
+    pub index: usize,
+    pub path: PathBuf,
+}
+
+impl SampleRef {
+    /// The stable id of the sample, if its path has a UTF-8 file name.
+    pub fn sample_id(&self) -> Option<SampleId> {
+        Some(SampleId::new(self.split, self.path.file_name()?.to_str()?))
+    }
+}
+
+/// A pair of (near-)identical images in two different splits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakageMatch {
+    pub left: SampleRef,
+    pub right: SampleRef,
+
+    /// The Hamming distance between the two images' hashes.
+    pub distance: u32,
+}
+
+/// The cross-split duplicates found by `find_leakage()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeakageReport {
+    pub matches: Vec<LeakageMatch>,
+}
+
+impl LeakageReport {
+    /// Were no cross-split duplicates found?
+    pub fn is_clean(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// The indices of `split` which have a duplicate in another split.
+    ///
+    /// Dropping these from the training split yields a protocol with
+    /// no overlap with the evaluation splits.
+    ///
+    /// # Returns
+    ///
+    /// Sorted, distinct item indices.
+    pub fn exclusions(
+        &self,
+        split: DataSet,
+    ) -> Vec<usize> {
+        self.matches
+            .iter()
+            .flat_map(|m| [&m.left, &m.right])
+            .filter(|s| s.split == split)
+            .map(|s| s.index)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Like `exclusions()`, as stable `SampleId`s.
+    pub fn exclusion_ids(
+        &self,
+        split: DataSet,
+    ) -> Vec<SampleId> {
+        self.matches
+            .iter()
+            .flat_map(|m| [&m.left, &m.right])
+            .filter(|s| s.split == split)
+            .filter_map(|s| s.sample_id())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Write the exclusion list of `split`, one `SampleId` per line.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The split to write exclusions for.
+    /// - `path`: The output file.
+    pub fn save_exclusions<P>(
+        &self,
+        split: DataSet,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut text = String::new();
+        for id in self.exclusion_ids(split) {
+            text.push_str(&id.to_string());
+            text.push('\n');
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Search for (near-)identical images which appear in more than one split.
+///
+/// Duplicates within a single split are not reported; see `dedup::find_duplicates()`.
+///
+/// # Parameters
+///
+/// - `splits`: The splits to compare, with their names.
+/// - `config`: The hash, distance and parallelism to use.
+///
+/// # Returns
+///
+/// A `Result` containing the `LeakageReport`.
+pub fn find_leakage(
+    splits: &[(DataSet, &DatasetIndex)],
+    config: &DedupConfig,
+) -> Result<LeakageReport> {
+    let hashes = splits
+        .iter()
+        .map(|&(_, index)| hash_split(index, config.parallelism))
+        .collect::<Result<Vec<_>>>()?;
+    let hashed: Vec<_> = splits
+        .iter()
+        .zip(hashes.iter())
+        .map(|(&(split, index), hashes)| (split, index, hashes.as_slice()))
+        .collect();
+    Ok(find_leakage_in_hashes(
+        &hashed,
+        config.kind,
+        config.max_distance,
+    ))
+}
+
+/// Like `find_leakage()`, over precomputed `hash_split()` results.
+///
+/// # Parameters
+///
+/// - `splits`: The splits to compare, with their names and hashes.
+/// - `kind`: The hash to compare.
+/// - `max_distance`: The maximum Hamming distance of a near-duplicate.
+///
+/// # Returns
+///
+/// The `LeakageReport`.
+pub fn find_leakage_in_hashes(
+    splits: &[(DataSet, &DatasetIndex, &[ImageHashes])],
+    kind: HashKind,
+    max_distance: u32,
+) -> LeakageReport {
+    let mut owners: Vec<(DataSet, &DatasetIndex, usize)> = Vec::new();
+    let mut hashes: Vec<ImageHashes> = Vec::new();
+    for &(split, index, split_hashes) in splits {
+        owners.extend((0..split_hashes.len()).map(|i| (split, index, i)));
+        hashes.extend_from_slice(split_hashes);
+    }
+
+    let sample_ref = |pos: usize| {
+        let (split, index, i) = owners[pos];
+        SampleRef {
+            split,
+            index: i,
+            path: index.index_to_path(i),
+        }
+    };
+
+    let mut report = LeakageReport::default();
+    for cluster in cluster_image_hashes(&hashes, kind, max_distance) {
+        for (k, &a) in cluster.iter().enumerate() {
+            for &b in &cluster[k + 1..] {
+                if owners[a].0 == owners[b].0 {
+                    continue;
+                }
+                // Clusters are transitive; only report directly matching pairs.
+                let distance = hashes[a].distance(&hashes[b], kind);
+                if distance <= max_distance {
+                    report.matches.push(LeakageMatch {
+                        left: sample_ref(a),
+                        right: sample_ref(b),
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+    report
+}
+
+impl Cinic10Index {
+    /// Search for images shared between the train, valid and test splits.
+    ///
+    /// See `find_leakage()`.
+    pub fn find_leakage(
+        &self,
+        config: &DedupConfig,
+    ) -> Result<LeakageReport> {
+        find_leakage(
+            &[
+                (DataSet::Train, &self.train),
+                (DataSet::Valid, &self.valid),
+                (DataSet::Test, &self.test),
+            ],
+            config,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+
+    fn split(
+        root: &Path,
+        name: &str,
+        images: &[(&str, u8)],
+    ) -> Result<DatasetIndex> {
+        Ok(fixtures::write_split(
+            root.join(name),
+            images.iter().map(|&(file, seed)| {
+                let img = RgbImage::from_fn(16, 16, |x, y| {
+                    Rgb([(x * 16) as u8 ^ seed, (y * 16) as u8, seed])
+                });
+                (ObjectClass::Cat, file, img)
+            }),
+        )?)
+    }
+
+    #[test]
+    fn test_find_leakage() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let train = split(
+            dir.path(),
+            "train",
+            &[("a.png", 0), ("b.png", 0xF0), ("c.png", 0)],
+        )?;
+        let test = split(dir.path(), "test", &[("x.png", 0x0F), ("y.png", 0)])?;
+
+        let config = DedupConfig {
+            kind: HashKind::Exact,
+            ..Default::default()
+        };
+        let report = find_leakage(&[(DataSet::Train, &train), (DataSet::Test, &test)], &config)?;
+
+        assert!(!report.is_clean());
+        assert_eq!(report.matches.len(), 2);
+        assert_eq!(report.exclusions(DataSet::Train), vec![0, 2]);
+        assert_eq!(report.exclusions(DataSet::Test), vec![1]);
+
+        let path = dir.path().join("exclude.txt");
+        report.save_exclusions(DataSet::Train, &path)?;
+        assert_eq!(fs::read_to_string(&path)?, "train/a.png\ntrain/c.png\n");
+
+        let clean = find_leakage(&[(DataSet::Train, &train)], &config)?;
+        assert!(clean.is_clean());
+
+        Ok(())
+    }
+}
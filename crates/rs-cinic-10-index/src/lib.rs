@@ -1,43 +1,204 @@
+#[cfg(any(feature = "download", feature = "zip"))]
+pub mod archive;
+pub mod augment;
+pub mod builder;
+pub mod cache;
+pub mod cas;
+pub mod checksum;
+pub mod cifar;
+pub mod color;
+pub mod config;
+pub mod coreset;
+pub mod corruptions;
+pub mod dataset_profile;
+pub mod dedup;
+pub mod diff;
+pub mod direct_io;
+#[cfg(feature = "download")]
+pub mod download;
+pub mod embeddings;
+pub mod error;
+pub mod image_folder;
 pub mod images;
 pub mod index;
+pub mod index_cache;
+mod index_serde;
+mod item_store;
+#[cfg(feature = "knn")]
+pub mod knn;
+pub mod labels;
+pub mod lazy;
+pub mod leakage;
+pub mod loader;
+pub mod manifest;
+pub mod memory;
+pub mod metrics;
+pub mod mock;
+pub mod openset;
+pub mod ordering;
+pub mod overlay;
+pub mod packed;
+mod parallel;
+pub mod patches;
+pub mod process_pool;
+pub mod profile;
+pub mod provenance;
+pub mod quality;
+pub mod query;
+mod readahead;
+pub mod record;
+pub mod registry;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod report;
+pub mod retry;
+pub mod sample_id;
+pub mod sampler;
+pub mod slow_ops;
+pub mod source;
+pub mod splits;
+pub mod stats;
+pub mod stream;
+pub mod synsets;
+pub mod tasks;
+pub mod transform;
+pub mod validation;
+pub mod view;
+pub mod warmup;
+#[cfg(feature = "watch")]
+pub mod watch;
+pub mod weights;
+pub mod wnid;
+pub mod writer;
 
+pub use error::Cinic10Error;
 pub use index::Cinic10Index;
 
 use std::env;
+use std::ffi::OsString;
 use std::path::PathBuf;
 use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 static STATIC_DEFAULT_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+static PLATFORM_FALLBACK: AtomicBool = AtomicBool::new(true);
 pub const CINC10_PATH_ENV_VAR: &str = "CINIC10_PATH";
 
+/// The directory name of the dataset under the platform cache dir.
+pub const PLATFORM_DATA_DIR_NAME: &str = "cinic10";
+
+/// The platform default path for CINIC-10 data, e.g. `~/.cache/cinic10` on Linux.
+///
+/// This is `$XDG_CACHE_HOME/cinic10` (or `~/.cache/cinic10`) on Linux,
+/// `~/Library/Caches/cinic10` on macOS, and `{FOLDERID_LocalAppData}\cinic10`
+/// on Windows; or `None` if the platform has no cache dir.
+pub fn platform_data_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(PLATFORM_DATA_DIR_NAME))
+}
+
 /// Get the default path for CINIC-10 data.
 ///
 /// Returns either the last call to `set_default_path()`;
 /// or the value of the `CINIC10_PATH` env var,
-/// or `None`.
+/// or `platform_data_path()`, if that directory exists
+/// and the fallback is enabled; see `set_platform_data_fallback()`.
 pub fn get_default_data_path() -> Option<PathBuf> {
-    let path = STATIC_DEFAULT_PATH.read().unwrap().clone();
-    if path.is_some() {
-        return path;
-    }
+    resolve_data_path(
+        STATIC_DEFAULT_PATH.read().unwrap().clone(),
+        env::var_os(CINC10_PATH_ENV_VAR),
+        platform_fallback_path(),
+    )
+}
 
-    match env::var(CINC10_PATH_ENV_VAR) {
-        Ok(path) => Some(PathBuf::from(path)),
-        Err(_) => None,
+/// The explicitly configured data path, or the enabled platform fallback.
+///
+/// Unlike `get_default_data_path()`, the platform path need not exist yet;
+/// this is where `download::download_cinic10_default()` downloads to.
+#[cfg(feature = "download")]
+pub(crate) fn default_download_path() -> Option<PathBuf> {
+    STATIC_DEFAULT_PATH
+        .read()
+        .unwrap()
+        .clone()
+        .or_else(|| env::var_os(CINC10_PATH_ENV_VAR).map(PathBuf::from))
+        .or_else(platform_fallback_path)
+}
+
+fn platform_fallback_path() -> Option<PathBuf> {
+    if PLATFORM_FALLBACK.load(Ordering::Relaxed) {
+        platform_data_path()
+    } else {
+        None
     }
 }
 
+fn resolve_data_path(
+    explicit: Option<PathBuf>,
+    env_value: Option<OsString>,
+    platform: Option<PathBuf>,
+) -> Option<PathBuf> {
+    explicit
+        .or_else(|| env_value.map(PathBuf::from))
+        .or_else(|| platform.filter(|path| path.is_dir()))
+}
+
+/// Get the default path for CINIC-10 data, or `Cinic10Error::NotConfigured`.
+///
+/// The error is returned when no path is set, the env var is unset,
+/// and there is no dataset dir at `platform_data_path()`.
+pub fn try_default_data_path() -> Result<PathBuf, Cinic10Error> {
+    get_default_data_path().ok_or(Cinic10Error::NotConfigured)
+}
+
 pub fn default_data_path_or_panic() -> PathBuf {
-    get_default_data_path().unwrap_or_else(|| {
-        panic!(
-            "CINIC-10 data path not set. \
-            Set the {} environment variable or call set_default_data_path()",
-            CINC10_PATH_ENV_VAR
-        )
-    })
+    try_default_data_path().unwrap_or_else(|err| panic!("{err}"))
 }
 
 /// Set the value of `get_default_path()` in future calls.
 pub fn set_default_data_path(path: Option<PathBuf>) {
     *STATIC_DEFAULT_PATH.write().unwrap() = path;
 }
+
+/// Enable or disable the `platform_data_path()` fallback of `get_default_data_path()`.
+///
+/// The fallback is enabled by default; disable it to require an explicit path
+/// or the `CINIC10_PATH` env var.
+pub fn set_platform_data_fallback(enabled: bool) {
+    PLATFORM_FALLBACK.store(enabled, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_data_path() {
+        if let Some(path) = platform_data_path() {
+            assert!(path.ends_with(PLATFORM_DATA_DIR_NAME));
+            assert_eq!(path.parent(), dirs::cache_dir().as_deref());
+        }
+    }
+
+    #[test]
+    fn test_resolve_data_path() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let missing = dir.path().join("missing");
+
+        assert_eq!(resolve_data_path(None, None, None), None);
+        assert_eq!(resolve_data_path(None, None, Some(missing.clone())), None);
+        assert_eq!(
+            resolve_data_path(None, None, Some(dir.path().to_path_buf())),
+            Some(dir.path().to_path_buf())
+        );
+        assert_eq!(
+            resolve_data_path(None, Some("/env".into()), Some(dir.path().to_path_buf())),
+            Some(PathBuf::from("/env"))
+        );
+        assert_eq!(
+            resolve_data_path(Some("/explicit".into()), Some("/env".into()), None),
+            Some(PathBuf::from("/explicit"))
+        );
+
+        Ok(())
+    }
+}
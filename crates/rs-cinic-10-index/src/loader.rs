@@ -0,0 +1,750 @@
+use crate::color::{ColorSpace, F32ImageBatch};
+use crate::error::{Cinic10Error, Result, bail};
+use crate::images::{
+    BatchBytes, DecoderBackend, RgbImageBatch, decode_rgbimage_as, read_batch_bytes,
+    read_image_bytes,
+};
+use crate::metrics;
+use crate::patches::{PatchBatch, PatchConfig};
+use crate::profile::{ProfileReport, Stage, StageProfiler};
+use crate::readahead::advise_batch;
+use crate::slow_ops::{self, SlowOpKind};
+use crate::source::DataSource;
+use crate::transform::{ImageTransform, sample_rng};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// What a `BatchLoader` does when an image can't be read or decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Fail the whole batch.
+    #[default]
+    FailFast,
+
+    /// Drop the sample from the batch, and report it.
+    Skip,
+
+    /// Substitute an image filled with the given RGB value, and report it.
+    Fill([u8; 3]),
+
+    /// Substitute a copy of the nearest loadable sample in the batch, and report it.
+    Duplicate,
+}
+
+/// How a failed sample was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureAction {
+    Dropped,
+    Filled,
+
+    /// Replaced by the sample at the given batch position.
+    Duplicated(usize),
+}
+
+/// A sample which could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadFailure {
+    /// The position of the sample in the requested batch.
+    pub position: usize,
+    pub path: PathBuf,
+    pub error: String,
+    pub action: FailureAction,
+}
+
+/// A loaded batch, with any samples which had to be dropped or substituted.
+#[derive(Debug, Clone)]
+pub struct LoadedBatch<T> {
+    pub batch: T,
+    pub failures: Vec<LoadFailure>,
+}
+
+impl<T> LoadedBatch<T> {
+    /// Positions (in the requested batch) of dropped samples.
+    pub fn dropped(&self) -> Vec<usize> {
+        self.failures
+            .iter()
+            .filter(|f| f.action == FailureAction::Dropped)
+            .map(|f| f.position)
+            .collect()
+    }
+
+    /// Map the positions of dropped samples back to the requested ids.
+    ///
+    /// # Parameters
+    ///
+    /// - `ids`: The ids (e.g. dataset indices) the batch was requested for.
+    pub fn dropped_from(
+        &self,
+        ids: &[usize],
+    ) -> Vec<usize> {
+        self.dropped().into_iter().map(|p| ids[p]).collect()
+    }
+
+    /// Transform the batch, keeping the failure report.
+    pub fn map<U, F>(
+        self,
+        f: F,
+    ) -> LoadedBatch<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        LoadedBatch {
+            batch: f(self.batch),
+            failures: self.failures,
+        }
+    }
+}
+
+/// Configuration for a `BatchLoader`.
+#[derive(Debug, Clone, Default)]
+pub struct LoaderConfig {
+    /// Record a per-stage timing breakdown of every batch load.
+    pub profile: bool,
+
+    /// How to handle unreadable or corrupted images.
+    pub on_error: ErrorPolicy,
+
+    /// Read packed datasets with direct IO, bypassing the OS page cache;
+    /// see `packed::open_or_pack()` and `direct_io::DirectReader`.
+    ///
+    /// Useful when the packed split is larger than RAM and streamed once per
+    /// epoch. Image files are always read through the page cache.
+    pub direct_io: bool,
+
+    /// Read each batch's files up front, on this many threads, into one
+    /// arena buffer; see `images::read_batch_bytes()`.
+    ///
+    /// `0` reads files one at a time, as they are decoded.
+    pub read_threads: usize,
+
+    /// The image decoder; `None` uses `images::ImageCrateDecoder`.
+    pub decoder: Option<Arc<dyn DecoderBackend>>,
+
+    /// Convert pixels to this color space during batch assembly.
+    ///
+    /// Byte batches hold `ColorSpace::convert_u8()` values, and float batches
+    /// `ColorSpace::convert_f32()` values; transforms still see sRGB images.
+    pub color_space: ColorSpace,
+
+    /// Read files from this source; `None` reads the local filesystem.
+    ///
+    /// `read_threads` only applies to the local filesystem.
+    pub source: Option<Arc<dyn DataSource>>,
+}
+
+/// Loads batches of images under a `LoaderConfig`.
+///
+/// Clones share the same profiler, so a loader can be handed to worker
+/// threads and the report read from any of them.
+#[derive(Debug, Clone, Default)]
+pub struct BatchLoader {
+    config: LoaderConfig,
+    profiler: Option<Arc<StageProfiler>>,
+    transform: Option<(Arc<dyn ImageTransform>, u64)>,
+}
+
+impl BatchLoader {
+    /// Create a new loader from a config.
+    pub fn new(config: LoaderConfig) -> Self {
+        let profiler = config.profile.then(|| Arc::new(StageProfiler::new()));
+        Self {
+            config,
+            profiler,
+            transform: None,
+        }
+    }
+
+    /// Apply `transform` to every decoded image.
+    ///
+    /// Each image draws from `transform::sample_rng(seed, path)`, so results
+    /// do not depend on batch composition.
+    pub fn with_transform(
+        mut self,
+        transform: Arc<dyn ImageTransform>,
+        seed: u64,
+    ) -> Self {
+        self.transform = Some((transform, seed));
+        self
+    }
+
+    pub fn config(&self) -> &LoaderConfig {
+        &self.config
+    }
+
+    pub fn transform(&self) -> Option<&Arc<dyn ImageTransform>> {
+        self.transform.as_ref().map(|(t, _)| t)
+    }
+
+    /// The loader's profiler, when profiling is enabled.
+    ///
+    /// Downstream stages (such as device transfer) record into this.
+    pub fn profiler(&self) -> Option<&StageProfiler> {
+        self.profiler.as_deref()
+    }
+
+    /// The accumulated profile, when profiling is enabled.
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.profiler.as_ref().map(|p| p.report())
+    }
+
+    /// Run `f`, timing it against `stage` when profiling is enabled.
+    pub fn time<T, F>(
+        &self,
+        stage: Stage,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        match &self.profiler {
+            Some(profiler) => profiler.time(stage, f),
+            None => f(),
+        }
+    }
+
+    fn load_image(
+        &self,
+        path: &Path,
+    ) -> Result<RgbImage> {
+        let bytes = self.time(Stage::Read, || {
+            if let Some(source) = &self.config.source {
+                source.read(path)
+            } else {
+                read_image_bytes(path)
+            }
+        })?;
+        self.decode_image(path, &bytes)
+    }
+
+    /// Decode and transform the encoded bytes of the image at `path`.
+    ///
+    /// The default decoder falls back to the format of the file extension,
+    /// like `load_rgbimage()`, for formats without a magic number such as TGA.
+    fn decode_image(
+        &self,
+        path: &Path,
+        bytes: &[u8],
+    ) -> Result<RgbImage> {
+        let start = Instant::now();
+        let img = match &self.config.decoder {
+            Some(decoder) => decoder.decode(bytes)?,
+            None => decode_rgbimage_as(bytes, ImageFormat::from_path(path).ok())?,
+        };
+        let elapsed = start.elapsed();
+        slow_ops::check(SlowOpKind::Decode, elapsed, Some(path), None);
+        if let Some(profiler) = &self.profiler {
+            profiler.record(Stage::Decode, elapsed);
+        }
+
+        self.transform_image(path, img)
+    }
+
+    /// Apply the loader's transform, if any, to the decoded image of `path`.
+    ///
+    /// This is the transform step of every load; it is public so that sources
+    /// which do not decode files (e.g. `mock::MockIndex`) transform alike.
+    pub fn transform_image(
+        &self,
+        path: &Path,
+        img: RgbImage,
+    ) -> Result<RgbImage> {
+        match &self.transform {
+            Some((transform, seed)) => self.time(Stage::Transform, || {
+                transform.apply(img, &mut sample_rng(*seed, path))
+            }),
+            None => Ok(img),
+        }
+    }
+
+    /// Loads a batch of images from the given paths.
+    ///
+    /// Failed samples are handled according to the configured `ErrorPolicy`;
+    /// use `load_batch_report()` to see which samples were affected.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    /// - `on_dims`: Called with dimensions, to build the batch object.
+    /// - `on_img`: Called for each loaded image.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the batch of images.
+    pub fn load_batch<T, P>(
+        &self,
+        paths: &[P],
+        on_dims: fn(&[usize; 4]) -> Result<T>,
+        on_img: fn(&mut T, idx: usize, img: &RgbImage) -> Result<()>,
+    ) -> Result<T>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.load_batch_report(paths, on_dims, on_img)?.batch)
+    }
+
+    /// Loads a batch of images, reporting samples which failed to load.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    /// - `on_dims`: Called with dimensions, to build the batch object.
+    /// - `on_img`: Called for each loaded image.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the batch of images and the failure report.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(batch_size = paths.len()))
+    )]
+    pub fn load_batch_report<T, P>(
+        &self,
+        paths: &[P],
+        on_dims: fn(&[usize; 4]) -> Result<T>,
+        on_img: fn(&mut T, idx: usize, img: &RgbImage) -> Result<()>,
+    ) -> Result<LoadedBatch<T>>
+    where
+        P: AsRef<Path>,
+    {
+        let start = Instant::now();
+        let prefetched: Option<BatchBytes> = if self.config.source.is_some() {
+            None
+        } else if self.config.read_threads > 0 {
+            let paths: Vec<&Path> = paths.iter().map(AsRef::as_ref).collect();
+            Some(self.time(Stage::Read, || {
+                read_batch_bytes(&paths, self.config.read_threads)
+            }))
+        } else {
+            advise_batch(paths);
+            None
+        };
+
+        let batch_size = paths.len();
+        let policy = self.config.on_error;
+
+        let mut dims: Option<(u32, u32)> = None;
+        let mut images: Vec<Option<RgbImage>> = Vec::with_capacity(batch_size);
+        let mut failures: Vec<LoadFailure> = Vec::new();
+
+        for (position, path) in paths.iter().enumerate() {
+            let path = path.as_ref();
+            let loaded = match &prefetched {
+                Some(batch_bytes) => batch_bytes
+                    .get(position)
+                    .and_then(|bytes| self.decode_image(path, bytes)),
+                None => self.load_image(path),
+            };
+            let result = loaded.and_then(|img| match dims {
+                None => {
+                    dims = Some(img.dimensions());
+                    Ok(img)
+                }
+                Some(expected) => {
+                    if img.dimensions() != expected {
+                        return Err(Cinic10Error::DimensionMismatch {
+                            path: path.to_path_buf(),
+                            expected,
+                            actual: img.dimensions(),
+                        });
+                    }
+                    Ok(img)
+                }
+            });
+
+            match result {
+                Ok(img) => images.push(Some(img)),
+                Err(err) if policy == ErrorPolicy::FailFast => {
+                    return Err(match err {
+                        Cinic10Error::DimensionMismatch { .. } => err,
+                        err => anyhow::Error::new(err)
+                            .context(format!("Failed to load {}", path.display()))
+                            .into(),
+                    });
+                }
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(path = %path.display(), error = %err, "failed to load image");
+
+                    images.push(None);
+                    failures.push(LoadFailure {
+                        position,
+                        path: path.to_path_buf(),
+                        error: format!("{err:#}"),
+                        action: FailureAction::Dropped,
+                    });
+                }
+            }
+        }
+
+        let Some((width, height)) = dims else {
+            bail!("No image in the batch of {batch_size} could be loaded");
+        };
+
+        for failure in &mut failures {
+            failure.action = match policy {
+                ErrorPolicy::FailFast | ErrorPolicy::Skip => FailureAction::Dropped,
+                ErrorPolicy::Fill(_) => FailureAction::Filled,
+                ErrorPolicy::Duplicate => {
+                    let p = failure.position;
+                    let source = (0..p)
+                        .rev()
+                        .chain(p + 1..batch_size)
+                        .find(|&i| images[i].is_some())
+                        .unwrap();
+                    FailureAction::Duplicated(source)
+                }
+            };
+        }
+
+        let out_size = match policy {
+            ErrorPolicy::Skip => batch_size - failures.len(),
+            _ => batch_size,
+        };
+        let shape = [out_size, height as usize, width as usize, 3];
+        let mut batch = on_dims(&shape)?;
+
+        let fill = match policy {
+            ErrorPolicy::Fill(rgb) => Some(RgbImage::from_pixel(width, height, Rgb(rgb))),
+            _ => None,
+        };
+        let mut failures_iter = failures.iter();
+        let mut out = 0;
+        for (position, img) in images.iter().enumerate() {
+            let img = match img {
+                Some(img) => img,
+                None => {
+                    let failure = failures_iter.next().unwrap();
+                    debug_assert_eq!(failure.position, position);
+                    match failure.action {
+                        FailureAction::Dropped => continue,
+                        FailureAction::Filled => fill.as_ref().unwrap(),
+                        FailureAction::Duplicated(source) => images[source].as_ref().unwrap(),
+                    }
+                }
+            };
+            self.time(Stage::Copy, || on_img(&mut batch, out, img))?;
+            out += 1;
+        }
+
+        let elapsed = start.elapsed();
+        slow_ops::check(
+            SlowOpKind::Batch,
+            elapsed,
+            Some(paths[0].as_ref()),
+            Some(batch_size),
+        );
+        metrics::record(|m| m.batch_latency(elapsed));
+        if let Some(profiler) = &self.profiler {
+            profiler.record_batch();
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            batch_size,
+            failures = failures.len(),
+            bytes = shape.iter().product::<usize>(),
+            elapsed_us = elapsed.as_micros() as u64,
+            "loaded image batch"
+        );
+
+        Ok(LoadedBatch { batch, failures })
+    }
+
+    /// Loads a batch of RGB images from the given paths into a single `RgbImageBatch`.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the batch of images.
+    pub fn load_rgbimagebatch<P>(
+        &self,
+        paths: &[P],
+    ) -> Result<RgbImageBatch>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.load_rgbimagebatch_report(paths)?.batch)
+    }
+
+    /// Loads a batch of RGB images, reporting samples which failed to load.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the batch of images and the failure report.
+    pub fn load_rgbimagebatch_report<P>(
+        &self,
+        paths: &[P],
+    ) -> Result<LoadedBatch<RgbImageBatch>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut loaded = self.load_batch_report::<RgbImageBatch, _>(
+            paths,
+            |shape| Ok(RgbImageBatch::new(shape)),
+            |batch, _idx, img| {
+                batch.push_rgb_pixels(img);
+                Ok(())
+            },
+        )?;
+        self.convert_color_space(&mut loaded.batch);
+        Ok(loaded)
+    }
+
+    /// Convert an assembled sRGB batch to the configured color space, in place.
+    pub(crate) fn convert_color_space(
+        &self,
+        batch: &mut RgbImageBatch,
+    ) {
+        let space = self.config.color_space;
+        if space != ColorSpace::Rgb {
+            self.time(Stage::Copy, || space.convert_u8_slice(&mut batch.data));
+        }
+    }
+
+    /// Loads a batch of images as floats, in the configured color space.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `[B, H, W, 3]` float batch and the failure report.
+    pub fn load_f32_batch_report<P>(
+        &self,
+        paths: &[P],
+    ) -> Result<LoadedBatch<F32ImageBatch>>
+    where
+        P: AsRef<Path>,
+    {
+        let loaded = self.load_batch_report::<RgbImageBatch, _>(
+            paths,
+            |shape| Ok(RgbImageBatch::new(shape)),
+            |batch, _idx, img| {
+                batch.push_rgb_pixels(img);
+                Ok(())
+            },
+        )?;
+        let space = self.config.color_space;
+        Ok(loaded.map(|batch| {
+            self.time(Stage::Copy, || {
+                F32ImageBatch::from_rgbimagebatch(&batch, space)
+            })
+        }))
+    }
+
+    /// Loads a batch of images as `[B, N, P*P*C]` patch sequences.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    /// - `config`: The patch configuration.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the patch batch and the failure report.
+    pub fn load_patchbatch_report<P>(
+        &self,
+        paths: &[P],
+        config: &PatchConfig,
+    ) -> Result<LoadedBatch<PatchBatch>>
+    where
+        P: AsRef<Path>,
+    {
+        let loaded = self.load_rgbimagebatch_report(paths)?;
+        let batch = self.time(Stage::Copy, || {
+            PatchBatch::from_rgbimagebatch(&loaded.batch, config)
+        })?;
+        Ok(LoadedBatch {
+            batch,
+            failures: loaded.failures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_profiled_batch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let paths = (0..2)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.png"));
+                RgbImage::from_pixel(4, 2, Rgb([i, 1, 2])).save(&path)?;
+                Ok(path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let loader = BatchLoader::new(LoaderConfig {
+            profile: true,
+            ..Default::default()
+        });
+        let batch = loader.load_rgbimagebatch(&paths)?;
+        assert_eq!(batch.shape, [2, 2, 4, 3]);
+        let arena = BatchLoader::new(LoaderConfig {
+            read_threads: 2,
+            ..Default::default()
+        });
+        assert_eq!(arena.load_rgbimagebatch(&paths)?.data, batch.data);
+        assert_eq!(&batch.data[..3], &[0, 1, 2]);
+        assert_eq!(&batch.data[24..27], &[1, 1, 2]);
+
+        let report = loader.clone().profile_report().unwrap();
+        assert_eq!(report.batches, 1);
+        assert_eq!(report.stage(Stage::Read).count, 2);
+        assert_eq!(report.stage(Stage::Decode).count, 2);
+        assert_eq!(report.stage(Stage::Copy).count, 2);
+        assert_eq!(report.stage(Stage::Transform).count, 0);
+
+        assert!(BatchLoader::default().profile_report().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_tga_batch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("0.tga");
+        RgbImage::from_pixel(2, 2, Rgb([7, 8, 9])).save(&path)?;
+
+        let batch = BatchLoader::default().load_rgbimagebatch(&[&path])?;
+        assert_eq!(batch.shape, [1, 2, 2, 3]);
+        assert_eq!(&batch.data[..3], &[7, 8, 9]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_batch_bytes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut paths = Vec::new();
+        for i in 0..5 {
+            let path = dir.path().join(format!("{i}.bin"));
+            if i != 3 {
+                fs::write(&path, vec![i as u8; i * 100])?;
+            }
+            paths.push(path);
+        }
+
+        let batch_bytes = read_batch_bytes(&paths, 3);
+        assert_eq!(batch_bytes.len(), 5);
+        assert_eq!(batch_bytes.arena_len(), 700);
+        for (i, path) in paths.iter().enumerate() {
+            match i {
+                3 => assert!(batch_bytes.get(i).is_err()),
+                _ => assert_eq!(batch_bytes.get(i)?, fs::read(path)?.as_slice()),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_policies() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let paths = (0..4)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.png"));
+                if i == 1 || i == 2 {
+                    fs::write(&path, b"truncated")?;
+                } else {
+                    RgbImage::from_pixel(1, 1, Rgb([i, i, i])).save(&path)?;
+                }
+                Ok(path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let loader = |on_error| {
+            BatchLoader::new(LoaderConfig {
+                on_error,
+                ..Default::default()
+            })
+        };
+
+        assert!(
+            loader(ErrorPolicy::FailFast)
+                .load_rgbimagebatch(&paths)
+                .is_err()
+        );
+
+        let loaded = loader(ErrorPolicy::Skip).load_rgbimagebatch_report(&paths)?;
+        assert_eq!(loaded.batch.shape, [2, 1, 1, 3]);
+        assert_eq!(loaded.batch.data, [0, 0, 0, 3, 3, 3]);
+        assert_eq!(loaded.dropped(), vec![1, 2]);
+        assert_eq!(loaded.dropped_from(&[10, 11, 12, 13]), vec![11, 12]);
+        assert_eq!(loaded.failures[0].path, paths[1]);
+
+        let loaded = loader(ErrorPolicy::Fill([9, 8, 7])).load_rgbimagebatch_report(&paths)?;
+        assert_eq!(loaded.batch.shape, [4, 1, 1, 3]);
+        assert_eq!(loaded.batch.data, [0, 0, 0, 9, 8, 7, 9, 8, 7, 3, 3, 3]);
+        assert!(loaded.dropped().is_empty());
+
+        let loaded = loader(ErrorPolicy::Duplicate).load_rgbimagebatch_report(&paths)?;
+        assert_eq!(loaded.batch.data, [0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 3, 3]);
+        assert_eq!(loaded.failures[1].action, FailureAction::Duplicated(0));
+
+        assert!(
+            loader(ErrorPolicy::Skip)
+                .load_rgbimagebatch(&paths[1..3])
+                .is_err()
+        );
+
+        let wide = dir.path().join("wide.png");
+        RgbImage::new(2, 1).save(&wide)?;
+        assert!(matches!(
+            loader(ErrorPolicy::FailFast).load_rgbimagebatch(&[&paths[0], &wide]),
+            Err(Cinic10Error::DimensionMismatch {
+                expected: (1, 1),
+                actual: (2, 1),
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_decoder() -> Result<()> {
+        /// Decodes anything as a single pixel holding the encoded length.
+        #[derive(Debug)]
+        struct LengthDecoder;
+
+        impl DecoderBackend for LengthDecoder {
+            fn name(&self) -> &str {
+                "length"
+            }
+
+            fn decode(
+                &self,
+                bytes: &[u8],
+            ) -> Result<RgbImage> {
+                Ok(RgbImage::from_pixel(1, 1, Rgb([bytes.len() as u8; 3])))
+            }
+        }
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("0.png");
+        fs::write(&path, b"not a png")?;
+
+        let loader = BatchLoader::new(LoaderConfig {
+            decoder: Some(Arc::new(LengthDecoder)),
+            ..Default::default()
+        });
+        assert_eq!(loader.load_rgbimagebatch(&[&path])?.data, [9, 9, 9]);
+        assert!(BatchLoader::default().load_rgbimagebatch(&[&path]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+use crate::Cinic10Index;
+use crate::error::{Result, bail};
+use crate::index::{
+    CONTRIB_FILE, DataSet, DatasetIndex, DatasetItem, ObjectClass, SYNSET_FILE,
+    parse_contrib_index, parse_synset_map,
+};
+use crate::parallel::par_fold;
+use crate::retry::with_retry;
+use crate::sample_id::SampleId;
+use anyhow::Context;
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The file format of an index manifest.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ManifestFormat {
+    /// A CSV file with a header row.
+    #[default]
+    Csv,
+
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl ManifestFormat {
+    /// Infer the format from a file extension: `.jsonl` is JSONL, anything else is CSV.
+    pub fn from_path<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        match path.as_ref().extension() {
+            Some(ext) if ext == "jsonl" => ManifestFormat::Jsonl,
+            _ => ManifestFormat::Csv,
+        }
+    }
+}
+
+/// One item of an index manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRow {
+    /// The image path, relative to the split directory, e.g. `"cat/n02123045_1.png"`.
+    pub path: PathBuf,
+    pub class: ObjectClass,
+
+    /// The class label, `ObjectClass::ordinal()`.
+    pub label: u8,
+
+    /// See `DatasetIndex::sample_id()`.
+    pub sample_id: Option<SampleId>,
+
+    /// The hex blake3 hash of the image file, if requested.
+    pub hash: Option<String>,
+}
+
+fn file_hash(path: &Path) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(with_retry(|| fs::File::open(path))?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+impl DatasetIndex {
+    /// Build the manifest rows of this index, in index order.
+    ///
+    /// # Parameters
+    ///
+    /// - `hashes`: Hash every image file.
+    /// - `parallelism`: The number of hashing threads; `0` means "all available cores".
+    pub fn manifest_rows(
+        &self,
+        hashes: bool,
+        parallelism: usize,
+    ) -> Result<Vec<ManifestRow>> {
+        let indices: Vec<usize> = (0..self.len()).collect();
+        par_fold(
+            &indices,
+            parallelism,
+            Vec::new,
+            |rows, &i| {
+                let class = self.index_to_class(i);
+                let hash = match hashes {
+                    true => Some(file_hash(&self.index_to_path(i))?),
+                    false => None,
+                };
+                rows.push(ManifestRow {
+                    path: self.rel_path(i),
+                    class,
+                    label: class.ordinal() as u8,
+                    sample_id: self.sample_id(i),
+                    hash,
+                });
+                Ok(())
+            },
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        )
+    }
+
+    /// Build an index from a manifest, instead of scanning directories.
+    ///
+    /// Items keep the manifest's order and membership, so exported subsets
+    /// and custom orderings are reproduced exactly.
+    /// The format is inferred with `ManifestFormat::from_path()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The split directory the manifest paths are relative to.
+    /// - `manifest_path`: The manifest file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; or an error if the manifest is malformed,
+    /// a label disagrees with its class, or any file is missing.
+    pub fn from_manifest<R, P>(
+        root: R,
+        manifest_path: P,
+    ) -> Result<Self>
+    where
+        R: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        Self::from_manifest_checked(root.as_ref(), manifest_path.as_ref(), true)
+    }
+
+    /// Build an index from a trusted manifest, without touching the image files.
+    ///
+    /// Like `from_manifest()`, but files are not checked for existence, so
+    /// construction costs one file read rather than a metadata syscall per
+    /// item; worthwhile on shared filesystems. Files are validated lazily:
+    /// a missing or corrupt file fails when it is first loaded, under the
+    /// loader's `ErrorPolicy`.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The split directory the manifest paths are relative to.
+    /// - `manifest_path`: The manifest file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index; or an error if the manifest is malformed,
+    /// or a label disagrees with its class.
+    pub fn from_trusted_manifest<R, P>(
+        root: R,
+        manifest_path: P,
+    ) -> Result<Self>
+    where
+        R: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        Self::from_manifest_checked(root.as_ref(), manifest_path.as_ref(), false)
+    }
+
+    fn from_manifest_checked(
+        root: &Path,
+        manifest_path: &Path,
+        check_files: bool,
+    ) -> Result<Self> {
+        let rows = read_manifest(manifest_path, ManifestFormat::from_path(manifest_path))?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        let mut missing = Vec::new();
+        for row in rows {
+            if row.label as usize != row.class.ordinal() as usize {
+                bail!(
+                    "Manifest label {} does not match class {} for {}",
+                    row.label,
+                    row.class,
+                    row.path.display()
+                );
+            }
+            let path = root.join(&row.path);
+            if check_files && !path.is_file() {
+                missing.push(row.path);
+                continue;
+            }
+            // Keep paths in the `{class}/{name}` layout relative, so the index is portable.
+            let path = match row.path.strip_prefix(row.class.to_string()) {
+                Ok(name) if name.components().count() == 1 => name.to_path_buf(),
+                _ => path,
+            };
+            items.push(DatasetItem {
+                class: row.class,
+                path,
+            });
+        }
+        if !missing.is_empty() {
+            bail!(
+                "{} manifest files missing under {}, first: {}",
+                missing.len(),
+                root.display(),
+                missing[0].display()
+            );
+        }
+
+        Ok(Self::new(root.to_path_buf(), items))
+    }
+
+    /// Write the index as a manifest, without file hashes.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The manifest file to write.
+    /// - `format`: The manifest format.
+    pub fn export_manifest<P>(
+        &self,
+        path: P,
+        format: ManifestFormat,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        write_manifest(path, format, &self.manifest_rows(false, 1)?)
+    }
+
+    /// Write the index as a manifest, including the blake3 hash of every file.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The manifest file to write.
+    /// - `format`: The manifest format.
+    /// - `parallelism`: The number of hashing threads; `0` means "all available cores".
+    pub fn export_manifest_with_hashes<P>(
+        &self,
+        path: P,
+        format: ManifestFormat,
+        parallelism: usize,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        write_manifest(path, format, &self.manifest_rows(true, parallelism)?)
+    }
+}
+
+/// The file name of a split's manifest in a manifest directory, e.g. `"train.csv"`.
+pub fn split_manifest_name(
+    data_set: DataSet,
+    format: ManifestFormat,
+) -> String {
+    match format {
+        ManifestFormat::Csv => format!("{data_set}.csv"),
+        ManifestFormat::Jsonl => format!("{data_set}.jsonl"),
+    }
+}
+
+impl Cinic10Index {
+    /// Write a manifest of every split to `dir`, named by `split_manifest_name()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `dir`: The manifest directory; created if missing.
+    /// - `format`: The manifest format.
+    pub fn export_manifests<P>(
+        &self,
+        dir: P,
+        format: ManifestFormat,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        for ds in DataSet::ALL {
+            self.split(ds)
+                .export_manifest(dir.join(split_manifest_name(ds, format)), format)?;
+        }
+        Ok(())
+    }
+
+    /// Build the index from trusted split manifests, skipping the directory scans.
+    ///
+    /// Each split is read from `manifest_dir/{split}.jsonl` or `manifest_dir/{split}.csv`
+    /// with `DatasetIndex::from_trusted_manifest()`; a split with no manifest
+    /// is scanned as by `new_from_dir()`. Write the manifests once with
+    /// `export_manifests()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The root directory of the CINIC-10 dataset.
+    /// - `manifest_dir`: The directory holding the split manifests.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index.
+    pub fn new_from_trusted_manifests<R, M>(
+        root: R,
+        manifest_dir: M,
+    ) -> Result<Self>
+    where
+        R: AsRef<Path>,
+        M: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let manifest_dir = manifest_dir.as_ref();
+        let load_split = |ds: DataSet| -> Result<DatasetIndex> {
+            let ds_path = root.join(ds.to_string());
+            let manifest = [ManifestFormat::Jsonl, ManifestFormat::Csv]
+                .into_iter()
+                .map(|format| manifest_dir.join(split_manifest_name(ds, format)))
+                .find(|path| path.is_file());
+            match manifest {
+                Some(manifest) => DatasetIndex::from_trusted_manifest(&ds_path, &manifest),
+                None => DatasetIndex::load_index_from_dir(&ds_path),
+            }
+        };
+
+        Ok(Cinic10Index::new(
+            root.to_path_buf(),
+            parse_contrib_index(with_retry(|| fs::File::open(root.join(CONTRIB_FILE)))?)?,
+            parse_synset_map(with_retry(|| fs::File::open(root.join(SYNSET_FILE)))?)?,
+            load_split(DataSet::Train)?,
+            load_split(DataSet::Test)?,
+            load_split(DataSet::Valid)?,
+        ))
+    }
+}
+
+/// Write manifest rows to a file.
+///
+/// # Parameters
+///
+/// - `path`: The manifest file to write.
+/// - `format`: The manifest format.
+/// - `rows`: The rows, in order.
+pub fn write_manifest<P>(
+    path: P,
+    format: ManifestFormat,
+    rows: &[ManifestRow],
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let file = fs::File::create(path)?;
+    match format {
+        ManifestFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(file);
+            for row in rows {
+                wtr.serialize(row)?;
+            }
+            wtr.flush()?;
+        }
+        ManifestFormat::Jsonl => {
+            let mut wtr = BufWriter::new(file);
+            for row in rows {
+                serde_json::to_writer(&mut wtr, row)?;
+                wtr.write_all(b"\n")?;
+            }
+            wtr.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Read manifest rows from a file.
+///
+/// # Parameters
+///
+/// - `path`: The manifest file.
+/// - `format`: The manifest format.
+///
+/// # Returns
+///
+/// A `Result` containing the rows, in file order.
+pub fn read_manifest<P>(
+    path: P,
+    format: ManifestFormat,
+) -> Result<Vec<ManifestRow>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file = with_retry(|| fs::File::open(path))?;
+    match format {
+        ManifestFormat::Csv => {
+            let mut rdr = csv::Reader::from_reader(file);
+            rdr.deserialize()
+                .enumerate()
+                .map(|(i, row)| {
+                    Ok(row.with_context(|| {
+                        format!("Malformed manifest row {} in {}", i + 1, path.display())
+                    })?)
+                })
+                .collect()
+        }
+        ManifestFormat::Jsonl => BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+            .map(|(i, line)| {
+                Ok(serde_json::from_str(&line?).with_context(|| {
+                    format!("Malformed manifest line {} in {}", i + 1, path.display())
+                })?)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_export_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ds_path = dir.path().join("train");
+        fs::create_dir_all(ds_path.join("cat"))?;
+        fs::write(ds_path.join("cat/a.png"), "a")?;
+        let index = DatasetIndex::new(
+            ds_path.clone(),
+            vec![DatasetItem {
+                class: ObjectClass::Cat,
+                path: ds_path.join("cat/a.png"),
+            }],
+        );
+
+        let csv_path = dir.path().join("manifest.csv");
+        index.export_manifest_with_hashes(&csv_path, ManifestFormat::Csv, 1)?;
+        let csv = fs::read_to_string(&csv_path)?;
+        assert_eq!(
+            csv,
+            format!(
+                "path,class,label,sample_id,hash\ncat/a.png,cat,3,train/a.png,{}\n",
+                blake3::hash(b"a").to_hex()
+            )
+        );
+
+        let jsonl_path = dir.path().join("manifest.jsonl");
+        index.export_manifest(&jsonl_path, ManifestFormat::Jsonl)?;
+        assert_eq!(
+            fs::read_to_string(&jsonl_path)?,
+            "{\"path\":\"cat/a.png\",\"class\":\"cat\",\"label\":3,\"sample_id\":\"train/a.png\",\"hash\":null}\n"
+        );
+
+        for manifest in [&csv_path, &jsonl_path] {
+            let loaded = DatasetIndex::from_manifest(&ds_path, manifest)?;
+            assert_eq!(loaded.fingerprint(), index.fingerprint());
+            assert_eq!(loaded.index_to_path(0), index.index_to_path(0));
+        }
+
+        fs::remove_file(ds_path.join("cat/a.png"))?;
+        assert!(DatasetIndex::from_manifest(&ds_path, &csv_path).is_err());
+        let trusted = DatasetIndex::from_trusted_manifest(&ds_path, &csv_path)?;
+        assert_eq!(trusted.index_to_path(0), index.index_to_path(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_from_trusted_manifests() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        fs::write(
+            root.join(CONTRIB_FILE),
+            "synset,image_num,cinic_set,class\nn02123045,1,train,cat\n",
+        )?;
+        fs::write(root.join(SYNSET_FILE), "cat\n--n02123045: tabby\n")?;
+
+        let manifests = root.join("manifests");
+        fs::create_dir_all(&manifests)?;
+        for ds in DataSet::ALL {
+            let rows = [ManifestRow {
+                path: PathBuf::from(format!("cat/n02123045_{ds}.png")),
+                class: ObjectClass::Cat,
+                label: ObjectClass::Cat.ordinal() as u8,
+                sample_id: None,
+                hash: None,
+            }];
+            let format = match ds {
+                DataSet::Train => ManifestFormat::Jsonl,
+                _ => ManifestFormat::Csv,
+            };
+            write_manifest(
+                manifests.join(split_manifest_name(ds, format)),
+                format,
+                &rows,
+            )?;
+        }
+
+        // No image exists; a trusted manifest is not checked until loading.
+        let cinic = Cinic10Index::new_from_trusted_manifests(root, &manifests)?;
+        assert_eq!(cinic.imagenet_contrib.len(), 1);
+        assert_eq!(
+            cinic.valid.index_to_path(0),
+            root.join("valid/cat/n02123045_valid.png")
+        );
+        assert!(cinic.train.load_rgbimagebatch(&[0]).is_err());
+
+        let exported = root.join("exported");
+        cinic.export_manifests(&exported, ManifestFormat::Csv)?;
+        let reloaded = Cinic10Index::new_from_trusted_manifests(root, &exported)?;
+        assert_eq!(reloaded.test.fingerprint(), cinic.test.fingerprint());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use crate::error::{Result, bail};
+use crate::index::{CHANNELS, DatasetIndex, HEIGHT, WIDTH};
+use crate::packed::PackedDataset;
+
+/// Element types a batch can be materialized as on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElementType {
+    U8,
+    F16,
+    F32,
+    F64,
+}
+
+impl ElementType {
+    /// Size of one element, in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            ElementType::U8 => 1,
+            ElementType::F16 => 2,
+            ElementType::F32 => 4,
+            ElementType::F64 => 8,
+        }
+    }
+}
+
+/// The dimensions of the images of a batch.
+///
+/// Dense `BHWC` and `BCHW` batches hold the same number of elements, so the
+/// dimensions, not the layout, decide the estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageDims {
+    pub height: usize,
+    pub width: usize,
+    pub channels: usize,
+}
+
+impl ImageDims {
+    /// The standard 32×32 RGB CINIC-10 images.
+    pub const CINIC10: ImageDims = ImageDims {
+        height: HEIGHT,
+        width: WIDTH,
+        channels: CHANNELS,
+    };
+
+    /// The dimensions of the images of a packed split, from its header.
+    pub fn of_packed(packed: &PackedDataset) -> Self {
+        let (width, height) = packed.dims();
+        ImageDims {
+            height,
+            width,
+            channels: CHANNELS,
+        }
+    }
+
+    /// The dimensions of the first image of a split, from its file header.
+    ///
+    /// Loaded batches are RGB, whatever the channels of the files.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the dimensions; or an error if the split is
+    /// empty, or its first image cannot be read.
+    pub fn of_split(split: &DatasetIndex) -> Result<Self> {
+        if split.is_empty() {
+            bail!("Cannot take image dimensions of an empty split");
+        }
+        let (width, height) = image::image_dimensions(split.abs_path(0))?;
+        Ok(ImageDims {
+            height: height as usize,
+            width: width as usize,
+            channels: CHANNELS,
+        })
+    }
+
+    /// Number of elements in a single image.
+    pub fn image_elements(&self) -> usize {
+        self.height * self.width * self.channels
+    }
+}
+
+impl Default for ImageDims {
+    fn default() -> Self {
+        Self::CINIC10
+    }
+}
+
+/// Estimate the size of a single batch of images.
+///
+/// # Parameters
+///
+/// - `batch_size`: The number of images in the batch.
+/// - `dtype`: The element type of the batch.
+/// - `dims`: The image dimensions; see `ImageDims::of_split()` and `ImageDims::of_packed()`.
+///
+/// # Returns
+///
+/// The batch size in bytes.
+pub fn estimate_batch_bytes(
+    batch_size: usize,
+    dtype: ElementType,
+    dims: ImageDims,
+) -> usize {
+    batch_size * dims.image_elements() * dtype.size()
+}
+
+/// Estimate the host memory held by a loader with `prefetch_depth` queued batches.
+///
+/// Each in-flight batch holds its decoded `u8` pixels; when `dtype` is not
+/// `U8`, the converted copy is counted as well. The batch being consumed
+/// counts as one in-flight batch in addition to the prefetched ones.
+///
+/// # Parameters
+///
+/// - `batch_size`: The number of images in each batch.
+/// - `prefetch_depth`: The number of batches loaded ahead of the consumer.
+/// - `dtype`: The element type batches are converted to.
+/// - `dims`: The image dimensions; see `ImageDims`.
+///
+/// # Returns
+///
+/// The estimated peak host memory, in bytes.
+pub fn estimate_loader_bytes(
+    batch_size: usize,
+    prefetch_depth: usize,
+    dtype: ElementType,
+    dims: ImageDims,
+) -> usize {
+    let mut per_batch = estimate_batch_bytes(batch_size, ElementType::U8, dims);
+    if dtype != ElementType::U8 {
+        per_batch += estimate_batch_bytes(batch_size, dtype, dims);
+    }
+    per_batch * (prefetch_depth + 1)
+}
+
+/// A suggested host-side loader configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchAdvice {
+    pub batch_size: usize,
+    pub prefetch_depth: usize,
+
+    /// The estimated peak host memory of this configuration, in bytes.
+    pub estimated_bytes: usize,
+}
+
+/// Suggest the largest batch size which fits a memory budget.
+///
+/// # Parameters
+///
+/// - `budget_bytes`: The host memory budget.
+/// - `prefetch_depth`: The desired number of prefetched batches.
+/// - `dtype`: The element type batches are converted to.
+/// - `dims`: The image dimensions; see `ImageDims`.
+///
+/// # Returns
+///
+/// The advice, or `None` if not even a single-image batch fits.
+pub fn advise_batch_size(
+    budget_bytes: usize,
+    prefetch_depth: usize,
+    dtype: ElementType,
+    dims: ImageDims,
+) -> Option<BatchAdvice> {
+    let per_image = estimate_loader_bytes(1, prefetch_depth, dtype, dims);
+    let batch_size = budget_bytes / per_image;
+    if batch_size == 0 {
+        return None;
+    }
+    Some(BatchAdvice {
+        batch_size,
+        prefetch_depth,
+        estimated_bytes: estimate_loader_bytes(batch_size, prefetch_depth, dtype, dims),
+    })
+}
+
+/// Suggest the deepest prefetch queue which fits a memory budget.
+///
+/// # Parameters
+///
+/// - `budget_bytes`: The host memory budget.
+/// - `batch_size`: The desired batch size.
+/// - `dtype`: The element type batches are converted to.
+/// - `dims`: The image dimensions; see `ImageDims`.
+///
+/// # Returns
+///
+/// The advice, or `None` if not even the batch being consumed fits.
+pub fn advise_prefetch_depth(
+    budget_bytes: usize,
+    batch_size: usize,
+    dtype: ElementType,
+    dims: ImageDims,
+) -> Option<BatchAdvice> {
+    let per_batch = estimate_loader_bytes(batch_size, 0, dtype, dims);
+    if per_batch == 0 || per_batch > budget_bytes {
+        return None;
+    }
+    let prefetch_depth = budget_bytes / per_batch - 1;
+    Some(BatchAdvice {
+        batch_size,
+        prefetch_depth,
+        estimated_bytes: estimate_loader_bytes(batch_size, prefetch_depth, dtype, dims),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::loader::BatchLoader;
+    use crate::mock::fixtures;
+    use crate::packed::open_or_pack;
+    use image::RgbImage;
+
+    #[test]
+    fn test_estimate_batch_bytes() {
+        assert_eq!(
+            estimate_batch_bytes(10, ElementType::U8, ImageDims::CINIC10),
+            10 * 32 * 32 * 3
+        );
+        assert_eq!(
+            estimate_batch_bytes(10, ElementType::F32, ImageDims::CINIC10),
+            4 * 10 * 32 * 32 * 3
+        );
+        assert_eq!(
+            estimate_loader_bytes(10, 2, ElementType::F32, ImageDims::CINIC10),
+            3 * 5 * 10 * 32 * 32 * 3
+        );
+
+        let dims = ImageDims {
+            height: 64,
+            width: 48,
+            channels: 3,
+        };
+        assert_eq!(
+            estimate_batch_bytes(10, ElementType::U8, dims),
+            10 * 64 * 48 * 3
+        );
+    }
+
+    #[test]
+    fn test_image_dims() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = fixtures::write_split(
+            dir.path().join("train"),
+            [(ObjectClass::Cat, "a.png", RgbImage::new(5, 4))],
+        )?;
+        let dims = ImageDims::of_split(&split)?;
+        assert_eq!(
+            dims,
+            ImageDims {
+                height: 4,
+                width: 5,
+                channels: 3
+            }
+        );
+
+        let packed = open_or_pack(&split, &BatchLoader::default())?;
+        assert_eq!(ImageDims::of_packed(&packed), dims);
+        assert!(ImageDims::of_split(&fixtures::split_of_classes("/x", &[])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_advice() {
+        let image = 32 * 32 * 3;
+
+        let advice =
+            advise_batch_size(100 * image, 1, ElementType::U8, ImageDims::CINIC10).unwrap();
+        assert_eq!(advice.batch_size, 50);
+        assert_eq!(advice.estimated_bytes, 100 * image);
+
+        let advice =
+            advise_prefetch_depth(100 * image, 10, ElementType::F32, ImageDims::CINIC10).unwrap();
+        assert_eq!(advice.prefetch_depth, 1);
+        assert!(advice.estimated_bytes <= 100 * image);
+
+        assert!(advise_batch_size(image, 1, ElementType::U8, ImageDims::CINIC10).is_none());
+        assert!(advise_prefetch_depth(image, 2, ElementType::U8, ImageDims::CINIC10).is_none());
+    }
+}
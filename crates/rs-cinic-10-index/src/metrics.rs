@@ -0,0 +1,233 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// A receiver for input pipeline metrics.
+///
+/// All methods have no-op defaults, so sinks only need to implement
+/// the measurements they care about.
+pub trait MetricsSink: Send + Sync {
+    /// Called after `count` images have been decoded.
+    fn images_decoded(
+        &self,
+        _count: u64,
+    ) {
+    }
+
+    /// Called after `bytes` have been read from storage.
+    fn bytes_read(
+        &self,
+        _bytes: u64,
+    ) {
+    }
+
+    /// Called when a cached read is served from the cache.
+    fn cache_hit(&self) {}
+
+    /// Called when a cached read has to go to the underlying storage.
+    fn cache_miss(&self) {}
+
+    /// Called with the wall-clock latency of each completed batch load.
+    fn batch_latency(
+        &self,
+        _latency: Duration,
+    ) {
+    }
+}
+
+static STATIC_METRICS_SINK: RwLock<Option<Arc<dyn MetricsSink>>> = RwLock::new(None);
+
+/// Get the currently installed metrics sink, if any.
+pub fn get_metrics_sink() -> Option<Arc<dyn MetricsSink>> {
+    STATIC_METRICS_SINK.read().unwrap().clone()
+}
+
+/// Install (or with `None`, remove) the process-wide metrics sink.
+pub fn set_metrics_sink(sink: Option<Arc<dyn MetricsSink>>) {
+    *STATIC_METRICS_SINK.write().unwrap() = sink;
+}
+
+/// Run `f` against the installed sink; does nothing when no sink is installed.
+pub(crate) fn record<F>(f: F)
+where
+    F: FnOnce(&dyn MetricsSink),
+{
+    if let Some(sink) = STATIC_METRICS_SINK.read().unwrap().as_deref() {
+        f(sink);
+    }
+}
+
+/// Upper bounds (in microseconds) of the `AtomicMetrics` latency histogram buckets.
+///
+/// Latencies above the last bound land in a final overflow bucket.
+pub const LATENCY_BUCKETS_US: [u64; 12] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+
+/// A lock-free, in-process `MetricsSink` which accumulates counters.
+#[derive(Debug, Default)]
+pub struct AtomicMetrics {
+    images_decoded: AtomicU64,
+    bytes_read: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    batch_latency: [AtomicU64; LATENCY_BUCKETS_US.len() + 1],
+}
+
+/// A point-in-time copy of the counters in an `AtomicMetrics`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub images_decoded: u64,
+    pub bytes_read: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+
+    /// Batch counts per `LATENCY_BUCKETS_US` bucket, plus the overflow bucket.
+    pub batch_latency: Vec<u64>,
+}
+
+impl MetricsSnapshot {
+    /// The fraction of cached reads served from the cache, if any were made.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            None
+        } else {
+            Some(self.cache_hits as f64 / total as f64)
+        }
+    }
+
+    /// The total number of batches recorded.
+    pub fn batches(&self) -> u64 {
+        self.batch_latency.iter().sum()
+    }
+}
+
+impl AtomicMetrics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Take a snapshot of the current counter values.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            images_decoded: self.images_decoded.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            batch_latency: self
+                .batch_latency
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+impl MetricsSink for AtomicMetrics {
+    fn images_decoded(
+        &self,
+        count: u64,
+    ) {
+        self.images_decoded.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn bytes_read(
+        &self,
+        bytes: u64,
+    ) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn batch_latency(
+        &self,
+        latency: Duration,
+    ) {
+        let us = latency.as_micros() as u64;
+        let bucket = LATENCY_BUCKETS_US
+            .iter()
+            .position(|&bound| us <= bound)
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+        self.batch_latency[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A `MetricsSink` which forwards to the [metrics](https://crates.io/crates/metrics) facade.
+///
+/// Reports `cinic10_images_decoded_total`, `cinic10_bytes_read_total`,
+/// `cinic10_cache_hits_total`, `cinic10_cache_misses_total`
+/// and the `cinic10_batch_latency_seconds` histogram.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MetricsCrateSink;
+
+#[cfg(feature = "metrics")]
+impl MetricsSink for MetricsCrateSink {
+    fn images_decoded(
+        &self,
+        count: u64,
+    ) {
+        metrics::counter!("cinic10_images_decoded_total").increment(count);
+    }
+
+    fn bytes_read(
+        &self,
+        bytes: u64,
+    ) {
+        metrics::counter!("cinic10_bytes_read_total").increment(bytes);
+    }
+
+    fn cache_hit(&self) {
+        metrics::counter!("cinic10_cache_hits_total").increment(1);
+    }
+
+    fn cache_miss(&self) {
+        metrics::counter!("cinic10_cache_misses_total").increment(1);
+    }
+
+    fn batch_latency(
+        &self,
+        latency: Duration,
+    ) {
+        metrics::histogram!("cinic10_batch_latency_seconds").record(latency.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atomic_metrics() {
+        let metrics = AtomicMetrics::new();
+        assert_eq!(metrics.snapshot().cache_hit_rate(), None);
+
+        metrics.images_decoded(3);
+        metrics.bytes_read(1024);
+        metrics.bytes_read(1024);
+        metrics.cache_hit();
+        metrics.cache_hit();
+        metrics.cache_hit();
+        metrics.cache_miss();
+        metrics.batch_latency(Duration::from_micros(50));
+        metrics.batch_latency(Duration::from_millis(3));
+        metrics.batch_latency(Duration::from_secs(5));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.images_decoded, 3);
+        assert_eq!(snapshot.bytes_read, 2048);
+        assert_eq!(snapshot.cache_hit_rate(), Some(0.75));
+        assert_eq!(snapshot.batches(), 3);
+        assert_eq!(snapshot.batch_latency[0], 1);
+        assert_eq!(snapshot.batch_latency[5], 1);
+        assert_eq!(snapshot.batch_latency[LATENCY_BUCKETS_US.len()], 1);
+    }
+}
@@ -0,0 +1,410 @@
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::index::ObjectClass;
+use crate::loader::{BatchLoader, LoadedBatch};
+use crate::profile::Stage;
+use crate::stats::ClassDistribution;
+use enum_ordinalize::Ordinalize;
+use image::{Rgb, RgbImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::PathBuf;
+
+/// A synthetic dataset, which fabricates deterministic images and labels.
+///
+/// Mirrors the `DatasetIndex` query and loading API, without touching the
+/// disk; for benchmarking and smoke-testing training loops when no dataset
+/// is present. Each sample is generated from `(seed, index)` alone, so any
+/// sample can be regenerated in any order.
+///
+/// Images are uniform noise around a per-class base color, so a model can
+/// learn the labels; the labels are drawn uniformly at random.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockIndex {
+    len: usize,
+    seed: u64,
+    width: u32,
+    height: u32,
+}
+
+impl MockIndex {
+    /// The amount each channel varies around the class base color.
+    const NOISE: u8 = 64;
+
+    /// A mock split of `len` 32x32 samples.
+    pub fn new(
+        len: usize,
+        seed: u64,
+    ) -> Self {
+        Self {
+            len,
+            seed,
+            width: 32,
+            height: 32,
+        }
+    }
+
+    /// Generate images of the given size, rather than 32x32.
+    pub fn with_dims(
+        mut self,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        assert!(width > 0 && height > 0, "Mock images must be non-empty");
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn dims(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The generator for one sample.
+    fn sample_rng(
+        &self,
+        index: usize,
+    ) -> StdRng {
+        assert!(
+            index < self.len,
+            "Index {index} out of range for a mock split of {}",
+            self.len
+        );
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"mock");
+        hasher.update(&self.seed.to_le_bytes());
+        hasher.update(&(index as u64).to_le_bytes());
+        StdRng::from_seed(*hasher.finalize().as_bytes())
+    }
+
+    /// The pseudo-path of a sample, e.g. `mock/cat/mock-17.png`.
+    ///
+    /// Nothing exists at this path; it names the sample in reports, and seeds
+    /// loader transforms, as a real path would.
+    pub fn index_to_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        PathBuf::from("mock")
+            .join(self.index_to_class(index).to_string())
+            .join(format!("mock-{index}.png"))
+    }
+
+    /// Convert a slice of indices to a vector of pseudo-paths.
+    pub fn indices_to_paths(
+        &self,
+        indices: &[usize],
+    ) -> Vec<PathBuf> {
+        indices.iter().map(|&i| self.index_to_path(i)).collect()
+    }
+
+    /// Convert an item index to an object class.
+    pub fn index_to_class(
+        &self,
+        index: usize,
+    ) -> ObjectClass {
+        let ordinal = self
+            .sample_rng(index)
+            .random_range(0..ObjectClass::ALL.len());
+        ObjectClass::ALL[ordinal]
+    }
+
+    /// Convert a slice of indices to a vector of object classes.
+    pub fn indices_to_classes(
+        &self,
+        indices: &[usize],
+    ) -> Vec<ObjectClass> {
+        indices.iter().map(|&i| self.index_to_class(i)).collect()
+    }
+
+    /// Count the items of each class.
+    pub fn class_distribution(&self) -> ClassDistribution {
+        ClassDistribution::from_classes((0..self.len).map(|i| self.index_to_class(i)))
+    }
+
+    /// Generate the image of one sample.
+    pub fn image(
+        &self,
+        index: usize,
+    ) -> RgbImage {
+        let mut rng = self.sample_rng(index);
+        let class = ObjectClass::ALL[rng.random_range(0..ObjectClass::ALL.len())];
+        let base = class_color(class);
+        RgbImage::from_fn(self.width, self.height, |_, _| {
+            Rgb(base.map(|c| c.saturating_add(rng.random_range(0..Self::NOISE))))
+        })
+    }
+
+    /// Generate an `RgbImageBatch` for a batch of indexes.
+    pub fn load_rgbimagebatch(
+        &self,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        self.load_rgbimagebatch_with(&BatchLoader::default(), indices)
+    }
+
+    /// Generate an `RgbImageBatch` for a batch of indexes, using the given loader.
+    pub fn load_rgbimagebatch_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        Ok(self.load_rgbimagebatch_report_with(loader, indices)?.batch)
+    }
+
+    /// Generate an `RgbImageBatch` with the given loader.
+    ///
+    /// The loader's transform is applied as for a real split, and generation
+    /// is profiled as `Stage::Decode`; no sample ever fails.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of indices to generate.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the batch, with an empty failure report.
+    pub fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        if indices.is_empty() {
+            bail!("Cannot load an empty batch");
+        }
+        let mut batch = None;
+        for &index in indices {
+            let img = loader.time(Stage::Decode, || self.image(index));
+            let img = loader.transform_image(&self.index_to_path(index), img)?;
+            let batch = batch.get_or_insert_with(|| {
+                RgbImageBatch::new(&[
+                    indices.len(),
+                    img.height() as usize,
+                    img.width() as usize,
+                    3,
+                ])
+            });
+            if (img.width() as usize, img.height() as usize) != (batch.width(), batch.height()) {
+                bail!(
+                    "Transformed mock image {index} is {:?}, expected {:?}",
+                    img.dimensions(),
+                    (batch.width(), batch.height())
+                );
+            }
+            loader.time(Stage::Copy, || batch.push_rgb_pixels(&img));
+        }
+        let mut batch = batch.unwrap();
+        loader.convert_color_space(&mut batch);
+        if let Some(profiler) = loader.profiler() {
+            profiler.record_batch();
+        }
+        Ok(LoadedBatch {
+            batch,
+            failures: Vec::new(),
+        })
+    }
+}
+
+/// A distinct base color per class, spread around the hue circle.
+fn class_color(class: ObjectClass) -> [u8; 3] {
+    let hue = class.ordinal() as f32 / ObjectClass::ALL.len() as f32 * 6.0;
+    let x = 1.0 - ((hue % 2.0) - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|c: f32| (c * 191.0) as u8)
+}
+
+/// Shared test fixtures: in-memory indexes naming files which need not exist.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::error::Result;
+    use crate::index::{Cinic10Index, DatasetIndex, DatasetItem, ObjectClass};
+    use image::RgbImage;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// A split at `ds_path` of `(class, name)` items, in order.
+    pub(crate) fn split<P, I, S>(
+        ds_path: P,
+        items: I,
+    ) -> DatasetIndex
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (ObjectClass, S)>,
+        S: AsRef<Path>,
+    {
+        DatasetIndex::new(
+            ds_path.as_ref().to_path_buf(),
+            items
+                .into_iter()
+                .map(|(class, name)| DatasetItem {
+                    class,
+                    path: name.as_ref().to_path_buf(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Save `(class, name, image)` items to `ds_path/{class}/{name}`, and index them in order.
+    pub(crate) fn write_split<P, I, S>(
+        ds_path: P,
+        items: I,
+    ) -> Result<DatasetIndex>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = (ObjectClass, S, RgbImage)>,
+        S: AsRef<Path>,
+    {
+        let ds_path = ds_path.as_ref();
+        let mut names = Vec::new();
+        for (class, name, img) in items {
+            let class_dir = ds_path.join(class.to_string());
+            fs::create_dir_all(&class_dir)?;
+            img.save(class_dir.join(name.as_ref()))?;
+            names.push((class, name));
+        }
+        Ok(split(ds_path, names))
+    }
+
+    /// A split at `ds_path` of `names`, all of `class`.
+    pub(crate) fn split_of_names<P>(
+        ds_path: P,
+        class: ObjectClass,
+        names: &[&str],
+    ) -> DatasetIndex
+    where
+        P: AsRef<Path>,
+    {
+        split(ds_path, names.iter().map(|name| (class, name)))
+    }
+
+    /// A split at `ds_path` whose item `i` is `{i}.png`, of class `classes[i]`.
+    pub(crate) fn split_of_classes<P>(
+        ds_path: P,
+        classes: &[ObjectClass],
+    ) -> DatasetIndex
+    where
+        P: AsRef<Path>,
+    {
+        split(
+            ds_path,
+            classes
+                .iter()
+                .enumerate()
+                .map(|(i, &class)| (class, format!("{i}.png"))),
+        )
+    }
+
+    /// A class-contiguous split at `ds_path`, of `per_class` items of each of `classes`.
+    pub(crate) fn numbered_split<P>(
+        ds_path: P,
+        classes: &[ObjectClass],
+        per_class: usize,
+    ) -> DatasetIndex
+    where
+        P: AsRef<Path>,
+    {
+        let classes: Vec<ObjectClass> = classes
+            .iter()
+            .flat_map(|&class| std::iter::repeat_n(class, per_class))
+            .collect();
+        split_of_classes(ds_path, &classes)
+    }
+
+    /// A `Cinic10Index` at `root` of the given splits, with no contrib records or synsets.
+    pub(crate) fn cinic<P>(
+        root: P,
+        train: DatasetIndex,
+        test: DatasetIndex,
+        valid: DatasetIndex,
+    ) -> Cinic10Index
+    where
+        P: AsRef<Path>,
+    {
+        Cinic10Index::new(
+            PathBuf::from(root.as_ref()),
+            Vec::new(),
+            Default::default(),
+            train,
+            test,
+            valid,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::LoaderConfig;
+    use crate::record::{ComponentRecord, Recordable};
+    use crate::transform::ImageTransform;
+    use rand::RngCore;
+    use std::sync::Arc;
+
+    struct Invert;
+
+    impl Recordable for Invert {
+        fn component_record(&self) -> ComponentRecord {
+            ComponentRecord::new("invert")
+        }
+    }
+
+    impl ImageTransform for Invert {
+        fn apply(
+            &self,
+            mut img: RgbImage,
+            _rng: &mut dyn RngCore,
+        ) -> Result<RgbImage> {
+            image::imageops::invert(&mut img);
+            Ok(img)
+        }
+    }
+
+    #[test]
+    fn test_mock_index() -> Result<()> {
+        let mock = MockIndex::new(100, 7);
+        assert_eq!(mock.len(), 100);
+        assert_eq!(mock.image(3), MockIndex::new(100, 7).image(3));
+        assert_ne!(mock.image(3), MockIndex::new(100, 8).image(3));
+        assert_eq!(mock.class_distribution().total(), 100);
+
+        let batch = mock.load_rgbimagebatch(&[5, 1, 5])?;
+        assert_eq!(batch.shape, vec![3, 32, 32, 3]);
+        assert_eq!(batch.image(0), mock.image(5));
+        assert_eq!(batch.image(0), batch.image(2));
+
+        let loader = BatchLoader::new(LoaderConfig {
+            profile: true,
+            ..Default::default()
+        })
+        .with_transform(Arc::new(Invert), 0);
+        let small = mock.with_dims(4, 2);
+        let inverted = small.load_rgbimagebatch_report_with(&loader, &[1])?;
+        assert!(inverted.failures.is_empty());
+        let mut expected = small.image(1);
+        image::imageops::invert(&mut expected);
+        assert_eq!(inverted.batch.image(0), expected);
+        assert_eq!(loader.profile_report().unwrap().batches, 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::{Result, bail};
+use crate::index::{DatasetIndex, ObjectClass, list_pngs_sorted};
+use crate::record::{ComponentRecord, Recordable};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+/// Where an open-set evaluation sample came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenSetSource {
+    /// A CINIC-10 split.
+    Split,
+
+    /// An external image pool.
+    External,
+}
+
+/// One tagged sample of an open-set evaluation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenSetSample {
+    pub path: PathBuf,
+
+    /// The CINIC-10 class, if any; external images have none.
+    pub class: Option<ObjectClass>,
+
+    /// Whether the sample belongs to a known class.
+    pub known: bool,
+
+    pub source: OpenSetSource,
+}
+
+/// A reproducible open-set recognition protocol.
+///
+/// Samples of the `known` classes are tagged known; samples of the remaining
+/// ("held-out") classes and of any external pools are tagged unknown.
+/// Models should be trained only on `known_indices()` of the training split.
+#[derive(Debug, Clone)]
+pub struct OpenSetProtocol {
+    known: Vec<ObjectClass>,
+    include_holdout: bool,
+    splits: Vec<DatasetIndex>,
+    external: Vec<PathBuf>,
+    max_unknown_ratio: Option<f32>,
+    seed: u64,
+}
+
+impl OpenSetProtocol {
+    /// Create a protocol with the given known classes.
+    ///
+    /// Held-out classes are included as unknowns by default.
+    pub fn new(known: &[ObjectClass]) -> Self {
+        Self {
+            known: known.to_vec(),
+            include_holdout: true,
+            splits: Vec::new(),
+            external: Vec::new(),
+            max_unknown_ratio: None,
+            seed: 0,
+        }
+    }
+
+    pub fn known_classes(&self) -> &[ObjectClass] {
+        &self.known
+    }
+
+    /// The classes which are not known.
+    pub fn holdout_classes(&self) -> Vec<ObjectClass> {
+        ObjectClass::iter()
+            .filter(|c| !self.known.contains(c))
+            .collect()
+    }
+
+    /// Add an in-distribution evaluation split.
+    pub fn with_split(
+        mut self,
+        split: &DatasetIndex,
+    ) -> Self {
+        self.splits.push(split.clone());
+        self
+    }
+
+    /// Whether held-out class samples of the splits are included as unknowns.
+    pub fn with_holdout(
+        mut self,
+        include: bool,
+    ) -> Self {
+        self.include_holdout = include;
+        self
+    }
+
+    /// Add external images as unknowns.
+    pub fn with_external_paths<I>(
+        mut self,
+        paths: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        self.external.extend(paths);
+        self
+    }
+
+    /// Add the PNG files of a directory as unknowns.
+    pub fn with_external_dir<P>(
+        self,
+        dir: P,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(self.with_external_paths(list_pngs_sorted(dir)?))
+    }
+
+    /// Subsample unknowns to at most `ratio` times the number of knowns.
+    pub fn with_max_unknown_ratio(
+        mut self,
+        ratio: f32,
+    ) -> Self {
+        self.max_unknown_ratio = Some(ratio);
+        self
+    }
+
+    /// Set the seed for shuffling and subsampling.
+    pub fn with_seed(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The indices of the known-class samples of a (training) split.
+    pub fn known_indices(
+        &self,
+        split: &DatasetIndex,
+    ) -> Vec<usize> {
+        (0..split.len())
+            .filter(|&i| self.known.contains(&split.index_to_class(i)))
+            .collect()
+    }
+
+    /// Build the tagged evaluation set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the evaluation, shuffled with the protocol seed;
+    /// or an error if there are no known classes, or no unknown samples.
+    pub fn build(&self) -> Result<OpenSetEval> {
+        if self.known.is_empty() {
+            bail!("Open-set protocol has no known classes");
+        }
+
+        let mut known = Vec::new();
+        let mut unknown = Vec::new();
+        for split in &self.splits {
+            for i in 0..split.len() {
+                let class = split.index_to_class(i);
+                let is_known = self.known.contains(&class);
+                if !is_known && !self.include_holdout {
+                    continue;
+                }
+                let sample = OpenSetSample {
+                    path: split.index_to_path(i),
+                    class: Some(class),
+                    known: is_known,
+                    source: OpenSetSource::Split,
+                };
+                if is_known {
+                    known.push(sample);
+                } else {
+                    unknown.push(sample);
+                }
+            }
+        }
+        unknown.extend(self.external.iter().map(|path| OpenSetSample {
+            path: path.clone(),
+            class: None,
+            known: false,
+            source: OpenSetSource::External,
+        }));
+        if unknown.is_empty() {
+            bail!("Open-set protocol has no unknown samples");
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        if let Some(ratio) = self.max_unknown_ratio {
+            let limit = (known.len() as f32 * ratio).round() as usize;
+            if unknown.len() > limit {
+                unknown.shuffle(&mut rng);
+                unknown.truncate(limit);
+            }
+        }
+
+        let mut samples = known;
+        samples.extend(unknown);
+        samples.shuffle(&mut rng);
+        Ok(OpenSetEval { samples })
+    }
+}
+
+impl Recordable for OpenSetProtocol {
+    fn component_record(&self) -> ComponentRecord {
+        let known: Vec<String> = self.known.iter().map(|c| c.to_string()).collect();
+        let mut record = ComponentRecord::new("open_set_protocol")
+            .with_param("known", known)
+            .with_param("include_holdout", self.include_holdout)
+            .with_param("external", self.external.len())
+            .with_param("seed", self.seed);
+        if let Some(ratio) = self.max_unknown_ratio {
+            record = record.with_param("max_unknown_ratio", ratio);
+        }
+        record
+    }
+}
+
+/// The tagged samples of an open-set evaluation, from `OpenSetProtocol::build()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenSetEval {
+    pub samples: Vec<OpenSetSample>,
+}
+
+impl OpenSetEval {
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &OpenSetSample> {
+        self.samples.iter()
+    }
+
+    pub fn known(&self) -> impl Iterator<Item = &OpenSetSample> {
+        self.samples.iter().filter(|s| s.known)
+    }
+
+    pub fn unknown(&self) -> impl Iterator<Item = &OpenSetSample> {
+        self.samples.iter().filter(|s| !s.known)
+    }
+
+    /// Iterate over the samples in batches; the last batch may be short.
+    pub fn batches(
+        &self,
+        batch_size: usize,
+    ) -> impl Iterator<Item = &[OpenSetSample]> {
+        self.samples.chunks(batch_size.max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    #[test]
+    fn test_open_set_protocol() -> Result<()> {
+        let split = fixtures::numbered_split(
+            "/data/test",
+            &[ObjectClass::Cat, ObjectClass::Dog, ObjectClass::Ship],
+            4,
+        );
+
+        let protocol = OpenSetProtocol::new(&[ObjectClass::Cat, ObjectClass::Dog])
+            .with_split(&split)
+            .with_external_paths([PathBuf::from("/svhn/0.png")])
+            .with_seed(7);
+        assert_eq!(protocol.holdout_classes().len(), 8);
+        assert_eq!(protocol.known_indices(&split), (0..8).collect::<Vec<_>>());
+
+        let eval = protocol.build()?;
+        assert_eq!(eval.len(), 13);
+        assert_eq!(eval.known().count(), 8);
+        assert_eq!(eval.unknown().count(), 5);
+        assert!(
+            eval.unknown()
+                .all(|s| s.class.is_none() || s.class == Some(ObjectClass::Ship))
+        );
+        assert_eq!(eval, protocol.build()?);
+        assert_eq!(
+            eval.batches(5).map(|b| b.len()).collect::<Vec<_>>(),
+            [5, 5, 3]
+        );
+
+        let balanced = protocol.clone().with_max_unknown_ratio(0.25).build()?;
+        assert_eq!(balanced.unknown().count(), 2);
+
+        let external_only = protocol.with_holdout(false).build()?;
+        assert!(
+            external_only
+                .unknown()
+                .all(|s| s.source == OpenSetSource::External)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use crate::index::{DatasetIndex, DatasetItem};
+use crate::provenance::{CifarSplit, ItemSource, parse_item_path};
+use crate::wnid::WnId;
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// The order of the items of each class of a scanned split.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum FileOrdering {
+    /// By file name, byte-wise; `cifar10-train-10.png` precedes `cifar10-train-2.png`.
+    #[default]
+    Lexical,
+
+    /// By file name, comparing runs of digits as numbers; `-2` precedes `-10`.
+    Natural,
+
+    /// By original dataset id: CIFAR-10 images by `(split, index)`, then
+    /// ImageNet images by `(synset, number)`; other names last, lexically.
+    OriginalId,
+}
+
+impl FileOrdering {
+    /// Is this the scan order, `FileOrdering::Lexical`?
+    pub fn is_lexical(&self) -> bool {
+        *self == FileOrdering::Lexical
+    }
+}
+
+/// Compare two strings, treating runs of ASCII digits as numbers.
+///
+/// Numerically equal runs with different zero padding are ordered by their
+/// length, shortest first; so the order is total, e.g. `a2b` < `a02a`.
+pub fn natural_cmp(
+    a: &str,
+    b: &str,
+) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (run_a, rest_a) = split_digits(a);
+                let (run_b, rest_b) = split_digits(b);
+                let (trim_a, trim_b) = (trim_zeros(run_a), trim_zeros(run_b));
+                let order = trim_a
+                    .len()
+                    .cmp(&trim_b.len())
+                    .then_with(|| trim_a.cmp(trim_b))
+                    .then_with(|| run_a.len().cmp(&run_b.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                (a, b) = (rest_a, rest_b);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    s.split_at(s.iter().take_while(|c| c.is_ascii_digit()).count())
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+/// The `FileOrdering::OriginalId` sort key of an item.
+type OriginalId = (u8, Option<CifarSplit>, Option<WnId>, usize);
+
+fn original_id(item: &DatasetItem) -> Option<OriginalId> {
+    let name = parse_item_path(&item.path)?;
+    let source = match name.source {
+        ItemSource::Cifar10 => 0,
+        ItemSource::ImageNet => 1,
+    };
+    Some((source, name.source_split, name.synset, name.number))
+}
+
+impl FileOrdering {
+    /// Compare two items of the same class.
+    pub fn compare(
+        &self,
+        a: &DatasetItem,
+        b: &DatasetItem,
+    ) -> Ordering {
+        match self {
+            FileOrdering::Lexical => a.path.cmp(&b.path),
+            FileOrdering::Natural => {
+                natural_cmp(&a.path.to_string_lossy(), &b.path.to_string_lossy())
+            }
+            // Unparsed names go after every parsed one.
+            FileOrdering::OriginalId => match (original_id(a), original_id(b)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.path.cmp(&b.path),
+            },
+        }
+    }
+}
+
+impl DatasetIndex {
+    /// Sort the items by class, and within each class by `ordering`.
+    ///
+    /// Scans produce `FileOrdering::Lexical` order; the new ordering is
+    /// recorded, see `ordering()`, and kept by `refresh()`.
+    /// The parts of a concatenated index are sorted separately, in place.
+    pub fn sort_items(
+        &mut self,
+        ordering: FileOrdering,
+    ) {
+        let mut items: Vec<DatasetItem> = self.items().collect();
+        let mut start = 0;
+        for run in self.runs() {
+            items[start..start + run.len].sort_by(|a, b| {
+                a.class
+                    .ordinal()
+                    .cmp(&b.class.ordinal())
+                    .then_with(|| ordering.compare(a, b))
+            });
+            start += run.len;
+        }
+        self.replace_items(items);
+        self.set_ordering(ordering);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use std::path::Path;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec!["a10", "a2", "a02", "b1", "a", "a2b", "a2a"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, vec!["a", "a2", "a2a", "a2b", "a02", "a10", "b1"]);
+        assert_eq!(natural_cmp("x007", "x007"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_sort_items() {
+        let mut split = fixtures::split(
+            "/cinic/train",
+            [
+                (ObjectClass::Dog, "n00000009_1.png"),
+                (ObjectClass::Dog, "cifar10-train-10.png"),
+                (ObjectClass::Cat, "z.png"),
+                (ObjectClass::Dog, "n00000002_5.png"),
+                (ObjectClass::Dog, "cifar10-train-2.png"),
+                (ObjectClass::Dog, "cifar10-test-30.png"),
+            ],
+        );
+        let names = |split: &DatasetIndex| -> Vec<String> {
+            split
+                .items()
+                .map(|i| i.path.to_string_lossy().into_owned())
+                .collect()
+        };
+
+        split.sort_items(FileOrdering::Lexical);
+        assert_eq!(
+            names(&split),
+            [
+                "z.png",
+                "cifar10-test-30.png",
+                "cifar10-train-10.png",
+                "cifar10-train-2.png",
+                "n00000002_5.png",
+                "n00000009_1.png",
+            ]
+        );
+
+        split.sort_items(FileOrdering::Natural);
+        assert_eq!(
+            names(&split)[2..4],
+            ["cifar10-train-2.png", "cifar10-train-10.png"]
+        );
+
+        split.sort_items(FileOrdering::OriginalId);
+        assert_eq!(
+            names(&split)[1..],
+            [
+                "cifar10-train-2.png",
+                "cifar10-train-10.png",
+                "cifar10-test-30.png",
+                "n00000002_5.png",
+                "n00000009_1.png",
+            ]
+        );
+        assert_eq!(
+            split.index_of_path(Path::new("cifar10-test-30.png")),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_refresh_keeps_ordering() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cat = dir.path().join("cat");
+        std::fs::create_dir_all(&cat)?;
+        for name in ["a10.png", "a2.png"] {
+            std::fs::write(cat.join(name), b"")?;
+        }
+
+        let mut split = DatasetIndex::new(dir.path().to_path_buf(), Vec::new());
+        split.refresh()?;
+        split.sort_items(FileOrdering::Natural);
+        assert_eq!(split.ordering(), FileOrdering::Natural);
+
+        std::fs::write(cat.join("a1.png"), b"")?;
+        split.refresh()?;
+        assert_eq!(
+            split.indices_to_paths(&[0, 1, 2]),
+            ["a1.png", "a2.png", "a10.png"].map(|name| cat.join(name))
+        );
+
+        let loaded: DatasetIndex = serde_json::from_str(&serde_json::to_string(&split)?)?;
+        assert_eq!(loaded.ordering(), FileOrdering::Natural);
+
+        Ok(())
+    }
+}
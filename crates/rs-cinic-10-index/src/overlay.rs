@@ -0,0 +1,319 @@
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::index::ObjectClass;
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use std::path::Path;
+
+/// A model prediction for one sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prediction {
+    pub class: ObjectClass,
+
+    /// The predicted class probability, in `[0, 1]`, if known.
+    pub confidence: Option<f32>,
+}
+
+impl From<ObjectClass> for Prediction {
+    fn from(class: ObjectClass) -> Self {
+        Self {
+            class,
+            confidence: None,
+        }
+    }
+}
+
+/// Layout settings for `render_prediction_grid()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayOptions {
+    /// The number of cells per row.
+    pub columns: usize,
+
+    /// The integer upscaling of each image.
+    pub scale: u32,
+
+    /// Only show misclassified samples.
+    pub errors_only: bool,
+}
+
+impl Default for OverlayOptions {
+    fn default() -> Self {
+        Self {
+            columns: 8,
+            scale: 3,
+            errors_only: false,
+        }
+    }
+}
+
+const CORRECT: Rgb<u8> = Rgb([40, 180, 60]);
+const INCORRECT: Rgb<u8> = Rgb([220, 40, 40]);
+const BACKGROUND: Rgb<u8> = Rgb([24, 24, 24]);
+const TEXT: Rgb<u8> = Rgb([235, 235, 235]);
+
+/// The frame width around each image.
+const BORDER: u32 = 2;
+
+/// The padding between cells, and around text.
+const PAD: u32 = 3;
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// A 3x5 bitmap glyph; one row per entry, high bit on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// The pixel width of `text`.
+fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * (GLYPH_WIDTH + 1)).saturating_sub(1)
+}
+
+/// Draw `text` with its top-left corner at `(x, y)`, clipped to the canvas.
+fn draw_text(
+    canvas: &mut RgbImage,
+    x: u32,
+    y: u32,
+    text: &str,
+    color: Rgb<u8>,
+) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as u32 * (GLYPH_WIDTH + 1);
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> col) != 0 {
+                    let (px, py) = (left + col, y + row as u32);
+                    if px < canvas.width() && py < canvas.height() {
+                        canvas.put_pixel(px, py, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Fill a rectangle, clipped to the canvas.
+fn fill_rect(
+    canvas: &mut RgbImage,
+    (x, y): (u32, u32),
+    (w, h): (u32, u32),
+    color: Rgb<u8>,
+) {
+    for py in y..(y + h).min(canvas.height()) {
+        for px in x..(x + w).min(canvas.width()) {
+            canvas.put_pixel(px, py, color);
+        }
+    }
+}
+
+/// Render a batch as an annotated grid of its predictions.
+///
+/// Each cell shows the (upscaled) image, framed green when the prediction is
+/// correct and red when not, with the true class, the predicted class and
+/// confidence, and a confidence bar; a one-glance view of what a model gets
+/// wrong.
+///
+/// # Parameters
+///
+/// - `batch`: The images.
+/// - `labels`: The true class of each image.
+/// - `predictions`: The predicted class (and confidence) of each image.
+/// - `options`: The layout settings.
+///
+/// # Returns
+///
+/// A `Result` containing the grid image.
+pub fn render_prediction_grid(
+    batch: &RgbImageBatch,
+    labels: &[ObjectClass],
+    predictions: &[Prediction],
+    options: &OverlayOptions,
+) -> Result<RgbImage> {
+    let n = batch.batch_size();
+    if labels.len() != n || predictions.len() != n {
+        bail!(
+            "Expected {n} labels and predictions, got {} and {}",
+            labels.len(),
+            predictions.len()
+        );
+    }
+    if options.columns == 0 || options.scale == 0 {
+        bail!("Columns and scale must be positive");
+    }
+
+    let shown: Vec<usize> = (0..n)
+        .filter(|&i| !options.errors_only || labels[i] != predictions[i].class)
+        .collect();
+
+    let label_lines = ["t:automobile", "p:automobile 100%"];
+    let image_w = batch.width() as u32 * options.scale;
+    let image_h = batch.height() as u32 * options.scale;
+    let text_w = label_lines.iter().map(|l| text_width(l)).max().unwrap();
+    let cell_w = (image_w + 2 * BORDER).max(text_w);
+    let text_h = 2 * (GLYPH_HEIGHT + 2) + 4;
+    let cell_h = image_h + 2 * BORDER + PAD + text_h;
+
+    let columns = options.columns.min(shown.len()).max(1) as u32;
+    let rows = shown.len().div_ceil(columns as usize).max(1) as u32;
+    let mut canvas = RgbImage::from_pixel(
+        PAD + columns * (cell_w + PAD),
+        PAD + rows * (cell_h + PAD),
+        BACKGROUND,
+    );
+
+    for (k, &i) in shown.iter().enumerate() {
+        let x = PAD + (k as u32 % columns) * (cell_w + PAD);
+        let y = PAD + (k as u32 / columns) * (cell_h + PAD);
+        let prediction = predictions[i];
+        let frame = if labels[i] == prediction.class {
+            CORRECT
+        } else {
+            INCORRECT
+        };
+
+        fill_rect(
+            &mut canvas,
+            (x, y),
+            (image_w + 2 * BORDER, image_h + 2 * BORDER),
+            frame,
+        );
+        let img = imageops::resize(&batch.image(i), image_w, image_h, FilterType::Nearest);
+        imageops::replace(&mut canvas, &img, (x + BORDER) as i64, (y + BORDER) as i64);
+
+        let text_y = y + image_h + 2 * BORDER + PAD;
+        draw_text(&mut canvas, x, text_y, &format!("t:{}", labels[i]), TEXT);
+        let predicted = match prediction.confidence {
+            Some(c) => format!("p:{} {:.0}%", prediction.class, c * 100.0),
+            None => format!("p:{}", prediction.class),
+        };
+        draw_text(&mut canvas, x, text_y + GLYPH_HEIGHT + 2, &predicted, frame);
+        if let Some(c) = prediction.confidence {
+            let bar_w = (c.clamp(0.0, 1.0) * cell_w as f32).round() as u32;
+            fill_rect(
+                &mut canvas,
+                (x, text_y + 2 * (GLYPH_HEIGHT + 2)),
+                (bar_w, 2),
+                frame,
+            );
+        }
+    }
+
+    Ok(canvas)
+}
+
+/// Render a prediction grid, and save it as a PNG; see `render_prediction_grid()`.
+pub fn save_prediction_grid<P>(
+    path: P,
+    batch: &RgbImageBatch,
+    labels: &[ObjectClass],
+    predictions: &[Prediction],
+    options: &OverlayOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    render_prediction_grid(batch, labels, predictions, options)?.save(path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_render_prediction_grid() -> Result<()> {
+        let mut batch = RgbImageBatch::new(&[3, 4, 4, 3]);
+        for v in [10, 20, 30] {
+            batch.push_rgb_pixels(&RgbImage::from_pixel(4, 4, Rgb([v, v, v])));
+        }
+        let labels = [ObjectClass::Cat, ObjectClass::Dog, ObjectClass::Ship];
+        let predictions = [
+            ObjectClass::Cat.into(),
+            Prediction {
+                class: ObjectClass::Cat,
+                confidence: Some(0.9),
+            },
+            ObjectClass::Ship.into(),
+        ];
+        let options = OverlayOptions {
+            columns: 2,
+            scale: 2,
+            ..Default::default()
+        };
+
+        let grid = render_prediction_grid(&batch, &labels, &predictions, &options)?;
+        let cell_w = text_width("p:automobile 100%");
+        assert_eq!(grid.width(), PAD + 2 * (cell_w + PAD));
+        assert_eq!(grid.get_pixel(PAD, PAD), &CORRECT);
+        assert_eq!(grid.get_pixel(2 * PAD + cell_w, PAD), &INCORRECT);
+        assert_eq!(
+            grid.get_pixel(PAD + BORDER + 1, PAD + BORDER + 1),
+            &Rgb([10, 10, 10])
+        );
+
+        let errors = render_prediction_grid(
+            &batch,
+            &labels,
+            &predictions,
+            &OverlayOptions {
+                errors_only: true,
+                ..options.clone()
+            },
+        )?;
+        assert_eq!(errors.width(), PAD + cell_w + PAD);
+        assert_eq!(errors.get_pixel(PAD, PAD), &INCORRECT);
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("grid.png");
+        save_prediction_grid(&path, &batch, &labels, &predictions, &options)?;
+        assert_eq!(image::open(&path)?.to_rgb8(), grid);
+
+        assert!(render_prediction_grid(&batch, &labels[..2], &predictions, &options).is_err());
+
+        Ok(())
+    }
+}
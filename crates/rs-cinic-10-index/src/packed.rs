@@ -0,0 +1,386 @@
+use crate::direct_io::DirectReader;
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::index::{DatasetIndex, ObjectClass};
+use crate::loader::{BatchLoader, FailureAction};
+use crate::stats::ClassDistribution;
+use anyhow::Context;
+use enum_ordinalize::Ordinalize;
+use image::RgbImage;
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"CNP1";
+
+/// The magic, item count (`u64`), height and width (`u32`), little-endian.
+const HEADER_LEN: usize = 20;
+
+/// The number of images decoded per batch while packing.
+const PACK_BATCH_SIZE: usize = 1024;
+
+/// A split pre-decoded into one memory-mapped file.
+///
+/// The file holds a header, one class ordinal byte per item, and then every
+/// image's pixels back to back, as one `[N, H, W, 3]` `u8` blob. Batches are
+/// copied straight out of the map, with no file opens or PNG decoding; the OS
+/// page cache keeps hot splits in memory (CINIC-10 is about 276 MB per split).
+///
+/// Opened with direct IO, batches are instead read with aligned direct reads,
+/// bypassing the page cache; see `open_direct()`.
+#[derive(Debug)]
+pub struct PackedDataset {
+    mmap: Mmap,
+    len: usize,
+    height: usize,
+    width: usize,
+    direct: Option<DirectReader>,
+}
+
+impl PackedDataset {
+    /// Decode every image of a split into a packed file.
+    ///
+    /// Images are decoded with `loader`, so its transform and color space are
+    /// baked in; pack with a loader without random augmentation. Every image
+    /// must have the same dimensions, and none may be dropped.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The split to pack.
+    /// - `loader`: The loader to decode images with.
+    /// - `path`: The packed file to write; replaced atomically.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` indicating success or failure.
+    pub fn pack<P>(
+        split: &DatasetIndex,
+        loader: &BatchLoader,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if split.is_empty() {
+            bail!("Cannot pack an empty split");
+        }
+        let indices: Vec<usize> = (0..split.len()).collect();
+
+        let tmp = path.with_extension("tmp");
+        let mut out = BufWriter::new(
+            File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?,
+        );
+        let mut dims: Option<(usize, usize)> = None;
+        for chunk in indices.chunks(PACK_BATCH_SIZE) {
+            let loaded = split.load_rgbimagebatch_report_with(loader, chunk)?;
+            if let Some(failure) = loaded
+                .failures
+                .iter()
+                .find(|f| f.action == FailureAction::Dropped)
+            {
+                bail!(
+                    "Failed to pack {}: {}",
+                    failure.path.display(),
+                    failure.error
+                );
+            }
+            let batch = loaded.batch;
+            match dims {
+                None => {
+                    dims = Some((batch.height(), batch.width()));
+                    out.write_all(MAGIC)?;
+                    out.write_all(&(split.len() as u64).to_le_bytes())?;
+                    out.write_all(&(batch.height() as u32).to_le_bytes())?;
+                    out.write_all(&(batch.width() as u32).to_le_bytes())?;
+                    for class in split.indices_to_classes(&indices) {
+                        out.write_all(&[class.ordinal() as u8])?;
+                    }
+                }
+                Some(expected) if expected != (batch.height(), batch.width()) => {
+                    bail!(
+                        "Image dimensions {:?} do not match {:?}",
+                        (batch.height(), batch.width()),
+                        expected
+                    );
+                }
+                Some(_) => {}
+            }
+            out.write_all(&batch.data)?;
+        }
+        out.flush()?;
+        drop(out);
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Memory-map a file written by `pack()`.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        // SAFETY: packed files are written once, and replaced by rename, never in place.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_LEN || &mmap[..4] != MAGIC {
+            bail!("Not a packed dataset file: {}", path.display());
+        }
+        let len = u64::from_le_bytes(mmap[4..12].try_into().unwrap()) as usize;
+        let height = u32::from_le_bytes(mmap[12..16].try_into().unwrap()) as usize;
+        let width = u32::from_le_bytes(mmap[16..20].try_into().unwrap()) as usize;
+        if mmap.len() != HEADER_LEN + len + len * height * width * 3 {
+            bail!("Truncated packed dataset file: {}", path.display());
+        }
+        Ok(Self {
+            mmap,
+            len,
+            height,
+            width,
+            direct: None,
+        })
+    }
+
+    /// Like `open()`, but `load_rgbimagebatch()` reads with direct IO.
+    ///
+    /// The header and labels are still read through the map.
+    pub fn open_direct<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut packed = Self::open(path)?;
+        packed.direct = Some(
+            DirectReader::open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?,
+        );
+        Ok(packed)
+    }
+
+    /// Are batches read with direct IO? See `open_direct()`.
+    pub fn is_direct(&self) -> bool {
+        self.direct.is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `(width, height)` of every image.
+    pub fn dims(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// The class ordinal of every item.
+    pub fn labels(&self) -> &[u8] {
+        &self.mmap[HEADER_LEN..HEADER_LEN + self.len]
+    }
+
+    /// The object class of an item.
+    pub fn index_to_class(
+        &self,
+        index: usize,
+    ) -> ObjectClass {
+        ObjectClass::from_ordinal(self.labels()[index] as i8).unwrap()
+    }
+
+    /// Convert a slice of indices to a vector of object classes.
+    pub fn indices_to_classes(
+        &self,
+        indices: &[usize],
+    ) -> Vec<ObjectClass> {
+        indices.iter().map(|&i| self.index_to_class(i)).collect()
+    }
+
+    /// Count the items of each class.
+    pub fn class_distribution(&self) -> ClassDistribution {
+        ClassDistribution::from_classes((0..self.len).map(|i| self.index_to_class(i)))
+    }
+
+    /// The byte range of an item's pixels in the file.
+    fn image_span(
+        &self,
+        index: usize,
+    ) -> Range<usize> {
+        assert!(
+            index < self.len,
+            "Index {index} out of range for a packed split of {}",
+            self.len
+        );
+        let size = self.height * self.width * 3;
+        let start = HEADER_LEN + self.len + index * size;
+        start..start + size
+    }
+
+    /// The packed `[H, W, 3]` pixels of an item.
+    pub fn image_bytes(
+        &self,
+        index: usize,
+    ) -> &[u8] {
+        &self.mmap[self.image_span(index)]
+    }
+
+    /// Copy out the image of an item.
+    pub fn image(
+        &self,
+        index: usize,
+    ) -> RgbImage {
+        RgbImage::from_raw(
+            self.width as u32,
+            self.height as u32,
+            self.image_bytes(index).to_vec(),
+        )
+        .unwrap()
+    }
+
+    /// Copy a batch of items into an `RgbImageBatch`.
+    ///
+    /// Opened with `open_direct()`, each image is read with an aligned
+    /// direct read; otherwise it is copied out of the map.
+    ///
+    /// # Parameters
+    ///
+    /// - `indices`: A slice of indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the batch.
+    pub fn load_rgbimagebatch(
+        &self,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        if indices.is_empty() {
+            bail!("Cannot load an empty batch");
+        }
+        let mut batch = RgbImageBatch::new(&[indices.len(), self.height, self.width, 3]);
+        match &self.direct {
+            Some(reader) => {
+                let size = self.height * self.width * 3;
+                batch.data.resize(indices.len() * size, 0);
+                for (&index, dst) in indices.iter().zip(batch.data.chunks_exact_mut(size)) {
+                    let span = self.image_span(index);
+                    reader
+                        .read_exact_at(span.start as u64, dst)
+                        .context("Failed to read a packed image")?;
+                }
+            }
+            None => {
+                for &index in indices {
+                    batch.data.extend_from_slice(self.image_bytes(index));
+                }
+            }
+        }
+        Ok(batch)
+    }
+}
+
+/// Where `open_or_pack()` stores the packed copy of a split.
+///
+/// Like `embeddings::embeddings_cache_path()`, the file is keyed by the split's fingerprint.
+pub fn packed_cache_path(split: &DatasetIndex) -> PathBuf {
+    let ds_path = split.ds_path();
+    let name = ds_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fingerprint = split.fingerprint();
+    ds_path
+        .parent()
+        .unwrap_or(ds_path)
+        .join(format!("packed-{name}-{}.bin", &fingerprint[..16]))
+}
+
+/// Open the packed copy of a split, packing it next to the dataset first if needed.
+///
+/// With `LoaderConfig::direct_io`, the copy is opened with
+/// `PackedDataset::open_direct()`.
+///
+/// # Parameters
+///
+/// - `split`: The split.
+/// - `loader`: The loader to decode images with; see `PackedDataset::pack()`.
+///
+/// # Returns
+///
+/// A `Result` containing the packed split.
+pub fn open_or_pack(
+    split: &DatasetIndex,
+    loader: &BatchLoader,
+) -> Result<PackedDataset> {
+    let path = packed_cache_path(split);
+    let open = |path: &Path| match loader.config().direct_io {
+        true => PackedDataset::open_direct(path),
+        false => PackedDataset::open(path),
+    };
+    if let Ok(packed) = open(&path)
+        && packed.len() == split.len()
+    {
+        return Ok(packed);
+    }
+    PackedDataset::pack(split, loader, &path)?;
+    open(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::LoaderConfig;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::Rgb;
+
+    #[test]
+    fn test_packed_dataset() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = fixtures::write_split(
+            dir.path().join("train"),
+            [ObjectClass::Cat, ObjectClass::Ship, ObjectClass::Dog]
+                .into_iter()
+                .enumerate()
+                .map(|(i, class)| {
+                    (
+                        class,
+                        "a.png",
+                        RgbImage::from_pixel(4, 2, Rgb([i as u8, 1, 2])),
+                    )
+                }),
+        )?;
+
+        let packed = open_or_pack(&split, &BatchLoader::default())?;
+        assert!(packed_cache_path(&split).exists());
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed.dims(), (4, 2));
+        assert_eq!(packed.labels(), &[3, 8, 5]);
+        assert_eq!(
+            packed.indices_to_classes(&[0, 2]),
+            split.indices_to_classes(&[0, 2])
+        );
+
+        let batch = packed.load_rgbimagebatch(&[2, 0])?;
+        assert_eq!(batch.shape, vec![2, 2, 4, 3]);
+        assert_eq!(batch.image(0), packed.image(2));
+        assert_eq!(batch.data, split.load_rgbimagebatch(&[2, 0])?.data);
+
+        let direct = open_or_pack(
+            &split,
+            &BatchLoader::new(LoaderConfig {
+                direct_io: true,
+                ..Default::default()
+            }),
+        )?;
+        assert!(direct.is_direct());
+        assert_eq!(direct.load_rgbimagebatch(&[2, 0])?.data, batch.data);
+
+        fs::write(packed_cache_path(&split), b"CNP1")?;
+        assert!(PackedDataset::open(packed_cache_path(&split)).is_err());
+
+        Ok(())
+    }
+}
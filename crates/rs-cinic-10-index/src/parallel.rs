@@ -0,0 +1,157 @@
+use crate::error::Result;
+use std::thread;
+
+/// Resolve a requested parallelism; `0` means "all available cores".
+pub(crate) fn resolve_parallelism(parallelism: usize) -> usize {
+    if parallelism > 0 {
+        parallelism
+    } else {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+/// Fold `items` in contiguous chunks on scoped threads, then merge the chunk results in order.
+///
+/// # Parameters
+///
+/// - `items`: The items to fold.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+/// - `init`: Builds an empty accumulator for each chunk.
+/// - `fold`: Folds one item into an accumulator.
+/// - `merge`: Merges two accumulators, left to right.
+///
+/// # Returns
+///
+/// The merged accumulator, or the first error raised by `fold`.
+pub(crate) fn par_fold<T, A, I, F, M>(
+    items: &[T],
+    parallelism: usize,
+    init: I,
+    fold: F,
+    merge: M,
+) -> Result<A>
+where
+    T: Sync,
+    A: Send,
+    I: Fn() -> A + Sync,
+    F: Fn(&mut A, &T) -> Result<()> + Sync,
+    M: Fn(A, A) -> A,
+{
+    let parallelism = resolve_parallelism(parallelism).min(items.len()).max(1);
+    let chunk_size = items.len().div_ceil(parallelism).max(1);
+
+    let results: Vec<Result<A>> = thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let init = &init;
+                let fold = &fold;
+                scope.spawn(move || {
+                    let mut acc = init();
+                    for item in chunk {
+                        fold(&mut acc, item)?;
+                    }
+                    Ok(acc)
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut merged = init();
+    for result in results {
+        merged = merge(merged, result?);
+    }
+    Ok(merged)
+}
+
+/// Map `items` on scoped threads, keeping their order.
+///
+/// # Parameters
+///
+/// - `items`: The items to map.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+/// - `f`: Maps one item.
+///
+/// # Returns
+///
+/// The mapped items, or the first error raised by `f`.
+pub(crate) fn par_map<T, R, F>(
+    items: &[T],
+    parallelism: usize,
+    f: F,
+) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    par_fold(
+        items,
+        parallelism,
+        Vec::new,
+        |acc, item| {
+            acc.push(f(item)?);
+            Ok(())
+        },
+        |mut left, right| {
+            left.extend(right);
+            left
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::bail;
+
+    #[test]
+    fn test_par_fold() -> Result<()> {
+        let items: Vec<u64> = (1..=100).collect();
+        for parallelism in [0, 1, 3, 200] {
+            let sum = par_fold(
+                &items,
+                parallelism,
+                || 0,
+                |a, x| {
+                    *a += x;
+                    Ok(())
+                },
+                |a, b| a + b,
+            )?;
+            assert_eq!(sum, 5050);
+        }
+
+        let empty: Vec<u64> = Vec::new();
+        assert_eq!(par_fold(&empty, 4, || 7, |_, _| Ok(()), |a, _| a)?, 7);
+
+        let failed = par_fold(
+            &items,
+            4,
+            || (),
+            |_, x| {
+                if *x == 50 {
+                    bail!("bad item");
+                }
+                Ok(())
+            },
+            |_, _| (),
+        );
+        assert!(failed.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_par_map() -> Result<()> {
+        let items: Vec<u64> = (0..37).collect();
+        for parallelism in [0, 1, 4, 100] {
+            let squares = par_map(&items, parallelism, |x| Ok(x * x))?;
+            assert_eq!(squares, items.iter().map(|x| x * x).collect::<Vec<_>>());
+        }
+        assert!(par_map(&items, 4, |&x| if x == 9 { bail!("nine") } else { Ok(x) }).is_err());
+
+        Ok(())
+    }
+}
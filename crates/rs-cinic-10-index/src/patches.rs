@@ -0,0 +1,169 @@
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use serde::{Deserialize, Serialize};
+
+/// How images are cut into patch sequences, for vision-transformer models.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PatchConfig {
+    /// The patch side length `P`; must divide the image height and width.
+    pub patch_size: usize,
+
+    /// Normalize each patch to zero mean and unit variance;
+    /// otherwise values are scaled to `[0, 1]`.
+    pub normalize: bool,
+
+    /// Added to the patch variance when normalizing.
+    pub eps: f32,
+}
+
+impl Default for PatchConfig {
+    fn default() -> Self {
+        Self {
+            patch_size: 4,
+            normalize: false,
+            eps: 1e-6,
+        }
+    }
+}
+
+/// A batch of patch sequences, with shape `[B, N, P*P*C]`.
+///
+/// Patches are in row-major order over the image, and each patch is
+/// flattened as `(row, col, channel)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchBatch {
+    pub data: Vec<f32>,
+    pub shape: [usize; 3],
+}
+
+impl PatchBatch {
+    /// Cut every image of a BHWC batch into patches.
+    ///
+    /// # Parameters
+    ///
+    /// - `batch`: The image batch.
+    /// - `config`: The patch configuration.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the patch batch; or an error if the patch size
+    /// does not divide the image size.
+    pub fn from_rgbimagebatch(
+        batch: &RgbImageBatch,
+        config: &PatchConfig,
+    ) -> Result<Self> {
+        let p = config.patch_size;
+        let (b, h, w, c) = (
+            batch.batch_size(),
+            batch.height(),
+            batch.width(),
+            batch.channels(),
+        );
+        if p == 0 || h % p != 0 || w % p != 0 {
+            bail!("Patch size {p} does not divide image size {h}x{w}");
+        }
+
+        let (rows, cols) = (h / p, w / p);
+        let patch_len = p * p * c;
+        let mut data = Vec::with_capacity(b * rows * cols * patch_len);
+        for image in batch.data.chunks_exact(h * w * c) {
+            for row in 0..rows {
+                for col in 0..cols {
+                    let start = data.len();
+                    for y in row * p..(row + 1) * p {
+                        let offset = (y * w + col * p) * c;
+                        data.extend(
+                            image[offset..offset + p * c]
+                                .iter()
+                                .map(|&v| v as f32 / 255.0),
+                        );
+                    }
+                    if config.normalize {
+                        normalize(&mut data[start..], config.eps);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            data,
+            shape: [b, rows * cols, patch_len],
+        })
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.shape[0]
+    }
+
+    pub fn num_patches(&self) -> usize {
+        self.shape[1]
+    }
+
+    pub fn patch_len(&self) -> usize {
+        self.shape[2]
+    }
+}
+
+fn normalize(
+    patch: &mut [f32],
+    eps: f32,
+) {
+    let n = patch.len() as f32;
+    let mean = patch.iter().sum::<f32>() / n;
+    let var = patch.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+    let scale = 1.0 / (var + eps).sqrt();
+    patch.iter_mut().for_each(|v| *v = (*v - mean) * scale);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_patch_batch() -> Result<()> {
+        // One 2x4 image; pixel (y, x) has every channel set to 10 * y + x.
+        let mut batch = RgbImageBatch::new(&[1, 2, 4, 3]);
+        for y in 0..2u8 {
+            for x in 0..4u8 {
+                batch.data.extend([10 * y + x; 3]);
+            }
+        }
+
+        let config = PatchConfig {
+            patch_size: 2,
+            ..Default::default()
+        };
+        let patches = PatchBatch::from_rgbimagebatch(&batch, &config)?;
+        assert_eq!(patches.shape, [1, 2, 12]);
+        let second: Vec<u8> = patches.data[12..]
+            .iter()
+            .step_by(3)
+            .map(|v| (v * 255.0).round() as u8)
+            .collect();
+        assert_eq!(second, [2, 3, 12, 13]);
+
+        let normalized = PatchBatch::from_rgbimagebatch(
+            &batch,
+            &PatchConfig {
+                normalize: true,
+                ..config
+            },
+        )?;
+        let mean = normalized.data[..12].iter().sum::<f32>() / 12.0;
+        assert!(mean.abs() < 1e-5);
+
+        assert!(
+            PatchBatch::from_rgbimagebatch(
+                &batch,
+                &PatchConfig {
+                    patch_size: 3,
+                    ..config
+                }
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+use crate::config::Cinic10Config;
+use crate::error::{Result, bail};
+use crate::images::RgbImageBatch;
+use crate::loader::BatchLoader;
+use anyhow::Context;
+use std::env;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Set in the environment of worker processes spawned by a `ProcessPool`.
+pub const WORKER_ENV_VAR: &str = "CINIC10_LOADER_WORKER";
+
+/// Written by a worker before its first response, so the pool can skip any
+/// output the host program prints at startup.
+const HANDSHAKE: &[u8; 8] = b"CINIC10W";
+
+const STATUS_OK: u8 = 0;
+const STATUS_ERR: u8 = 1;
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes<R: Read>(
+    r: &mut R,
+    len: usize,
+) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Write a batch request: a path count, then each path, length-prefixed.
+fn write_request<W: Write>(
+    w: &mut W,
+    paths: &[&Path],
+) -> Result<()> {
+    w.write_all(&(paths.len() as u32).to_le_bytes())?;
+    for path in paths {
+        let Some(path) = path.to_str() else {
+            bail!("Path is not valid UTF-8: {}", path.display());
+        };
+        w.write_all(&(path.len() as u32).to_le_bytes())?;
+        w.write_all(path.as_bytes())?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read a batch request; `None` at end of input.
+fn read_request<R: Read>(r: &mut R) -> io::Result<Option<Vec<PathBuf>>> {
+    let count = match read_u32(r) {
+        Ok(count) => count,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    (0..count)
+        .map(|_| {
+            let len = read_u32(r)? as usize;
+            String::from_utf8(read_bytes(r, len)?)
+                .map(PathBuf::from)
+                .map_err(io::Error::other)
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .map(Some)
+}
+
+/// Write a batch response: a status byte, then the shape and pixels, or an error message.
+fn write_response<W: Write>(
+    w: &mut W,
+    result: &Result<RgbImageBatch>,
+) -> io::Result<()> {
+    match result {
+        Ok(batch) => {
+            w.write_all(&[STATUS_OK])?;
+            for &dim in &batch.shape {
+                w.write_all(&(dim as u64).to_le_bytes())?;
+            }
+            w.write_all(&batch.data)?;
+        }
+        Err(err) => {
+            let message = format!("{err:#}");
+            w.write_all(&[STATUS_ERR])?;
+            w.write_all(&(message.len() as u32).to_le_bytes())?;
+            w.write_all(message.as_bytes())?;
+        }
+    }
+    w.flush()
+}
+
+/// Read a batch response.
+///
+/// The outer `Result` is a transport failure; the inner one is the worker's load result.
+fn read_response<R: Read>(r: &mut R) -> io::Result<Result<RgbImageBatch>> {
+    let mut status = [0u8];
+    r.read_exact(&mut status)?;
+    match status[0] {
+        STATUS_OK => {
+            let shape = (0..4)
+                .map(|_| read_u64(r).map(|d| d as usize))
+                .collect::<io::Result<Vec<_>>>()?;
+            let data = read_bytes(r, shape.iter().product())?;
+            Ok(Ok(RgbImageBatch { data, shape }))
+        }
+        STATUS_ERR => {
+            let len = read_u32(r)? as usize;
+            let message = String::from_utf8_lossy(&read_bytes(r, len)?).into_owned();
+            Ok(Err(anyhow::anyhow!(message).into()))
+        }
+        other => Err(io::Error::other(format!("Bad worker status byte {other}"))),
+    }
+}
+
+/// Run the worker loop, if this process was spawned as a `ProcessPool` worker.
+///
+/// Call this first thing in `main()` of any program using a `ProcessPool`
+/// with the default command, which re-executes the current binary. In a
+/// worker, this serves batch requests on stdin/stdout until the pool closes
+/// the pipe, then exits the process; otherwise it returns immediately.
+///
+/// Workers load with `Cinic10Config::from_env()`, so the pool's `CINIC10_*`
+/// settings are inherited.
+pub fn run_worker_if_requested() {
+    if env::var_os(WORKER_ENV_VAR).is_none() {
+        return;
+    }
+    let code = match run_worker(io::stdin().lock(), io::stdout().lock()) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("cinic-10 loader worker failed: {err:#}");
+            1
+        }
+    };
+    process::exit(code);
+}
+
+/// Serve batch requests from `input` until it closes.
+fn run_worker<R: Read, W: Write>(
+    input: R,
+    output: W,
+) -> Result<()> {
+    let loader = BatchLoader::new(Cinic10Config::from_env()?.loader_config()?);
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+    output.write_all(HANDSHAKE)?;
+    output.flush()?;
+    while let Some(paths) = read_request(&mut input)? {
+        let result = loader.load_rgbimagebatch(&paths);
+        write_response(&mut output, &result)?;
+    }
+    Ok(())
+}
+
+/// A running worker process.
+#[derive(Debug)]
+struct Worker {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Worker {
+    fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .env(WORKER_ENV_VAR, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to spawn loader worker")?;
+        let stdin = BufWriter::new(child.stdin.take().unwrap());
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let mut window = [0u8; HANDSHAKE.len()];
+        stdout
+            .read_exact(&mut window)
+            .context("Loader worker exited before its handshake")?;
+        while &window != HANDSHAKE {
+            window.rotate_left(1);
+            stdout
+                .read_exact(&mut window[HANDSHAKE.len() - 1..])
+                .context("Loader worker exited before its handshake")?;
+        }
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn load(
+        &mut self,
+        paths: &[&Path],
+    ) -> Result<Result<RgbImageBatch>> {
+        write_request(&mut self.stdin, paths)?;
+        Ok(read_response(&mut self.stdout)?)
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A pool of worker subprocesses which load and decode batches.
+///
+/// Each batch is sent to one worker over a pipe, and returned as raw pixels.
+/// A decoder crash (or abort, or OOM kill) takes down only its worker: the
+/// batch fails, and the worker is respawned on next use. Workers also keep
+/// decoder allocations out of the host process, which helps when the crate
+/// is embedded in a larger runtime.
+pub struct ProcessPool {
+    command: Box<dyn Fn() -> Command + Send + Sync>,
+    workers: Vec<Mutex<Option<Worker>>>,
+    next: AtomicUsize,
+}
+
+impl std::fmt::Debug for ProcessPool {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("ProcessPool")
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
+
+impl ProcessPool {
+    /// Start `workers` processes re-executing the current binary.
+    ///
+    /// The binary must call `run_worker_if_requested()` at the start of `main()`.
+    ///
+    /// Workers do not receive the caller's `LoaderConfig` or `BatchLoader`:
+    /// each builds its own loader from `Cinic10Config::from_env()`, inherited
+    /// from this process. A custom decoder, data source, or transform is not
+    /// applied; use `with_command()` to set `CINIC10_*` variables per worker.
+    pub fn new(workers: usize) -> Result<Self> {
+        let exe = env::current_exe().context("Failed to locate the current executable")?;
+        Self::with_command(workers, move || Command::new(&exe))
+    }
+
+    /// Start `workers` processes from a command factory.
+    ///
+    /// # Parameters
+    ///
+    /// - `workers`: The number of worker processes.
+    /// - `command`: Builds the command for a worker; the program must call
+    ///   `run_worker_if_requested()` at startup.
+    ///   Its environment configures the worker's loader; see `new()`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the pool, once every worker is ready.
+    pub fn with_command<F>(
+        workers: usize,
+        command: F,
+    ) -> Result<Self>
+    where
+        F: Fn() -> Command + Send + Sync + 'static,
+    {
+        if workers == 0 {
+            bail!("A process pool needs at least one worker");
+        }
+        let workers = (0..workers)
+            .map(|_| Worker::spawn(command()).map(|w| Mutex::new(Some(w))))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            command: Box::new(command),
+            workers,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+
+    /// Load a batch on the next worker, in round-robin order.
+    ///
+    /// Safe to call from several threads; each worker serves one batch at a time.
+    ///
+    /// # Parameters
+    ///
+    /// - `paths`: A slice of paths to the images.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the batch; or the worker's load error, or an
+    /// error if the worker died.
+    pub fn load_rgbimagebatch<P>(
+        &self,
+        paths: &[P],
+    ) -> Result<RgbImageBatch>
+    where
+        P: AsRef<Path>,
+    {
+        let paths: Vec<&Path> = paths.iter().map(AsRef::as_ref).collect();
+        let slot = self.next.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+        let mut worker = self.workers[slot].lock().unwrap();
+        if worker.is_none() {
+            *worker = Some(Worker::spawn((self.command)())?);
+        }
+        match worker.as_mut().unwrap().load(&paths) {
+            Ok(result) => result,
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %err, slot, "loader worker died; respawning on next use");
+
+                *worker = None;
+                Err(anyhow::Error::new(err)
+                    .context("Loader worker process died")
+                    .into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+
+    /// The worker entry point, when this test binary is spawned by a pool.
+    #[test]
+    fn worker_main() {
+        run_worker_if_requested();
+    }
+
+    fn test_worker_command() -> Command {
+        let mut command = Command::new(env::current_exe().unwrap());
+        command.args([
+            "process_pool::tests::worker_main",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ]);
+        command
+    }
+
+    #[test]
+    fn test_process_pool() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let paths = (0..3u8)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.png"));
+                RgbImage::from_pixel(2, 2, Rgb([i, 1, 2])).save(&path)?;
+                Ok(path)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pool = ProcessPool::with_command(2, test_worker_command)?;
+        let batch = pool.load_rgbimagebatch(&paths)?;
+        assert_eq!(batch.shape, vec![3, 2, 2, 3]);
+        assert_eq!(
+            batch.data,
+            BatchLoader::default().load_rgbimagebatch(&paths)?.data
+        );
+
+        assert!(
+            pool.load_rgbimagebatch(&[dir.path().join("missing.png")])
+                .is_err()
+        );
+
+        // A killed worker fails its batch, and is replaced.
+        for worker in &pool.workers {
+            worker.lock().unwrap().as_mut().unwrap().child.kill()?;
+        }
+        assert!(pool.load_rgbimagebatch(&paths).is_err());
+        assert!(pool.load_rgbimagebatch(&paths).is_err());
+        assert_eq!(pool.load_rgbimagebatch(&paths)?.data, batch.data);
+
+        Ok(())
+    }
+}
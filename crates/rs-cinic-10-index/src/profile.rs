@@ -0,0 +1,207 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use strum::EnumCount;
+
+/// The stages of a batch load.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+    strum_macros::EnumCount,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum Stage {
+    /// Reading encoded image bytes from storage.
+    Read,
+
+    /// Decoding image bytes to pixels.
+    Decode,
+
+    /// Applying image transforms.
+    Transform,
+
+    /// Copying pixels into the batch buffer.
+    Copy,
+
+    /// Moving the assembled batch to a device.
+    DeviceTransfer,
+}
+
+const STAGE_COUNT: usize = Stage::COUNT;
+
+/// Accumulates time spent per `Stage` across batch loads.
+#[derive(Debug, Default)]
+pub struct StageProfiler {
+    nanos: [AtomicU64; STAGE_COUNT],
+    counts: [AtomicU64; STAGE_COUNT],
+    batches: AtomicU64,
+}
+
+impl StageProfiler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record `elapsed` time spent in `stage`.
+    pub fn record(
+        &self,
+        stage: Stage,
+        elapsed: Duration,
+    ) {
+        let i = stage as usize;
+        self.nanos[i].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.counts[i].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Run `f`, recording its duration against `stage`.
+    pub fn time<T, F>(
+        &self,
+        stage: Stage,
+        f: F,
+    ) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let result = f();
+        self.record(stage, start.elapsed());
+        result
+    }
+
+    /// Record the completion of a batch.
+    pub fn record_batch(&self) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Clear all accumulated timings.
+    pub fn reset(&self) {
+        for i in 0..STAGE_COUNT {
+            self.nanos[i].store(0, Ordering::Relaxed);
+            self.counts[i].store(0, Ordering::Relaxed);
+        }
+        self.batches.store(0, Ordering::Relaxed);
+    }
+
+    /// Build a report of the accumulated timings.
+    pub fn report(&self) -> ProfileReport {
+        use strum::IntoEnumIterator;
+
+        ProfileReport {
+            batches: self.batches.load(Ordering::Relaxed),
+            stages: Stage::iter()
+                .map(|stage| StageTiming {
+                    stage,
+                    total: Duration::from_nanos(self.nanos[stage as usize].load(Ordering::Relaxed)),
+                    count: self.counts[stage as usize].load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The accumulated timing of a single `Stage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageTiming {
+    pub stage: Stage,
+    pub total: Duration,
+
+    /// The number of timed operations in this stage.
+    pub count: u64,
+}
+
+/// A per-stage breakdown of time spent loading batches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub batches: u64,
+    pub stages: Vec<StageTiming>,
+}
+
+impl ProfileReport {
+    /// Total time across all stages.
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|s| s.total).sum()
+    }
+
+    /// The timing for a given stage.
+    pub fn stage(
+        &self,
+        stage: Stage,
+    ) -> StageTiming {
+        self.stages[stage as usize]
+    }
+
+    /// The fraction of total time spent in `stage`.
+    pub fn fraction(
+        &self,
+        stage: Stage,
+    ) -> f64 {
+        let total = self.total().as_secs_f64();
+        if total == 0.0 {
+            0.0
+        } else {
+            self.stage(stage).total.as_secs_f64() / total
+        }
+    }
+
+    /// The stage with the most accumulated time, if any time was recorded.
+    pub fn dominant_stage(&self) -> Option<Stage> {
+        self.stages
+            .iter()
+            .filter(|s| !s.total.is_zero())
+            .max_by_key(|s| s.total)
+            .map(|s| s.stage)
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        writeln!(f, "batches: {}", self.batches)?;
+        for timing in &self.stages {
+            writeln!(
+                f,
+                "{:>16}: {:>12.3?} {:>6.1}% ({} ops)",
+                timing.stage.to_string(),
+                timing.total,
+                100.0 * self.fraction(timing.stage),
+                timing.count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler() {
+        let profiler = StageProfiler::new();
+        assert_eq!(profiler.report().dominant_stage(), None);
+
+        profiler.record(Stage::Read, Duration::from_millis(10));
+        profiler.record(Stage::Read, Duration::from_millis(20));
+        profiler.record(Stage::Decode, Duration::from_millis(70));
+        profiler.record_batch();
+
+        let report = profiler.report();
+        assert_eq!(report.batches, 1);
+        assert_eq!(report.total(), Duration::from_millis(100));
+        assert_eq!(report.stage(Stage::Read).count, 2);
+        assert_eq!(report.stage(Stage::Read).total, Duration::from_millis(30));
+        assert!((report.fraction(Stage::Decode) - 0.7).abs() < 1e-9);
+        assert_eq!(report.dominant_stage(), Some(Stage::Decode));
+
+        profiler.reset();
+        assert_eq!(profiler.report().total(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,692 @@
+use crate::Cinic10Index;
+use crate::error::{Result, bail};
+use crate::index::{DataSet, DatasetIndex, DatasetItem, IndexRecord, ObjectClass};
+use crate::synsets::synset_children_map;
+use crate::view::DatasetIndexView;
+use crate::wnid::WnId;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A split of the original CIFAR-10 dataset.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum CifarSplit {
+    Train,
+    Test,
+}
+
+/// The dataset a CINIC-10 image was drawn from.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+    strum_macros::EnumIter,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ItemSource {
+    Cifar10,
+    ImageNet,
+}
+
+/// The parsed name of a CINIC-10 image file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ItemName {
+    pub source: ItemSource,
+
+    /// The original CIFAR-10 split; `None` for ImageNet images.
+    pub source_split: Option<CifarSplit>,
+
+    /// The ImageNet synset id; `None` for CIFAR-10 images.
+    pub synset: Option<WnId>,
+
+    /// The CIFAR-10 index, or the ImageNet image number.
+    pub number: usize,
+}
+
+impl ItemName {
+    /// Parse the file name of an image path; the directories are ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the parsed name; see `parse_item_filename()`.
+    pub fn parse<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            bail!("Item path has no UTF-8 file name: {}", path.display());
+        };
+        parse_item_filename(name)
+    }
+
+    /// Format the name back into a file name, with the standard `.png` extension.
+    pub fn to_filename(&self) -> String {
+        match (&self.source_split, &self.synset) {
+            (Some(split), _) => format!("cifar10-{split}-{}.png", self.number),
+            (None, Some(synset)) => format!("{synset}_{}.png", self.number),
+            (None, None) => format!("{}.png", self.number),
+        }
+    }
+}
+
+fn parse_number(
+    name: &str,
+    number: &str,
+) -> Result<usize> {
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        bail!("Malformed item number in file name: {name:?}");
+    }
+    Ok(number
+        .parse()
+        .with_context(|| format!("Malformed item number in file name: {name:?}"))?)
+}
+
+/// Parse a CINIC-10 image file name.
+///
+/// Two families are accepted:
+///
+/// - `cifar10-{train|test}-{number}.png`, for CIFAR-10 images;
+/// - `{wnid}_{number}.png`, for ImageNet images of synset `wnid`; see `WnId`.
+///
+/// Any image file extension the `image` crate knows is accepted in place of
+/// `.png`, so recompressed copies (e.g. `.jpg`, `.webp`) parse the same.
+///
+/// # Parameters
+///
+/// - `name`: The file name, without directories.
+///
+/// # Returns
+///
+/// A `Result` containing the parsed name; or an error if `name` is in neither family.
+pub fn parse_item_filename(name: &str) -> Result<ItemName> {
+    let Some((stem, ext)) = name.rsplit_once('.') else {
+        bail!("Item file name has no extension: {name:?}");
+    };
+    if image::ImageFormat::from_extension(ext).is_none() {
+        bail!("Item file name is not an image: {name:?}");
+    }
+
+    if let Some(rest) = stem.strip_prefix("cifar10-") {
+        let Some((split, number)) = rest.split_once('-') else {
+            bail!("Malformed CIFAR-10 file name: {name:?}");
+        };
+        let Ok(split) = CifarSplit::from_str(split) else {
+            bail!("Unknown CIFAR-10 split in file name: {name:?}");
+        };
+        return Ok(ItemName {
+            source: ItemSource::Cifar10,
+            source_split: Some(split),
+            synset: None,
+            number: parse_number(name, number)?,
+        });
+    }
+
+    let Some((synset, number)) = stem.split_once('_') else {
+        bail!("Unrecognized item file name: {name:?}");
+    };
+    let Ok(synset) = WnId::from_str(synset) else {
+        bail!("Malformed ImageNet synset in file name: {name:?}");
+    };
+    Ok(ItemName {
+        source: ItemSource::ImageNet,
+        source_split: None,
+        synset: Some(synset),
+        number: parse_number(name, number)?,
+    })
+}
+
+/// Parse the file name of an item path; `None` if it is not a CINIC-10 name.
+pub(crate) fn parse_item_path(path: &Path) -> Option<ItemName> {
+    ItemName::parse(path).ok()
+}
+
+/// The indices of a split whose source images came from any of `synsets`.
+///
+/// # Parameters
+///
+/// - `split`: The split to search.
+/// - `synsets`: The synset ids to match.
+///
+/// # Returns
+///
+/// The matching indices, in order.
+pub fn indices_for_synsets(
+    split: &DatasetIndex,
+    synsets: &HashSet<WnId>,
+) -> Vec<usize> {
+    split
+        .items()
+        .enumerate()
+        .filter(|(_, item)| {
+            parse_item_path(&item.path)
+                .and_then(|name| name.synset)
+                .is_some_and(|s| synsets.contains(&s))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Mismatches between `CONTRIB_FILE` records and the ImageNet files on disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContribCheck {
+    /// Records with no matching file in their split and class.
+    pub missing_files: Vec<IndexRecord>,
+
+    /// Non-CIFAR files with no matching record, as `(split, path)`.
+    pub unrecorded_files: Vec<(DataSet, PathBuf)>,
+}
+
+impl ContribCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.missing_files.is_empty() && self.unrecorded_files.is_empty()
+    }
+}
+
+impl DatasetIndex {
+    /// The original CIFAR-10 split and index of a CIFAR-sourced sample.
+    ///
+    /// # Returns
+    ///
+    /// The `(split, index)` encoded in the file name;
+    /// or `None` for ImageNet-sourced samples.
+    pub fn original_cifar_index(
+        &self,
+        index: usize,
+    ) -> Option<(CifarSplit, usize)> {
+        let name = self.meta(index)?;
+        Some((name.source_split?, name.number))
+    }
+
+    /// The provenance of a sample, parsed from its file name.
+    ///
+    /// # Returns
+    ///
+    /// The source dataset, original split, and CIFAR-10 index or ImageNet
+    /// synset and image number; or `None` if the name is not a CINIC-10 name.
+    pub fn meta(
+        &self,
+        index: usize,
+    ) -> Option<ItemName> {
+        parse_item_path(&self.item(index).path)
+    }
+
+    /// The dataset a sample was drawn from, parsed from its file name.
+    ///
+    /// # Returns
+    ///
+    /// The sample's `ItemSource`; or `None` if its name is not a CINIC-10 name.
+    pub fn origin(
+        &self,
+        index: usize,
+    ) -> Option<ItemSource> {
+        self.meta(index).map(|name| name.source)
+    }
+
+    /// A view of the samples drawn from one source dataset, in source order.
+    ///
+    /// E.g. train on `ItemSource::ImageNet` and test on `ItemSource::Cifar10`
+    /// to measure the shift between them; unparsed names match neither.
+    pub fn filter_origin(
+        &self,
+        source: ItemSource,
+    ) -> DatasetIndexView<'_> {
+        let indices = (0..self.len())
+            .filter(|&i| self.origin(i) == Some(source))
+            .collect();
+        DatasetIndexView::new(self, ObjectClass::ALL.to_vec(), indices)
+    }
+
+    /// Map original CIFAR-10 `(split, index)` pairs to indices of this split.
+    ///
+    /// Build this once when cross-referencing many CIFAR-10 samples.
+    pub fn cifar_index_map(&self) -> HashMap<(CifarSplit, usize), usize> {
+        (0..self.len())
+            .filter_map(|i| self.original_cifar_index(i).map(|origin| (origin, i)))
+            .collect()
+    }
+}
+
+impl Cinic10Index {
+    /// Find the CINIC-10 sample built from an original CIFAR-10 sample.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The original CIFAR-10 split.
+    /// - `index`: The original CIFAR-10 index.
+    ///
+    /// # Returns
+    ///
+    /// The CINIC-10 `(split, index)`, if the sample is present.
+    pub fn find_cifar_sample(
+        &self,
+        split: CifarSplit,
+        index: usize,
+    ) -> Option<(DataSet, usize)> {
+        [
+            (DataSet::Train, &self.train),
+            (DataSet::Test, &self.test),
+            (DataSet::Valid, &self.valid),
+        ]
+        .into_iter()
+        .find_map(|(ds, di)| {
+            (0..di.len())
+                .find(|&i| di.original_cifar_index(i) == Some((split, index)))
+                .map(|i| (ds, i))
+        })
+    }
+
+    /// Cross-reference the contributor records against the indexed files.
+    ///
+    /// CIFAR-10 files have no records, and are not checked.
+    /// Any mismatch indicates a corrupted or non-standard dataset copy.
+    pub fn check_contrib(&self) -> ContribCheck {
+        type Key = (DataSet, ObjectClass, String);
+        let splits = [
+            (DataSet::Train, &self.train),
+            (DataSet::Test, &self.test),
+            (DataSet::Valid, &self.valid),
+        ];
+
+        let mut on_disk: HashSet<Key> = HashSet::new();
+        let mut check = ContribCheck::default();
+        let recorded: HashSet<Key> = self
+            .imagenet_contrib
+            .iter()
+            .map(|r| (r.data_set(), r.class(), r.filename()))
+            .collect();
+        for (ds, split) in splits {
+            for (i, item) in split.items().enumerate() {
+                if parse_item_path(&item.path).is_some_and(|n| n.source == ItemSource::Cifar10) {
+                    continue;
+                }
+                let name = item
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let key = (ds, item.class, name);
+                if !recorded.contains(&key) {
+                    check.unrecorded_files.push((ds, split.abs_path(i)));
+                }
+                on_disk.insert(key);
+            }
+        }
+        check.missing_files = self
+            .imagenet_contrib
+            .iter()
+            .filter(|r| !on_disk.contains(&(r.data_set(), r.class(), r.filename())))
+            .cloned()
+            .collect();
+        check
+    }
+
+    /// The path of a contributor record's image: `{root}/{set}/{class}/{filename}`.
+    ///
+    /// The file need not exist; see `record_location()`.
+    pub fn record_path(
+        &self,
+        record: &IndexRecord,
+    ) -> PathBuf {
+        self.split(record.data_set())
+            .ds_path
+            .join(record.class().to_string())
+            .join(record.filename())
+    }
+
+    /// Find the sample a contributor record describes.
+    ///
+    /// # Returns
+    ///
+    /// The `(split, index)` of the record's file; or `None` if it is not indexed.
+    pub fn record_location(
+        &self,
+        record: &IndexRecord,
+    ) -> Option<(DataSet, usize)> {
+        let rel_path = Path::new(&record.class().to_string()).join(record.filename());
+        self.split(record.data_set())
+            .index_of_path(&rel_path)
+            .map(|i| (record.data_set(), i))
+    }
+
+    /// The contributor record of each item of a split.
+    ///
+    /// CIFAR-10 items, and files with no record, are `None`.
+    pub fn item_records(
+        &self,
+        data_set: DataSet,
+    ) -> Vec<Option<&IndexRecord>> {
+        self.split(data_set)
+            .items()
+            .map(|item| self.record_of_item(data_set, &item))
+            .collect()
+    }
+
+    /// The contributor record of a sample; `None` for CIFAR-10 or unrecorded samples.
+    ///
+    /// Lookups go through a `(synset, image_num)` map built on first use.
+    pub fn record_of(
+        &self,
+        data_set: DataSet,
+        index: usize,
+    ) -> Option<&IndexRecord> {
+        self.record_of_item(data_set, &self.split(data_set).item(index))
+    }
+
+    fn record_of_item(
+        &self,
+        data_set: DataSet,
+        item: &DatasetItem,
+    ) -> Option<&IndexRecord> {
+        let name = parse_item_path(&item.path)?;
+        self.record_index()
+            .get(&(name.synset?, name.number))?
+            .iter()
+            .map(|&i| &self.imagenet_contrib[i])
+            .find(|r| r.data_set() == data_set && r.class() == item.class)
+    }
+
+    /// A synset id and all of its descendants in the synset map.
+    pub fn synset_descendants(
+        &self,
+        synset_id: WnId,
+    ) -> Vec<WnId> {
+        let children = synset_children_map(&self.synset_map);
+
+        let mut found = vec![synset_id];
+        let mut pending = vec![synset_id];
+        while let Some(id) = pending.pop() {
+            for &child in children.get(&id).into_iter().flatten() {
+                found.push(child);
+                pending.push(child);
+            }
+        }
+        found
+    }
+
+    /// Find every sample whose source image came from the given synset.
+    ///
+    /// # Parameters
+    ///
+    /// - `synset_id`: The synset id, e.g. `"n02690373"`.
+    /// - `include_descendants`: Also match descendant synsets in the synset map.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the matching `(split, index)` pairs, in split order;
+    /// or an error if the synset is not in the synset map.
+    pub fn items_for_synset(
+        &self,
+        synset_id: WnId,
+        include_descendants: bool,
+    ) -> Result<Vec<(DataSet, usize)>> {
+        if !self.synset_map.contains_key(&synset_id) {
+            bail!("Unknown synset: {synset_id}");
+        }
+        let synsets: HashSet<WnId> = if include_descendants {
+            self.synset_descendants(synset_id).into_iter().collect()
+        } else {
+            HashSet::from([synset_id])
+        };
+
+        let splits = [
+            (DataSet::Train, &self.train),
+            (DataSet::Test, &self.test),
+            (DataSet::Valid, &self.valid),
+        ];
+        Ok(splits
+            .into_iter()
+            .flat_map(|(ds, split)| {
+                indices_for_synsets(split, &synsets)
+                    .into_iter()
+                    .map(move |i| (ds, i))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{parse_contrib_index, parse_synset_map};
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_item_filename() -> Result<()> {
+        let name = parse_item_filename("cifar10-train-3318.png")?;
+        assert_eq!(name.source, ItemSource::Cifar10);
+        assert_eq!(name.source_split, Some(CifarSplit::Train));
+        assert_eq!(name.number, 3318);
+        assert_eq!(name.to_filename(), "cifar10-train-3318.png");
+
+        let name = parse_item_filename("n02690373_6332.png")?;
+        assert_eq!(name.source, ItemSource::ImageNet);
+        assert_eq!(name.synset, Some("n02690373".parse()?));
+        assert_eq!(name.number, 6332);
+        assert_eq!(name.to_filename(), "n02690373_6332.png");
+        assert_eq!(
+            ItemName::parse("/data/train/airplane/n02690373_6332.png")?,
+            name
+        );
+        assert!(ItemName::parse("/data/train/airplane").is_err());
+        assert_eq!(parse_item_filename("n02690373_6332.JPEG")?, name);
+        assert_eq!(parse_item_filename("cifar10-test-7.webp")?.number, 7);
+
+        for bad in [
+            "cifar10-train-3318.txt",
+            "cifar10-train-3318",
+            "cifar10-valid-1.png",
+            "cifar10-test-.png",
+            "cifar10-test-1a.png",
+            "n0269x373_1.png",
+            "n002690373_1.png",
+            "n02690373_.png",
+            "x02690373_1.png",
+            "n02690373-1.png",
+            "",
+        ] {
+            assert!(parse_item_filename(bad).is_err(), "{bad}");
+        }
+
+        Ok(())
+    }
+
+    const CONTRIB: &str = "synset, image_num, cinic_set, class\n\
+                           n00000123, 1, train, dog\n\
+                           n00000123, 2, train, dog\n\
+                           n00000009, 5, test, dog\n";
+
+    fn dogs(
+        ds_path: &str,
+        names: &[&str],
+    ) -> DatasetIndex {
+        fixtures::split_of_names(ds_path, ObjectClass::Dog, names)
+    }
+
+    #[test]
+    fn test_check_contrib() -> Result<()> {
+        let mut cinic = fixtures::cinic(
+            "/data",
+            dogs(
+                "/data",
+                &["n00000123_1.png", "cifar10-train-7.png", "n00000009_5.png"],
+            ),
+            dogs("/data", &["n00000009_5.png"]),
+            dogs("/data", &[]),
+        );
+        cinic.imagenet_contrib = parse_contrib_index(CONTRIB.as_bytes())?;
+
+        let check = cinic.check_contrib();
+        assert!(!check.is_consistent());
+        assert_eq!(check.missing_files.len(), 1);
+        assert_eq!(check.missing_files[0].filename(), "n00000123_2.png");
+        assert_eq!(
+            check.unrecorded_files,
+            vec![(DataSet::Train, PathBuf::from("/data/dog/n00000009_5.png"))]
+        );
+
+        cinic.train = dogs("/data", &["n00000123_1.png", "n00000123_2.png"]);
+        assert!(cinic.check_contrib().is_consistent());
+
+        Ok(())
+    }
+
+    /// A dataset with one train file for each kind of record match.
+    fn joined_cinic() -> Result<Cinic10Index> {
+        let mut cinic = fixtures::cinic(
+            "/data",
+            dogs(
+                "/data/train",
+                &["cifar10-train-7.png", "n00000009_5.png", "n00000123_1.png"],
+            ),
+            dogs("/data/test", &[]),
+            dogs("/data/valid", &[]),
+        );
+        cinic.imagenet_contrib = parse_contrib_index(CONTRIB.as_bytes())?;
+        Ok(cinic)
+    }
+
+    #[test]
+    fn test_record_location() -> Result<()> {
+        let cinic = joined_cinic()?;
+        let records = &cinic.imagenet_contrib;
+
+        assert_eq!(
+            cinic.record_path(&records[1]),
+            PathBuf::from("/data/train/dog/n00000123_2.png")
+        );
+        assert_eq!(
+            cinic.record_location(&records[0]),
+            Some((DataSet::Train, 2))
+        );
+        assert_eq!(cinic.record_location(&records[1]), None);
+        assert_eq!(cinic.record_location(&records[2]), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_of() -> Result<()> {
+        let cinic = joined_cinic()?;
+        let records = &cinic.imagenet_contrib;
+
+        assert_eq!(
+            cinic.item_records(DataSet::Train),
+            vec![None, None, Some(&records[0])]
+        );
+        assert_eq!(cinic.record_of(DataSet::Train, 2), Some(&records[0]));
+        assert_eq!(cinic.record_of(DataSet::Train, 1), None);
+
+        let mut cinic = cinic.clone();
+        cinic.imagenet_contrib.clear();
+        cinic.clear_record_index();
+        assert_eq!(cinic.record_of(DataSet::Train, 2), None);
+
+        Ok(())
+    }
+
+    /// A dataset of mixed CIFAR and ImageNet files, with a synset hierarchy.
+    fn synset_cinic() -> Result<Cinic10Index> {
+        let synsets = "dog\n--n123: good boy\n----n1230: bestest boy\n--n9: cujo\n";
+        let mut cinic = fixtures::cinic(
+            "/data",
+            dogs(
+                "/data",
+                &["n123_1.png", "cifar10-train-7.png", "n1230_4.png"],
+            ),
+            dogs("/data", &["n9_2.png"]),
+            dogs("/data", &["n1230_9.png", "n123x_1.png"]),
+        );
+        cinic.synset_map = parse_synset_map(synsets.as_bytes())?;
+        Ok(cinic)
+    }
+
+    #[test]
+    fn test_items_for_synset() -> Result<()> {
+        let cinic = synset_cinic()?;
+
+        assert_eq!(
+            cinic.items_for_synset("n123".parse()?, false)?,
+            vec![(DataSet::Train, 0)]
+        );
+        assert_eq!(
+            cinic.items_for_synset("n123".parse()?, true)?,
+            vec![
+                (DataSet::Train, 0),
+                (DataSet::Train, 2),
+                (DataSet::Valid, 0)
+            ]
+        );
+        assert!(cinic.items_for_synset("n404".parse()?, true).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cifar_index() -> Result<()> {
+        let cinic = synset_cinic()?;
+
+        assert_eq!(cinic.train.original_cifar_index(0), None);
+        assert_eq!(
+            cinic.train.original_cifar_index(1),
+            Some((CifarSplit::Train, 7))
+        );
+        assert_eq!(
+            cinic.train.cifar_index_map(),
+            HashMap::from([((CifarSplit::Train, 7), 1)])
+        );
+        assert_eq!(
+            cinic.find_cifar_sample(CifarSplit::Train, 7),
+            Some((DataSet::Train, 1))
+        );
+        assert_eq!(cinic.find_cifar_sample(CifarSplit::Test, 7), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_origin() -> Result<()> {
+        let cinic = synset_cinic()?;
+
+        assert_eq!(
+            cinic.train.meta(2).and_then(|m| m.synset),
+            Some("n1230".parse()?)
+        );
+        assert_eq!(cinic.train.origin(1), Some(ItemSource::Cifar10));
+        assert_eq!(cinic.valid.origin(1), None);
+        let imagenet = cinic.train.filter_origin(ItemSource::ImageNet);
+        assert_eq!(imagenet.source_indices(), &[0, 2]);
+        assert_eq!(cinic.valid.filter_origin(ItemSource::ImageNet).len(), 1);
+        assert!(cinic.test.filter_origin(ItemSource::Cifar10).is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::Result;
+use crate::images::load_rgbimage;
+use crate::index::{CHANNELS, DatasetIndex};
+use crate::parallel::par_fold;
+use crate::stats::ChannelAccumulator;
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Summary statistics used to judge how much information an image carries.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    /// The largest per-channel `max - min` pixel value range.
+    pub range: u8,
+
+    /// The mean of the per-channel standard deviations, on the `[0, 1]` scale.
+    pub std: f64,
+
+    /// The fraction of pixels in the most common quantized color.
+    pub dominant_fraction: f64,
+}
+
+impl ImageInfo {
+    /// Measure an image.
+    ///
+    /// # Parameters
+    ///
+    /// - `img`: The image.
+    /// - `color_bin`: The width of the per-channel quantization bins used to find the dominant color.
+    pub fn measure(
+        img: &RgbImage,
+        color_bin: u8,
+    ) -> Self {
+        let color_bin = color_bin.max(1);
+        let mut lo = [u8::MAX; CHANNELS];
+        let mut hi = [u8::MIN; CHANNELS];
+        let mut acc = ChannelAccumulator::new();
+        let mut colors: HashMap<[u8; CHANNELS], usize> = HashMap::new();
+
+        for px in img.pixels() {
+            for c in 0..CHANNELS {
+                lo[c] = lo[c].min(px[c]);
+                hi[c] = hi[c].max(px[c]);
+            }
+            acc.push(px.0);
+            *colors.entry(px.0.map(|v| v / color_bin)).or_default() += 1;
+        }
+
+        let pixels = (img.width() * img.height()).max(1) as f64;
+        let std = acc.stats().std;
+        Self {
+            range: (0..CHANNELS)
+                .map(|c| hi[c].saturating_sub(lo[c]))
+                .max()
+                .unwrap_or(0),
+            std: std.iter().sum::<f64>() / CHANNELS as f64,
+            dominant_fraction: colors.values().copied().max().unwrap_or(0) as f64 / pixels,
+        }
+    }
+}
+
+/// Why an image was flagged as low-information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LowInfoReason {
+    /// Every channel spans at most `LowInfoConfig::max_range` values.
+    NearConstant,
+
+    /// The mean channel standard deviation is at most `LowInfoConfig::max_std`.
+    LowVariance,
+
+    /// At least `LowInfoConfig::max_dominant_fraction` of the pixels share one color.
+    DominantColor,
+}
+
+/// Thresholds for `find_low_information()`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LowInfoConfig {
+    pub max_range: u8,
+    pub max_std: f64,
+    pub max_dominant_fraction: f64,
+
+    /// The width of the per-channel quantization bins of `ImageInfo::dominant_fraction`.
+    pub color_bin: u8,
+
+    /// The number of threads; `0` means "all available cores".
+    pub parallelism: usize,
+}
+
+impl Default for LowInfoConfig {
+    fn default() -> Self {
+        Self {
+            max_range: 8,
+            max_std: 0.02,
+            max_dominant_fraction: 0.9,
+            color_bin: 16,
+            parallelism: 0,
+        }
+    }
+}
+
+impl LowInfoConfig {
+    /// The reasons an image with the given statistics would be flagged; empty if it passes.
+    pub fn check(
+        &self,
+        info: &ImageInfo,
+    ) -> Vec<LowInfoReason> {
+        let mut reasons = Vec::new();
+        if info.range <= self.max_range {
+            reasons.push(LowInfoReason::NearConstant);
+        }
+        if info.std <= self.max_std {
+            reasons.push(LowInfoReason::LowVariance);
+        }
+        if info.dominant_fraction >= self.max_dominant_fraction {
+            reasons.push(LowInfoReason::DominantColor);
+        }
+        reasons
+    }
+}
+
+/// An image flagged by `find_low_information()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LowInfoImage {
+    pub index: usize,
+    pub path: PathBuf,
+    pub info: ImageInfo,
+    pub reasons: Vec<LowInfoReason>,
+}
+
+/// Flag the near-constant, low-variance and single-color images of a split.
+///
+/// # Parameters
+///
+/// - `split`: The split to audit.
+/// - `config`: The thresholds and parallelism to use.
+///
+/// # Returns
+///
+/// A `Result` containing the flagged images, in index order;
+/// their `index` fields form a candidate exclusion list.
+pub fn find_low_information(
+    split: &DatasetIndex,
+    config: &LowInfoConfig,
+) -> Result<Vec<LowInfoImage>> {
+    let indices: Vec<usize> = (0..split.len()).collect();
+    par_fold(
+        &indices,
+        config.parallelism,
+        Vec::new,
+        |flagged, &i| {
+            let path = split.index_to_path(i);
+            let info = ImageInfo::measure(&load_rgbimage(&path)?, config.color_bin);
+            let reasons = config.check(&info);
+            if !reasons.is_empty() {
+                flagged.push(LowInfoImage {
+                    index: i,
+                    path,
+                    info,
+                    reasons,
+                });
+            }
+            Ok(())
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::Rgb;
+
+    #[test]
+    fn test_find_low_information() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let textured = RgbImage::from_fn(16, 16, |x, y| Rgb([(x * 16) as u8, (y * 16) as u8, 0]));
+        let constant = RgbImage::from_pixel(16, 16, Rgb([90, 90, 90]));
+        let letterboxed = RgbImage::from_fn(16, 16, |x, y| {
+            if x == 0 && y < 8 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+
+        let split = fixtures::write_split(
+            dir.path().join("train"),
+            [
+                ("a.png", textured),
+                ("b.png", constant),
+                ("c.png", letterboxed),
+            ]
+            .map(|(name, img)| (ObjectClass::Dog, name, img)),
+        )?;
+
+        let flagged = find_low_information(&split, &LowInfoConfig::default())?;
+        assert_eq!(flagged.len(), 2);
+
+        assert_eq!(flagged[0].index, 1);
+        assert_eq!(
+            flagged[0].reasons,
+            vec![
+                LowInfoReason::NearConstant,
+                LowInfoReason::LowVariance,
+                LowInfoReason::DominantColor
+            ]
+        );
+
+        assert_eq!(flagged[1].index, 2);
+        assert_eq!(flagged[1].reasons, vec![LowInfoReason::DominantColor]);
+        assert_eq!(flagged[1].info.range, 255);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use crate::Cinic10Index;
+use crate::error::{Result, bail};
+use crate::index::{DataSet, DatasetIndex, ObjectClass};
+use crate::provenance::{ItemSource, parse_item_path};
+use crate::wnid::WnId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A selection of samples across splits, as `(split, index)` pairs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subset {
+    pub entries: Vec<(DataSet, usize)>,
+}
+
+impl Subset {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (DataSet, usize)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// The selected indices of one split, in order.
+    pub fn indices(
+        &self,
+        split: DataSet,
+    ) -> Vec<usize> {
+        self.iter()
+            .filter(|&(ds, _)| ds == split)
+            .map(|(_, i)| i)
+            .collect()
+    }
+
+    /// The image paths of the selected samples, in order.
+    pub fn paths(
+        &self,
+        cinic: &Cinic10Index,
+    ) -> Vec<PathBuf> {
+        self.iter()
+            .map(|(ds, i)| cinic.split(ds).index_to_path(i))
+            .collect()
+    }
+}
+
+/// A composable filter over the samples of a `Cinic10Index`; see `Cinic10Index::query()`.
+#[derive(Debug, Clone)]
+pub struct Query<'a> {
+    cinic: &'a Cinic10Index,
+    splits: Option<Vec<DataSet>>,
+    classes: Option<Vec<ObjectClass>>,
+    source: Option<ItemSource>,
+    synset_under: Option<String>,
+    limit: Option<usize>,
+}
+
+impl<'a> Query<'a> {
+    /// Keep only samples of these splits.
+    pub fn splits<I>(
+        mut self,
+        splits: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = DataSet>,
+    {
+        self.splits = Some(splits.into_iter().collect());
+        self
+    }
+
+    /// Keep only samples of these classes.
+    pub fn classes<I>(
+        mut self,
+        classes: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = ObjectClass>,
+    {
+        self.classes = Some(classes.into_iter().collect());
+        self
+    }
+
+    /// Keep only samples drawn from this source dataset.
+    pub fn source(
+        mut self,
+        source: ItemSource,
+    ) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Keep only ImageNet samples of this synset, or of its descendants.
+    pub fn synset_under<S>(
+        mut self,
+        synset_id: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        self.synset_under = Some(synset_id.into());
+        self
+    }
+
+    /// Keep at most `n` samples, in split and index order.
+    pub fn limit(
+        mut self,
+        n: usize,
+    ) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Run the query.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the matching samples, in split and index order;
+    /// or an error if the `synset_under()` synset is malformed, or not in the synset map.
+    pub fn collect(&self) -> Result<Subset> {
+        let synsets: Option<HashSet<WnId>> = match &self.synset_under {
+            Some(id) => {
+                let id = WnId::from_str(id)?;
+                if !self.cinic.synset_map.contains_key(&id) {
+                    bail!("Unknown synset: {id}");
+                }
+                Some(self.cinic.synset_descendants(id).into_iter().collect())
+            }
+            None => None,
+        };
+
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let mut entries = Vec::new();
+        for ds in DataSet::ALL {
+            if self.splits.as_ref().is_some_and(|s| !s.contains(&ds)) {
+                continue;
+            }
+            let split = self.cinic.split(ds);
+            for (i, item) in split.items().enumerate() {
+                if entries.len() >= limit {
+                    return Ok(Subset { entries });
+                }
+                if self
+                    .classes
+                    .as_ref()
+                    .is_some_and(|c| !c.contains(&item.class))
+                {
+                    continue;
+                }
+                if self.source.is_some() || synsets.is_some() {
+                    let Some(name) = parse_item_path(&item.path) else {
+                        continue;
+                    };
+                    if self.source.is_some_and(|s| s != name.source) {
+                        continue;
+                    }
+                    if let Some(synsets) = &synsets
+                        && !name.synset.is_some_and(|s| synsets.contains(&s))
+                    {
+                        continue;
+                    }
+                }
+                entries.push((ds, i));
+            }
+        }
+        Ok(Subset { entries })
+    }
+}
+
+impl Cinic10Index {
+    pub fn split(
+        &self,
+        data_set: DataSet,
+    ) -> &DatasetIndex {
+        match data_set {
+            DataSet::Train => &self.train,
+            DataSet::Test => &self.test,
+            DataSet::Valid => &self.valid,
+        }
+    }
+
+    /// Start a query over all samples; e.g.
+    /// `cinic.query().classes([Cat, Dog]).source(ItemSource::ImageNet).limit(5000).collect()`.
+    pub fn query(&self) -> Query<'_> {
+        Query {
+            cinic: self,
+            splits: None,
+            classes: None,
+            source: None,
+            synset_under: None,
+            limit: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::parse_synset_map;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    fn cinic() -> Result<Cinic10Index> {
+        let synsets = "dog\n--n123: good boy\n----n1230: bestest boy\ncat\n--n9: chonk\n";
+        let mut cinic = fixtures::cinic(
+            "/data",
+            fixtures::split(
+                "/data",
+                [
+                    (ObjectClass::Dog, "n123_1.png"),
+                    (ObjectClass::Dog, "cifar10-train-7.png"),
+                    (ObjectClass::Cat, "n9_2.png"),
+                    (ObjectClass::Ship, "cifar10-train-8.png"),
+                ],
+            ),
+            fixtures::split("/data", [(ObjectClass::Dog, "n1230_4.png")]),
+            fixtures::split("/data", [(ObjectClass::Cat, "cifar10-test-1.png")]),
+        );
+        cinic.synset_map = parse_synset_map(synsets.as_bytes())?;
+        Ok(cinic)
+    }
+
+    #[test]
+    fn test_query_all() -> Result<()> {
+        let cinic = cinic()?;
+        assert_eq!(cinic.query().collect()?.len(), 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_classes_and_source() -> Result<()> {
+        let cinic = cinic()?;
+        let pets = cinic
+            .query()
+            .classes([ObjectClass::Cat, ObjectClass::Dog])
+            .source(ItemSource::ImageNet)
+            .collect()?;
+        assert_eq!(
+            pets.entries,
+            vec![(DataSet::Train, 0), (DataSet::Train, 2), (DataSet::Test, 0)]
+        );
+        assert_eq!(pets.indices(DataSet::Train), vec![0, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_synset_under() -> Result<()> {
+        let cinic = cinic()?;
+        let dogs = cinic.query().synset_under("n123").collect()?;
+        assert_eq!(dogs.entries, vec![(DataSet::Train, 0), (DataSet::Test, 0)]);
+        assert_eq!(
+            dogs.paths(&cinic)[1],
+            PathBuf::from("/data/dog/n1230_4.png")
+        );
+
+        assert!(cinic.query().synset_under("n404").collect().is_err());
+        assert!(cinic.query().synset_under("dog").collect().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_splits_and_limit() -> Result<()> {
+        let cinic = cinic()?;
+        let limited = cinic
+            .query()
+            .splits([DataSet::Train, DataSet::Valid])
+            .source(ItemSource::Cifar10)
+            .limit(2)
+            .collect()?;
+        assert_eq!(
+            limited.entries,
+            vec![(DataSet::Train, 1), (DataSet::Train, 3)]
+        );
+
+        Ok(())
+    }
+}
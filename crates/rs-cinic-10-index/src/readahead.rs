@@ -0,0 +1,90 @@
+use crate::index::DatasetIndex;
+use std::path::Path;
+
+/// The number of files hinted at once by `advise_split_ahead()`.
+pub(crate) const READAHEAD_WINDOW: usize = 64;
+
+/// Hint to the OS that a whole file will be read soon, sequentially.
+///
+/// Uses `posix_fadvise(WILLNEED | SEQUENTIAL)` on Linux and Android,
+/// and `F_RDADVISE` on macOS and iOS; elsewhere this does nothing.
+/// Hints are best-effort, and errors are ignored.
+pub(crate) fn advise_will_need(path: &Path) {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    ))]
+    {
+        use std::fs::File;
+        use std::os::fd::AsRawFd;
+
+        let Ok(file) = File::open(path) else {
+            return;
+        };
+        let fd = file.as_raw_fd();
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        // SAFETY: advisory calls on an open descriptor.
+        unsafe {
+            libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+            libc::posix_fadvise(fd, 0, 0, libc::POSIX_FADV_WILLNEED);
+        }
+
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        if let Ok(meta) = file.metadata() {
+            let advisory = libc::radvisory {
+                ra_offset: 0,
+                ra_count: meta.len().min(i32::MAX as u64) as libc::c_int,
+            };
+            // SAFETY: `F_RDADVISE` reads a `radvisory` through the pointer.
+            unsafe {
+                libc::fcntl(fd, libc::F_RDADVISE, &advisory);
+            }
+        }
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    let _ = path;
+}
+
+/// Hint every file of a batch, so the reads overlap on the device.
+pub(crate) fn advise_batch<P>(paths: &[P])
+where
+    P: AsRef<Path>,
+{
+    if paths.len() > 1 {
+        for path in paths {
+            advise_will_need(path.as_ref());
+        }
+    }
+}
+
+/// During an in-order pass over a split, hint the window of files ahead of `index`.
+///
+/// Hints are issued once per `READAHEAD_WINDOW` items, for the next window
+/// (and at `0`, for the first two); so the device fetches a window while
+/// the previous one is processed.
+pub(crate) fn advise_split_ahead(
+    split: &DatasetIndex,
+    index: usize,
+) {
+    if !index.is_multiple_of(READAHEAD_WINDOW) {
+        return;
+    }
+    let start = if index == 0 {
+        0
+    } else {
+        index + READAHEAD_WINDOW
+    };
+    let end = (index + 2 * READAHEAD_WINDOW).min(split.len());
+    for i in start..end {
+        advise_will_need(&split.index_to_path(i));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::error::{Result, bail};
+use crate::index::DatasetIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Parameters of a recorded pipeline component, keyed by name.
+pub type ComponentParams = BTreeMap<String, serde_json::Value>;
+
+/// A single recorded pipeline component, such as a sampler or an augmentation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentRecord {
+    /// The component type, e.g. `"curriculum_sampler"`.
+    pub kind: String,
+
+    #[serde(default)]
+    pub params: ComponentParams,
+}
+
+impl ComponentRecord {
+    pub fn new<S>(kind: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            kind: kind.into(),
+            params: Default::default(),
+        }
+    }
+
+    /// Add a parameter to the record.
+    pub fn with_param<K, V>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<serde_json::Value>,
+    {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Pipeline components which can describe themselves in a `PipelineRecord`.
+pub trait Recordable {
+    fn component_record(&self) -> ComponentRecord;
+}
+
+/// The recorded identity of a split used by a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitRecord {
+    /// See `DatasetIndex::fingerprint()`.
+    pub fingerprint: String,
+    pub len: usize,
+}
+
+impl SplitRecord {
+    pub fn from_index(index: &DatasetIndex) -> Self {
+        Self {
+            fingerprint: index.fingerprint(),
+            len: index.len(),
+        }
+    }
+}
+
+/// The reproducibility-relevant configuration of a data pipeline.
+///
+/// Log this with experiment metadata; on replay, load it, check the splits
+/// with `verify_split()`, and rebuild the components from their records.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineRecord {
+    /// The version of this crate which produced the record.
+    pub crate_version: String,
+
+    pub seed: Option<u64>,
+
+    /// Splits used by the pipeline, keyed by name.
+    #[serde(default)]
+    pub splits: BTreeMap<String, SplitRecord>,
+
+    pub sampler: Option<ComponentRecord>,
+
+    /// Augmentations, in application order.
+    #[serde(default)]
+    pub augmentations: Vec<ComponentRecord>,
+}
+
+impl Default for PipelineRecord {
+    fn default() -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: None,
+            splits: Default::default(),
+            sampler: None,
+            augmentations: Default::default(),
+        }
+    }
+}
+
+impl PipelineRecord {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_seed(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Record a split under the given name.
+    pub fn with_split<S>(
+        mut self,
+        name: S,
+        index: &DatasetIndex,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        self.splits
+            .insert(name.into(), SplitRecord::from_index(index));
+        self
+    }
+
+    pub fn with_sampler<R>(
+        mut self,
+        sampler: &R,
+    ) -> Self
+    where
+        R: Recordable + ?Sized,
+    {
+        self.sampler = Some(sampler.component_record());
+        self
+    }
+
+    /// Append an augmentation; record them in application order.
+    pub fn with_augmentation<R>(
+        mut self,
+        augmentation: &R,
+    ) -> Self
+    where
+        R: Recordable + ?Sized,
+    {
+        self.augmentations.push(augmentation.component_record());
+        self
+    }
+
+    /// Check that `index` matches the split recorded under `name`.
+    ///
+    /// # Parameters
+    ///
+    /// - `name`: The recorded split name.
+    /// - `index`: The index being replayed.
+    ///
+    /// # Returns
+    ///
+    /// An error if the split was not recorded or its fingerprint differs.
+    pub fn verify_split(
+        &self,
+        name: &str,
+        index: &DatasetIndex,
+    ) -> Result<()> {
+        let Some(expected) = self.splits.get(name) else {
+            bail!("Split not recorded: {name}");
+        };
+        let actual = SplitRecord::from_index(index);
+        if &actual != expected {
+            bail!(
+                "Split {name} does not match the record: expected {} items with fingerprint {}, found {} items with fingerprint {}",
+                expected.len,
+                expected.fingerprint,
+                actual.len,
+                actual.fingerprint
+            );
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Write the record as JSON.
+    pub fn save<P>(
+        &self,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Read a record written by `save()`.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    struct Flip(f64);
+
+    impl Recordable for Flip {
+        fn component_record(&self) -> ComponentRecord {
+            ComponentRecord::new("horizontal_flip").with_param("p", self.0)
+        }
+    }
+
+    fn index(names: &[&str]) -> DatasetIndex {
+        fixtures::split_of_names("/data/train", ObjectClass::Frog, names)
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let train = index(&["a.png", "b.png"]);
+        let record = PipelineRecord::new()
+            .with_seed(42)
+            .with_split("train", &train)
+            .with_augmentation(&Flip(0.5));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("pipeline.json");
+        record.save(&path)?;
+        let loaded = PipelineRecord::load(&path)?;
+
+        assert_eq!(loaded, record);
+        assert_eq!(loaded.seed, Some(42));
+        assert_eq!(loaded.augmentations[0].params["p"], 0.5);
+
+        loaded.verify_split("train", &train)?;
+        assert!(
+            loaded
+                .verify_split("train", &index(&["b.png", "a.png"]))
+                .is_err()
+        );
+        assert!(loaded.verify_split("valid", &train).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::Cinic10Index;
+use crate::error::{Result, bail};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Named dataset roots, as `name=path` entries separated by commas.
+///
+/// E.g. `CINIC10_DATASETS=clean=/data/cinic-clean,mini=/scratch/cinic-mini`.
+pub const CINIC10_DATASETS_ENV_VAR: &str = "CINIC10_DATASETS";
+
+static REGISTRY: RwLock<BTreeMap<String, PathBuf>> = RwLock::new(BTreeMap::new());
+
+/// Register a dataset root under `name`, replacing any earlier registration.
+///
+/// # Parameters
+///
+/// - `name`: The dataset name, e.g. `"cinic10-clean"`.
+/// - `root`: The dataset root directory.
+pub fn register_dataset<S, P>(
+    name: S,
+    root: P,
+) where
+    S: Into<String>,
+    P: Into<PathBuf>,
+{
+    REGISTRY.write().unwrap().insert(name.into(), root.into());
+}
+
+/// Remove a registered dataset; returns its root, if it was registered.
+pub fn unregister_dataset(name: &str) -> Option<PathBuf> {
+    REGISTRY.write().unwrap().remove(name)
+}
+
+/// Parse `name=path` entries, one per item; blank items and `#` comments are skipped.
+fn parse_entries<'a, I>(entries: I) -> Result<Vec<(String, PathBuf)>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    entries
+        .into_iter()
+        .map(str::trim)
+        .filter(|e| !e.is_empty() && !e.starts_with('#'))
+        .map(|entry| match entry.split_once('=') {
+            Some((name, path)) if !name.trim().is_empty() && !path.trim().is_empty() => {
+                Ok((name.trim().to_string(), PathBuf::from(path.trim())))
+            }
+            _ => bail!("Malformed dataset entry, expected `name=path`: {entry:?}"),
+        })
+        .collect()
+}
+
+/// The datasets named by `CINIC10_DATASETS_ENV_VAR`.
+fn env_datasets() -> Result<Vec<(String, PathBuf)>> {
+    match env::var(CINIC10_DATASETS_ENV_VAR) {
+        Ok(value) => Ok(parse_entries(value.split(','))
+            .with_context(|| format!("Failed to parse {CINIC10_DATASETS_ENV_VAR}"))?),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Register every dataset listed in a config file.
+///
+/// The file has one `name = path` entry per line; blank lines and lines
+/// starting with `#` are ignored. Relative paths are resolved against the
+/// file's directory.
+///
+/// # Parameters
+///
+/// - `path`: The config file.
+///
+/// # Returns
+///
+/// A `Result` containing the number of datasets registered.
+pub fn register_datasets_from_file<P>(path: P) -> Result<usize>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read dataset registry {}", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+    let entries = parse_entries(text.lines())
+        .with_context(|| format!("Failed to parse dataset registry {}", path.display()))?;
+    let count = entries.len();
+    for (name, root) in entries {
+        register_dataset(name, base.join(root));
+    }
+    Ok(count)
+}
+
+/// Resolve a dataset name to its root.
+///
+/// Programmatic registrations take precedence over `CINIC10_DATASETS`.
+///
+/// # Parameters
+///
+/// - `name`: The dataset name.
+///
+/// # Returns
+///
+/// A `Result` containing the root; or an error listing the known names.
+pub fn resolve_dataset(name: &str) -> Result<PathBuf> {
+    if let Some(root) = REGISTRY.read().unwrap().get(name) {
+        return Ok(root.clone());
+    }
+    if let Some((_, root)) = env_datasets()?.into_iter().find(|(n, _)| n == name) {
+        return Ok(root);
+    }
+    let known: Vec<String> = registered_datasets()?.into_keys().collect();
+    bail!("Unknown dataset {name:?}; registered datasets: {known:?}")
+}
+
+/// Every registered dataset, by name; including those from `CINIC10_DATASETS`.
+pub fn registered_datasets() -> Result<BTreeMap<String, PathBuf>> {
+    let mut datasets: BTreeMap<String, PathBuf> = env_datasets()?.into_iter().collect();
+    datasets.extend(
+        REGISTRY
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone())),
+    );
+    Ok(datasets)
+}
+
+impl Cinic10Index {
+    /// Load a registered dataset by name; see `register_dataset()`.
+    ///
+    /// # Parameters
+    ///
+    /// - `name`: The dataset name, e.g. `"cinic10-clean"`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Cinic10Index`.
+    pub fn named(name: &str) -> Result<Cinic10Index> {
+        let root = resolve_dataset(name)?;
+        if !root.is_dir() {
+            bail!(
+                "Dataset {name:?} root is not a directory: {}",
+                root.display()
+            );
+        }
+        Cinic10Index::new_from_dir(root)
+    }
+
+    /// Load a registered dataset by name; an alias of `named()`.
+    ///
+    /// Lets hosts with several copies (e.g. a scratch SSD and NFS) pick a
+    /// root at runtime, without sharing the single default data path.
+    pub fn from_registry(name: &str) -> Result<Cinic10Index> {
+        Cinic10Index::named(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_registry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config = dir.path().join("datasets.conf");
+        fs::write(
+            &config,
+            "# scratch copies\nregistry-test-clean = clean\n\nregistry-test-mini=/mini\n",
+        )?;
+        assert_eq!(register_datasets_from_file(&config)?, 2);
+        assert_eq!(
+            resolve_dataset("registry-test-clean")?,
+            dir.path().join("clean")
+        );
+
+        register_dataset("registry-test-mini", "/other");
+        assert_eq!(
+            resolve_dataset("registry-test-mini")?,
+            PathBuf::from("/other")
+        );
+        assert!(registered_datasets()?.contains_key("registry-test-clean"));
+
+        assert_eq!(
+            unregister_dataset("registry-test-mini"),
+            Some(PathBuf::from("/other"))
+        );
+        let err = resolve_dataset("registry-test-mini").unwrap_err();
+        assert!(err.to_string().contains("registry-test-clean"));
+        assert!(Cinic10Index::named("registry-test-clean").is_err());
+        assert!(Cinic10Index::from_registry("registry-test-mini").is_err());
+
+        fs::write(&config, "no-equals-sign\n")?;
+        assert!(register_datasets_from_file(&config).is_err());
+
+        Ok(())
+    }
+}
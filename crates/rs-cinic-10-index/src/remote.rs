@@ -0,0 +1,458 @@
+use crate::checksum::{CHECKSUMS_FILE_STEM, ChecksumAlgorithm, ChecksumManifest};
+use crate::error::{Result, bail};
+use crate::metrics;
+use crate::source::DataSource;
+use anyhow::Context;
+use object_store::ObjectStore;
+use object_store::aws::AmazonS3Builder;
+use object_store::azure::MicrosoftAzureBuilder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path as StorePath;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use tokio::runtime::Runtime;
+
+/// A read-only `DataSource` over public HTTP(S); e.g. a web mirror of the dataset.
+///
+/// Plain HTTP can't list directories, so the dataset's files are listed by a
+/// checksum manifest (see `ChecksumManifest::write()`) published at its root,
+/// as `CHECKSUMS.b3` or `CHECKSUMS.sha256`. Requests are unsigned; for
+/// private buckets, use `ObjectStoreSource`.
+///
+/// Index paths are under the URL as given, so build the index with
+/// `Cinic10Index::new_from_source(&source, source.root())`.
+#[derive(Debug)]
+pub struct HttpSource {
+    root: PathBuf,
+    base_url: String,
+    manifest: ChecksumManifest,
+    agent: ureq::Agent,
+}
+
+impl HttpSource {
+    /// Open a remote dataset, fetching its manifest.
+    ///
+    /// # Parameters
+    ///
+    /// - `url`: The dataset root, an `http://` or `https://` URL.
+    /// - `timeout`: The connect and read timeout of each request.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the source.
+    pub fn open(
+        url: &str,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let base_url = url.trim_end_matches('/').to_string();
+        if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+            bail!("Not an HTTP(S) URL: {url}; open object stores with ObjectStoreSource");
+        }
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(timeout)
+            .timeout_read(timeout)
+            .build();
+
+        let mut manifest = None;
+        for algorithm in ChecksumAlgorithm::iter() {
+            let manifest_url =
+                format!("{base_url}/{CHECKSUMS_FILE_STEM}.{}", algorithm.extension());
+            match agent.get(&manifest_url).call() {
+                Ok(response) => {
+                    manifest = Some(
+                        ChecksumManifest::parse(BufReader::new(response.into_reader()), algorithm)
+                            .with_context(|| format!("Failed to read {manifest_url}"))?,
+                    );
+                    break;
+                }
+                Err(ureq::Error::Status(404 | 403, _)) => continue,
+                Err(err) => {
+                    return Err(anyhow::Error::new(err)
+                        .context(format!("Failed to fetch {manifest_url}"))
+                        .into());
+                }
+            }
+        }
+        let Some(manifest) = manifest else {
+            bail!("No {CHECKSUMS_FILE_STEM} manifest found at {base_url}");
+        };
+
+        Ok(Self {
+            root: PathBuf::from(url.trim_end_matches('/')),
+            base_url,
+            manifest,
+            agent,
+        })
+    }
+
+    /// The dataset root, as index paths see it.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The path of `path` relative to the dataset root.
+    fn key<'a>(
+        &self,
+        path: &'a Path,
+    ) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+impl DataSource for HttpSource {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        let Some(rel) = self.key(path).to_str() else {
+            bail!("Path is not valid UTF-8: {}", path.display());
+        };
+        let url = format!("{}/{}", self.base_url, rel.replace('\\', "/"));
+        let response = self
+            .agent
+            .get(&url)
+            .call()
+            .with_context(|| format!("Failed to fetch {url}"))?;
+        let mut bytes = Vec::new();
+        response.into_reader().read_to_end(&mut bytes)?;
+        metrics::record(|m| m.bytes_read(bytes.len() as u64));
+        Ok(bytes)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        let dir = self.key(dir);
+        Ok(self
+            .manifest
+            .entries
+            .keys()
+            .filter(|rel| rel.parent() == Some(dir))
+            .filter_map(|rel| rel.file_name()?.to_str().map(str::to_string))
+            .collect())
+    }
+
+    fn digest(
+        &self,
+        path: &Path,
+    ) -> Option<(ChecksumAlgorithm, String)> {
+        let digest = self.manifest.entries.get(self.key(path))?;
+        Some((self.manifest.algorithm, digest.clone()))
+    }
+}
+
+/// A read-only `DataSource` over an S3, GCS, or Azure bucket, via `object_store`.
+///
+/// Credentials come from each store's standard chain: environment variables
+/// (e.g. `AWS_ACCESS_KEY_ID`, `GOOGLE_APPLICATION_CREDENTIALS`,
+/// `AZURE_STORAGE_ACCOUNT_NAME`), then web identity and instance metadata on
+/// cloud VMs; so private buckets work wherever the training job's role does.
+/// Directories are listed from the bucket itself; no manifest is needed.
+/// Reads block on the source's own tokio runtime, so call them from loader
+/// threads, not from async tasks.
+///
+/// Index paths are under the URL as given, so build the index with
+/// `Cinic10Index::new_from_source(&source, source.root())`.
+#[derive(Debug)]
+pub struct ObjectStoreSource {
+    root: PathBuf,
+    store: Arc<dyn ObjectStore>,
+    prefix: StorePath,
+    runtime: Runtime,
+}
+
+impl ObjectStoreSource {
+    /// Open a dataset in a bucket.
+    ///
+    /// # Parameters
+    ///
+    /// - `url`: The dataset root; `s3://bucket/prefix`, `gs://bucket/prefix`,
+    ///   or `az://container/prefix`, with the account in `AZURE_STORAGE_ACCOUNT_NAME`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the source; or an error for another scheme, or
+    /// a store which cannot be configured.
+    pub fn open(url: &str) -> Result<Self> {
+        let url = url.trim_end_matches('/');
+        let Some((scheme, rest)) = url.split_once("://") else {
+            bail!("Not a URL: {url}");
+        };
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        let bucket_url = format!("{scheme}://{bucket}");
+        let store: Arc<dyn ObjectStore> = match scheme {
+            "s3" | "s3a" => AmazonS3Builder::from_env()
+                .with_url(bucket_url)
+                .build()
+                .map(|s| Arc::new(s) as _),
+            "gs" => GoogleCloudStorageBuilder::from_env()
+                .with_url(bucket_url)
+                .build()
+                .map(|s| Arc::new(s) as _),
+            "az" | "azure" | "abfs" | "abfss" => MicrosoftAzureBuilder::from_env()
+                .with_url(bucket_url)
+                .build()
+                .map(|s| Arc::new(s) as _),
+            _ => bail!("Unsupported object store scheme: {url}"),
+        }
+        .with_context(|| format!("Failed to configure the object store for {url}"))?;
+        Self::new(store, url, prefix)
+    }
+
+    /// Read the dataset under `prefix` of an already configured store.
+    ///
+    /// # Parameters
+    ///
+    /// - `store`: The object store.
+    /// - `root`: The dataset root, as index paths see it.
+    /// - `prefix`: The dataset's key prefix in the store.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the source.
+    pub fn new<P>(
+        store: Arc<dyn ObjectStore>,
+        root: P,
+        prefix: &str,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the object store runtime")?;
+        Ok(Self {
+            root: root.as_ref().to_path_buf(),
+            store,
+            prefix: StorePath::parse(prefix)
+                .with_context(|| format!("Malformed object store prefix: {prefix:?}"))?,
+            runtime,
+        })
+    }
+
+    /// The dataset root, as index paths see it.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The store key of `path`.
+    fn key(
+        &self,
+        path: &Path,
+    ) -> Result<StorePath> {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        let mut key = self.prefix.clone();
+        for component in rel.components() {
+            let Component::Normal(part) = component else {
+                bail!("Unsupported path for an object store: {}", path.display());
+            };
+            let Some(part) = part.to_str() else {
+                bail!("Path is not valid UTF-8: {}", path.display());
+            };
+            key = key.child(part);
+        }
+        Ok(key)
+    }
+}
+
+impl DataSource for ObjectStoreSource {
+    fn name(&self) -> &str {
+        "object_store"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        let key = self.key(path)?;
+        let bytes = self
+            .runtime
+            .block_on(async { self.store.get(&key).await?.bytes().await })
+            .with_context(|| format!("Failed to fetch {key}"))?;
+        metrics::record(|m| m.bytes_read(bytes.len() as u64));
+        Ok(bytes.to_vec())
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        let key = self.key(dir)?;
+        let listing = self
+            .runtime
+            .block_on(self.store.list_with_delimiter(Some(&key)))
+            .with_context(|| format!("Failed to list {key}"))?;
+        let mut names: Vec<String> = listing
+            .objects
+            .iter()
+            .filter_map(|meta| meta.location.filename().map(str::to_string))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cinic10Index;
+    use crate::index::{CONTRIB_FILE, ObjectClass, SYNSET_FILE};
+    use crate::loader::{BatchLoader, LoaderConfig};
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+    use object_store::PutPayload;
+    use object_store::memory::InMemory;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::{BufRead, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve the files under `root` over HTTP, returning the base URL.
+    fn serve(root: &Path) -> Result<String> {
+        let mut files = HashMap::new();
+        let (_, rels) = crate::diff::walk(root)?;
+        for rel in rels {
+            files.insert(format!("/{}", rel.display()), fs::read(root.join(&rel))?);
+        }
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match files.get(path) {
+                    Some(body) => ("200 OK", body.as_slice()),
+                    None => ("404 Not Found", &[][..]),
+                };
+                let header = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                let _ = stream.write_all(body);
+            }
+        });
+        Ok(url)
+    }
+
+    #[test]
+    fn test_http_source() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for (i, class) in ["cat", "ship"].into_iter().enumerate() {
+            for data_set in ["train", "test", "valid"] {
+                let class_dir = root.join(data_set).join(class);
+                fs::create_dir_all(&class_dir)?;
+                RgbImage::from_pixel(2, 2, Rgb([i as u8, 0, 0])).save(class_dir.join("a.png"))?;
+            }
+        }
+        fs::write(
+            root.join(CONTRIB_FILE),
+            "synset,image_num,cinic_set,class\n",
+        )?;
+        fs::write(root.join(SYNSET_FILE), "cat\n")?;
+
+        let url = serve(root)?;
+        assert!(HttpSource::open(&url, Duration::from_secs(5)).is_err());
+        assert!(HttpSource::open("s3://bucket/cinic10", Duration::from_secs(5)).is_err());
+
+        ChecksumManifest::generate(root, ChecksumAlgorithm::Sha256, 1)?
+            .write(root.join("CHECKSUMS.sha256"))?;
+        let url = serve(root)?;
+        let source = HttpSource::open(&format!("{url}/"), Duration::from_secs(5))?;
+        assert_eq!(source.base_url(), url);
+        assert_eq!(
+            source.list(&source.root().join("train/cat"))?,
+            vec!["a.png"]
+        );
+
+        let cinic = Cinic10Index::new_from_source(&source, source.root())?;
+        assert_eq!(
+            cinic.valid.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        let loader = BatchLoader::new(LoaderConfig {
+            source: Some(Arc::new(source)),
+            ..Default::default()
+        });
+        let batch = cinic.train.load_rgbimagebatch_with(&loader, &[0, 1])?;
+        assert_eq!(batch.shape, vec![2, 2, 2, 3]);
+        assert_eq!(batch.data[12], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_store_source() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for (i, class) in ["cat", "ship"].into_iter().enumerate() {
+            for data_set in ["train", "test", "valid"] {
+                let class_dir = root.join(data_set).join(class);
+                fs::create_dir_all(&class_dir)?;
+                RgbImage::from_pixel(2, 2, Rgb([i as u8, 0, 0])).save(class_dir.join("a.png"))?;
+            }
+        }
+        fs::write(
+            root.join(CONTRIB_FILE),
+            "synset,image_num,cinic_set,class\n",
+        )?;
+        fs::write(root.join(SYNSET_FILE), "cat\n")?;
+
+        let store = Arc::new(InMemory::new());
+        let source = ObjectStoreSource::new(store.clone(), "s3://bucket/cinic10", "cinic10")?;
+        let (_, rels) = crate::diff::walk(root)?;
+        for rel in rels {
+            let key = source.key(&rel)?;
+            let bytes = fs::read(root.join(&rel))?;
+            source
+                .runtime
+                .block_on(store.put(&key, PutPayload::from(bytes)))?;
+        }
+
+        assert_eq!(
+            source.list(&source.root().join("train/cat"))?,
+            vec!["a.png"]
+        );
+        assert!(source.list(&source.root().join("train/dog"))?.is_empty());
+        assert!(source.read(&source.root().join("train/dog/a.png")).is_err());
+
+        let cinic = Cinic10Index::new_from_source(&source, source.root())?;
+        assert_eq!(
+            cinic.valid.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        let loader = BatchLoader::new(LoaderConfig {
+            source: Some(Arc::new(source)),
+            ..Default::default()
+        });
+        let batch = cinic.train.load_rgbimagebatch_with(&loader, &[0, 1])?;
+        assert_eq!(batch.data[12], 1);
+
+        assert!(ObjectStoreSource::open("ftp://host/x").is_err());
+        assert!(ObjectStoreSource::open("/local/path").is_err());
+
+        Ok(())
+    }
+}
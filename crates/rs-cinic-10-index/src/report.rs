@@ -0,0 +1,412 @@
+use crate::dedup::{DedupConfig, cluster_image_hashes, hash_split};
+use crate::error::Result;
+use crate::images::load_rgbimage;
+use crate::index::{Cinic10Index, DataSet, DatasetIndex, HEIGHT, ObjectClass, WIDTH};
+use crate::leakage::find_leakage_in_hashes;
+use crate::provenance::parse_item_path;
+use crate::stats::{HISTOGRAM_BINS, compute_channel_stats, compute_histogram};
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use strum::IntoEnumIterator;
+
+/// Which sections `generate_report()` includes.
+///
+/// Channel statistics, histograms and duplicate detection read every image,
+/// and dominate the cost of a report over the full dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportOptions {
+    pub title: String,
+
+    /// Include per-split channel mean/std.
+    pub channel_stats: bool,
+
+    /// Include per-split channel histograms.
+    pub histograms: bool,
+
+    /// The number of montage samples per class; `0` disables montages.
+    pub montage_per_class: usize,
+
+    /// Include duplicate and leakage summaries, using this configuration.
+    pub dedup: Option<DedupConfig>,
+
+    /// Include CIFAR-10 / ImageNet source breakdowns.
+    pub provenance: bool,
+
+    /// The number of threads; `0` means "all available cores".
+    pub parallelism: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            title: "CINIC-10 Dataset Report".to_string(),
+            channel_stats: true,
+            histograms: true,
+            montage_per_class: 8,
+            dedup: Some(DedupConfig::default()),
+            provenance: true,
+            parallelism: 0,
+        }
+    }
+}
+
+/// The number of duplicate clusters and leakage matches listed in full.
+const LISTED_FINDINGS: usize = 20;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+img.montage { image-rendering: pixelated; height: 64px; }
+code { font-size: 0.9em; }";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn png_data_uri(img: &RgbImage) -> Result<String> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+    Ok(format!("data:image/png;base64,{}", base64(&bytes)))
+}
+
+/// The source dataset of a CINIC-10 file, from its name.
+fn file_source(path: &Path) -> String {
+    parse_item_path(path).map_or_else(|| "unknown".to_string(), |name| name.source.to_string())
+}
+
+/// A strip of evenly spaced samples of each class.
+fn montage(
+    split: &DatasetIndex,
+    class: ObjectClass,
+    count: usize,
+) -> Result<Option<RgbImage>> {
+    let indices: Vec<usize> = (0..split.len())
+        .filter(|&i| split.index_to_class(i) == class)
+        .collect();
+    if indices.is_empty() {
+        return Ok(None);
+    }
+    let count = count.min(indices.len());
+
+    let mut strip = RgbImage::from_pixel((WIDTH * count) as u32, HEIGHT as u32, Rgb([0, 0, 0]));
+    for k in 0..count {
+        let mut img = load_rgbimage(split.index_to_path(indices[k * indices.len() / count]))?;
+        if img.dimensions() != (WIDTH as u32, HEIGHT as u32) {
+            img = imageops::resize(&img, WIDTH as u32, HEIGHT as u32, FilterType::Triangle);
+        }
+        imageops::replace(&mut strip, &img, (WIDTH * k) as i64, 0);
+    }
+    Ok(Some(strip))
+}
+
+fn histogram_svg(frequencies: &[Vec<f64>]) -> String {
+    let peak = frequencies
+        .iter()
+        .flatten()
+        .copied()
+        .fold(0.0f64, f64::max)
+        .max(f64::MIN_POSITIVE);
+    let mut svg = format!(
+        r#"<svg width="{HISTOGRAM_BINS}" height="100" viewBox="0 0 {HISTOGRAM_BINS} 100">"#
+    );
+    for (freqs, color) in frequencies.iter().zip(["red", "green", "blue"]) {
+        let points: Vec<String> = freqs
+            .iter()
+            .enumerate()
+            .map(|(x, f)| format!("{x},{:.1}", 100.0 - 100.0 * f / peak))
+            .collect();
+        let _ = write!(
+            svg,
+            r#"<polyline fill="none" stroke="{color}" points="{}"/>"#,
+            points.join(" ")
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Render a self-contained HTML audit report of a set of splits.
+///
+/// # Parameters
+///
+/// - `splits`: The splits to report on, with their names.
+/// - `options`: Which sections to include.
+///
+/// # Returns
+///
+/// A `Result` containing the HTML document.
+pub fn render_report(
+    splits: &[(DataSet, &DatasetIndex)],
+    options: &ReportOptions,
+) -> Result<String> {
+    let mut html = String::new();
+    let title = escape(&options.title);
+    let _ = writeln!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n<h1>{title}</h1>"
+    );
+
+    html.push_str("<h2>Class Counts</h2>\n<table>\n<tr><th>class</th>");
+    for (split, _) in splits {
+        let _ = write!(html, "<th>{split}</th>");
+    }
+    html.push_str("<th>total</th></tr>\n");
+    let dists: Vec<_> = splits
+        .iter()
+        .map(|(_, index)| index.class_distribution())
+        .collect();
+    for class in ObjectClass::iter() {
+        let _ = write!(html, "<tr><td>{class}</td>");
+        for dist in &dists {
+            let _ = write!(html, "<td>{}</td>", dist.count(class));
+        }
+        let total: usize = dists.iter().map(|d| d.count(class)).sum();
+        let _ = writeln!(html, "<td>{total}</td></tr>");
+    }
+    html.push_str("<tr><th>total</th>");
+    for dist in &dists {
+        let _ = write!(html, "<th>{}</th>", dist.total());
+    }
+    let total: usize = dists.iter().map(|d| d.total()).sum();
+    let _ = writeln!(html, "<th>{total}</th></tr>\n</table>");
+
+    if options.provenance {
+        html.push_str("<h2>Provenance</h2>\n<table>\n<tr><th>split</th><th>class</th><th>cifar10</th><th>imagenet</th><th>unknown</th></tr>\n");
+        for (split, index) in splits {
+            let mut counts: HashMap<(ObjectClass, String), usize> = HashMap::new();
+            for i in 0..index.len() {
+                let class = index.index_to_class(i);
+                *counts
+                    .entry((class, file_source(&index.index_to_path(i))))
+                    .or_default() += 1;
+            }
+            for class in ObjectClass::iter() {
+                let _ = write!(html, "<tr><td>{split}</td><td>{class}</td>");
+                for source in ["cifar10", "imagenet", "unknown"] {
+                    let n = counts
+                        .get(&(class, source.to_string()))
+                        .copied()
+                        .unwrap_or(0);
+                    let _ = write!(html, "<td>{n}</td>");
+                }
+                html.push_str("</tr>\n");
+            }
+        }
+        html.push_str("</table>\n");
+    }
+
+    if options.channel_stats {
+        html.push_str("<h2>Channel Statistics</h2>\n<table>\n<tr><th>split</th><th>mean (r, g, b)</th><th>std (r, g, b)</th></tr>\n");
+        for (split, index) in splits {
+            let stats = compute_channel_stats(index, options.parallelism)?;
+            let fmt = |v: &[f64; 3]| format!("{:.4}, {:.4}, {:.4}", v[0], v[1], v[2]);
+            let _ = writeln!(
+                html,
+                "<tr><td>{split}</td><td>{}</td><td>{}</td></tr>",
+                fmt(&stats.mean),
+                fmt(&stats.std)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    if options.histograms {
+        html.push_str("<h2>Channel Histograms</h2>\n");
+        for (split, index) in splits {
+            let hist = compute_histogram(index, options.parallelism)?;
+            let freqs: Vec<Vec<f64>> = (0..3).map(|c| hist.frequencies(c)).collect();
+            let _ = writeln!(html, "<h3>{split}</h3>\n{}", histogram_svg(&freqs));
+        }
+    }
+
+    if options.montage_per_class > 0 {
+        html.push_str("<h2>Samples</h2>\n");
+        for (split, index) in splits {
+            let _ = writeln!(html, "<h3>{split}</h3>\n<table>");
+            for class in ObjectClass::iter() {
+                if let Some(strip) = montage(index, class, options.montage_per_class)? {
+                    let _ = writeln!(
+                        html,
+                        "<tr><td>{class}</td><td><img class=\"montage\" alt=\"{split} {class}\" src=\"{}\"></td></tr>",
+                        png_data_uri(&strip)?
+                    );
+                }
+            }
+            html.push_str("</table>\n");
+        }
+    }
+
+    if let Some(dedup) = &options.dedup {
+        let hashes = splits
+            .iter()
+            .map(|(_, index)| hash_split(index, dedup.parallelism))
+            .collect::<Result<Vec<_>>>()?;
+
+        html.push_str("<h2>Duplicates</h2>\n<table>\n<tr><th>split</th><th>clusters</th><th>images</th></tr>\n");
+        let mut listed = String::new();
+        for ((split, index), hashes) in splits.iter().zip(&hashes) {
+            let clusters = cluster_image_hashes(hashes, dedup.kind, dedup.max_distance);
+            let images: usize = clusters.iter().map(|c| c.len()).sum();
+            let _ = writeln!(
+                html,
+                "<tr><td>{split}</td><td>{}</td><td>{images}</td></tr>",
+                clusters.len()
+            );
+            for cluster in clusters.iter().take(LISTED_FINDINGS) {
+                let paths: Vec<String> = index
+                    .indices_to_paths(cluster)
+                    .iter()
+                    .map(|p| format!("<code>{}</code>", escape(&p.display().to_string())))
+                    .collect();
+                let _ = writeln!(listed, "<li>{split}: {}</li>", paths.join(", "));
+            }
+        }
+        let _ = writeln!(html, "</table>\n<ul>\n{listed}</ul>");
+
+        html.push_str("<h2>Cross-Split Leakage</h2>\n");
+        let hashed: Vec<_> = splits
+            .iter()
+            .zip(&hashes)
+            .map(|(&(split, index), hashes)| (split, index, hashes.as_slice()))
+            .collect();
+        let report = find_leakage_in_hashes(&hashed, dedup.kind, dedup.max_distance);
+        if report.is_clean() {
+            html.push_str("<p>No images are shared between splits.</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>split</th><th>leaked images</th></tr>\n");
+            for (split, _) in splits {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{split}</td><td>{}</td></tr>",
+                    report.exclusions(*split).len()
+                );
+            }
+            html.push_str("</table>\n<ul>\n");
+            for m in report.matches.iter().take(LISTED_FINDINGS) {
+                let _ = writeln!(
+                    html,
+                    "<li><code>{}</code> ~ <code>{}</code> (distance {})</li>",
+                    escape(&m.left.path.display().to_string()),
+                    escape(&m.right.path.display().to_string()),
+                    m.distance
+                );
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+impl Cinic10Index {
+    /// Write a self-contained HTML audit report of all three splits.
+    ///
+    /// # Parameters
+    ///
+    /// - `path`: The output file.
+    /// - `options`: Which sections to include.
+    pub fn generate_report<P>(
+        &self,
+        path: P,
+        options: &ReportOptions,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let html = render_report(
+            &[
+                (DataSet::Train, &self.train),
+                (DataSet::Valid, &self.valid),
+                (DataSet::Test, &self.test),
+            ],
+            options,
+        )?;
+        fs::write(path, html)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+
+    #[test]
+    fn test_render_report() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut splits = Vec::new();
+        for (name, files) in [
+            ("train", ["cifar10-train-1.png", "n02123045_7.png"]),
+            ("test", ["cifar10-test-4.png", "n02123045_9.png"]),
+        ] {
+            splits.push(fixtures::write_split(
+                dir.path().join(name),
+                files.iter().enumerate().map(|(k, file)| {
+                    let img = RgbImage::from_fn(32, 32, |x, y| {
+                        Rgb([(x * 8) as u8, (y * 8) as u8, k as u8])
+                    });
+                    (ObjectClass::Cat, file, img)
+                }),
+            )?);
+        }
+
+        let options = ReportOptions {
+            title: "A <test>".to_string(),
+            parallelism: 2,
+            ..Default::default()
+        };
+        let html = render_report(
+            &[(DataSet::Train, &splits[0]), (DataSet::Test, &splits[1])],
+            &options,
+        )?;
+
+        assert!(html.contains("<h1>A &lt;test&gt;</h1>"));
+        assert!(html.contains("<tr><td>cat</td><td>2</td><td>2</td><td>4</td></tr>"));
+        assert!(html.contains("<tr><td>train</td><td>cat</td><td>1</td><td>1</td><td>0</td></tr>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("src=\"data:image/png;base64,"));
+        assert!(html.contains("<tr><td>train</td><td>1</td><td>2</td></tr>"));
+        assert!(html.contains("<tr><td>test</td><td>2</td></tr>"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+use std::io;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+/// Raw OS error codes retried by default: `EIO`.
+///
+/// `ESTALE` is covered by `io::ErrorKind::StaleNetworkFileHandle`.
+pub const DEFAULT_RETRYABLE_OS_ERRORS: [i32; 1] = [5];
+
+/// A retry policy for transient IO errors in the file-reading layer.
+///
+/// Used by the directory scanner, metadata file readers, and batch loaders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first; `1` disables retries.
+    pub max_attempts: u32,
+
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+
+    /// Factor applied to the delay after each retry.
+    pub backoff_multiplier: f64,
+
+    /// Upper bound on the delay between retries.
+    pub max_backoff: Duration,
+
+    /// Error kinds which are considered transient.
+    pub retryable_kinds: Vec<io::ErrorKind>,
+
+    /// Raw OS error codes which are considered transient.
+    pub retryable_os_errors: Vec<i32>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            backoff_multiplier: 2.0,
+            max_backoff: Duration::from_secs(1),
+            retryable_kinds: vec![
+                io::ErrorKind::Interrupted,
+                io::ErrorKind::TimedOut,
+                io::ErrorKind::WouldBlock,
+                io::ErrorKind::StaleNetworkFileHandle,
+            ],
+            retryable_os_errors: DEFAULT_RETRYABLE_OS_ERRORS.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Is `err` a transient error under this policy?
+    pub fn is_retryable(
+        &self,
+        err: &io::Error,
+    ) -> bool {
+        self.retryable_kinds.contains(&err.kind())
+            || err
+                .raw_os_error()
+                .is_some_and(|code| self.retryable_os_errors.contains(&code))
+    }
+
+    /// The delay before retry number `retry` (starting from 0).
+    pub fn backoff(
+        &self,
+        retry: u32,
+    ) -> Duration {
+        let scale = self.backoff_multiplier.powi(retry as i32);
+        self.initial_backoff.mul_f64(scale).min(self.max_backoff)
+    }
+
+    /// Run `op`, retrying transient failures with backoff.
+    ///
+    /// # Parameters
+    ///
+    /// - `op`: The IO operation to run.
+    ///
+    /// # Returns
+    ///
+    /// The first success, or the last error once attempts are exhausted
+    /// or a non-retryable error is seen.
+    pub fn run<T, F>(
+        &self,
+        mut op: F,
+    ) -> io::Result<T>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        let mut retry = 0;
+        loop {
+            match op() {
+                Ok(v) => return Ok(v),
+                Err(err) if retry + 1 < self.max_attempts && self.is_retryable(&err) => {
+                    let delay = self.backoff(retry);
+
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        error = %err,
+                        retry,
+                        delay_ms = delay.as_millis() as u64,
+                        "retrying transient IO error"
+                    );
+
+                    thread::sleep(delay);
+                    retry += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+static STATIC_RETRY_POLICY: RwLock<Option<RetryPolicy>> = RwLock::new(None);
+
+/// Get the current retry policy, if any.
+pub fn get_retry_policy() -> Option<RetryPolicy> {
+    STATIC_RETRY_POLICY.read().unwrap().clone()
+}
+
+/// Set (or with `None`, disable) retries of transient IO errors.
+pub fn set_retry_policy(policy: Option<RetryPolicy>) {
+    *STATIC_RETRY_POLICY.write().unwrap() = policy;
+}
+
+/// Run `op` under the current retry policy; runs it once when none is set.
+pub(crate) fn with_retry<T, F>(mut op: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    match get_retry_policy() {
+        Some(policy) => policy.run(op),
+        None => op(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(2), Duration::from_millis(40));
+        assert_eq!(policy.backoff(20), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&io::Error::from_raw_os_error(5)));
+        assert!(policy.is_retryable(&io::Error::from(io::ErrorKind::TimedOut)));
+        assert!(!policy.is_retryable(&io::Error::from(io::ErrorKind::NotFound)));
+    }
+
+    #[test]
+    fn test_run_retries_transient_errors() {
+        let mut calls = 0;
+        let result = fast_policy().run(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(io::Error::from_raw_os_error(5))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: io::Result<()> = fast_policy().run(|| {
+            calls += 1;
+            Err(io::Error::from_raw_os_error(5))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_run_fails_fast_on_permanent_errors() {
+        let mut calls = 0;
+        let result: io::Result<()> = fast_policy().run(|| {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::error::{Cinic10Error, Result, bail};
+use crate::index::{DataSet, DatasetIndex};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A stable identifier for a sample: its split and file name.
+///
+/// Unlike positional indices, sample ids survive index rebuilds, filtering,
+/// and differences in directory sort order between machines.
+///
+/// The string form is `"{split}/{filename}"`, e.g. `"train/cifar10-train-3318.png"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct SampleId {
+    pub split: DataSet,
+    pub filename: String,
+}
+
+impl SampleId {
+    pub fn new<S>(
+        split: DataSet,
+        filename: S,
+    ) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            split,
+            filename: filename.into(),
+        }
+    }
+
+    /// The id of the image at `path`, in the `.../{split}/{class}/{filename}` layout.
+    ///
+    /// # Returns
+    ///
+    /// The id; or `None` if the grandparent directory is not a split name.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let filename = path.file_name()?.to_str()?;
+        let split = path.parent()?.parent()?.file_name()?.to_str()?;
+        Some(Self::new(DataSet::from_str(split).ok()?, filename))
+    }
+}
+
+impl fmt::Display for SampleId {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}/{}", self.split, self.filename)
+    }
+}
+
+impl FromStr for SampleId {
+    type Err = Cinic10Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (split, filename) = s
+            .split_once('/')
+            .with_context(|| format!("Malformed sample id, expected split/filename: {s}"))?;
+        if filename.is_empty() || filename.contains('/') {
+            bail!("Malformed sample id, expected split/filename: {s}");
+        }
+        let split = DataSet::from_str(split)
+            .with_context(|| format!("Malformed sample id, unknown split: {s}"))?;
+        Ok(Self::new(split, filename))
+    }
+}
+
+impl From<SampleId> for String {
+    fn from(id: SampleId) -> Self {
+        id.to_string()
+    }
+}
+
+impl TryFrom<String> for SampleId {
+    type Error = Cinic10Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        SampleId::from_str(&s)
+    }
+}
+
+impl DatasetIndex {
+    /// The split this index was built from, derived from the `ds_path` directory name.
+    pub fn data_set(&self) -> Option<DataSet> {
+        DataSet::from_str(self.ds_path.file_name()?.to_str()?).ok()
+    }
+
+    /// Get the `SampleId` of an item.
+    ///
+    /// # Parameters
+    ///
+    /// - `index`: The index of the item.
+    ///
+    /// # Returns
+    ///
+    /// The id, or `None` if the index is out of range or the split is unknown.
+    pub fn sample_id(
+        &self,
+        index: usize,
+    ) -> Option<SampleId> {
+        let split = self.data_set()?;
+        let item = self.get(index)?;
+        let filename = item.path.file_name()?.to_str()?;
+        Some(SampleId::new(split, filename))
+    }
+
+    /// Build a map from `SampleId` to item index, for bulk lookups.
+    pub fn sample_id_map(&self) -> HashMap<SampleId, usize> {
+        (0..self.len())
+            .filter_map(|i| Some((self.sample_id(i)?, i)))
+            .collect()
+    }
+
+    /// Convert a slice of indices to `SampleId`s.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the ids; or an error if an index is out of
+    /// range, or the split is unknown.
+    pub fn indices_to_sample_ids(
+        &self,
+        indices: &[usize],
+    ) -> Result<Vec<SampleId>> {
+        indices
+            .iter()
+            .map(|&i| match self.sample_id(i) {
+                Some(id) => Ok(id),
+                None => bail!("No sample id for item {i} of {}", self.ds_path.display()),
+            })
+            .collect()
+    }
+
+    /// Lay out values keyed by `SampleId` in item order, e.g. per-sample weights.
+    ///
+    /// # Parameters
+    ///
+    /// - `values`: The values; ids of other splits are ignored.
+    /// - `default`: The value of items with no entry.
+    ///
+    /// # Returns
+    ///
+    /// One value per item.
+    pub fn values_by_sample_id<T>(
+        &self,
+        values: &HashMap<SampleId, T>,
+        default: T,
+    ) -> Vec<T>
+    where
+        T: Clone,
+    {
+        (0..self.len())
+            .map(|i| {
+                self.sample_id(i)
+                    .and_then(|id| values.get(&id))
+                    .unwrap_or(&default)
+                    .clone()
+            })
+            .collect()
+    }
+
+    /// Find the index of the item with the given `SampleId`.
+    ///
+    /// This is a linear scan; use `sample_id_map()` for repeated lookups.
+    pub fn index_of_sample_id(
+        &self,
+        id: &SampleId,
+    ) -> Option<usize> {
+        if self.data_set()? != id.split {
+            return None;
+        }
+        self.items().position(|item| {
+            item.path
+                .file_name()
+                .is_some_and(|n| n == id.filename.as_str())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::ObjectClass;
+    use crate::mock::fixtures;
+
+    #[test]
+    fn test_parse_sample_id() {
+        let id = SampleId::from_str("train/cifar10-train-3318.png").unwrap();
+        assert_eq!(id, SampleId::new(DataSet::Train, "cifar10-train-3318.png"));
+        assert_eq!(id.to_string(), "train/cifar10-train-3318.png");
+
+        assert!(SampleId::from_str("bad/x.png").is_err());
+        assert!(SampleId::from_str("train").is_err());
+        assert!(SampleId::from_str("train/").is_err());
+        assert!(SampleId::from_str("train/cat/x.png").is_err());
+    }
+
+    #[test]
+    fn test_index_sample_ids() -> Result<()> {
+        let index = fixtures::split_of_names("/data/valid", ObjectClass::Ship, &["a.png", "b.png"]);
+
+        assert_eq!(index.data_set(), Some(DataSet::Valid));
+        let id = index.sample_id(1).unwrap();
+        assert_eq!(id.to_string(), "valid/b.png");
+        assert_eq!(index.index_of_sample_id(&id), Some(1));
+        assert_eq!(index.sample_id_map()[&id], 1);
+        assert_eq!(
+            index.index_of_sample_id(&SampleId::new(DataSet::Test, "b.png")),
+            None
+        );
+        assert_eq!(index.sample_id(2), None);
+
+        assert_eq!(
+            index.indices_to_sample_ids(&[1, 0])?,
+            vec![id.clone(), SampleId::new(DataSet::Valid, "a.png")]
+        );
+        assert!(index.indices_to_sample_ids(&[2]).is_err());
+        let weights = HashMap::from([
+            (id.clone(), 3.0),
+            (SampleId::new(DataSet::Test, "a.png"), 9.0),
+        ]);
+        assert_eq!(index.values_by_sample_id(&weights, 1.0), vec![1.0, 3.0]);
+
+        assert_eq!(SampleId::from_path(&index.abs_path(1)), Some(id));
+        assert_eq!(SampleId::from_path(Path::new("/data/ship/b.png")), None);
+
+        Ok(())
+    }
+}
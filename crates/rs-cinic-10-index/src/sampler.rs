@@ -0,0 +1,1137 @@
+use crate::error::{Result, bail};
+use crate::index::DatasetIndex;
+use crate::record::{ComponentRecord, Recordable};
+use crate::sample_id::SampleId;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// A source of dataset indices for training epochs.
+pub trait Sampler {
+    /// The indices to visit in `epoch`, in visiting order.
+    ///
+    /// Calling this twice for the same epoch (with no feedback in between)
+    /// returns the same indices.
+    fn epoch_indices(
+        &mut self,
+        epoch: usize,
+    ) -> Vec<usize>;
+
+    /// Feed back per-sample training losses, as `(index, loss)` pairs.
+    ///
+    /// Adaptive samplers use these to adjust later epochs; the default ignores them.
+    fn report_losses(
+        &mut self,
+        _losses: &[(usize, f32)],
+    ) {
+    }
+
+    /// `epoch_indices()`, as the `SampleId`s of the items of `split`.
+    fn epoch_sample_ids(
+        &mut self,
+        epoch: usize,
+        split: &DatasetIndex,
+    ) -> Result<Vec<SampleId>> {
+        split.indices_to_sample_ids(&self.epoch_indices(epoch))
+    }
+
+    /// `report_losses()`, keyed by `SampleId`; ids not in `split` are ignored.
+    fn report_sample_losses(
+        &mut self,
+        split: &DatasetIndex,
+        losses: &[(SampleId, f32)],
+    ) {
+        let ids = split.sample_id_map();
+        let losses: Vec<(usize, f32)> = losses
+            .iter()
+            .filter_map(|(id, loss)| Some((*ids.get(id)?, *loss)))
+            .collect();
+        self.report_losses(&losses);
+    }
+}
+
+/// Mix a sampler seed and an epoch into one 64-bit seed.
+fn mix_seed(
+    seed: u64,
+    epoch: usize,
+) -> u64 {
+    seed ^ (epoch as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// Creates the per-epoch RNGs of the seeded samplers.
+///
+/// Samplers draw from a fresh RNG per epoch (or acquisition round), so any
+/// epoch can be replayed from `(seed, epoch)` alone. Implement this to inject
+/// another generator; e.g. a counter-based RNG (such as Philox) keyed by `seed`
+/// with `epoch` as the counter, to match another framework's draws.
+pub trait RngSource {
+    type Rng: Rng;
+
+    /// The RNG for one epoch.
+    ///
+    /// # Parameters
+    ///
+    /// - `seed`: The sampler seed.
+    /// - `epoch`: The epoch, or acquisition round.
+    fn epoch_rng(
+        &self,
+        seed: u64,
+        epoch: usize,
+    ) -> Self::Rng;
+
+    /// The generator name, recorded with the sampler's `ComponentRecord`.
+    fn name(&self) -> String {
+        std::any::type_name::<Self::Rng>().to_string()
+    }
+}
+
+/// An `RngSource` which seeds any `SeedableRng` from the mixed seed and epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeededRngSource<R> {
+    phantom: PhantomData<fn() -> R>,
+}
+
+impl<R> Default for SeededRngSource<R> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<R> RngSource for SeededRngSource<R>
+where
+    R: Rng + SeedableRng,
+{
+    type Rng = R;
+
+    fn epoch_rng(
+        &self,
+        seed: u64,
+        epoch: usize,
+    ) -> R {
+        R::seed_from_u64(mix_seed(seed, epoch))
+    }
+}
+
+/// The `RngSource` used unless a sampler is given another.
+pub type DefaultRngSource = SeededRngSource<StdRng>;
+
+/// Draw `k` distinct positions, with probability proportional to `weights`.
+///
+/// Uses Efraimidis-Spirakis keys (`u^(1/w)`); zero-weight positions are
+/// only drawn once every positive-weight position has been drawn.
+pub(crate) fn weighted_sample_without_replacement<R>(
+    rng: &mut R,
+    weights: &[f64],
+    k: usize,
+) -> Vec<usize>
+where
+    R: Rng + ?Sized,
+{
+    let mut keyed: Vec<(f64, usize)> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| {
+            let u: f64 = rng.random();
+            let key = if w > 0.0 {
+                u.ln() / w
+            } else {
+                f64::NEG_INFINITY
+            };
+            (key, i)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    keyed.into_iter().take(k).map(|(_, i)| i).collect()
+}
+
+/// How an `ActiveSampler` turns acquisition scores into a batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum AcquisitionStrategy {
+    /// Take the highest-scoring indices.
+    #[default]
+    TopK,
+
+    /// Sample without replacement, proportionally to `max(score, 0)`.
+    Proportional,
+
+    /// Sample without replacement, proportionally to `exp(score / temperature)`.
+    Softmax { temperature: f64 },
+}
+
+/// Selects which samples to label next, for active learning.
+///
+/// The sampler tracks the acquired (labeled) pool; as a `Sampler`,
+/// it yields a shuffle of the acquired pool each epoch.
+#[derive(Debug, Clone)]
+pub struct ActiveSampler<S = DefaultRngSource> {
+    len: usize,
+    strategy: AcquisitionStrategy,
+    seed: u64,
+    rng_source: S,
+    rounds: usize,
+    acquired: Vec<usize>,
+    is_acquired: Vec<bool>,
+}
+
+impl ActiveSampler {
+    /// Create a sampler over `len` samples, with nothing acquired.
+    pub fn new(
+        len: usize,
+        strategy: AcquisitionStrategy,
+        seed: u64,
+    ) -> Self {
+        Self {
+            len,
+            strategy,
+            seed,
+            rng_source: Default::default(),
+            rounds: 0,
+            acquired: Vec::new(),
+            is_acquired: vec![false; len],
+        }
+    }
+
+    /// Draw from another RNG source; see `RngSource`.
+    pub fn with_rng_source<T>(
+        self,
+        rng_source: T,
+    ) -> ActiveSampler<T>
+    where
+        T: RngSource,
+    {
+        ActiveSampler {
+            len: self.len,
+            strategy: self.strategy,
+            seed: self.seed,
+            rounds: self.rounds,
+            acquired: self.acquired,
+            is_acquired: self.is_acquired,
+            rng_source,
+        }
+    }
+}
+
+impl<S> ActiveSampler<S>
+where
+    S: RngSource,
+{
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn strategy(&self) -> AcquisitionStrategy {
+        self.strategy
+    }
+
+    /// The acquired indices, in acquisition order.
+    pub fn acquired(&self) -> &[usize] {
+        &self.acquired
+    }
+
+    pub fn is_acquired(
+        &self,
+        index: usize,
+    ) -> bool {
+        self.is_acquired.get(index).copied().unwrap_or(false)
+    }
+
+    /// The indices which have not been acquired, in increasing order.
+    pub fn unlabeled(&self) -> Vec<usize> {
+        (0..self.len).filter(|&i| !self.is_acquired[i]).collect()
+    }
+
+    /// Mark indices as acquired, e.g. an initial labeled seed set.
+    ///
+    /// Already acquired indices are ignored.
+    pub fn acquire(
+        &mut self,
+        indices: &[usize],
+    ) -> Result<()> {
+        if let Some(&i) = indices.iter().find(|&&i| i >= self.len) {
+            bail!("Index {i} out of range for {} samples", self.len);
+        }
+        for &i in indices {
+            if !self.is_acquired[i] {
+                self.is_acquired[i] = true;
+                self.acquired.push(i);
+            }
+        }
+        Ok(())
+    }
+
+    /// Select and acquire the next batch, from scores over all samples.
+    ///
+    /// # Parameters
+    ///
+    /// - `scores`: One acquisition score per sample; higher is more informative.
+    ///   Scores of acquired samples are ignored.
+    /// - `batch_size`: The number of samples to acquire.
+    ///
+    /// # Returns
+    ///
+    /// The newly acquired indices; fewer than `batch_size` when the unlabeled pool runs out.
+    pub fn select_next(
+        &mut self,
+        scores: &[f32],
+        batch_size: usize,
+    ) -> Result<Vec<usize>> {
+        if scores.len() != self.len {
+            bail!(
+                "Expected {} acquisition scores, got {}",
+                self.len,
+                scores.len()
+            );
+        }
+        let pool = self.unlabeled();
+        let pool_scores: Vec<f32> = pool.iter().map(|&i| scores[i]).collect();
+        self.select_from_pool(&pool, &pool_scores, batch_size)
+    }
+
+    /// Select and acquire the next batch, scoring only the unlabeled pool.
+    ///
+    /// # Parameters
+    ///
+    /// - `batch_size`: The number of samples to acquire.
+    /// - `score`: Maps the unlabeled indices to their acquisition scores.
+    ///
+    /// # Returns
+    ///
+    /// The newly acquired indices.
+    pub fn select_next_with<F>(
+        &mut self,
+        batch_size: usize,
+        score: F,
+    ) -> Result<Vec<usize>>
+    where
+        F: FnOnce(&[usize]) -> Result<Vec<f32>>,
+    {
+        let pool = self.unlabeled();
+        let pool_scores = score(&pool)?;
+        if pool_scores.len() != pool.len() {
+            bail!(
+                "Expected {} acquisition scores, got {}",
+                pool.len(),
+                pool_scores.len()
+            );
+        }
+        self.select_from_pool(&pool, &pool_scores, batch_size)
+    }
+
+    fn select_from_pool(
+        &mut self,
+        pool: &[usize],
+        scores: &[f32],
+        batch_size: usize,
+    ) -> Result<Vec<usize>> {
+        let k = batch_size.min(pool.len());
+        let mut rng = self.rng_source.epoch_rng(self.seed, self.rounds);
+        self.rounds += 1;
+
+        let positions: Vec<usize> = match self.strategy {
+            AcquisitionStrategy::TopK => {
+                let mut order: Vec<usize> = (0..pool.len()).collect();
+                order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+                order.truncate(k);
+                order
+            }
+            AcquisitionStrategy::Proportional => {
+                let weights: Vec<f64> = scores.iter().map(|&s| (s as f64).max(0.0)).collect();
+                weighted_sample_without_replacement(&mut rng, &weights, k)
+            }
+            AcquisitionStrategy::Softmax { temperature } => {
+                if temperature <= 0.0 {
+                    bail!("Softmax temperature must be positive: {temperature}");
+                }
+                let peak = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+                let weights: Vec<f64> = scores
+                    .iter()
+                    .map(|&s| ((s as f64 - peak) / temperature).exp())
+                    .collect();
+                weighted_sample_without_replacement(&mut rng, &weights, k)
+            }
+        };
+
+        let batch: Vec<usize> = positions.into_iter().map(|p| pool[p]).collect();
+        self.acquire(&batch)?;
+        Ok(batch)
+    }
+}
+
+impl<S> Sampler for ActiveSampler<S>
+where
+    S: RngSource,
+{
+    fn epoch_indices(
+        &mut self,
+        epoch: usize,
+    ) -> Vec<usize> {
+        let mut indices = self.acquired.clone();
+        indices.shuffle(&mut self.rng_source.epoch_rng(self.seed, epoch));
+        indices
+    }
+}
+
+impl<S> Recordable for ActiveSampler<S>
+where
+    S: RngSource,
+{
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("active_sampler")
+            .with_param("len", self.len)
+            .with_param("seed", self.seed)
+            .with_param("rng", self.rng_source.name())
+            .with_param(
+                "strategy",
+                serde_json::to_value(self.strategy).unwrap_or_default(),
+            )
+            .with_param("acquired", self.acquired.len())
+    }
+}
+
+/// How much of the data a `CurriculumSampler` shows at each epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Pacing {
+    /// The easiest `start` fraction at epoch 0, growing linearly to all samples at `epochs`.
+    Linear { start: f64, epochs: usize },
+
+    /// The easiest `start` fraction, growing by `increment` every `every` epochs.
+    Step {
+        start: f64,
+        increment: f64,
+        every: usize,
+    },
+
+    /// Samples with difficulty at most `threshold * growth^epoch`.
+    ///
+    /// Pair with `CurriculumSampler::set_difficulty()` to re-score samples
+    /// (e.g. by current loss) as training progresses.
+    SelfPaced { threshold: f64, growth: f64 },
+}
+
+impl Pacing {
+    /// The fraction of samples visible at `epoch`; `None` for `Pacing::SelfPaced`.
+    pub fn visible_fraction(
+        &self,
+        epoch: usize,
+    ) -> Option<f64> {
+        let fraction = match *self {
+            Pacing::Linear { start, epochs } => {
+                if epochs == 0 {
+                    1.0
+                } else {
+                    start + (1.0 - start) * epoch as f64 / epochs as f64
+                }
+            }
+            Pacing::Step {
+                start,
+                increment,
+                every,
+            } => start + increment * (epoch / every.max(1)) as f64,
+            Pacing::SelfPaced { .. } => return None,
+        };
+        Some(fraction.clamp(0.0, 1.0))
+    }
+}
+
+/// The order in which a `CurriculumSampler` visits the visible samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CurriculumOrder {
+    #[default]
+    Shuffled,
+
+    /// Easiest first; ties are broken by index.
+    EasyFirst,
+}
+
+/// Shows samples from easy to hard, according to per-sample difficulty scores.
+#[derive(Debug, Clone)]
+pub struct CurriculumSampler<S = DefaultRngSource> {
+    difficulty: Vec<f32>,
+    pacing: Pacing,
+    order: CurriculumOrder,
+    seed: u64,
+    rng_source: S,
+}
+
+impl CurriculumSampler {
+    /// Create a sampler.
+    ///
+    /// # Parameters
+    ///
+    /// - `difficulty`: One score per sample; lower is easier.
+    /// - `pacing`: How the visible subset grows over epochs.
+    /// - `order`: The visiting order within an epoch.
+    /// - `seed`: Seeds the per-epoch shuffles.
+    pub fn new(
+        difficulty: Vec<f32>,
+        pacing: Pacing,
+        order: CurriculumOrder,
+        seed: u64,
+    ) -> Self {
+        Self {
+            difficulty,
+            pacing,
+            order,
+            seed,
+            rng_source: Default::default(),
+        }
+    }
+
+    /// Create a sampler from difficulty scores keyed by `SampleId`; see `new()`.
+    ///
+    /// Scores saved by id, e.g. from a previous run, still apply after the
+    /// split is rebuilt or reordered; items with no score get `default`.
+    pub fn from_sample_difficulty(
+        split: &DatasetIndex,
+        difficulty: &HashMap<SampleId, f32>,
+        default: f32,
+        pacing: Pacing,
+        order: CurriculumOrder,
+        seed: u64,
+    ) -> Self {
+        Self::new(
+            split.values_by_sample_id(difficulty, default),
+            pacing,
+            order,
+            seed,
+        )
+    }
+
+    /// Draw from another RNG source; see `RngSource`.
+    pub fn with_rng_source<T>(
+        self,
+        rng_source: T,
+    ) -> CurriculumSampler<T>
+    where
+        T: RngSource,
+    {
+        CurriculumSampler {
+            difficulty: self.difficulty,
+            pacing: self.pacing,
+            order: self.order,
+            seed: self.seed,
+            rng_source,
+        }
+    }
+}
+
+impl<S> CurriculumSampler<S>
+where
+    S: RngSource,
+{
+    pub fn len(&self) -> usize {
+        self.difficulty.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.difficulty.is_empty()
+    }
+
+    pub fn difficulty(&self) -> &[f32] {
+        &self.difficulty
+    }
+
+    pub fn pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    /// Replace the difficulty scores, e.g. with current per-sample losses.
+    pub fn set_difficulty(
+        &mut self,
+        difficulty: Vec<f32>,
+    ) -> Result<()> {
+        if difficulty.len() != self.difficulty.len() {
+            bail!(
+                "Expected {} difficulty scores, got {}",
+                self.difficulty.len(),
+                difficulty.len()
+            );
+        }
+        self.difficulty = difficulty;
+        Ok(())
+    }
+
+    /// The samples visible at `epoch`, easiest first.
+    pub fn visible(
+        &self,
+        epoch: usize,
+    ) -> Vec<usize> {
+        let mut by_difficulty: Vec<usize> = (0..self.len()).collect();
+        by_difficulty.sort_by(|&a, &b| {
+            self.difficulty[a]
+                .total_cmp(&self.difficulty[b])
+                .then(a.cmp(&b))
+        });
+
+        match self.pacing {
+            Pacing::SelfPaced { threshold, growth } => {
+                let limit = threshold * growth.powi(epoch as i32);
+                by_difficulty.retain(|&i| self.difficulty[i] as f64 <= limit);
+            }
+            _ => {
+                let fraction = self.pacing.visible_fraction(epoch).unwrap_or(1.0);
+                by_difficulty.truncate((fraction * self.len() as f64).ceil() as usize);
+            }
+        }
+        by_difficulty
+    }
+
+    /// Per-sample weights for `epoch`: `1.0` for visible samples, `0.0` otherwise.
+    ///
+    /// Use these to reweight a loss instead of subsetting the data.
+    pub fn epoch_weights(
+        &self,
+        epoch: usize,
+    ) -> Vec<f32> {
+        let mut weights = vec![0.0; self.len()];
+        for i in self.visible(epoch) {
+            weights[i] = 1.0;
+        }
+        weights
+    }
+}
+
+impl<S> Sampler for CurriculumSampler<S>
+where
+    S: RngSource,
+{
+    fn epoch_indices(
+        &mut self,
+        epoch: usize,
+    ) -> Vec<usize> {
+        let mut indices = self.visible(epoch);
+        if self.order == CurriculumOrder::Shuffled {
+            indices.shuffle(&mut self.rng_source.epoch_rng(self.seed, epoch));
+        }
+        indices
+    }
+
+    /// Replace the difficulty of the reported samples with their loss.
+    fn report_losses(
+        &mut self,
+        losses: &[(usize, f32)],
+    ) {
+        for &(i, loss) in losses {
+            if let Some(d) = self.difficulty.get_mut(i) {
+                *d = loss;
+            }
+        }
+    }
+}
+
+impl<S> Recordable for CurriculumSampler<S>
+where
+    S: RngSource,
+{
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("curriculum_sampler")
+            .with_param("len", self.len())
+            .with_param("seed", self.seed)
+            .with_param("rng", self.rng_source.name())
+            .with_param(
+                "pacing",
+                serde_json::to_value(self.pacing).unwrap_or_default(),
+            )
+            .with_param(
+                "order",
+                serde_json::to_value(self.order).unwrap_or_default(),
+            )
+    }
+}
+
+/// Online hard-example mining settings for a `WeightedSampler`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HardMining {
+    /// The weight of the previous loss estimate when a new loss is reported.
+    pub momentum: f32,
+
+    /// Sharpens (`> 1`) or flattens (`< 1`) the preference for high-loss samples.
+    pub power: f32,
+
+    /// The fraction of each weight which ignores loss, so easy samples are still revisited.
+    pub uniform_mix: f64,
+}
+
+impl Default for HardMining {
+    fn default() -> Self {
+        Self {
+            momentum: 0.9,
+            power: 1.0,
+            uniform_mix: 0.2,
+        }
+    }
+}
+
+/// Draws samples with probability proportional to per-sample weights.
+///
+/// With `HardMining` enabled, the weights are further scaled by a running
+/// estimate of each sample's loss, fed back through `Sampler::report_losses()`.
+#[derive(Debug, Clone)]
+pub struct WeightedSampler<S = DefaultRngSource> {
+    weights: Vec<f64>,
+    num_samples: usize,
+    replacement: bool,
+    seed: u64,
+    rng_source: S,
+    mining: Option<HardMining>,
+
+    /// Running loss estimates; `None` until a sample's loss is first reported.
+    losses: Vec<Option<f32>>,
+}
+
+impl WeightedSampler {
+    /// Create a sampler.
+    ///
+    /// # Parameters
+    ///
+    /// - `weights`: One non-negative weight per sample.
+    /// - `num_samples`: The number of draws per epoch.
+    /// - `replacement`: Draw with replacement; otherwise an epoch has no repeats,
+    ///   and is truncated to the number of samples.
+    /// - `seed`: Seeds the per-epoch draws.
+    pub fn new(
+        weights: Vec<f64>,
+        num_samples: usize,
+        replacement: bool,
+        seed: u64,
+    ) -> Result<Self> {
+        if let Some(w) = weights.iter().find(|w| !w.is_finite() || **w < 0.0) {
+            bail!("Sample weights must be finite and non-negative: {w}");
+        }
+        let len = weights.len();
+        Ok(Self {
+            weights,
+            num_samples,
+            replacement,
+            seed,
+            rng_source: Default::default(),
+            mining: None,
+            losses: vec![None; len],
+        })
+    }
+
+    /// Create a sampler from weights keyed by `SampleId`; see `new()`.
+    ///
+    /// Items with no weight get `default`.
+    pub fn from_sample_weights(
+        split: &DatasetIndex,
+        weights: &HashMap<SampleId, f64>,
+        default: f64,
+        num_samples: usize,
+        replacement: bool,
+        seed: u64,
+    ) -> Result<Self> {
+        Self::new(
+            split.values_by_sample_id(weights, default),
+            num_samples,
+            replacement,
+            seed,
+        )
+    }
+
+    /// Draw from another RNG source; see `RngSource`.
+    pub fn with_rng_source<T>(
+        self,
+        rng_source: T,
+    ) -> WeightedSampler<T>
+    where
+        T: RngSource,
+    {
+        WeightedSampler {
+            weights: self.weights,
+            num_samples: self.num_samples,
+            replacement: self.replacement,
+            seed: self.seed,
+            mining: self.mining,
+            losses: self.losses,
+            rng_source,
+        }
+    }
+}
+
+impl<S> WeightedSampler<S>
+where
+    S: RngSource,
+{
+    /// Enable online hard-example mining.
+    pub fn with_hard_mining(
+        mut self,
+        mining: HardMining,
+    ) -> Self {
+        self.mining = Some(mining);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// The weights used for the next epoch, including hard-mining adjustments.
+    ///
+    /// Samples with no reported loss are treated as having the mean reported loss.
+    pub fn effective_weights(&self) -> Vec<f64> {
+        let Some(mining) = self.mining else {
+            return self.weights.clone();
+        };
+        let reported: Vec<f64> = self.losses.iter().flatten().map(|&l| l as f64).collect();
+        if reported.is_empty() {
+            return self.weights.clone();
+        }
+        let default_loss = reported.iter().sum::<f64>() / reported.len() as f64;
+
+        let hardness: Vec<f64> = self
+            .losses
+            .iter()
+            .map(|l| {
+                l.map_or(default_loss, |l| l as f64)
+                    .max(0.0)
+                    .powf(mining.power as f64)
+            })
+            .collect();
+        let mean = hardness.iter().sum::<f64>() / hardness.len() as f64;
+        let mix = mining.uniform_mix.clamp(0.0, 1.0);
+
+        self.weights
+            .iter()
+            .zip(&hardness)
+            .map(|(&w, &h)| {
+                let relative = if mean > 0.0 { h / mean } else { 1.0 };
+                w * ((1.0 - mix) * relative + mix)
+            })
+            .collect()
+    }
+}
+
+impl<S> Sampler for WeightedSampler<S>
+where
+    S: RngSource,
+{
+    fn epoch_indices(
+        &mut self,
+        epoch: usize,
+    ) -> Vec<usize> {
+        let weights = self.effective_weights();
+        let mut rng = self.rng_source.epoch_rng(self.seed, epoch);
+        if !self.replacement {
+            return weighted_sample_without_replacement(&mut rng, &weights, self.num_samples);
+        }
+
+        let cumulative: Vec<f64> = weights
+            .iter()
+            .scan(0.0, |acc, &w| {
+                *acc += w;
+                Some(*acc)
+            })
+            .collect();
+        let total = cumulative.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return Vec::new();
+        }
+        (0..self.num_samples)
+            .map(|_| {
+                let target = rng.random::<f64>() * total;
+                cumulative
+                    .partition_point(|&c| c <= target)
+                    .min(weights.len() - 1)
+            })
+            .collect()
+    }
+
+    fn report_losses(
+        &mut self,
+        losses: &[(usize, f32)],
+    ) {
+        let Some(mining) = self.mining else {
+            return;
+        };
+        for &(i, loss) in losses {
+            if let Some(slot) = self.losses.get_mut(i)
+                && loss.is_finite()
+            {
+                *slot = Some(match *slot {
+                    Some(prev) => mining.momentum * prev + (1.0 - mining.momentum) * loss,
+                    None => loss,
+                });
+            }
+        }
+    }
+}
+
+impl<S> Recordable for WeightedSampler<S>
+where
+    S: RngSource,
+{
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("weighted_sampler")
+            .with_param("len", self.len())
+            .with_param("num_samples", self.num_samples)
+            .with_param("replacement", self.replacement)
+            .with_param("seed", self.seed)
+            .with_param("rng", self.rng_source.name())
+            .with_param(
+                "hard_mining",
+                serde_json::to_value(self.mining).unwrap_or_default(),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{DataSet, ObjectClass};
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use rand::RngCore;
+
+    /// A counter-based SplitMix64 stream, keyed by `(seed, epoch)`.
+    struct CounterRng {
+        key: u64,
+        counter: u64,
+    }
+
+    impl RngCore for CounterRng {
+        fn next_u32(&mut self) -> u32 {
+            (self.next_u64() >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.counter += 1;
+            let mut z = self
+                .key
+                .wrapping_add(self.counter.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+
+        fn fill_bytes(
+            &mut self,
+            dst: &mut [u8],
+        ) {
+            rand::rand_core::impls::fill_bytes_via_next(self, dst)
+        }
+    }
+
+    struct CounterRngSource;
+
+    impl RngSource for CounterRngSource {
+        type Rng = CounterRng;
+
+        fn epoch_rng(
+            &self,
+            seed: u64,
+            epoch: usize,
+        ) -> CounterRng {
+            CounterRng {
+                key: seed,
+                counter: (epoch as u64) << 32,
+            }
+        }
+
+        fn name(&self) -> String {
+            "splitmix_counter".to_string()
+        }
+    }
+
+    #[test]
+    fn test_custom_rng_source() -> Result<()> {
+        let mut sampler = WeightedSampler::new(vec![1.0, 0.0, 2.0, 1.0], 50, true, 9)?
+            .with_rng_source(CounterRngSource);
+        let epoch = sampler.epoch_indices(2);
+        assert_eq!(epoch.len(), 50);
+        assert!(!epoch.contains(&1));
+        assert_eq!(epoch, sampler.epoch_indices(2));
+        assert_ne!(epoch, sampler.epoch_indices(3));
+        assert_eq!(
+            sampler.component_record().params["rng"],
+            serde_json::json!("splitmix_counter")
+        );
+
+        let mut curriculum = CurriculumSampler::new(
+            vec![0.0; 8],
+            Pacing::Linear {
+                start: 1.0,
+                epochs: 0,
+            },
+            CurriculumOrder::Shuffled,
+            1,
+        )
+        .with_rng_source(SeededRngSource::<rand::rngs::StdRng>::default());
+        let mut epoch = curriculum.epoch_indices(0);
+        epoch.sort();
+        assert_eq!(epoch, (0..8).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_id_keyed() -> Result<()> {
+        let split = fixtures::split_of_names(
+            "/data/train",
+            ObjectClass::Cat,
+            &["a.png", "b.png", "c.png"],
+        );
+        let id = |name: &str| SampleId::new(DataSet::Train, name);
+
+        let weights = HashMap::from([(id("b.png"), 1.0)]);
+        let mut sampler = WeightedSampler::from_sample_weights(&split, &weights, 0.0, 5, true, 3)?;
+        assert_eq!(sampler.epoch_sample_ids(0, &split)?, vec![id("b.png"); 5]);
+
+        let difficulty = HashMap::from([(id("c.png"), 0.1), (id("a.png"), 0.5)]);
+        let mut curriculum = CurriculumSampler::from_sample_difficulty(
+            &split,
+            &difficulty,
+            1.0,
+            Pacing::Linear {
+                start: 1.0,
+                epochs: 0,
+            },
+            CurriculumOrder::EasyFirst,
+            0,
+        );
+        assert_eq!(curriculum.epoch_indices(0), vec![2, 0, 1]);
+        curriculum.report_sample_losses(&split, &[(id("b.png"), 0.0), (id("x.png"), 0.0)]);
+        assert_eq!(
+            curriculum.epoch_sample_ids(0, &split)?,
+            vec![id("b.png"), id("c.png"), id("a.png")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_active_sampler_top_k() -> Result<()> {
+        let mut sampler = ActiveSampler::new(6, AcquisitionStrategy::TopK, 0);
+        sampler.acquire(&[0])?;
+        assert!(sampler.acquire(&[6]).is_err());
+
+        let scores = [9.0, 0.1, 0.5, 0.3, 0.9, 0.2];
+        assert_eq!(sampler.select_next(&scores, 2)?, vec![4, 2]);
+        assert_eq!(sampler.acquired(), &[0, 4, 2]);
+        assert_eq!(sampler.unlabeled(), vec![1, 3, 5]);
+
+        let batch =
+            sampler.select_next_with(5, |pool| Ok(pool.iter().map(|&i| i as f32).collect()))?;
+        assert_eq!(batch, vec![5, 3, 1]);
+        assert!(sampler.unlabeled().is_empty());
+
+        let mut epoch = sampler.epoch_indices(3);
+        assert_eq!(epoch, sampler.epoch_indices(3));
+        epoch.sort();
+        assert_eq!(epoch, (0..6).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_active_sampler_stochastic() -> Result<()> {
+        for strategy in [
+            AcquisitionStrategy::Proportional,
+            AcquisitionStrategy::Softmax { temperature: 0.01 },
+        ] {
+            let mut sampler = ActiveSampler::new(5, strategy, 7);
+            let scores = [0.0, 0.0, 1.0, 0.0, 1.0];
+            let mut batch = sampler.select_next(&scores, 2)?;
+            batch.sort();
+            assert_eq!(batch, vec![2, 4], "{strategy:?}");
+            assert_eq!(sampler.select_next(&scores, 10)?.len(), 3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_curriculum_sampler() -> Result<()> {
+        let difficulty = vec![0.9, 0.1, 0.5, 0.3, 0.7];
+
+        let mut linear = CurriculumSampler::new(
+            difficulty.clone(),
+            Pacing::Linear {
+                start: 0.4,
+                epochs: 2,
+            },
+            CurriculumOrder::EasyFirst,
+            0,
+        );
+        assert_eq!(linear.epoch_indices(0), vec![1, 3]);
+        assert_eq!(linear.epoch_indices(1), vec![1, 3, 2, 4]);
+        assert_eq!(linear.epoch_indices(5), vec![1, 3, 2, 4, 0]);
+        assert_eq!(linear.epoch_weights(0), vec![0.0, 1.0, 0.0, 1.0, 0.0]);
+
+        let step = Pacing::Step {
+            start: 0.2,
+            increment: 0.2,
+            every: 2,
+        };
+        assert_eq!(step.visible_fraction(3), Some(0.4));
+
+        let mut self_paced = CurriculumSampler::new(
+            difficulty,
+            Pacing::SelfPaced {
+                threshold: 0.2,
+                growth: 2.0,
+            },
+            CurriculumOrder::Shuffled,
+            3,
+        );
+        let mut epoch = self_paced.epoch_indices(1);
+        epoch.sort();
+        assert_eq!(epoch, vec![1, 3]);
+        self_paced.set_difficulty(vec![0.0; 5])?;
+        assert_eq!(self_paced.visible(0).len(), 5);
+        assert!(self_paced.set_difficulty(vec![0.0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_sampler_hard_mining() -> Result<()> {
+        assert!(WeightedSampler::new(vec![1.0, -1.0], 4, true, 0).is_err());
+
+        let mut sampler = WeightedSampler::new(vec![1.0, 0.0, 1.0, 1.0], 200, true, 5)?
+            .with_hard_mining(HardMining {
+                momentum: 0.5,
+                power: 1.0,
+                uniform_mix: 0.0,
+            });
+        let epoch = sampler.epoch_indices(0);
+        assert_eq!(epoch.len(), 200);
+        assert!(!epoch.contains(&1));
+        assert_eq!(epoch, sampler.epoch_indices(0));
+
+        sampler.report_losses(&[(0, 4.0), (2, 0.0)]);
+        sampler.report_losses(&[(0, 2.0)]);
+        // Sample 3 has no reported loss, and takes the mean (1.5).
+        assert_eq!(sampler.effective_weights(), vec![2.0, 0.0, 0.0, 1.0]);
+        let epoch = sampler.epoch_indices(1);
+        assert!(!epoch.contains(&2));
+        let hard = epoch.iter().filter(|&&i| i == 0).count();
+        assert!(hard > 100, "{hard}");
+
+        let mut unique = WeightedSampler::new(vec![1.0; 5], 10, false, 0)?;
+        let mut epoch = unique.epoch_indices(0);
+        epoch.sort();
+        assert_eq!(epoch, vec![0, 1, 2, 3, 4]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// The kind of operation which exceeded its latency threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlowOpKind {
+    /// Reading a single file from storage.
+    Read,
+
+    /// Decoding a single image.
+    Decode,
+
+    /// Loading a whole batch.
+    Batch,
+}
+
+/// A report of a single slow operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowOp {
+    pub kind: SlowOpKind,
+
+    /// The offending file; for batches, the first file of the batch.
+    pub path: Option<PathBuf>,
+
+    /// The batch size, for `SlowOpKind::Batch` reports.
+    pub batch_size: Option<usize>,
+
+    pub elapsed: Duration,
+    pub threshold: Duration,
+}
+
+impl fmt::Display for SlowOp {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "slow {:?} took {:?} (threshold {:?})",
+            self.kind, self.elapsed, self.threshold
+        )?;
+        if let Some(batch_size) = self.batch_size {
+            write!(f, " batch_size={}", batch_size)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, " path={}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// A callback invoked for each slow operation.
+pub type SlowOpCallback = Arc<dyn Fn(&SlowOp) + Send + Sync>;
+
+/// Latency thresholds for slow-operation warnings.
+///
+/// Unset thresholds are never reported. When no `callback` is set,
+/// slow operations are logged (via `tracing` when that feature is enabled,
+/// otherwise to stderr).
+#[derive(Clone, Default)]
+pub struct SlowOpConfig {
+    /// Threshold for each individual file read or decode.
+    pub file_threshold: Option<Duration>,
+
+    /// Threshold for each whole batch load.
+    pub batch_threshold: Option<Duration>,
+
+    pub callback: Option<SlowOpCallback>,
+}
+
+impl fmt::Debug for SlowOpConfig {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        f.debug_struct("SlowOpConfig")
+            .field("file_threshold", &self.file_threshold)
+            .field("batch_threshold", &self.batch_threshold)
+            .field("callback", &self.callback.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl SlowOpConfig {
+    fn threshold(
+        &self,
+        kind: SlowOpKind,
+    ) -> Option<Duration> {
+        match kind {
+            SlowOpKind::Read | SlowOpKind::Decode => self.file_threshold,
+            SlowOpKind::Batch => self.batch_threshold,
+        }
+    }
+
+    /// Report `elapsed` if it exceeds the configured threshold for `kind`.
+    pub fn check(
+        &self,
+        kind: SlowOpKind,
+        elapsed: Duration,
+        path: Option<&Path>,
+        batch_size: Option<usize>,
+    ) {
+        let Some(threshold) = self.threshold(kind) else {
+            return;
+        };
+        if elapsed <= threshold {
+            return;
+        }
+
+        self.report(&SlowOp {
+            kind,
+            path: path.map(Path::to_path_buf),
+            batch_size,
+            elapsed,
+            threshold,
+        });
+    }
+
+    fn report(
+        &self,
+        op: &SlowOp,
+    ) {
+        match &self.callback {
+            Some(callback) => callback(op),
+            None => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    kind = ?op.kind,
+                    path = ?op.path,
+                    batch_size = ?op.batch_size,
+                    elapsed_us = op.elapsed.as_micros() as u64,
+                    threshold_us = op.threshold.as_micros() as u64,
+                    "slow operation"
+                );
+
+                #[cfg(not(feature = "tracing"))]
+                eprintln!("cinic-10: {}", op);
+            }
+        }
+    }
+}
+
+static STATIC_SLOW_OP_CONFIG: RwLock<Option<SlowOpConfig>> = RwLock::new(None);
+
+/// Get the current slow-operation configuration, if any.
+pub fn get_slow_op_config() -> Option<SlowOpConfig> {
+    STATIC_SLOW_OP_CONFIG.read().unwrap().clone()
+}
+
+/// Set (or with `None`, disable) slow-operation warnings.
+pub fn set_slow_op_config(config: Option<SlowOpConfig>) {
+    *STATIC_SLOW_OP_CONFIG.write().unwrap() = config;
+}
+
+/// Report `elapsed` against the configured threshold for `kind`.
+pub(crate) fn check(
+    kind: SlowOpKind,
+    elapsed: Duration,
+    path: Option<&Path>,
+    batch_size: Option<usize>,
+) {
+    if let Some(config) = STATIC_SLOW_OP_CONFIG.read().unwrap().as_ref() {
+        config.check(kind, elapsed, path, batch_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_report() {
+        let seen: Arc<Mutex<Vec<SlowOp>>> = Default::default();
+        let config = SlowOpConfig {
+            file_threshold: Some(Duration::from_millis(10)),
+            batch_threshold: None,
+            callback: Some({
+                let seen = seen.clone();
+                Arc::new(move |op| seen.lock().unwrap().push(op.clone()))
+            }),
+        };
+
+        let path = Path::new("train/cat/a.png");
+        for (kind, elapsed) in [
+            (SlowOpKind::Read, Duration::from_millis(5)),
+            (SlowOpKind::Decode, Duration::from_millis(50)),
+            (SlowOpKind::Batch, Duration::from_secs(50)),
+        ] {
+            config.check(kind, elapsed, Some(path), None);
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].kind, SlowOpKind::Decode);
+        assert_eq!(seen[0].path.as_deref(), Some(path));
+        assert_eq!(
+            seen[0].to_string(),
+            "slow Decode took 50ms (threshold 10ms) path=train/cat/a.png"
+        );
+    }
+}
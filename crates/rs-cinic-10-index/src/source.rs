@@ -0,0 +1,210 @@
+use crate::checksum::ChecksumAlgorithm;
+use crate::error::{Result, bail};
+use crate::images::read_image_bytes;
+use crate::retry::with_retry;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the bytes of dataset files come from.
+///
+/// Set one on `LoaderConfig::source` to load batches from somewhere other
+/// than the local filesystem; e.g. an archive, or an in-memory fixture.
+/// Index paths are passed through unchanged, so a source and the index
+/// built from it (see `Cinic10Index::new_from_source()`) must agree on them.
+pub trait DataSource: fmt::Debug + Send + Sync {
+    /// A short name for the source, for logs and configuration.
+    fn name(&self) -> &str;
+
+    /// Read the bytes of one file.
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>>;
+
+    /// The names of the files directly in `dir`, sorted; empty if `dir` does not exist.
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>>;
+
+    /// The expected content digest of a file, if the source knows it without reading it.
+    ///
+    /// Lets `cache::CachedSource` address cached files by content.
+    fn digest(
+        &self,
+        _path: &Path,
+    ) -> Option<(ChecksumAlgorithm, String)> {
+        None
+    }
+}
+
+/// The default `DataSource`: the local filesystem, via `images::read_image_bytes()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocalSource;
+
+impl DataSource for LocalSource {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        Ok(read_image_bytes(path).with_context(|| format!("Failed to read {}", path.display()))?)
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut names: Vec<String> = with_retry(|| {
+            Ok(fs::read_dir(dir)?
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    entry.file_type().ok()?.is_file().then_some(())?;
+                    entry.file_name().into_string().ok()
+                })
+                .collect())
+        })?;
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// A `DataSource` of files held in memory; for tests and small fixtures.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add (or replace) a file.
+    pub fn insert<P>(
+        &mut self,
+        path: P,
+        bytes: Vec<u8>,
+    ) where
+        P: Into<PathBuf>,
+    {
+        self.files.insert(path.into(), bytes);
+    }
+
+    /// Add (or replace) a file.
+    pub fn with_file<P>(
+        mut self,
+        path: P,
+        bytes: Vec<u8>,
+    ) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.insert(path, bytes);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl DataSource for MemorySource {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn read(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<u8>> {
+        match self.files.get(path) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => bail!("{} is not in the memory source", path.display()),
+        }
+    }
+
+    fn list(
+        &self,
+        dir: &Path,
+    ) -> Result<Vec<String>> {
+        // The map is sorted by path, so the names of one directory are too.
+        Ok(self
+            .files
+            .keys()
+            .filter(|path| path.parent() == Some(dir))
+            .filter_map(|path| path.file_name()?.to_str().map(str::to_string))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{CONTRIB_FILE, Cinic10Index, DataSet, ObjectClass, SYNSET_FILE};
+    use crate::loader::{BatchLoader, LoaderConfig};
+    use anyhow::Result;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn png(value: u8) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        RgbImage::from_pixel(2, 2, Rgb([value; 3]))
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_memory_source() -> Result<()> {
+        let root = Path::new("/cinic");
+        let mut source = MemorySource::new()
+            .with_file(
+                root.join(CONTRIB_FILE),
+                b"synset,image_num,cinic_set,class\n".to_vec(),
+            )
+            .with_file(root.join(SYNSET_FILE), b"cat\n".to_vec());
+        for data_set in DataSet::ALL {
+            let ds_path = root.join(data_set.to_string());
+            source.insert(ds_path.join("cat/b.png"), png(2)?);
+            source.insert(ds_path.join("cat/a.png"), png(1)?);
+            source.insert(ds_path.join("ship/a.png"), png(3)?);
+        }
+        assert_eq!(
+            source.list(&root.join("train/cat"))?,
+            vec!["a.png", "b.png"]
+        );
+        assert!(source.list(&root.join("train/dog"))?.is_empty());
+
+        let cinic = Cinic10Index::new_from_source(&source, root)?;
+        assert_eq!(cinic.train.len(), 3);
+        assert_eq!(
+            cinic.valid.indices_to_classes(&[0, 2]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+
+        let loader = BatchLoader::new(LoaderConfig {
+            source: Some(Arc::new(source)),
+            ..Default::default()
+        });
+        let batch = cinic.test.load_rgbimagebatch_with(&loader, &[1, 2])?;
+        assert_eq!(batch.shape, vec![2, 2, 2, 3]);
+        assert_eq!(&batch.data[..3], &[2, 2, 2]);
+        assert!(loader.load_rgbimagebatch(&[root.join("nope.png")]).is_err());
+
+        Ok(())
+    }
+}
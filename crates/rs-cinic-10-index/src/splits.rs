@@ -0,0 +1,509 @@
+use crate::error::{Result, bail};
+use crate::index::{Cinic10Index, DataSet, DatasetIndex, IndexRecord, ObjectClass};
+use crate::record::{ComponentRecord, Recordable};
+use crate::view::DatasetIndexView;
+use crate::wnid::WnId;
+use enum_ordinalize::Ordinalize;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::hash::Hash;
+use strum::EnumCount;
+
+/// The fractions and seed of a custom train/valid/test partition.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SplitSpec {
+    pub train: f64,
+    pub valid: f64,
+    pub test: f64,
+    pub seed: u64,
+}
+
+impl SplitSpec {
+    /// Create a spec with the given fractions, which must sum to 1; the seed is 0.
+    pub fn new(
+        train: f64,
+        valid: f64,
+        test: f64,
+    ) -> Self {
+        Self {
+            train,
+            valid,
+            test,
+            seed: 0,
+        }
+    }
+
+    pub fn with_seed(
+        mut self,
+        seed: u64,
+    ) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        let fractions = [self.train, self.valid, self.test];
+        if fractions.iter().any(|f| !(0.0..=1.0).contains(f)) {
+            bail!("Split fractions must be in [0, 1]: {fractions:?}");
+        }
+        if (fractions.iter().sum::<f64>() - 1.0).abs() > 1e-6 {
+            bail!("Split fractions must sum to 1: {fractions:?}");
+        }
+        Ok(())
+    }
+}
+
+impl Recordable for SplitSpec {
+    fn component_record(&self) -> ComponentRecord {
+        ComponentRecord::new("split_spec")
+            .with_param("train", self.train)
+            .with_param("valid", self.valid)
+            .with_param("test", self.test)
+            .with_param("seed", self.seed)
+    }
+}
+
+/// How a `Resplit` was made; serialize it to reproduce the partition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitDescription {
+    pub spec: SplitSpec,
+
+    /// The `DatasetIndex::fingerprint()` of the union of the source splits.
+    pub source_fingerprint: String,
+
+    /// The sizes of the new splits, in `[train, valid, test]` order.
+    pub counts: [usize; 3],
+
+    /// The `DatasetIndex::fingerprint()` of each new split, in `[train, valid, test]` order.
+    pub fingerprints: [String; 3],
+}
+
+impl SplitDescription {
+    /// Repartition `cinic` as described.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the same partition as the original; or an error
+    /// if `cinic` does not hold the same samples, in the same order, or the
+    /// new splits differ from the recorded ones.
+    pub fn apply(
+        &self,
+        cinic: &Cinic10Index,
+    ) -> Result<Resplit> {
+        let resplit = cinic.resplit(&self.spec)?;
+        if resplit.description.source_fingerprint != self.source_fingerprint {
+            bail!(
+                "Split source fingerprint {} does not match {}",
+                resplit.description.source_fingerprint,
+                self.source_fingerprint
+            );
+        }
+        if resplit.description.fingerprints != self.fingerprints {
+            bail!(
+                "Split fingerprints {:?} do not match {:?}",
+                resplit.description.fingerprints,
+                self.fingerprints
+            );
+        }
+        Ok(resplit)
+    }
+}
+
+/// New train/valid/test splits, from `Cinic10Index::resplit()`.
+#[derive(Debug, Clone)]
+pub struct Resplit {
+    pub train: DatasetIndex,
+    pub valid: DatasetIndex,
+    pub test: DatasetIndex,
+    pub description: SplitDescription,
+}
+
+impl Cinic10Index {
+    /// Repartition the union of all splits into new train/valid/test splits.
+    ///
+    /// The partition is stratified: each class's samples are shuffled with
+    /// the seed and divided by the fractions, so every split keeps the class
+    /// balance of the union. The new splits keep the union's item order.
+    ///
+    /// Shuffles use `ChaCha8Rng`, whose output is stable across platforms and
+    /// `rand` releases, so a seed names the same partition everywhere.
+    ///
+    /// # Parameters
+    ///
+    /// - `spec`: The fractions and seed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the new splits and their description; or an
+    /// error if the fractions are invalid.
+    pub fn resplit(
+        &self,
+        spec: &SplitSpec,
+    ) -> Result<Resplit> {
+        spec.validate()?;
+        let union = DatasetIndex::concat(&[&self.train, &self.test, &self.valid]);
+        let mut rng = ChaCha8Rng::seed_from_u64(spec.seed);
+
+        let mut parts: [Vec<usize>; 3] = Default::default();
+        for (_, indices) in union.iter_by_class() {
+            let mut indices: Vec<usize> = indices.collect();
+            indices.shuffle(&mut rng);
+            let n = indices.len() as f64;
+            let n_train = (n * spec.train).round() as usize;
+            let n_valid = ((n * spec.valid).round() as usize).min(indices.len() - n_train);
+            parts[0].extend(&indices[..n_train]);
+            parts[1].extend(&indices[n_train..n_train + n_valid]);
+            parts[2].extend(&indices[n_train + n_valid..]);
+        }
+        let [train, valid, test] = parts.map(|mut indices| {
+            indices.sort_unstable();
+            union.subset(&indices).map(|view| view.to_index())
+        });
+        let (train, valid, test) = (train?, valid?, test?);
+
+        let description = SplitDescription {
+            spec: *spec,
+            source_fingerprint: union.fingerprint(),
+            counts: [train.len(), valid.len(), test.len()],
+            fingerprints: [train.fingerprint(), valid.fingerprint(), test.fingerprint()],
+        };
+        Ok(Resplit {
+            train,
+            valid,
+            test,
+            description,
+        })
+    }
+}
+
+impl DatasetIndex {
+    /// Stratified k-fold cross-validation splits.
+    ///
+    /// Each class's items are shuffled with the seed and dealt round-robin
+    /// into `k` folds, so every fold has the class balance of the split, and
+    /// fold sizes differ by at most one per class.
+    ///
+    /// # Parameters
+    ///
+    /// - `k`: The number of folds; at least 2.
+    /// - `seed`: The shuffle seed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `k` `(train, valid)` view pairs, where `valid` is
+    /// one fold and `train` is the rest; both in source order.
+    pub fn kfold(
+        &self,
+        k: usize,
+        seed: u64,
+    ) -> Result<Vec<(DatasetIndexView<'_>, DatasetIndexView<'_>)>> {
+        if k < 2 {
+            bail!("k-fold needs at least 2 folds, got {k}");
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut folds = vec![0; self.len()];
+        for (_, indices) in self.iter_by_class() {
+            let mut indices: Vec<usize> = indices.collect();
+            indices.shuffle(&mut rng);
+            for (position, index) in indices.into_iter().enumerate() {
+                folds[index] = position % k;
+            }
+        }
+
+        self.fold_views(&folds, k)
+    }
+
+    /// Group-aware k-fold cross-validation splits.
+    ///
+    /// Items which share a group always land in the same fold, so near
+    /// duplicates (e.g. images of one ImageNet synset) never straddle train
+    /// and valid. Groups are shuffled with the seed, then placed largest
+    /// first into the fold with the fewest items of the group's class, which
+    /// keeps folds roughly class-balanced.
+    ///
+    /// # Parameters
+    ///
+    /// - `groups`: The group of each item; `None` puts an item in a group of its own.
+    /// - `k`: The number of folds; at least 2.
+    /// - `seed`: The shuffle seed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing `k` `(train, valid)` view pairs, as for `kfold()`;
+    /// or an error if `groups` is not one per item.
+    pub fn group_kfold<G>(
+        &self,
+        groups: &[Option<G>],
+        k: usize,
+        seed: u64,
+    ) -> Result<Vec<(DatasetIndexView<'_>, DatasetIndexView<'_>)>>
+    where
+        G: Hash + Eq,
+    {
+        if k < 2 {
+            bail!("k-fold needs at least 2 folds, got {k}");
+        }
+        if groups.len() != self.len() {
+            bail!(
+                "Expected one group per item ({}), got {}",
+                self.len(),
+                groups.len()
+            );
+        }
+
+        let mut members: Vec<Vec<usize>> = Vec::new();
+        let mut slots: HashMap<&G, usize> = HashMap::new();
+        for (index, group) in groups.iter().enumerate() {
+            match group {
+                Some(group) => {
+                    let slot = *slots.entry(group).or_insert_with(|| {
+                        members.push(Vec::new());
+                        members.len() - 1
+                    });
+                    members[slot].push(index);
+                }
+                None => members.push(vec![index]),
+            }
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        members.shuffle(&mut rng);
+        // Stable, so equal-sized groups keep their shuffled order.
+        members.sort_by_key(|m| Reverse(m.len()));
+
+        let mut load = vec![[0usize; ObjectClass::COUNT]; k];
+        let mut folds = vec![0; self.len()];
+        for indices in members {
+            let class = self.index_to_class(indices[0]).ordinal() as usize;
+            let fold = (0..k).min_by_key(|&f| load[f][class]).unwrap_or(0);
+            load[fold][class] += indices.len();
+            for index in indices {
+                folds[index] = fold;
+            }
+        }
+
+        self.fold_views(&folds, k)
+    }
+
+    /// The `(train, valid)` views of each fold, given every item's fold.
+    fn fold_views(
+        &self,
+        folds: &[usize],
+        k: usize,
+    ) -> Result<Vec<(DatasetIndexView<'_>, DatasetIndexView<'_>)>> {
+        (0..k)
+            .map(|fold| {
+                let (valid, train): (Vec<usize>, Vec<usize>) =
+                    (0..self.len()).partition(|&i| folds[i] == fold);
+                Ok((self.subset(&train)?, self.subset(&valid)?))
+            })
+            .collect()
+    }
+}
+
+impl Cinic10Index {
+    /// The ImageNet synset of each item of a split, from the contributor records.
+    ///
+    /// CIFAR-10 items, and files with no record, are `None`.
+    pub fn synset_groups(
+        &self,
+        data_set: DataSet,
+    ) -> Vec<Option<WnId>> {
+        self.item_records(data_set)
+            .into_iter()
+            .map(|record| record.map(IndexRecord::synset))
+            .collect()
+    }
+
+    /// K-fold splits of one split which keep each ImageNet synset in one fold.
+    ///
+    /// See `DatasetIndex::group_kfold()` and `synset_groups()`.
+    pub fn synset_kfold(
+        &self,
+        data_set: DataSet,
+        k: usize,
+        seed: u64,
+    ) -> Result<Vec<(DatasetIndexView<'_>, DatasetIndexView<'_>)>> {
+        self.split(data_set)
+            .group_kfold(&self.synset_groups(data_set), k, seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{ObjectClass, parse_contrib_index};
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    fn cinic() -> Cinic10Index {
+        let split = |name: &str| {
+            fixtures::numbered_split(
+                Path::new("/cinic").join(name),
+                &[ObjectClass::Cat, ObjectClass::Dog],
+                10,
+            )
+        };
+        fixtures::cinic("/cinic", split("train"), split("test"), split("valid"))
+    }
+
+    #[test]
+    fn test_resplit() -> Result<()> {
+        let cinic = cinic();
+        let resplit = cinic.resplit(&SplitSpec::new(0.8, 0.1, 0.1).with_seed(7))?;
+        assert_eq!(resplit.description.counts, [48, 6, 6]);
+        assert_eq!(
+            resplit.valid.class_distribution().count(ObjectClass::Dog),
+            3
+        );
+
+        let paths: HashSet<PathBuf> = [&resplit.train, &resplit.valid, &resplit.test]
+            .into_iter()
+            .flat_map(|split| split.indices_to_paths(&(0..split.len()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(paths.len(), 60);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resplit_seed() -> Result<()> {
+        let cinic = cinic();
+        let spec = SplitSpec::new(0.8, 0.1, 0.1).with_seed(7);
+        let resplit = cinic.resplit(&spec)?;
+        assert_eq!(
+            cinic.resplit(&spec)?.test.fingerprint(),
+            resplit.test.fingerprint()
+        );
+        assert_ne!(
+            cinic.resplit(&spec.with_seed(8))?.test.fingerprint(),
+            resplit.test.fingerprint()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resplit_bad_fractions() {
+        assert!(cinic().resplit(&SplitSpec::new(0.5, 0.5, 0.5)).is_err());
+        assert!(cinic().resplit(&SplitSpec::new(1.5, -0.5, 0.0)).is_err());
+    }
+
+    #[test]
+    fn test_split_description_apply() -> Result<()> {
+        let cinic = cinic();
+        let resplit = cinic.resplit(&SplitSpec::new(0.8, 0.1, 0.1).with_seed(7))?;
+
+        let json = serde_json::to_string(&resplit.description)?;
+        let description: SplitDescription = serde_json::from_str(&json)?;
+        let again = description.apply(&cinic)?;
+        assert_eq!(again.test.fingerprint(), resplit.test.fingerprint());
+
+        let mut other = cinic.clone();
+        other.valid.remove(other.valid.len() - 1);
+        assert!(description.apply(&other).is_err());
+        let mut tampered = description.clone();
+        tampered.fingerprints.swap(1, 2);
+        assert!(tampered.apply(&cinic).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kfold() -> Result<()> {
+        let split = cinic().train;
+        let folds = split.kfold(3, 1)?;
+        assert_eq!(folds.len(), 3);
+
+        let mut seen = vec![0; split.len()];
+        for (train, valid) in &folds {
+            assert_eq!(train.len() + valid.len(), split.len());
+            let dist = valid.class_distribution();
+            assert!((3..=4).contains(&dist.count(ObjectClass::Cat)));
+            assert!(
+                valid
+                    .source_indices()
+                    .iter()
+                    .all(|i| !train.source_indices().contains(i))
+            );
+            for &i in valid.source_indices() {
+                seen[i] += 1;
+            }
+        }
+        assert!(seen.iter().all(|&n| n == 1));
+
+        assert_eq!(
+            split.kfold(3, 1)?[0].1.source_indices(),
+            folds[0].1.source_indices()
+        );
+        // Pinned: a seed must name the same folds on every platform and release.
+        assert_eq!(folds[0].1.source_indices(), &[1, 2, 3, 6, 11, 12, 15, 16]);
+        assert!(split.kfold(1, 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_synset_kfold() -> Result<()> {
+        let names = [
+            "n00000001_1.png",
+            "cifar10-train-3.png",
+            "n00000002_1.png",
+            "n00000001_2.png",
+            "n00000003_1.png",
+            "n00000002_2.png",
+            "n00000001_3.png",
+            "cifar10-train-4.png",
+        ];
+        let contrib = "synset, image_num, cinic_set, class\n\
+                       n00000001, 1, train, dog\n\
+                       n00000001, 2, train, dog\n\
+                       n00000001, 3, train, dog\n\
+                       n00000002, 1, train, dog\n\
+                       n00000002, 2, train, dog\n\
+                       n00000003, 1, train, dog\n";
+        let mut cinic = cinic();
+        cinic.imagenet_contrib = parse_contrib_index(contrib.as_bytes())?;
+        cinic.train = fixtures::split_of_names("/cinic/train", ObjectClass::Dog, &names);
+
+        let groups = cinic.synset_groups(DataSet::Train);
+        assert_eq!(groups[0], Some("n00000001".parse()?));
+        assert_eq!(groups[1], None);
+
+        let folds = cinic.synset_kfold(DataSet::Train, 2, 3)?;
+        assert_eq!(folds.len(), 2);
+        for (train, valid) in &folds {
+            assert_eq!(train.len() + valid.len(), names.len());
+            for view in [train, valid] {
+                let synsets: HashSet<_> = view
+                    .source_indices()
+                    .iter()
+                    .filter_map(|&i| groups[i])
+                    .collect();
+                let members = groups
+                    .iter()
+                    .filter(|g| g.is_some_and(|g| synsets.contains(&g)))
+                    .count();
+                let grouped = view
+                    .source_indices()
+                    .iter()
+                    .filter(|&&i| groups[i].is_some())
+                    .count();
+                assert_eq!(members, grouped);
+            }
+        }
+        // The 3-item synset fills one fold; the rest balance the other.
+        assert_eq!(folds[0].1.len(), 4);
+        assert_eq!(folds[1].1.len(), 4);
+
+        assert!(cinic.train.group_kfold(&groups[1..], 2, 3).is_err());
+        assert!(cinic.synset_kfold(DataSet::Train, 1, 3).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+use crate::error::Result;
+use crate::images::load_rgbimage;
+use crate::index::{CHANNELS, DatasetIndex, ObjectClass};
+use crate::parallel::par_fold;
+use crate::readahead::advise_split_ahead;
+use enum_ordinalize::Ordinalize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use strum::{EnumCount, IntoEnumIterator};
+
+/// Published per-channel (RGB) mean of CINIC-10, on the `[0, 1]` scale.
+pub const CINIC10_MEAN: [f64; CHANNELS] = [0.47889522, 0.47227842, 0.43047404];
+
+/// Published per-channel (RGB) standard deviation of CINIC-10, on the `[0, 1]` scale.
+pub const CINIC10_STD: [f64; CHANNELS] = [0.24205776, 0.23828046, 0.25874835];
+
+/// Per-channel mean and (population) standard deviation, on the `[0, 1]` scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ChannelStats {
+    /// The number of pixels the statistics were computed over.
+    pub count: u64,
+    pub mean: [f64; CHANNELS],
+    pub std: [f64; CHANNELS],
+}
+
+/// A streaming (Welford) accumulator of per-channel statistics.
+///
+/// Accumulators over disjoint data can be merged, which makes the
+/// computation parallel and order-independent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChannelAccumulator {
+    count: u64,
+    mean: [f64; CHANNELS],
+    m2: [f64; CHANNELS],
+}
+
+impl ChannelAccumulator {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a single `u8` RGB pixel.
+    pub fn push(
+        &mut self,
+        pixel: [u8; CHANNELS],
+    ) {
+        self.count += 1;
+        let n = self.count as f64;
+        for (c, &v) in pixel.iter().enumerate() {
+            let x = v as f64 / 255.0;
+            let delta = x - self.mean[c];
+            self.mean[c] += delta / n;
+            self.m2[c] += delta * (x - self.mean[c]);
+        }
+    }
+
+    /// Add every pixel of an interleaved RGB buffer.
+    pub fn push_rgb_pixels(
+        &mut self,
+        data: &[u8],
+    ) {
+        for px in data.chunks_exact(CHANNELS) {
+            self.push([px[0], px[1], px[2]]);
+        }
+    }
+
+    /// Merge another accumulator into this one.
+    pub fn merge(
+        &mut self,
+        other: &ChannelAccumulator,
+    ) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let n_a = self.count as f64;
+        let n_b = other.count as f64;
+        let n = n_a + n_b;
+        for c in 0..CHANNELS {
+            let delta = other.mean[c] - self.mean[c];
+            self.mean[c] += delta * n_b / n;
+            self.m2[c] += other.m2[c] + delta * delta * n_a * n_b / n;
+        }
+        self.count += other.count;
+    }
+
+    /// The statistics accumulated so far.
+    pub fn stats(&self) -> ChannelStats {
+        let mut std = [0.0; CHANNELS];
+        if self.count > 0 {
+            for (c, s) in std.iter_mut().enumerate() {
+                *s = (self.m2[c] / self.count as f64).sqrt();
+            }
+        }
+        ChannelStats {
+            count: self.count,
+            mean: self.mean,
+            std,
+        }
+    }
+}
+
+/// Compute per-channel mean/std over a whole split.
+///
+/// # Parameters
+///
+/// - `split`: The split to compute statistics for.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the statistics.
+pub fn compute_channel_stats(
+    split: &DatasetIndex,
+    parallelism: usize,
+) -> Result<ChannelStats> {
+    let indices: Vec<usize> = (0..split.len()).collect();
+    compute_subset_channel_stats(split, &indices, parallelism)
+}
+
+/// Compute per-channel mean/std over a subset of a split.
+///
+/// # Parameters
+///
+/// - `split`: The split the subset is drawn from.
+/// - `indices`: The item indices of the subset.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the statistics.
+pub fn compute_subset_channel_stats(
+    split: &DatasetIndex,
+    indices: &[usize],
+    parallelism: usize,
+) -> Result<ChannelStats> {
+    let acc = par_fold(
+        indices,
+        parallelism,
+        ChannelAccumulator::new,
+        |acc, &i| {
+            advise_split_ahead(split, i);
+            let img = load_rgbimage(split.index_to_path(i))?;
+            acc.push_rgb_pixels(img.as_raw());
+            Ok(())
+        },
+        |mut a, b| {
+            a.merge(&b);
+            a
+        },
+    )?;
+    Ok(acc.stats())
+}
+
+/// Per-class sample counts of a dataset view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassDistribution {
+    /// Counts indexed by `ObjectClass::ordinal()`.
+    pub counts: [usize; ObjectClass::COUNT],
+}
+
+impl ClassDistribution {
+    /// Count the classes of a sequence of samples.
+    pub fn from_classes<I>(classes: I) -> Self
+    where
+        I: IntoIterator<Item = ObjectClass>,
+    {
+        let mut dist = Self::default();
+        for class in classes {
+            dist.counts[class.ordinal() as usize] += 1;
+        }
+        dist
+    }
+
+    /// The total number of samples.
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// The number of samples of a class.
+    pub fn count(
+        &self,
+        class: ObjectClass,
+    ) -> usize {
+        self.counts[class.ordinal() as usize]
+    }
+
+    /// The fraction of samples of a class; `0.0` when empty.
+    pub fn proportion(
+        &self,
+        class: ObjectClass,
+    ) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            0.0
+        } else {
+            self.count(class) as f64 / total as f64
+        }
+    }
+
+    /// `(class, count, proportion)` for every class, in class order.
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectClass, usize, f64)> + '_ {
+        ObjectClass::iter().map(|c| (c, self.count(c), self.proportion(c)))
+    }
+
+    /// Do all classes have the same number of samples?
+    pub fn is_balanced(&self) -> bool {
+        self.counts.iter().all(|&n| n == self.counts[0])
+    }
+}
+
+/// The number of bins in a `ChannelHistogram`; one per `u8` value.
+pub const HISTOGRAM_BINS: usize = 256;
+
+/// Per-channel 256-bin histograms of `u8` pixel values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelHistogram {
+    /// `counts[channel][value]` is the number of pixels with that channel value.
+    pub counts: [[u64; HISTOGRAM_BINS]; CHANNELS],
+}
+
+impl Default for ChannelHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [[0; HISTOGRAM_BINS]; CHANNELS],
+        }
+    }
+}
+
+impl ChannelHistogram {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add every pixel of an interleaved RGB buffer.
+    pub fn push_rgb_pixels(
+        &mut self,
+        data: &[u8],
+    ) {
+        for px in data.chunks_exact(CHANNELS) {
+            for (c, &v) in px.iter().enumerate() {
+                self.counts[c][v as usize] += 1;
+            }
+        }
+    }
+
+    /// Merge another histogram into this one.
+    pub fn merge(
+        &mut self,
+        other: &ChannelHistogram,
+    ) {
+        for (a, b) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for (x, y) in a.iter_mut().zip(b.iter()) {
+                *x += y;
+            }
+        }
+    }
+
+    /// The number of pixels counted.
+    pub fn total(&self) -> u64 {
+        self.counts[0].iter().sum()
+    }
+
+    /// The normalized histogram of a channel; all zeros when empty.
+    pub fn frequencies(
+        &self,
+        channel: usize,
+    ) -> Vec<f64> {
+        let total = self.total().max(1) as f64;
+        self.counts[channel]
+            .iter()
+            .map(|&n| n as f64 / total)
+            .collect()
+    }
+
+    /// The number of distinct values used by a channel.
+    ///
+    /// Data which was scaled twice, or quantized by a gamma bug,
+    /// typically uses far fewer than 256 values.
+    pub fn occupied_bins(
+        &self,
+        channel: usize,
+    ) -> usize {
+        self.counts[channel].iter().filter(|&&n| n > 0).count()
+    }
+}
+
+/// Compute per-channel histograms over a whole split.
+///
+/// # Parameters
+///
+/// - `split`: The split to compute histograms for.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the histograms.
+pub fn compute_histogram(
+    split: &DatasetIndex,
+    parallelism: usize,
+) -> Result<ChannelHistogram> {
+    let indices: Vec<usize> = (0..split.len()).collect();
+    compute_subset_histogram(split, &indices, parallelism)
+}
+
+/// Compute per-channel histograms over a subset of a split.
+///
+/// # Parameters
+///
+/// - `split`: The split the subset is drawn from.
+/// - `indices`: The item indices of the subset.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the histograms.
+pub fn compute_subset_histogram(
+    split: &DatasetIndex,
+    indices: &[usize],
+    parallelism: usize,
+) -> Result<ChannelHistogram> {
+    par_fold(
+        indices,
+        parallelism,
+        ChannelHistogram::new,
+        |hist, &i| {
+            advise_split_ahead(split, i);
+            let img = load_rgbimage(split.index_to_path(i))?;
+            hist.push_rgb_pixels(img.as_raw());
+            Ok(())
+        },
+        |mut a, b| {
+            a.merge(&b);
+            a
+        },
+    )
+}
+
+/// Where `cached_channel_stats()` stores the statistics of a split.
+///
+/// The file sits next to the split directory, and is keyed by the split's
+/// fingerprint, so rebuilt or filtered indices never reuse stale results.
+pub fn channel_stats_cache_path(split: &DatasetIndex) -> PathBuf {
+    let ds_path = split.ds_path();
+    let name = ds_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let fingerprint = split.fingerprint();
+    ds_path.parent().unwrap_or(ds_path).join(format!(
+        "channel-stats-{}-{}.json",
+        name,
+        &fingerprint[..16]
+    ))
+}
+
+/// Compute per-channel mean/std over a split, caching the result next to the dataset.
+///
+/// Failing to write the cache (e.g. on a read-only dataset copy) is not an error.
+///
+/// # Parameters
+///
+/// - `split`: The split to compute statistics for.
+/// - `parallelism`: The number of threads; `0` means "all available cores".
+///
+/// # Returns
+///
+/// A `Result` containing the statistics.
+pub fn cached_channel_stats(
+    split: &DatasetIndex,
+    parallelism: usize,
+) -> Result<ChannelStats> {
+    let path = channel_stats_cache_path(split);
+    if let Ok(json) = fs::read_to_string(&path)
+        && let Ok(stats) = serde_json::from_str(&json)
+    {
+        return Ok(stats);
+    }
+
+    let stats = compute_channel_stats(split, parallelism)?;
+    if let Err(_err) = fs::write(&path, serde_json::to_string_pretty(&stats)?) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(path = %path.display(), error = %_err, "failed to cache channel stats");
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+
+    fn assert_close(
+        a: f64,
+        b: f64,
+    ) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_accumulator_merge() {
+        let pixels: Vec<[u8; 3]> = (0..50u8).map(|i| [i, 255 - i, i / 2]).collect();
+
+        let mut whole = ChannelAccumulator::new();
+        pixels.iter().for_each(|&p| whole.push(p));
+
+        let mut left = ChannelAccumulator::new();
+        let mut right = ChannelAccumulator::new();
+        pixels[..17].iter().for_each(|&p| left.push(p));
+        pixels[17..].iter().for_each(|&p| right.push(p));
+        left.merge(&right);
+
+        let (a, b) = (whole.stats(), left.stats());
+        assert_eq!(a.count, b.count);
+        for c in 0..CHANNELS {
+            assert_close(a.mean[c], b.mean[c]);
+            assert_close(a.std[c], b.std[c]);
+        }
+    }
+
+    #[test]
+    fn test_class_distribution() {
+        let dist = ClassDistribution::from_classes([
+            ObjectClass::Cat,
+            ObjectClass::Dog,
+            ObjectClass::Cat,
+            ObjectClass::Cat,
+        ]);
+        assert_eq!(dist.total(), 4);
+        assert_eq!(dist.count(ObjectClass::Cat), 3);
+        assert_eq!(dist.proportion(ObjectClass::Dog), 0.25);
+        assert_eq!(dist.proportion(ObjectClass::Ship), 0.0);
+        assert!(!dist.is_balanced());
+        assert_eq!(dist.iter().nth(3), Some((ObjectClass::Cat, 3, 0.75)));
+
+        assert!(ClassDistribution::from_classes(ObjectClass::iter()).is_balanced());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut a = ChannelHistogram::new();
+        a.push_rgb_pixels(&[0, 10, 255, 0, 20, 255]);
+        let mut b = ChannelHistogram::new();
+        b.push_rgb_pixels(&[1, 10, 255]);
+        a.merge(&b);
+
+        assert_eq!(a.total(), 3);
+        assert_eq!(a.counts[0][0], 2);
+        assert_eq!(a.counts[0][1], 1);
+        assert_eq!(a.counts[1][10], 2);
+        assert_eq!(a.counts[2][255], 3);
+        assert_eq!(a.occupied_bins(0), 2);
+        assert_eq!(a.occupied_bins(2), 1);
+        assert_eq!(a.frequencies(2)[255], 1.0);
+    }
+
+    #[test]
+    fn test_cached_channel_stats() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = fixtures::write_split(
+            dir.path().join("train"),
+            [0u8, 255].map(|v| {
+                (
+                    ObjectClass::Cat,
+                    format!("{v}.png"),
+                    RgbImage::from_pixel(2, 2, Rgb([v, 0, 255])),
+                )
+            }),
+        )?;
+
+        let stats = cached_channel_stats(&split, 2)?;
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.mean, [0.5, 0.0, 1.0]);
+        assert_eq!(stats.std, [0.5, 0.0, 0.0]);
+
+        let cache = channel_stats_cache_path(&split);
+        assert!(cache.starts_with(dir.path()));
+        assert!(cache.exists());
+        assert_eq!(cached_channel_stats(&split, 1)?, stats);
+
+        Ok(())
+    }
+}
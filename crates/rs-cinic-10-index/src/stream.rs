@@ -0,0 +1,379 @@
+use crate::error::{Result, bail};
+use crate::images::{DecoderBackend, RgbImageBatch, decode_rgbimage};
+use crate::index::ObjectClass;
+use crate::retry::with_retry;
+use anyhow::Context;
+use std::fs::{self, ReadDir};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The encoded bytes of one sample, read from a stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedSample {
+    pub class: ObjectClass,
+
+    /// The file name of the sample.
+    pub name: String,
+
+    pub bytes: Vec<u8>,
+}
+
+/// Streams the samples of a split directory, one class directory at a time.
+///
+/// Directory entries are read lazily, in filesystem order; so memory use
+/// does not grow with the size of the split.
+#[derive(Debug)]
+pub struct DirSampleStream {
+    ds_path: PathBuf,
+    next_class: usize,
+    current: Option<(ObjectClass, ReadDir)>,
+}
+
+impl DirSampleStream {
+    pub fn new<P>(ds_path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            ds_path: ds_path.as_ref().to_path_buf(),
+            next_class: 0,
+            current: None,
+        }
+    }
+}
+
+impl Iterator for DirSampleStream {
+    type Item = Result<EncodedSample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((class, entries)) = &mut self.current else {
+                let class = *ObjectClass::ALL.get(self.next_class)?;
+                self.next_class += 1;
+                let class_path = self.ds_path.join(class.to_string());
+                match fs::read_dir(&class_path) {
+                    Ok(entries) => self.current = Some((class, entries)),
+                    Err(err) => {
+                        return Some(Err(anyhow::Error::from(err)
+                            .context(format!("Failed to list {}", class_path.display()))
+                            .into()));
+                    }
+                }
+                continue;
+            };
+
+            let entry = match entries.next() {
+                Some(Ok(entry)) => entry,
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "png") {
+                continue;
+            }
+            let class = *class;
+            return Some(
+                with_retry(|| fs::read(&path))
+                    .with_context(|| format!("Failed to read {}", path.display()))
+                    .map_err(Into::into)
+                    .map(|bytes| EncodedSample {
+                        class,
+                        name: entry.file_name().to_string_lossy().into_owned(),
+                        bytes,
+                    }),
+            );
+        }
+    }
+}
+
+/// Streams the samples of one split of a CINIC-10 `.tar.gz` archive.
+///
+/// The archive is read on a background thread, a bounded number of
+/// samples ahead of the consumer.
+#[cfg(feature = "download")]
+#[derive(Debug)]
+pub struct TarSampleStream {
+    receiver: std::sync::mpsc::Receiver<Result<EncodedSample>>,
+}
+
+#[cfg(feature = "download")]
+impl TarSampleStream {
+    /// The number of archive samples buffered ahead of the consumer.
+    const BUFFERED: usize = 256;
+
+    /// Stream the samples of `{split}/{class}/*.png` in `archive`, in archive order.
+    pub fn new<P>(
+        archive: P,
+        split: &str,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        use std::io::Read;
+        use std::str::FromStr;
+
+        let archive = archive.as_ref();
+        let file = fs::File::open(archive)
+            .with_context(|| format!("Failed to open archive {}", archive.display()))?;
+        let split = split.to_string();
+        let (sender, receiver) = std::sync::mpsc::sync_channel(Self::BUFFERED);
+        std::thread::spawn(move || {
+            let result: Result<()> = (|| {
+                let decoder = flate2::read::GzDecoder::new(std::io::BufReader::new(file));
+                let mut archive = tar::Archive::new(decoder);
+                for entry in archive.entries()? {
+                    let mut entry = entry?;
+                    let path = entry.path()?.into_owned();
+                    let parts: Vec<&str> = path.iter().filter_map(|c| c.to_str()).collect();
+                    let [.., dir, class, name] = parts.as_slice() else {
+                        continue;
+                    };
+                    if *dir != split || !name.ends_with(".png") {
+                        continue;
+                    }
+                    let Ok(class) = ObjectClass::from_str(class) else {
+                        continue;
+                    };
+                    let mut bytes = Vec::with_capacity(entry.size() as usize);
+                    entry.read_to_end(&mut bytes)?;
+                    let sample = EncodedSample {
+                        class,
+                        name: name.to_string(),
+                        bytes,
+                    };
+                    if sender.send(Ok(sample)).is_err() {
+                        return Ok(());
+                    }
+                }
+                Ok(())
+            })();
+            if let Err(err) = result {
+                let _ = sender.send(Err(err));
+            }
+        });
+        Ok(Self { receiver })
+    }
+}
+
+#[cfg(feature = "download")]
+impl Iterator for TarSampleStream {
+    type Item = Result<EncodedSample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// A fixed-size group of decoded samples.
+#[derive(Debug, Clone)]
+pub struct Shard {
+    /// The file name of each sample.
+    pub names: Vec<String>,
+
+    pub labels: Vec<ObjectClass>,
+
+    /// The `[N, H, W, 3]` images.
+    pub images: RgbImageBatch,
+}
+
+impl Shard {
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+/// Groups a stream of encoded samples into decoded `Shard`s.
+///
+/// Only one shard is held in memory at a time; useful for map-reduce style
+/// passes (stats, exports, hashing) on memory-constrained workers, which
+/// never need a `DatasetIndex`. The last shard may be short.
+#[derive(Debug)]
+pub struct ShardIter<S> {
+    samples: S,
+    shard_size: usize,
+    decoder: Option<Arc<dyn DecoderBackend>>,
+}
+
+impl<S> ShardIter<S>
+where
+    S: Iterator<Item = Result<EncodedSample>>,
+{
+    /// Group `samples` into shards of `shard_size`.
+    pub fn new(
+        samples: S,
+        shard_size: usize,
+    ) -> Self {
+        assert!(shard_size > 0, "Shard size must be positive");
+        Self {
+            samples,
+            shard_size,
+            decoder: None,
+        }
+    }
+
+    /// Decode with `decoder`, rather than `images::decode_rgbimage()`.
+    pub fn with_decoder(
+        mut self,
+        decoder: Arc<dyn DecoderBackend>,
+    ) -> Self {
+        self.decoder = Some(decoder);
+        self
+    }
+
+    fn decode_shard(
+        &self,
+        samples: Vec<EncodedSample>,
+    ) -> Result<Shard> {
+        let mut images = None;
+        let mut names = Vec::with_capacity(samples.len());
+        let mut labels = Vec::with_capacity(samples.len());
+        let n = samples.len();
+        for sample in samples {
+            let img = match &self.decoder {
+                Some(decoder) => decoder.decode(&sample.bytes),
+                None => decode_rgbimage(&sample.bytes),
+            }
+            .with_context(|| format!("Failed to decode {}", sample.name))?;
+            let batch = images.get_or_insert_with(|| {
+                RgbImageBatch::new(&[n, img.height() as usize, img.width() as usize, 3])
+            });
+            if (img.width() as usize, img.height() as usize) != (batch.width(), batch.height()) {
+                bail!(
+                    "Image {} is {:?}, expected {:?}",
+                    sample.name,
+                    img.dimensions(),
+                    (batch.width(), batch.height())
+                );
+            }
+            batch.push_rgb_pixels(&img);
+            names.push(sample.name);
+            labels.push(sample.class);
+        }
+        Ok(Shard {
+            names,
+            labels,
+            images: images.unwrap(),
+        })
+    }
+}
+
+impl<S> Iterator for ShardIter<S>
+where
+    S: Iterator<Item = Result<EncodedSample>>,
+{
+    type Item = Result<Shard>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut samples = Vec::with_capacity(self.shard_size);
+        for sample in self.samples.by_ref() {
+            match sample {
+                Ok(sample) => samples.push(sample),
+                Err(err) => return Some(Err(err)),
+            }
+            if samples.len() == self.shard_size {
+                break;
+            }
+        }
+        if samples.is_empty() {
+            return None;
+        }
+        Some(self.decode_shard(samples))
+    }
+}
+
+/// Stream decoded shards of a split directory; see `DirSampleStream` and `ShardIter`.
+///
+/// # Parameters
+///
+/// - `ds_path`: The split directory, e.g. `{root}/train`.
+/// - `shard_size`: The number of samples per shard.
+///
+/// # Returns
+///
+/// An iterator of shards.
+pub fn stream_dir_shards<P>(
+    ds_path: P,
+    shard_size: usize,
+) -> ShardIter<DirSampleStream>
+where
+    P: AsRef<Path>,
+{
+    ShardIter::new(DirSampleStream::new(ds_path), shard_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn write_split(root: &Path) -> Result<()> {
+        for class in ObjectClass::ALL {
+            fs::create_dir_all(root.join(class.to_string()))?;
+        }
+        for (class, count) in [(ObjectClass::Cat, 3), (ObjectClass::Ship, 2)] {
+            for i in 0..count {
+                let path = root.join(class.to_string()).join(format!("{i}.png"));
+                RgbImage::from_pixel(2, 2, Rgb([i, 0, 0])).save(path)?;
+            }
+        }
+        fs::write(root.join("cat/notes.txt"), "not an image")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_dir_shards() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_split(dir.path())?;
+
+        let shards = stream_dir_shards(dir.path(), 3).collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            shards.iter().map(Shard::len).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        assert_eq!(shards[0].images.shape, vec![3, 2, 2, 3]);
+        let mut labels: Vec<ObjectClass> = shards
+            .iter()
+            .flat_map(|s| s.labels.iter().copied())
+            .collect();
+        labels.dedup();
+        assert_eq!(labels, vec![ObjectClass::Cat, ObjectClass::Ship]);
+
+        assert!(
+            stream_dir_shards(dir.path().join("missing"), 3)
+                .next()
+                .unwrap()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "download")]
+    #[test]
+    fn test_tar_sample_stream() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = dir.path().join("CINIC-10/train");
+        write_split(&split)?;
+        let archive = dir.path().join("cinic.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&archive)?,
+            flate2::Compression::fast(),
+        ));
+        builder.append_dir_all("CINIC-10", dir.path().join("CINIC-10"))?;
+        builder.into_inner()?.finish()?;
+
+        let shards = ShardIter::new(TarSampleStream::new(&archive, "train")?, 4)
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(shards.iter().map(Shard::len).sum::<usize>(), 5);
+        assert_eq!(TarSampleStream::new(&archive, "test")?.count(), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use crate::Cinic10Index;
+use crate::index::{ObjectClass, SynsetNode};
+use crate::wnid::WnId;
+use std::collections::HashMap;
+
+/// How a synset alias matched a search query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AliasMatchKind {
+    /// Every query word is a whole word of the alias.
+    Token,
+
+    /// The query is a substring of the alias.
+    Substring,
+
+    /// Every query word is within the edit budget of a word of the alias.
+    Fuzzy,
+}
+
+/// A synset whose alias matched a search query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SynsetMatch<'a> {
+    pub node: &'a SynsetNode,
+
+    /// The best matching alias.
+    pub alias: &'a str,
+    pub kind: AliasMatchKind,
+}
+
+fn tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// The Levenshtein distance between two words, in chars.
+fn edit_distance(
+    a: &str,
+    b: &str,
+) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diag + usize::from(ca != cb))
+                .min(above + 1)
+                .min(row[j] + 1);
+            diag = above;
+        }
+    }
+    row[b.len()]
+}
+
+fn match_alias(
+    alias: &str,
+    query: &str,
+    query_tokens: &[String],
+    max_edits: usize,
+) -> Option<AliasMatchKind> {
+    let alias_tokens = tokens(alias);
+    if !query_tokens.is_empty() && query_tokens.iter().all(|t| alias_tokens.contains(t)) {
+        Some(AliasMatchKind::Token)
+    } else if alias.to_lowercase().contains(query) {
+        Some(AliasMatchKind::Substring)
+    } else if max_edits > 0
+        && !query_tokens.is_empty()
+        && query_tokens.iter().all(|t| {
+            alias_tokens
+                .iter()
+                .any(|a| edit_distance(t, a) <= max_edits)
+        })
+    {
+        Some(AliasMatchKind::Fuzzy)
+    } else {
+        None
+    }
+}
+
+/// Search synset aliases, case-insensitively.
+///
+/// # Parameters
+///
+/// - `synset_map`: The synsets to search.
+/// - `query`: The search text, e.g. `"pickup"`.
+///
+/// # Returns
+///
+/// The matching synsets, whole-word matches first, then by class and id.
+pub fn find_synsets_matching<'a>(
+    synset_map: &'a HashMap<WnId, SynsetNode>,
+    query: &str,
+) -> Vec<SynsetMatch<'a>> {
+    find_synsets_fuzzy(synset_map, query, 0)
+}
+
+/// Search synset aliases, case-insensitively, tolerating misspelled words.
+///
+/// Like `find_synsets_matching()`, also matching aliases where every query
+/// word is within `max_edits` single-char edits of a word of the alias;
+/// e.g. `"pikup"` finds `"pickup truck"` with `max_edits = 1`.
+///
+/// # Parameters
+///
+/// - `synset_map`: The synsets to search.
+/// - `query`: The search text.
+/// - `max_edits`: The edit budget per word; 0 disables fuzzy matching.
+///
+/// # Returns
+///
+/// The matching synsets; whole-word, then substring, then fuzzy matches,
+/// each by class and id.
+pub fn find_synsets_fuzzy<'a>(
+    synset_map: &'a HashMap<WnId, SynsetNode>,
+    query: &str,
+    max_edits: usize,
+) -> Vec<SynsetMatch<'a>> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query_tokens = tokens(&query);
+
+    let mut matches: Vec<SynsetMatch> = synset_map
+        .values()
+        .filter_map(|node| {
+            node.aliases
+                .iter()
+                .filter_map(|alias| {
+                    match_alias(alias, &query, &query_tokens, max_edits).map(|kind| SynsetMatch {
+                        node,
+                        alias: alias.as_str(),
+                        kind,
+                    })
+                })
+                .min_by_key(|m| m.kind)
+        })
+        .collect();
+    matches.sort_by_key(|m| (m.kind, m.node.object_class as u8, m.node.synset_id));
+    matches
+}
+
+/// Map each synset to its children, sorted by id.
+pub(crate) fn synset_children_map(
+    synset_map: &HashMap<WnId, SynsetNode>
+) -> HashMap<WnId, Vec<WnId>> {
+    let mut children: HashMap<WnId, Vec<WnId>> = HashMap::new();
+    for node in synset_map.values() {
+        if let Some(parent) = node.synset_base_id {
+            children.entry(parent).or_default().push(node.synset_id);
+        }
+    }
+    for ids in children.values_mut() {
+        ids.sort_unstable();
+    }
+    children
+}
+
+impl Cinic10Index {
+    /// The parent chain of a synset, nearest first; empty for a top-level synset.
+    ///
+    /// A parent missing from the synset map ends the chain after its id.
+    pub fn synset_ancestors(
+        &self,
+        synset_id: WnId,
+    ) -> Vec<WnId> {
+        let mut ancestors = Vec::new();
+        let mut current = self.synset_map.get(&synset_id);
+        while let Some(parent) = current.and_then(|node| node.synset_base_id) {
+            // `synset_base_id` links come from indentation, so cannot cycle;
+            // the bound guards hand-built maps.
+            if ancestors.len() > self.synset_map.len() {
+                break;
+            }
+            ancestors.push(parent);
+            current = self.synset_map.get(&parent);
+        }
+        ancestors
+    }
+
+    /// The direct children of a synset, sorted by id.
+    pub fn synset_children(
+        &self,
+        synset_id: WnId,
+    ) -> Vec<WnId> {
+        let mut children: Vec<WnId> = self
+            .synset_map
+            .values()
+            .filter(|node| node.synset_base_id == Some(synset_id))
+            .map(|node| node.synset_id)
+            .collect();
+        children.sort_unstable();
+        children
+    }
+
+    /// The top-level synsets of a class, sorted by id.
+    pub fn class_root_synsets(
+        &self,
+        class: ObjectClass,
+    ) -> Vec<WnId> {
+        let mut roots: Vec<WnId> = self
+            .synset_map
+            .values()
+            .filter(|node| node.object_class == class && node.synset_base_id.is_none())
+            .map(|node| node.synset_id)
+            .collect();
+        roots.sort_unstable();
+        roots
+    }
+
+    /// The synsets of a class with no children, sorted by id.
+    pub fn class_leaf_synsets(
+        &self,
+        class: ObjectClass,
+    ) -> Vec<WnId> {
+        let children = synset_children_map(&self.synset_map);
+        let mut leaves: Vec<WnId> = self
+            .synset_map
+            .values()
+            .filter(|node| node.object_class == class && !children.contains_key(&node.synset_id))
+            .map(|node| node.synset_id)
+            .collect();
+        leaves.sort_unstable();
+        leaves
+    }
+
+    /// Search synset aliases; see `synsets::find_synsets_matching()`.
+    pub fn find_synsets_matching(
+        &self,
+        query: &str,
+    ) -> Vec<SynsetMatch<'_>> {
+        find_synsets_matching(&self.synset_map, query)
+    }
+
+    /// Search synset aliases, tolerating misspellings; see `synsets::find_synsets_fuzzy()`.
+    pub fn find_synsets_fuzzy(
+        &self,
+        query: &str,
+        max_edits: usize,
+    ) -> Vec<SynsetMatch<'_>> {
+        find_synsets_fuzzy(&self.synset_map, query, max_edits)
+    }
+
+    /// The synsets with an alias containing `query`, case-insensitively.
+    ///
+    /// Shorthand for the nodes of `find_synsets_matching()`, best matches first.
+    pub fn find_synsets(
+        &self,
+        query: &str,
+    ) -> Vec<&SynsetNode> {
+        self.find_synsets_matching(query)
+            .into_iter()
+            .map(|m| m.node)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::parse_synset_map;
+    use crate::mock::fixtures;
+
+    fn synsets() -> anyhow::Result<HashMap<WnId, SynsetNode>> {
+        let source = "truck\n\
+                      --n03930630: pickup, pickup truck\n\
+                      --n04467665: trailer truck, tractor trailer\n\
+                      automobile\n\
+                      --n02958343: car, auto, automobile\n\
+                      --n03100240: convertible\n\
+                      ship\n\
+                      --n04194289: ship, Pickupboat\n";
+        Ok(parse_synset_map(source.as_bytes())?)
+    }
+
+    #[test]
+    fn test_find_synsets_matching() -> anyhow::Result<()> {
+        let synsets = synsets()?;
+
+        let found = find_synsets_matching(&synsets, "PICKUP");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].node.object_class, ObjectClass::Truck);
+        assert_eq!(found[0].alias, "pickup");
+        assert_eq!(found[0].kind, AliasMatchKind::Token);
+        assert_eq!(found[1].node.object_class, ObjectClass::Ship);
+        assert_eq!(found[1].kind, AliasMatchKind::Substring);
+
+        let found = find_synsets_matching(&synsets, "trailer tractor");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].node.synset_id, "n04467665".parse()?);
+
+        assert_eq!(find_synsets_matching(&synsets, "vert").len(), 1);
+        assert!(find_synsets_matching(&synsets, "  ").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_synsets_fuzzy() -> anyhow::Result<()> {
+        let synsets = synsets()?;
+
+        assert!(find_synsets_matching(&synsets, "pikup trck").is_empty());
+        let found = find_synsets_fuzzy(&synsets, "pikup trck", 1);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].alias, "pickup truck");
+        assert_eq!(found[0].kind, AliasMatchKind::Fuzzy);
+        let found = find_synsets_fuzzy(&synsets, "convertable", 2);
+        assert_eq!(found[0].node.synset_id, "n03100240".parse()?);
+        assert_eq!(
+            find_synsets_fuzzy(&synsets, "pickup", 1)[0].kind,
+            AliasMatchKind::Token
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    fn cinic() -> anyhow::Result<Cinic10Index> {
+        let source = "dog\n\
+                      --n123: good boy\n\
+                      ----n1230: bestest boy\n\
+                      ------n12300: goodest boy\n\
+                      ----n1231: okay boy\n\
+                      --n9: cujo\n\
+                      cat\n\
+                      --n999: chonk\n";
+        let empty = || fixtures::split_of_classes("/data", &[]);
+        let mut cinic = fixtures::cinic("/data", empty(), empty(), empty());
+        cinic.synset_map = parse_synset_map(source.as_bytes())?;
+        Ok(cinic)
+    }
+
+    fn ids(ids: &[&str]) -> Vec<WnId> {
+        ids.iter().map(|id| id.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_synset_hierarchy() -> anyhow::Result<()> {
+        let cinic = cinic()?;
+
+        assert_eq!(
+            cinic.synset_ancestors("n12300".parse()?),
+            ids(&["n1230", "n123"])
+        );
+        assert!(cinic.synset_ancestors("n9".parse()?).is_empty());
+        assert_eq!(
+            cinic.synset_children("n123".parse()?),
+            ids(&["n1230", "n1231"])
+        );
+        assert_eq!(
+            cinic.synset_descendants("n123".parse()?),
+            ids(&["n123", "n1230", "n1231", "n12300"])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_class_synsets() -> anyhow::Result<()> {
+        let cinic = cinic()?;
+
+        assert_eq!(
+            cinic.class_root_synsets(ObjectClass::Dog),
+            ids(&["n9", "n123"])
+        );
+        assert_eq!(
+            cinic.class_leaf_synsets(ObjectClass::Dog),
+            ids(&["n9", "n1231", "n12300"])
+        );
+        assert_eq!(cinic.class_leaf_synsets(ObjectClass::Cat), ids(&["n999"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_synsets() -> anyhow::Result<()> {
+        let cinic = cinic()?;
+
+        let found = cinic.find_synsets("BOY");
+        assert_eq!(found.len(), 4);
+        assert!(
+            found
+                .iter()
+                .all(|node| node.object_class == ObjectClass::Dog)
+        );
+        assert_eq!(
+            cinic.find_synsets_fuzzy("chunk", 1)[0].node.synset_id,
+            "n999".parse()?
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use crate::Cinic10Index;
+use crate::error::{Result, bail};
+use crate::index::{DataSet, DatasetIndex, ObjectClass};
+use serde::{Deserialize, Serialize};
+
+/// Label counts of one split of a `BinaryTask`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinarySplitStats {
+    /// Sample counts for labels `0` and `1`.
+    pub counts: [usize; 2],
+}
+
+impl BinarySplitStats {
+    pub fn total(&self) -> usize {
+        self.counts[0] + self.counts[1]
+    }
+
+    /// The fraction of samples with label `1`; `0.0` for an empty split.
+    pub fn positive_fraction(&self) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.counts[1] as f64 / total as f64,
+        }
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.counts[0] == self.counts[1]
+    }
+}
+
+/// One split of a `BinaryTask`: the samples of the two task classes.
+#[derive(Debug, Clone)]
+pub struct BinarySplit {
+    classes: [ObjectClass; 2],
+
+    /// The filtered index; load batches from it as from any split.
+    pub index: DatasetIndex,
+
+    /// The index of each sample in the source split.
+    pub source_indices: Vec<usize>,
+}
+
+impl BinarySplit {
+    /// Filter a split down to the samples of two classes.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The source split.
+    /// - `classes`: The classes labelled `0` and `1`.
+    pub fn new(
+        split: &DatasetIndex,
+        classes: [ObjectClass; 2],
+    ) -> Self {
+        let source_indices: Vec<usize> = (0..split.len())
+            .filter(|&i| classes.contains(&split.index_to_class(i)))
+            .collect();
+        let index = split.select(&source_indices);
+        Self {
+            classes,
+            index,
+            source_indices,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The `{0, 1}` label of a sample of the filtered index.
+    pub fn label(
+        &self,
+        index: usize,
+    ) -> u8 {
+        (self.index.index_to_class(index) == self.classes[1]) as u8
+    }
+
+    /// Convert a slice of filtered indices to `{0, 1}` labels.
+    pub fn labels(
+        &self,
+        indices: &[usize],
+    ) -> Vec<u8> {
+        indices.iter().map(|&i| self.label(i)).collect()
+    }
+
+    pub fn stats(&self) -> BinarySplitStats {
+        let mut stats = BinarySplitStats::default();
+        for i in 0..self.len() {
+            stats.counts[self.label(i) as usize] += 1;
+        }
+        stats
+    }
+}
+
+/// A one-vs-one probing task over two classes, with labels remapped to `{0, 1}`.
+#[derive(Debug, Clone)]
+pub struct BinaryTask {
+    /// The classes labelled `0` and `1`.
+    pub classes: [ObjectClass; 2],
+
+    pub train: BinarySplit,
+    pub valid: BinarySplit,
+    pub test: BinarySplit,
+}
+
+impl BinaryTask {
+    pub fn split(
+        &self,
+        data_set: DataSet,
+    ) -> &BinarySplit {
+        match data_set {
+            DataSet::Train => &self.train,
+            DataSet::Valid => &self.valid,
+            DataSet::Test => &self.test,
+        }
+    }
+
+    /// The label counts of every split.
+    pub fn stats(&self) -> [(DataSet, BinarySplitStats); 3] {
+        DataSet::ALL.map(|ds| (ds, self.split(ds).stats()))
+    }
+}
+
+impl Cinic10Index {
+    /// Build a one-vs-one task; `class_a` is labelled `0`, and `class_b` is labelled `1`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the task; or an error if the classes are equal.
+    pub fn binary_task(
+        &self,
+        class_a: ObjectClass,
+        class_b: ObjectClass,
+    ) -> Result<BinaryTask> {
+        if class_a == class_b {
+            bail!("Binary task classes must differ, got {class_a} twice");
+        }
+        let classes = [class_a, class_b];
+        Ok(BinaryTask {
+            classes,
+            train: BinarySplit::new(&self.train, classes),
+            valid: BinarySplit::new(&self.valid, classes),
+            test: BinarySplit::new(&self.test, classes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+
+    #[test]
+    fn test_binary_split() {
+        let split = fixtures::split_of_classes(
+            "/data/test",
+            &[
+                ObjectClass::Cat,
+                ObjectClass::Dog,
+                ObjectClass::Cat,
+                ObjectClass::Ship,
+                ObjectClass::Dog,
+                ObjectClass::Dog,
+            ],
+        );
+
+        let task = BinarySplit::new(&split, [ObjectClass::Dog, ObjectClass::Cat]);
+        assert_eq!(task.source_indices, vec![0, 1, 2, 4, 5]);
+        assert_eq!(task.labels(&[0, 1, 2, 3, 4]), vec![1, 0, 1, 0, 0]);
+        assert_eq!(task.index.index_to_path(3), split.index_to_path(4));
+
+        let stats = task.stats();
+        assert_eq!(stats.counts, [3, 2]);
+        assert_eq!(stats.total(), 5);
+        assert!(!stats.is_balanced());
+        assert!((stats.positive_fraction() - 0.4).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error::Result;
+use crate::record::{ComponentRecord, Recordable};
+use image::RgbImage;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// An image-to-image transform, such as an augmentation or a corruption.
+///
+/// Transforms draw all randomness from the supplied `rng`, so a pipeline
+/// is reproducible given its seed.
+pub trait ImageTransform: Recordable + Send + Sync {
+    fn apply(
+        &self,
+        img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage>;
+}
+
+impl fmt::Debug for dyn ImageTransform {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "ImageTransform({})", self.component_record().kind)
+    }
+}
+
+/// The deterministic RNG for transforming one sample.
+///
+/// Seeding by the sample's file name (rather than by position in a batch)
+/// gives each image the same random draws regardless of batching, ordering,
+/// parallelism, or where the dataset is installed.
+pub fn sample_rng(
+    seed: u64,
+    path: &Path,
+) -> StdRng {
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&seed.to_le_bytes());
+    hasher.update(name.as_encoded_bytes());
+    StdRng::from_seed(*hasher.finalize().as_bytes())
+}
+
+/// Applies a sequence of transforms, in order.
+#[derive(Debug, Clone, Default)]
+pub struct Compose {
+    transforms: Vec<Arc<dyn ImageTransform>>,
+}
+
+impl Compose {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Append a transform.
+    pub fn then<T>(
+        mut self,
+        transform: T,
+    ) -> Self
+    where
+        T: ImageTransform + 'static,
+    {
+        self.transforms.push(Arc::new(transform));
+        self
+    }
+
+    pub fn transforms(&self) -> &[Arc<dyn ImageTransform>] {
+        &self.transforms
+    }
+}
+
+impl Recordable for Compose {
+    fn component_record(&self) -> ComponentRecord {
+        let steps: Vec<serde_json::Value> = self
+            .transforms
+            .iter()
+            .map(|t| serde_json::to_value(t.component_record()).unwrap_or_default())
+            .collect();
+        ComponentRecord::new("compose").with_param("transforms", steps)
+    }
+}
+
+impl ImageTransform for Compose {
+    fn apply(
+        &self,
+        mut img: RgbImage,
+        rng: &mut dyn RngCore,
+    ) -> Result<RgbImage> {
+        for transform in &self.transforms {
+            img = transform.apply(img, rng)?;
+        }
+        Ok(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use rand::Rng;
+
+    struct AddNoise;
+
+    impl Recordable for AddNoise {
+        fn component_record(&self) -> ComponentRecord {
+            ComponentRecord::new("add_noise")
+        }
+    }
+
+    impl ImageTransform for AddNoise {
+        fn apply(
+            &self,
+            mut img: RgbImage,
+            rng: &mut dyn RngCore,
+        ) -> Result<RgbImage> {
+            for px in img.pixels_mut() {
+                px[0] = px[0].wrapping_add(rng.random_range(0..8));
+            }
+            Ok(img)
+        }
+    }
+
+    #[test]
+    fn test_compose() -> Result<()> {
+        let pipeline = Compose::new().then(AddNoise).then(AddNoise);
+        let record = pipeline.component_record();
+        assert_eq!(record.params["transforms"][1]["kind"], "add_noise");
+
+        let img = RgbImage::from_pixel(4, 4, Rgb([10, 20, 30]));
+        let path = Path::new("train/cat/a.png");
+        let a = pipeline.apply(img.clone(), &mut sample_rng(1, path))?;
+        let b = pipeline.apply(img.clone(), &mut sample_rng(1, path))?;
+        let c = pipeline.apply(img.clone(), &mut sample_rng(2, path))?;
+        let moved = pipeline.apply(img, &mut sample_rng(1, Path::new("/mnt/cat/a.png")))?;
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, moved);
+
+        Ok(())
+    }
+}
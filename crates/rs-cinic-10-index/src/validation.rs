@@ -0,0 +1,264 @@
+use crate::dataset_profile::DatasetProfile;
+use crate::index::{CONTRIB_FILE, Cinic10Index, DataSet, ObjectClass, SYNSET_FILE};
+use crate::stats::ClassDistribution;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A deviation from the expected layout of a `DatasetProfile`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LayoutDeviation {
+    /// A metadata file, e.g. `CONTRIB_FILE`, is not in the root.
+    MissingMetadata { file: String },
+
+    /// A split directory does not exist.
+    MissingSplit { split: DataSet },
+
+    /// A class directory of an existing split does not exist.
+    MissingClassDir { split: DataSet, class: ObjectClass },
+
+    /// A class has other than the profile's images per class.
+    ClassCount {
+        split: DataSet,
+        class: ObjectClass,
+        expected: usize,
+        found: usize,
+    },
+
+    /// The first image of a split is not the profile's `(width, height)`.
+    ImageSize {
+        split: DataSet,
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+}
+
+impl fmt::Display for LayoutDeviation {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::MissingMetadata { file } => write!(f, "missing metadata file {file}"),
+            Self::MissingSplit { split } => write!(f, "missing split directory {split}"),
+            Self::MissingClassDir { split, class } => {
+                write!(f, "missing class directory {split}/{class}")
+            }
+            Self::ClassCount {
+                split,
+                class,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{split}/{class}: expected {expected} images, found {found}"
+            ),
+            Self::ImageSize {
+                split,
+                expected: (ew, eh),
+                found: (fw, fh),
+            } => write!(f, "{split}: expected {ew}x{eh} images, found {fw}x{fh}"),
+        }
+    }
+}
+
+/// The per-class counts of a dataset copy, and how it deviates from the expected layout.
+///
+/// See `Cinic10Index::validate()` and `Cinic10IndexBuilder::build_with_report()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// The class counts of each checked split, in `DataSet::ALL` order.
+    pub counts: Vec<(DataSet, ClassDistribution)>,
+
+    pub deviations: Vec<LayoutDeviation>,
+}
+
+impl ValidationReport {
+    /// Does the copy match the expected layout?
+    pub fn is_valid(&self) -> bool {
+        self.deviations.is_empty()
+    }
+
+    /// The class counts of a split; `None` if it was not checked.
+    pub fn split_counts(
+        &self,
+        split: DataSet,
+    ) -> Option<&ClassDistribution> {
+        self.counts
+            .iter()
+            .find(|(ds, _)| *ds == split)
+            .map(|(_, dist)| dist)
+    }
+}
+
+impl Cinic10Index {
+    /// Compare the index and its directories against the standard CINIC-10 layout.
+    ///
+    /// Unlike strict loading, this never fails; it lists every deviation, so
+    /// partial or extended copies can be inspected before use.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_profile(&DatasetProfile::cinic10())
+    }
+
+    /// Like `validate()`, against the splits, counts and image size of `profile`.
+    ///
+    /// Only the first image of each split is decoded, to check the size;
+    /// an unreadable one is skipped.
+    pub fn validate_profile(
+        &self,
+        profile: &DatasetProfile,
+    ) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for file in [CONTRIB_FILE, SYNSET_FILE] {
+            if !self.root.join(file).exists() {
+                report.deviations.push(LayoutDeviation::MissingMetadata {
+                    file: file.to_string(),
+                });
+            }
+        }
+
+        for split in DataSet::ALL.into_iter().filter(|&ds| profile.has_split(ds)) {
+            let index = self.split(split);
+            let dist = index.class_distribution();
+            report.counts.push((split, dist));
+
+            if !index.ds_path().is_dir() {
+                report
+                    .deviations
+                    .push(LayoutDeviation::MissingSplit { split });
+                continue;
+            }
+            let samples_per_class = profile.samples_per_class(split);
+            for (class, found, _) in dist.iter() {
+                if !index.ds_path().join(class.to_string()).is_dir() {
+                    report
+                        .deviations
+                        .push(LayoutDeviation::MissingClassDir { split, class });
+                } else if let Some(expected) = samples_per_class
+                    && found != expected
+                {
+                    report.deviations.push(LayoutDeviation::ClassCount {
+                        split,
+                        class,
+                        expected,
+                        found,
+                    });
+                }
+            }
+            if !index.is_empty()
+                && let Ok(found) = image::image_dimensions(index.abs_path(0))
+                && found != profile.image_size
+            {
+                report.deviations.push(LayoutDeviation::ImageSize {
+                    split,
+                    expected: profile.image_size,
+                    found,
+                });
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::SAMPLES_PER_CLASS;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+    use std::fs;
+
+    #[test]
+    fn test_build_with_report() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for (class, n) in [("cat", 3), ("dog", 1)] {
+            let class_dir = root.join("train").join(class);
+            fs::create_dir_all(&class_dir)?;
+            for i in 0..n {
+                fs::write(class_dir.join(format!("{i}.png")), b"")?;
+            }
+        }
+        fs::write(root.join(SYNSET_FILE), "")?;
+
+        let (cinic, report) = Cinic10Index::builder().root(root).build_with_report()?;
+        assert_eq!(cinic.train.len(), 4);
+        assert!(!report.is_valid());
+        assert_eq!(report.counts.len(), 3);
+
+        let train = report.split_counts(DataSet::Train).unwrap();
+        assert_eq!(train.count(ObjectClass::Cat), 3);
+        assert!(report.deviations.contains(&LayoutDeviation::ClassCount {
+            split: DataSet::Train,
+            class: ObjectClass::Dog,
+            expected: SAMPLES_PER_CLASS,
+            found: 1,
+        }));
+        assert!(
+            report
+                .deviations
+                .contains(&LayoutDeviation::MissingClassDir {
+                    split: DataSet::Train,
+                    class: ObjectClass::Frog,
+                })
+        );
+        assert!(report.deviations.contains(&LayoutDeviation::MissingSplit {
+            split: DataSet::Test
+        }));
+        assert_eq!(
+            report.deviations[0].to_string(),
+            format!("missing metadata file {CONTRIB_FILE}")
+        );
+        assert_eq!(report.deviations.len(), 1 + 10 + 2);
+
+        let (_, report) = Cinic10Index::builder()
+            .root(root)
+            .splits([DataSet::Train])
+            .build_with_report()?;
+        assert_eq!(report.counts.len(), 1);
+        assert!(report.split_counts(DataSet::Valid).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_profile() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        for class in ObjectClass::ALL {
+            let class_dir = root.join("train").join(class.to_string());
+            fs::create_dir_all(&class_dir)?;
+            for i in 0..2 {
+                RgbImage::from_pixel(4, 4, Rgb([i; 3])).save(class_dir.join(format!("{i}.png")))?;
+            }
+        }
+        fs::write(root.join(CONTRIB_FILE), "")?;
+        fs::write(root.join(SYNSET_FILE), "")?;
+
+        let profile =
+            DatasetProfile::imagenet_downsampled(4).with_splits([(DataSet::Train, Some(2))]);
+        assert!(Cinic10Index::builder().root(root).build().is_err());
+        let cinic = Cinic10Index::builder()
+            .root(root)
+            .profile(profile.clone())
+            .build()?;
+        assert_eq!(cinic.train.len(), 20);
+        assert!(cinic.validate_profile(&profile).is_valid());
+
+        let report = cinic.validate_profile(&profile.with_image_size(8, 8));
+        assert_eq!(
+            report.deviations,
+            vec![LayoutDeviation::ImageSize {
+                split: DataSet::Train,
+                expected: (8, 8),
+                found: (4, 4),
+            }]
+        );
+        assert_eq!(
+            report.deviations[0].to_string(),
+            "train: expected 8x8 images, found 4x4"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,439 @@
+use crate::error::{Cinic10Error, Result};
+use crate::images::RgbImageBatch;
+use crate::index::{Cinic10Index, DatasetIndex, ObjectClass};
+use crate::loader::{BatchLoader, LoadedBatch};
+use crate::stats::ClassDistribution;
+use enum_ordinalize::Ordinalize;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use std::path::PathBuf;
+use strum::EnumCount;
+
+/// A view of a subset of a `DatasetIndex`.
+///
+/// See `DatasetIndex::filter_classes()`, `DatasetIndex::subset()`,
+/// `DatasetIndex::take_per_class()`, `DatasetIndex::sample_per_class()`,
+/// and `DatasetIndex::filter_origin()`.
+/// View indices run over just the selected samples, `0..len()`, and map back
+/// to the source with `source_index()`. Labels are remapped to positions in
+/// the view's class list, so a cat-vs-dog view has labels `{0, 1}`.
+#[derive(Debug, Clone)]
+pub struct DatasetIndexView<'a> {
+    source: &'a DatasetIndex,
+    classes: Vec<ObjectClass>,
+    indices: Vec<usize>,
+}
+
+impl<'a> DatasetIndexView<'a> {
+    /// A view of `indices` of `source`; every item must be of one of `classes`.
+    pub(crate) fn new(
+        source: &'a DatasetIndex,
+        classes: Vec<ObjectClass>,
+        indices: Vec<usize>,
+    ) -> Self {
+        Self {
+            source,
+            classes,
+            indices,
+        }
+    }
+
+    pub fn source(&self) -> &'a DatasetIndex {
+        self.source
+    }
+
+    /// The selected classes; a class's label is its position here.
+    pub fn classes(&self) -> &[ObjectClass] {
+        &self.classes
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// The index of a view item in the source split.
+    pub fn source_index(
+        &self,
+        index: usize,
+    ) -> usize {
+        self.indices[index]
+    }
+
+    /// The source split index of every view item, in order.
+    pub fn source_indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Convert a view index to an object class.
+    pub fn index_to_class(
+        &self,
+        index: usize,
+    ) -> ObjectClass {
+        self.source.index_to_class(self.source_index(index))
+    }
+
+    /// Convert a slice of view indices to a vector of object classes.
+    pub fn indices_to_classes(
+        &self,
+        indices: &[usize],
+    ) -> Vec<ObjectClass> {
+        indices.iter().map(|&i| self.index_to_class(i)).collect()
+    }
+
+    /// The remapped label of a view item: its class's position in `classes()`.
+    pub fn index_to_label(
+        &self,
+        index: usize,
+    ) -> usize {
+        let class = self.index_to_class(index);
+        self.classes.iter().position(|&c| c == class).unwrap()
+    }
+
+    /// Convert a slice of view indices to remapped labels.
+    pub fn indices_to_labels(
+        &self,
+        indices: &[usize],
+    ) -> Vec<usize> {
+        indices.iter().map(|&i| self.index_to_label(i)).collect()
+    }
+
+    /// Count the items of each class.
+    pub fn class_distribution(&self) -> ClassDistribution {
+        ClassDistribution::from_classes((0..self.len()).map(|i| self.index_to_class(i)))
+    }
+
+    /// Convert a view index to an image path.
+    pub fn index_to_path(
+        &self,
+        index: usize,
+    ) -> PathBuf {
+        self.source.index_to_path(self.source_index(index))
+    }
+
+    /// Convert a slice of view indices to a vector of image paths.
+    pub fn indices_to_paths(
+        &self,
+        indices: &[usize],
+    ) -> Vec<PathBuf> {
+        indices.iter().map(|&i| self.index_to_path(i)).collect()
+    }
+
+    /// The source split indices of a slice of view indices.
+    pub fn to_source_indices(
+        &self,
+        indices: &[usize],
+    ) -> Vec<usize> {
+        indices.iter().map(|&i| self.source_index(i)).collect()
+    }
+
+    /// Load an `RgbImageBatch` for a batch of view indices.
+    ///
+    /// # Parameters
+    ///
+    /// - `indices`: A slice of view indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loaded `RgbImageBatch` on success, or an error on failure.
+    pub fn load_rgbimagebatch(
+        &self,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        self.source
+            .load_rgbimagebatch(&self.to_source_indices(indices))
+    }
+
+    /// Load an `RgbImageBatch` for a batch of view indices, using the given loader.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of view indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loaded `RgbImageBatch` on success, or an error on failure.
+    pub fn load_rgbimagebatch_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<RgbImageBatch> {
+        self.source
+            .load_rgbimagebatch_with(loader, &self.to_source_indices(indices))
+    }
+
+    /// Load an `RgbImageBatch` with the given loader, reporting failed samples.
+    ///
+    /// # Parameters
+    ///
+    /// - `loader`: The loader to use.
+    /// - `indices`: A slice of view indices to load.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the loaded batch and failure report.
+    pub fn load_rgbimagebatch_report_with(
+        &self,
+        loader: &BatchLoader,
+        indices: &[usize],
+    ) -> Result<LoadedBatch<RgbImageBatch>> {
+        self.source
+            .load_rgbimagebatch_report_with(loader, &self.to_source_indices(indices))
+    }
+
+    /// Copy the view into a standalone `DatasetIndex`.
+    pub fn to_index(&self) -> DatasetIndex {
+        self.source.select(&self.indices)
+    }
+}
+
+impl DatasetIndex {
+    /// A view of the items of the given classes.
+    ///
+    /// # Parameters
+    ///
+    /// - `classes`: The classes to keep; their order defines the view's labels.
+    ///
+    /// # Returns
+    ///
+    /// A `DatasetIndexView` over the matching items, in source order.
+    pub fn filter_classes(
+        &self,
+        classes: &[ObjectClass],
+    ) -> DatasetIndexView<'_> {
+        let indices = (0..self.len())
+            .filter(|&i| classes.contains(&self.index_to_class(i)))
+            .collect();
+        DatasetIndexView::new(self, classes.to_vec(), indices)
+    }
+
+    /// A view of the given items, in the given order.
+    ///
+    /// Labels are the class ordinals, as for the full split.
+    ///
+    /// # Parameters
+    ///
+    /// - `indices`: The source indices; may repeat.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a `DatasetIndexView` of the items; or a
+    /// `Cinic10Error::IndexOutOfRange` if an index is out of range.
+    pub fn subset(
+        &self,
+        indices: &[usize],
+    ) -> Result<DatasetIndexView<'_>> {
+        if let Some(&index) = indices.iter().find(|&&i| i >= self.len()) {
+            return Err(Cinic10Error::IndexOutOfRange {
+                index,
+                len: self.len(),
+            });
+        }
+        Ok(DatasetIndexView::new(
+            self,
+            ObjectClass::ALL.to_vec(),
+            indices.to_vec(),
+        ))
+    }
+
+    /// A view of the first `n` items of each class, in source order.
+    ///
+    /// Deterministic, so the same split always yields the same sub-dataset;
+    /// classes with fewer than `n` items are kept whole.
+    pub fn take_per_class(
+        &self,
+        n: usize,
+    ) -> DatasetIndexView<'_> {
+        let mut counts = [0; ObjectClass::COUNT];
+        let indices = (0..self.len())
+            .filter(|&i| {
+                let count = &mut counts[self.index_to_class(i).ordinal() as usize];
+                *count += 1;
+                *count <= n
+            })
+            .collect();
+        DatasetIndexView::new(self, ObjectClass::ALL.to_vec(), indices)
+    }
+
+    /// A view of `n` seeded random items of each class, in source order.
+    ///
+    /// Classes with fewer than `n` items are kept whole.
+    pub fn sample_per_class(
+        &self,
+        n: usize,
+        seed: u64,
+    ) -> DatasetIndexView<'_> {
+        self.sample_per_class_with(n, &mut StdRng::seed_from_u64(seed))
+    }
+
+    fn sample_per_class_with(
+        &self,
+        n: usize,
+        rng: &mut StdRng,
+    ) -> DatasetIndexView<'_> {
+        let mut indices: Vec<usize> = ObjectClass::ALL
+            .iter()
+            .flat_map(|&class| self.items_of_class(class).choose_multiple(rng, n).copied())
+            .collect();
+        indices.sort_unstable();
+        DatasetIndexView::new(self, ObjectClass::ALL.to_vec(), indices)
+    }
+}
+
+/// Small class-balanced views of every split, from `Cinic10Index::dev_sample()`.
+#[derive(Debug, Clone)]
+pub struct DevSample<'a> {
+    pub train: DatasetIndexView<'a>,
+    pub test: DatasetIndexView<'a>,
+    pub valid: DatasetIndexView<'a>,
+}
+
+impl Cinic10Index {
+    /// Sample a tiny class-balanced copy of the dataset, for smoke tests and CI.
+    ///
+    /// # Parameters
+    ///
+    /// - `n_per_class`: The number of items of each class to keep, per split.
+    /// - `seed`: The sampling seed; the same seed always picks the same items.
+    ///
+    /// # Returns
+    ///
+    /// A `DevSample` of views of each split; see `DatasetIndex::sample_per_class()`.
+    pub fn dev_sample(
+        &self,
+        n_per_class: usize,
+        seed: u64,
+    ) -> DevSample<'_> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        DevSample {
+            train: self.train.sample_per_class_with(n_per_class, &mut rng),
+            test: self.test.sample_per_class_with(n_per_class, &mut rng),
+            valid: self.valid.sample_per_class_with(n_per_class, &mut rng),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+    use anyhow::Result;
+    use image::{Rgb, RgbImage};
+    use std::path::Path;
+
+    const CLASSES: [ObjectClass; 4] = [
+        ObjectClass::Cat,
+        ObjectClass::Ship,
+        ObjectClass::Dog,
+        ObjectClass::Cat,
+    ];
+
+    /// A split of `CLASSES` under `dir`, whose image `i` is filled with `i`.
+    fn split_on_disk(dir: &Path) -> Result<DatasetIndex> {
+        Ok(fixtures::write_split(
+            dir.join("valid"),
+            CLASSES.iter().enumerate().map(|(i, &class)| {
+                (
+                    class,
+                    format!("{i}.png"),
+                    RgbImage::from_pixel(2, 2, Rgb([i as u8; 3])),
+                )
+            }),
+        )?)
+    }
+
+    #[test]
+    fn test_filter_classes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = split_on_disk(dir.path())?;
+
+        let view = split.filter_classes(&[ObjectClass::Dog, ObjectClass::Cat]);
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.source_indices(), &[0, 2, 3]);
+        assert_eq!(view.indices_to_labels(&[0, 1, 2]), vec![1, 0, 1]);
+        assert_eq!(view.index_to_path(1), split.index_to_path(2));
+        assert_eq!(view.class_distribution().count(ObjectClass::Cat), 2);
+
+        let batch = view.load_rgbimagebatch(&[2, 1])?;
+        assert_eq!(batch.shape, vec![2, 2, 2, 3]);
+        assert_eq!((batch.data[0], batch.data[12]), (3, 2));
+
+        assert!(split.filter_classes(&[ObjectClass::Frog]).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_index() {
+        let split = fixtures::split_of_classes("/cinic/valid", &CLASSES);
+        let owned = split
+            .filter_classes(&[ObjectClass::Dog, ObjectClass::Cat])
+            .to_index();
+        assert_eq!(owned.len(), 3);
+        assert_eq!(owned.index_to_path(2), split.index_to_path(3));
+    }
+
+    #[test]
+    fn test_subset() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let split = split_on_disk(dir.path())?;
+
+        let subset = split.subset(&[3, 1])?;
+        assert_eq!(
+            subset.indices_to_classes(&[0, 1]),
+            vec![ObjectClass::Cat, ObjectClass::Ship]
+        );
+        assert_eq!(
+            subset.index_to_label(1),
+            ObjectClass::Ship.ordinal() as usize
+        );
+        assert_eq!(subset.load_rgbimagebatch(&[0])?.data[0], 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subset_out_of_range() {
+        let split = fixtures::split_of_classes("/cinic/valid", &CLASSES);
+        assert!(matches!(
+            split.subset(&[1, 4]),
+            Err(Cinic10Error::IndexOutOfRange { index: 4, len: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_take_per_class() {
+        let split = fixtures::split_of_classes("/cinic/valid", &CLASSES);
+        assert_eq!(split.take_per_class(1).source_indices(), &[0, 1, 2]);
+        assert_eq!(split.take_per_class(5).len(), 4);
+    }
+
+    #[test]
+    fn test_dev_sample() {
+        let split = |name: &str| {
+            fixtures::numbered_split(Path::new("/cinic").join(name), &ObjectClass::ALL[..3], 10)
+        };
+        let cinic = fixtures::cinic("/cinic", split("train"), split("test"), split("valid"));
+
+        let dev = cinic.dev_sample(4, 9);
+        for view in [&dev.train, &dev.test, &dev.valid] {
+            assert_eq!(view.len(), 12);
+            assert_eq!(view.class_distribution().count(ObjectClass::Bird), 4);
+            assert!(view.source_indices().is_sorted());
+        }
+        assert_eq!(
+            cinic.dev_sample(4, 9).train.source_indices(),
+            dev.train.source_indices()
+        );
+        assert_ne!(
+            cinic.dev_sample(4, 10).train.source_indices(),
+            dev.train.source_indices()
+        );
+        assert_eq!(cinic.train.sample_per_class(20, 0).len(), 30);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::Result;
+use crate::index::{Cinic10Index, DataSet, DatasetIndex};
+use crate::readahead::advise_will_need;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+
+/// The shared progress of a warmup.
+#[derive(Debug, Default)]
+struct WarmupProgress {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+/// The result of a finished warmup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WarmupReport {
+    /// The number of files read.
+    pub files: u64,
+
+    /// The number of bytes read.
+    pub bytes: u64,
+
+    /// The number of files which could not be read; these are skipped.
+    pub errors: u64,
+
+    /// Was the warmup cancelled before it finished?
+    pub cancelled: bool,
+}
+
+/// A handle to a background warmup, started by `DatasetIndex::warmup()`.
+///
+/// Dropping the handle does not stop the warmup; use `cancel()`.
+#[derive(Debug)]
+pub struct WarmupHandle {
+    total_files: u64,
+    progress: Arc<WarmupProgress>,
+    thread: JoinHandle<WarmupReport>,
+}
+
+impl WarmupHandle {
+    /// The number of files the warmup will read, if it is not cancelled.
+    pub fn total_files(&self) -> u64 {
+        self.total_files
+    }
+
+    /// The number of files read so far.
+    pub fn files_read(&self) -> u64 {
+        self.progress.files.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.progress.bytes.load(Ordering::Relaxed)
+    }
+
+    /// The fraction of files read so far, in `[0, 1]`.
+    pub fn fraction(&self) -> f64 {
+        match self.total_files {
+            0 => 1.0,
+            total => self.files_read() as f64 / total as f64,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the warmup after the file currently being read.
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Wait for the warmup to finish.
+    pub fn join(self) -> Result<WarmupReport> {
+        self.thread
+            .join()
+            .map_err(|_| anyhow::anyhow!("Warmup thread panicked").into())
+    }
+}
+
+/// Read every file of `paths`, in order, until cancelled or `budget` bytes are read.
+fn run_warmup(
+    paths: Vec<PathBuf>,
+    budget: u64,
+    progress: &WarmupProgress,
+) -> WarmupReport {
+    let mut report = WarmupReport::default();
+    let mut buf = vec![0u8; 64 << 10];
+    for path in paths {
+        if progress.cancelled.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        if report.bytes >= budget {
+            break;
+        }
+        advise_will_need(&path);
+        let result = File::open(&path).and_then(|mut file| {
+            let mut len = 0;
+            loop {
+                match file.read(&mut buf) {
+                    Ok(0) => return Ok(len),
+                    Ok(n) => len += n as u64,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        });
+        match result {
+            Ok(len) => {
+                report.files += 1;
+                report.bytes += len;
+                progress.files.fetch_add(1, Ordering::Relaxed);
+                progress.bytes.fetch_add(len, Ordering::Relaxed);
+            }
+            Err(_err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(path = %path.display(), error = %_err, "warmup read failed");
+
+                report.errors += 1;
+            }
+        }
+    }
+    report
+}
+
+impl DatasetIndex {
+    /// Read the split's image files on a background thread, to warm the OS page cache.
+    ///
+    /// Files are read in index order, so the first epoch (or the first
+    /// batches of a sequential pass) don't pay cold-cache reads. Reading stops
+    /// once `budget` bytes have been read; size it to the memory the page cache
+    /// can spare, or `u64::MAX` for the whole split.
+    ///
+    /// # Parameters
+    ///
+    /// - `budget`: The maximum number of bytes to read.
+    ///
+    /// # Returns
+    ///
+    /// A handle to watch progress, cancel, or wait for the warmup.
+    pub fn warmup(
+        &self,
+        budget: u64,
+    ) -> WarmupHandle {
+        let paths: Vec<PathBuf> = (0..self.len()).map(|i| self.index_to_path(i)).collect();
+        let total_files = paths.len() as u64;
+        let progress = Arc::new(WarmupProgress::default());
+        let thread = {
+            let progress = progress.clone();
+            thread::spawn(move || run_warmup(paths, budget, &progress))
+        };
+        WarmupHandle {
+            total_files,
+            progress,
+            thread,
+        }
+    }
+}
+
+impl Cinic10Index {
+    /// Warm the page cache for a split; see `DatasetIndex::warmup()`.
+    pub fn warmup(
+        &self,
+        split: DataSet,
+        budget: u64,
+    ) -> WarmupHandle {
+        self.split(split).warmup(budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{DatasetItem, ObjectClass};
+    use anyhow::Result;
+    use std::fs;
+
+    #[test]
+    fn test_warmup() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let items = (0..10)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.png"));
+                fs::write(&path, vec![0u8; 100])?;
+                Ok(DatasetItem {
+                    class: ObjectClass::Cat,
+                    path,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let index = DatasetIndex::new(dir.path().to_path_buf(), items);
+
+        let handle = index.warmup(u64::MAX);
+        assert_eq!(handle.total_files(), 10);
+        let report = handle.join()?;
+        assert_eq!(report.files, 10);
+        assert_eq!(report.bytes, 1000);
+        assert!(!report.cancelled);
+
+        let report = index.warmup(250).join()?;
+        assert_eq!(report.files, 3);
+
+        fs::remove_file(dir.path().join("0.png"))?;
+        let handle = index.warmup(u64::MAX);
+        handle.cancel();
+        let report = handle.join()?;
+        assert!(report.cancelled || report.errors == 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::Result;
+use crate::index::{DatasetIndex, ObjectClass, RefreshDelta};
+use anyhow::Context;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Watches a `DatasetIndex` directory for added and removed files.
+///
+/// The watcher only records which class directories changed;
+/// call `IndexWatcher::refresh` to apply the changes to the index.
+pub struct IndexWatcher {
+    _watcher: RecommendedWatcher,
+    changed: Arc<Mutex<HashSet<ObjectClass>>>,
+}
+
+/// The class directory an event path belongs to, if any.
+fn path_class(path: &Path) -> Option<ObjectClass> {
+    if let Ok(class) = ObjectClass::from_str(path.file_name()?.to_str()?) {
+        return Some(class);
+    }
+    ObjectClass::from_str(path.parent()?.file_name()?.to_str()?).ok()
+}
+
+impl IndexWatcher {
+    /// Start watching the directory of `index`.
+    ///
+    /// # Parameters
+    ///
+    /// - `index`: The index whose `ds_path` should be watched.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the running watcher.
+    pub fn new(index: &DatasetIndex) -> Result<Self> {
+        let changed: Arc<Mutex<HashSet<ObjectClass>>> = Default::default();
+
+        let mut watcher = notify::recommended_watcher({
+            let changed = changed.clone();
+            move |res: notify::Result<Event>| {
+                if let Ok(event) = res {
+                    let mut changed = changed.lock().unwrap();
+                    changed.extend(event.paths.iter().filter_map(|p| path_class(p)));
+                }
+            }
+        })
+        .context("Failed to create a file watcher")?;
+        watcher
+            .watch(index.ds_path(), RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", index.ds_path().display()))?;
+
+        Ok(Self {
+            _watcher: watcher,
+            changed,
+        })
+    }
+
+    /// Has any class directory changed since the last refresh?
+    pub fn has_changes(&self) -> bool {
+        !self.changed.lock().unwrap().is_empty()
+    }
+
+    /// Re-scan the class directories which changed since the last refresh.
+    ///
+    /// # Parameters
+    ///
+    /// - `index`: The watched index.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `RefreshDelta` of added and removed files.
+    pub fn refresh(
+        &self,
+        index: &mut DatasetIndex,
+    ) -> Result<RefreshDelta> {
+        let classes: Vec<ObjectClass> = self.changed.lock().unwrap().drain().collect();
+        if classes.is_empty() {
+            return Ok(RefreshDelta::default());
+        }
+        index.refresh_classes(&classes)
+    }
+}
+
+impl DatasetIndex {
+    /// Start an `IndexWatcher` on this index's directory.
+    pub fn watch(&self) -> Result<IndexWatcher> {
+        IndexWatcher::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cinic10Index;
+    use crate::index::DataSet;
+    use crate::ordering::FileOrdering;
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_path_class() {
+        assert_eq!(
+            path_class(Path::new("/data/train/cat/x.png")),
+            Some(ObjectClass::Cat)
+        );
+        assert_eq!(
+            path_class(Path::new("/data/train/dog")),
+            Some(ObjectClass::Dog)
+        );
+        assert_eq!(path_class(Path::new("/data/train/x.png")), None);
+    }
+
+    #[test]
+    fn test_refresh_keeps_extensions_and_ordering() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cat = dir.path().join("train/cat");
+        fs::create_dir_all(&cat)?;
+        for name in ["a10.jpg", "a2.png"] {
+            fs::write(cat.join(name), b"")?;
+        }
+        let mut index = Cinic10Index::builder()
+            .root(dir.path())
+            .require_metadata(false)
+            .strict(false)
+            .extensions(["png", "jpg"])
+            .ordering(FileOrdering::Natural)
+            .build_split(DataSet::Train)?;
+
+        let watcher = index.watch()?;
+        fs::write(cat.join("a1.jpg"), b"")?;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !watcher.has_changes() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        let delta = watcher.refresh(&mut index)?;
+
+        assert_eq!(delta.added, vec![cat.join("a1.jpg")]);
+        assert_eq!(
+            index.indices_to_paths(&[0, 1, 2]),
+            ["a1.jpg", "a2.png", "a10.jpg"].map(|name| cat.join(name))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use crate::index::{DatasetIndex, ObjectClass};
+use crate::provenance::{ItemSource, parse_item_path};
+use crate::wnid::WnId;
+use enum_ordinalize::Ordinalize;
+use std::collections::HashMap;
+use strum::EnumCount;
+
+/// Per-source sample weights, for `source_weights()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceWeights {
+    /// The weight of CIFAR-10-origin images.
+    pub cifar10: f64,
+
+    /// The weight of ImageNet-origin images.
+    pub imagenet: f64,
+
+    /// The weight of images whose file names carry no provenance.
+    pub unknown: f64,
+}
+
+impl Default for SourceWeights {
+    fn default() -> Self {
+        Self {
+            cifar10: 1.0,
+            imagenet: 1.0,
+            unknown: 1.0,
+        }
+    }
+}
+
+/// Scale weights in place to a mean of `1.0`; all-zero weights are left as they are.
+fn normalize(weights: &mut [f64]) {
+    let total: f64 = weights.iter().sum();
+    if total > 0.0 {
+        let scale = weights.len() as f64 / total;
+        weights.iter_mut().for_each(|w| *w *= scale);
+    }
+}
+
+/// Weight each sample by the dataset it was drawn from.
+///
+/// E.g. `SourceWeights { cifar10: 0.5, ..Default::default() }` downweights
+/// CIFAR-10 images relative to ImageNet ones. The weights are normalized to
+/// a mean of `1.0`, and plug into `WeightedSampler::new()`.
+///
+/// # Parameters
+///
+/// - `split`: The split to weight.
+/// - `source_weights`: The weight of each source.
+///
+/// # Returns
+///
+/// One weight per sample.
+pub fn source_weights(
+    split: &DatasetIndex,
+    source_weights: &SourceWeights,
+) -> Vec<f64> {
+    let mut weights: Vec<f64> = split
+        .items()
+        .map(
+            |item| match parse_item_path(&item.path).map(|name| name.source) {
+                Some(ItemSource::Cifar10) => source_weights.cifar10,
+                Some(ItemSource::ImageNet) => source_weights.imagenet,
+                None => source_weights.unknown,
+            },
+        )
+        .collect();
+    normalize(&mut weights);
+    weights
+}
+
+/// Weight each sample so that, within each class, every source carries equal total weight.
+///
+/// CINIC-10 classes mix CIFAR-10 and ImageNet images in different proportions;
+/// this removes the source imbalance within each class, without changing the
+/// class balance.
+///
+/// # Parameters
+///
+/// - `split`: The split to weight.
+///
+/// # Returns
+///
+/// One weight per sample, normalized to a mean of `1.0`.
+pub fn balanced_source_weights(split: &DatasetIndex) -> Vec<f64> {
+    let sources: Vec<Option<ItemSource>> = split
+        .items()
+        .map(|item| parse_item_path(&item.path).map(|name| name.source))
+        .collect();
+    let mut counts: HashMap<(ObjectClass, Option<ItemSource>), usize> = HashMap::new();
+    for (item, source) in split.items().zip(&sources) {
+        *counts.entry((item.class, *source)).or_default() += 1;
+    }
+    let mut class_sources = [0usize; ObjectClass::COUNT];
+    for (class, _) in counts.keys() {
+        class_sources[class.ordinal() as usize] += 1;
+    }
+    let class_sizes = split.class_distribution();
+
+    let mut weights: Vec<f64> = split
+        .items()
+        .zip(&sources)
+        .map(|(item, source)| {
+            let group = counts[&(item.class, *source)] as f64;
+            let share = class_sizes.count(item.class) as f64
+                / class_sources[item.class.ordinal() as usize] as f64;
+            share / group
+        })
+        .collect();
+    normalize(&mut weights);
+    weights
+}
+
+/// Weight each sample by the inverse frequency of its source synset, raised to `power`.
+///
+/// ImageNet images are grouped by synset; CIFAR-10 images (and images without
+/// provenance) are grouped by class. `power = 1.0` gives every synset equal
+/// total weight; `0.5` is a softer rebalancing; `0.0` is uniform.
+///
+/// # Parameters
+///
+/// - `split`: The split to weight.
+/// - `power`: The exponent of the inverse frequency.
+///
+/// # Returns
+///
+/// One weight per sample, normalized to a mean of `1.0`.
+pub fn synset_frequency_weights(
+    split: &DatasetIndex,
+    power: f64,
+) -> Vec<f64> {
+    let groups: Vec<(ObjectClass, Option<WnId>)> = split
+        .items()
+        .map(|item| {
+            let synset = parse_item_path(&item.path).and_then(|name| name.synset);
+            (item.class, synset)
+        })
+        .collect();
+    let mut counts: HashMap<(ObjectClass, Option<WnId>), usize> = HashMap::new();
+    for group in &groups {
+        *counts.entry(*group).or_default() += 1;
+    }
+    let mut weights: Vec<f64> = groups
+        .iter()
+        .map(|group| (counts[group] as f64).powf(-power))
+        .collect();
+    normalize(&mut weights);
+    weights
+}
+
+/// The share of the total sampling weight that falls on each class.
+///
+/// Use this to check how a weighting shifts the class balance, or to derive
+/// loss class weights which compensate for it.
+///
+/// # Parameters
+///
+/// - `split`: The split the weights are for.
+/// - `weights`: One weight per sample.
+///
+/// # Returns
+///
+/// The share of each class, in `ObjectClass` ordinal order; summing to `1.0`.
+pub fn class_weight_shares(
+    split: &DatasetIndex,
+    weights: &[f64],
+) -> [f64; ObjectClass::COUNT] {
+    assert_eq!(
+        weights.len(),
+        split.len(),
+        "One weight per sample is required"
+    );
+    let mut shares = [0.0; ObjectClass::COUNT];
+    for (item, w) in split.items().zip(weights) {
+        shares[item.class.ordinal() as usize] += w;
+    }
+    let total: f64 = shares.iter().sum();
+    if total > 0.0 {
+        shares.iter_mut().for_each(|s| *s /= total);
+    }
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::fixtures;
+    use crate::sampler::{Sampler, WeightedSampler};
+
+    fn split() -> DatasetIndex {
+        let names = [
+            (ObjectClass::Cat, "cifar10-train-1.png"),
+            (ObjectClass::Cat, "cifar10-train-2.png"),
+            (ObjectClass::Cat, "cifar10-test-3.png"),
+            (ObjectClass::Cat, "n02121808_1.png"),
+            (ObjectClass::Dog, "n02084071_1.png"),
+            (ObjectClass::Dog, "n02084071_2.png"),
+            (ObjectClass::Dog, "n02085620_1.png"),
+            (ObjectClass::Dog, "other.png"),
+        ];
+        fixtures::split("/data/train", names)
+    }
+
+    #[test]
+    fn test_provenance_weights() -> anyhow::Result<()> {
+        let split = split();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        let weights = source_weights(
+            &split,
+            &SourceWeights {
+                cifar10: 0.5,
+                imagenet: 1.0,
+                unknown: 0.0,
+            },
+        );
+        assert!(close(weights.iter().sum::<f64>(), 8.0));
+        assert!(close(weights[0] * 2.0, weights[3]));
+        assert_eq!(weights[7], 0.0);
+
+        let weights = balanced_source_weights(&split);
+        let cat_cifar: f64 = weights[..3].iter().sum();
+        assert!(close(cat_cifar, weights[3]));
+        let shares = class_weight_shares(&split, &weights);
+        assert!(close(shares[ObjectClass::Cat.ordinal() as usize], 0.5));
+
+        let weights = synset_frequency_weights(&split, 1.0);
+        assert!(close(weights[4] + weights[5], weights[6]));
+        assert!(close(weights[3], weights[6]));
+        assert!(
+            synset_frequency_weights(&split, 0.0)
+                .iter()
+                .all(|&w| close(w, 1.0))
+        );
+
+        let mut sampler = WeightedSampler::new(weights, 16, true, 0)?;
+        assert_eq!(sampler.epoch_indices(0).len(), 16);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::{Cinic10Error, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A WordNet noun synset id, such as `n02084071`.
+///
+/// Parsing accepts an upper or lower case `n` and 1 to 8 offset digits,
+/// and normalizes to the canonical lowercase, zero-padded 8-digit form;
+/// so `"N2084071"` and `"n02084071"` are the same id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct WnId(u32);
+
+impl WnId {
+    /// The largest WordNet offset; offsets have at most 8 digits.
+    pub const MAX_OFFSET: u32 = 99_999_999;
+
+    /// Create an id from its numeric offset.
+    ///
+    /// # Returns
+    ///
+    /// The id, or `None` if `offset` exceeds `MAX_OFFSET`.
+    pub fn new(offset: u32) -> Option<Self> {
+        (offset <= Self::MAX_OFFSET).then_some(Self(offset))
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for WnId {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "n{:08}", self.0)
+    }
+}
+
+impl FromStr for WnId {
+    type Err = Cinic10Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix(['n', 'N']).unwrap_or("");
+        if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("Malformed WordNet id, expected n########: {s:?}");
+        }
+        // At most 8 digits, so this always fits.
+        Ok(Self(digits.parse().unwrap()))
+    }
+}
+
+impl From<WnId> for String {
+    fn from(id: WnId) -> Self {
+        id.to_string()
+    }
+}
+
+impl TryFrom<String> for WnId {
+    type Error = Cinic10Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        WnId::from_str(&s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wnid() -> anyhow::Result<()> {
+        let id: WnId = "n02084071".parse()?;
+        assert_eq!(id.offset(), 2084071);
+        assert_eq!(id.to_string(), "n02084071");
+        assert_eq!("N2084071".parse::<WnId>()?, id);
+        assert_eq!(serde_json::to_string(&id)?, "\"n02084071\"");
+        assert_eq!(serde_json::from_str::<WnId>("\"n02084071\"")?, id);
+
+        for bad in [
+            "",
+            "n",
+            "02084071",
+            "n020840712",
+            "n0208407x",
+            "x02084071",
+            " n1",
+        ] {
+            assert!(bad.parse::<WnId>().is_err(), "{bad}");
+        }
+        assert!(WnId::new(100_000_000).is_none());
+
+        Ok(())
+    }
+}
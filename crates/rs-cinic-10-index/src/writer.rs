@@ -0,0 +1,164 @@
+use crate::error::Result;
+use crate::images::load_rgbimage;
+use crate::index::{DatasetIndex, ObjectClass};
+use crate::parallel::par_fold;
+use crate::transform::{ImageTransform, sample_rng};
+use anyhow::Context;
+use image::RgbImage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes images into a CINIC-10 style `{root}/{split}/{class}/{filename}` tree.
+#[derive(Debug, Clone)]
+pub struct DatasetWriter {
+    root: PathBuf,
+}
+
+impl DatasetWriter {
+    pub fn new<P>(root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write one image as PNG, creating directories as needed.
+    ///
+    /// # Parameters
+    ///
+    /// - `split`: The split directory name, e.g. `"test"`.
+    /// - `class`: The class directory.
+    /// - `filename`: The file name; use a `.png` extension.
+    /// - `img`: The image.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the written path.
+    pub fn write_image(
+        &self,
+        split: &str,
+        class: ObjectClass,
+        filename: &str,
+        img: &RgbImage,
+    ) -> Result<PathBuf> {
+        let dir = self.root.join(split).join(class.to_string());
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(filename);
+        img.save(&path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Copy a split into the tree, optionally transforming every image.
+    ///
+    /// Each image draws from `transform::sample_rng(seed, path)`, so the
+    /// output matches loading the source split through
+    /// `BatchLoader::with_transform()` with the same transform and seed.
+    ///
+    /// # Parameters
+    ///
+    /// - `split_name`: The output split directory name.
+    /// - `split`: The source split.
+    /// - `transform`: An optional transform, with its seed.
+    /// - `parallelism`: The number of threads; `0` means "all available cores".
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the index of the written split.
+    pub fn write_split(
+        &self,
+        split_name: &str,
+        split: &DatasetIndex,
+        transform: Option<(&dyn ImageTransform, u64)>,
+        parallelism: usize,
+    ) -> Result<DatasetIndex> {
+        let indices: Vec<usize> = (0..split.len()).collect();
+        par_fold(
+            &indices,
+            parallelism,
+            || (),
+            |_, &i| {
+                let path = split.index_to_path(i);
+                let mut img = load_rgbimage(&path)?;
+                if let Some((transform, seed)) = transform {
+                    img = transform.apply(img, &mut sample_rng(seed, &path))?;
+                }
+                let filename = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .with_context(|| format!("Bad file name: {}", path.display()))?;
+                self.write_image(split_name, split.index_to_class(i), filename, &img)?;
+                Ok(())
+            },
+            |_, _| (),
+        )?;
+
+        let mut written = DatasetIndex::new(self.root.join(split_name), Vec::new());
+        written.refresh()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::DatasetItem;
+    use crate::record::{ComponentRecord, Recordable};
+    use image::Rgb;
+    use rand::RngCore;
+
+    struct Invert;
+
+    impl Recordable for Invert {
+        fn component_record(&self) -> ComponentRecord {
+            ComponentRecord::new("invert")
+        }
+    }
+
+    impl ImageTransform for Invert {
+        fn apply(
+            &self,
+            mut img: RgbImage,
+            _rng: &mut dyn RngCore,
+        ) -> Result<RgbImage> {
+            image::imageops::invert(&mut img);
+            Ok(img)
+        }
+    }
+
+    #[test]
+    fn test_write_split() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let src = DatasetWriter::new(dir.path().join("src"));
+        src.write_image(
+            "test",
+            ObjectClass::Ship,
+            "a.png",
+            &RgbImage::from_pixel(2, 2, Rgb([0, 100, 255])),
+        )?;
+        let split = DatasetIndex::new(
+            src.root().join("test"),
+            vec![DatasetItem {
+                class: ObjectClass::Ship,
+                path: PathBuf::from("a.png"),
+            }],
+        );
+
+        let out = DatasetWriter::new(dir.path().join("out"));
+        let written = out.write_split("test", &split, Some((&Invert, 0)), 2)?;
+        assert_eq!(written.len(), 1);
+        assert_eq!(written.index_to_class(0), ObjectClass::Ship);
+        assert_eq!(
+            load_rgbimage(written.index_to_path(0))?.get_pixel(0, 0),
+            &Rgb([255, 155, 0])
+        );
+
+        Ok(())
+    }
+}